@@ -0,0 +1,92 @@
+const COMMANDS: &[&str] = &[
+    "get_offline_data",
+    "save_offline_data",
+    "delete_offline_data",
+    "clear_offline_data",
+    "get_offline_keys",
+    "check_connectivity",
+    "get_connectivity_status",
+    "minimize_window",
+    "maximize_window",
+    "close_window",
+    "get_supabase_config",
+    "get_effective_config",
+    "set_session",
+    "clear_session",
+    "get_session_status",
+    "refresh_session",
+    "supabase_get",
+    "supabase_query",
+    "supabase_post",
+    "supabase_patch",
+    "supabase_delete",
+    "get_outbox",
+    "sync_outbox",
+    "retry_outbox_entry",
+    "discard_outbox_entry",
+    "start_paged_fetch",
+    "cancel_paged_fetch",
+    "resume_paged_fetch",
+    "get_paged_fetches",
+    "delete_paged_fetch",
+    "storage_upload",
+    "storage_download",
+    "cancel_storage_transfer",
+    "resume_storage_transfer",
+    "get_storage_transfers",
+    "delete_storage_transfer",
+    "realtime_subscribe",
+    "realtime_unsubscribe",
+    "get_realtime_status",
+    "send_notification",
+    "get_notification_preferences",
+    "set_notification_preferences",
+    "get_notification_history",
+    "get_pharmacy_schema_version",
+    "open_sale",
+    "get_sale",
+    "get_open_sales",
+    "add_sale_item",
+    "update_sale_item",
+    "remove_sale_item",
+    "apply_sale_discount",
+    "remove_sale_discount",
+    "add_sale_payment",
+    "remove_sale_payment",
+    "finalize_sale",
+    "void_sale",
+    "get_expiring_batches",
+    "consume_stock",
+    "get_exchange_rate",
+    "get_exchange_rate_history",
+    "refresh_exchange_rates",
+    "set_exchange_rate",
+    "convert_amount",
+    "list_serial_ports",
+    "get_fiscal_printer",
+    "set_fiscal_printer",
+    "get_fiscal_printer_status",
+    "print_fiscal_invoice",
+    "print_fiscal_credit_note",
+    "print_x_report",
+    "print_z_report",
+    "get_sync_conflicts",
+    "resolve_sync_conflict",
+    "get_conflict_policies",
+    "set_conflict_policy",
+    "save_file_locally",
+    "open_file",
+    "read_file_locally",
+    "unlock_storage",
+    "lock_storage",
+    "get_storage_status",
+    "change_storage_secret",
+    "rotate_storage_key",
+    "list_profiles",
+    "switch_profile",
+    "wipe_profile",
+];
+
+fn main() {
+    tauri_plugin::Builder::new(COMMANDS).build();
+}
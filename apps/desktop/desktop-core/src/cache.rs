@@ -0,0 +1,165 @@
+use serde::{Deserialize, Serialize};
+
+use crate::store::OfflineEntry;
+
+/// TTL por defecto de una respuesta cacheada
+const DEFAULT_TTL_SECS: u64 = 5 * 60;
+
+/// Estrategia de caché de `supabase_get`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CacheMode {
+    /// Devuelve la caché si es utilizable; si está vencida pero dentro de
+    /// `max_stale`, la devuelve y refresca en segundo plano.
+    #[default]
+    CacheFirst,
+    /// Va siempre a la red y solo usa la caché si la petición falla por red
+    /// o Supabase no está atendiendo (5xx, 429).
+    NetworkFirst,
+}
+
+/// Política de caché por clave que envía el webview
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CachePolicy {
+    #[serde(default)]
+    pub mode: CacheMode,
+    /// Segundos durante los que la entrada se considera fresca
+    #[serde(default = "default_ttl")]
+    pub ttl_secs: u64,
+    /// Segundos tras vencer durante los que aún se puede servir la entrada.
+    /// `None` permite servirla indefinidamente.
+    #[serde(default)]
+    pub max_stale_secs: Option<u64>,
+}
+
+fn default_ttl() -> u64 {
+    DEFAULT_TTL_SECS
+}
+
+impl Default for CachePolicy {
+    fn default() -> Self {
+        Self {
+            mode: CacheMode::default(),
+            ttl_secs: DEFAULT_TTL_SECS,
+            max_stale_secs: None,
+        }
+    }
+}
+
+/// Metadatos que se guardan junto a cada respuesta cacheada
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CacheMeta {
+    /// Milisegundos desde epoch en que se obtuvo la respuesta
+    pub fetched_at: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub etag: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_range: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub endpoint: Option<String>,
+}
+
+impl CacheMeta {
+    /// Lee los metadatos de una entrada; las entradas antiguas sin
+    /// `fetched_at` usan la fecha de última escritura.
+    pub fn from_entry(entry: &OfflineEntry) -> Self {
+        serde_json::from_value::<CacheMeta>(entry.metadata.clone())
+            .ok()
+            .filter(|meta| meta.fetched_at > 0)
+            .unwrap_or_else(|| CacheMeta {
+                fetched_at: entry.updated_at,
+                ..Default::default()
+            })
+    }
+
+    pub fn to_value(&self) -> serde_json::Value {
+        serde_json::to_value(self).unwrap_or_default()
+    }
+}
+
+/// Estado de una entrada respecto a su política
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Freshness {
+    /// Dentro del TTL
+    Fresh,
+    /// Vencida pero dentro de `max_stale`
+    Stale,
+    /// Ya no se puede servir
+    Expired,
+}
+
+impl CachePolicy {
+    pub fn freshness(&self, meta: &CacheMeta, now_millis: i64) -> Freshness {
+        let age = now_millis.saturating_sub(meta.fetched_at).max(0) as u64;
+        let ttl = self.ttl_secs.saturating_mul(1000);
+        if age < ttl {
+            return Freshness::Fresh;
+        }
+        match self.max_stale_secs {
+            None => Freshness::Stale,
+            Some(max_stale) if age < ttl.saturating_add(max_stale.saturating_mul(1000)) => {
+                Freshness::Stale
+            }
+            Some(_) => Freshness::Expired,
+        }
+    }
+}
+
+/// Evento emitido cuando un refresco en segundo plano trae datos nuevos
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheUpdated {
+    pub cache_key: String,
+    pub endpoint: String,
+    pub fetched_at: i64,
+}
+
+/// Nombre del evento de refresco de caché
+pub const CACHE_UPDATED_EVENT: &str = "supabase-cache-updated";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn meta(fetched_at: i64) -> CacheMeta {
+        CacheMeta {
+            fetched_at,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn freshness_follows_ttl_and_max_stale() {
+        let policy = CachePolicy {
+            ttl_secs: 60,
+            max_stale_secs: Some(60),
+            ..Default::default()
+        };
+        assert_eq!(policy.freshness(&meta(0), 59_999), Freshness::Fresh);
+        assert_eq!(policy.freshness(&meta(0), 60_000), Freshness::Stale);
+        assert_eq!(policy.freshness(&meta(0), 119_999), Freshness::Stale);
+        assert_eq!(policy.freshness(&meta(0), 120_000), Freshness::Expired);
+    }
+
+    #[test]
+    fn unlimited_max_stale_never_expires() {
+        let policy = CachePolicy {
+            ttl_secs: 0,
+            ..Default::default()
+        };
+        assert_eq!(policy.freshness(&meta(0), i64::MAX), Freshness::Stale);
+    }
+
+    #[test]
+    fn legacy_entries_use_updated_at() {
+        let entry = OfflineEntry {
+            key: "citas".into(),
+            value: "[]".into(),
+            metadata: serde_json::json!({}),
+            created_at: 1,
+            updated_at: 42,
+        };
+        assert_eq!(CacheMeta::from_entry(&entry).fetched_at, 42);
+    }
+}
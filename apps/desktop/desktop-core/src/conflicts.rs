@@ -0,0 +1,712 @@
+// Detección y resolución de conflictos de sincronización
+// Un PATCH que lleva la versión de la fila en la que se basó (`base`) se envía
+// con un filtro sobre esa versión. Si el servidor no actualiza ninguna fila es
+// que otro cliente la cambió antes, y se aplica la política de la tabla.
+// Las tres versiones de la fila que se guardan para resolverlo van cifradas.
+
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use tauri::{AppHandle, Emitter, Runtime, State};
+
+use crate::error::{Error, Result};
+use crate::http::HttpClient;
+use crate::outbox::{self, Mutation, Outbox};
+use crate::query::is_identifier;
+use crate::response::SupabaseResponse;
+use crate::store::{now_millis, OfflineStore};
+use crate::supabase::{send_get, send_mutation, MutationMethod};
+
+pub(crate) const SCHEMA: &str = "
+    ALTER TABLE outbox ADD COLUMN base TEXT;
+    CREATE TABLE IF NOT EXISTS sync_conflicts (
+        id              INTEGER PRIMARY KEY AUTOINCREMENT,
+        table_name      TEXT NOT NULL,
+        endpoint        TEXT NOT NULL,
+        idempotency_key TEXT NOT NULL,
+        base            TEXT,
+        local           TEXT NOT NULL,
+        remote          TEXT,
+        fields          TEXT NOT NULL DEFAULT '[]',
+        created_at      INTEGER NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS conflict_policies (
+        table_name     TEXT PRIMARY KEY NOT NULL,
+        policy         TEXT NOT NULL,
+        version_column TEXT NOT NULL DEFAULT 'updated_at'
+    );
+";
+
+/// Evento con el número de conflictos sin resolver
+pub const CONFLICTS_CHANGED_EVENT: &str = "sync-conflicts-changed";
+
+const DEFAULT_VERSION_COLUMN: &str = "updated_at";
+
+/// Qué hacer cuando la fila cambió en el servidor desde que se editó
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictPolicy {
+    /// Se descarta el cambio local
+    ServerWins,
+    /// Se sobrescribe el servidor con el cambio local
+    ClientWins,
+    /// Se aplican los campos que el servidor no tocó; si ambos cambiaron el
+    /// mismo campo con valores distintos, queda como conflicto
+    #[default]
+    FieldMerge,
+}
+
+impl ConflictPolicy {
+    fn as_str(self) -> &'static str {
+        match self {
+            ConflictPolicy::ServerWins => "server_wins",
+            ConflictPolicy::ClientWins => "client_wins",
+            ConflictPolicy::FieldMerge => "field_merge",
+        }
+    }
+
+    fn parse(policy: &str) -> Self {
+        match policy {
+            "server_wins" => ConflictPolicy::ServerWins,
+            "client_wins" => ConflictPolicy::ClientWins,
+            _ => ConflictPolicy::FieldMerge,
+        }
+    }
+}
+
+/// Política configurada para una tabla
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TablePolicy {
+    pub table: String,
+    pub policy: ConflictPolicy,
+    /// Columna que identifica la versión de la fila (`updated_at` por defecto)
+    pub version_column: String,
+}
+
+/// Conflicto pendiente de resolución manual
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncConflict {
+    pub id: i64,
+    pub table: String,
+    pub endpoint: String,
+    pub idempotency_key: String,
+    /// Fila en la que se basó la edición local
+    pub base: Option<Value>,
+    /// Cambios locales (cuerpo del PATCH)
+    pub local: Value,
+    /// Fila actual en el servidor; `None` si fue eliminada
+    pub remote: Option<Value>,
+    /// Campos que cambiaron en ambos lados
+    pub fields: Vec<String>,
+    pub created_at: i64,
+}
+
+impl SyncConflict {
+    fn from_row(store: &OfflineStore, row: &Row<'_>) -> Result<Self> {
+        let idempotency_key: String = row.get("idempotency_key")?;
+        let json = |column: &str| -> Result<Option<Value>> {
+            let value =
+                store.open_value(row.get(column)?, &sealed_aad(&idempotency_key, column))?;
+            Ok(value.and_then(|v| serde_json::from_str(&v).ok()))
+        };
+        let (base, local, remote) = (json("base")?, json("local")?, json("remote")?);
+        let fields: String = row.get("fields")?;
+        Ok(Self {
+            id: row.get("id")?,
+            table: row.get("table_name")?,
+            endpoint: row.get("endpoint")?,
+            base,
+            local: local.unwrap_or(Value::Null),
+            remote,
+            fields: serde_json::from_str(&fields).unwrap_or_default(),
+            created_at: row.get("created_at")?,
+            idempotency_key,
+        })
+    }
+}
+
+/// Lado que se conserva al resolver un conflicto manualmente
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictResolution {
+    Server,
+    Client,
+    Merged,
+}
+
+/// Resultado de un PATCH con comprobación de versión
+pub(crate) enum PatchOutcome {
+    /// Se envió; estado y cuerpo de la respuesta
+    Sent(SupabaseResponse),
+    /// Ganó el servidor; se devuelve la fila actual
+    Dropped(Option<Value>),
+    /// Quedó registrado como conflicto sin resolver
+    Conflict(i64),
+}
+
+// ============================================
+// ENDPOINTS
+// ============================================
+
+/// Tabla de un endpoint PostgREST (`/rest/v1/{tabla}?...`)
+pub(crate) fn table_from_endpoint(endpoint: &str) -> Option<&str> {
+    let path = endpoint.split('?').next()?;
+    let table = path.strip_prefix("/rest/v1/")?.split('/').next()?;
+    (!table.is_empty() && table != "rpc").then_some(table)
+}
+
+/// Añade un filtro `columna=eq.valor` al endpoint
+fn with_eq_filter(endpoint: &str, column: &str, value: &Value) -> String {
+    let value = match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    };
+    let encoded: String = url::form_urlencoded::byte_serialize(value.as_bytes()).collect();
+    let separator = if endpoint.contains('?') { '&' } else { '?' };
+    format!("{}{}{}=eq.{}", endpoint, separator, column, encoded)
+}
+
+// ============================================
+// MERGE
+// ============================================
+
+/// Fusión a tres bandas de un PATCH.
+///
+/// Para cada campo del cambio local: si el servidor conserva el valor de
+/// `base`, se aplica el local; si el servidor ya tiene el valor local, se
+/// omite; si ambos cambiaron a valores distintos, es un conflicto. Devuelve
+/// el PATCH resultante o la lista de campos en conflicto.
+pub fn merge_fields(
+    base: &Map<String, Value>,
+    local: &Map<String, Value>,
+    remote: &Map<String, Value>,
+) -> std::result::Result<Map<String, Value>, Vec<String>> {
+    let mut merged = Map::new();
+    let mut conflicts = Vec::new();
+
+    for (field, local_value) in local {
+        let remote_value = remote.get(field);
+        if remote_value == Some(local_value) {
+            continue;
+        }
+        if remote_value == base.get(field) {
+            merged.insert(field.clone(), local_value.clone());
+        } else {
+            conflicts.push(field.clone());
+        }
+    }
+
+    if conflicts.is_empty() {
+        Ok(merged)
+    } else {
+        Err(conflicts)
+    }
+}
+
+// ============================================
+// PERSISTENCIA
+// ============================================
+
+pub(crate) fn table_policy(store: &OfflineStore, table: &str) -> Result<TablePolicy> {
+    store.with_conn(|conn| {
+        let row = conn
+            .query_row(
+                "SELECT policy, version_column FROM conflict_policies WHERE table_name = ?1",
+                params![table],
+                |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)),
+            )
+            .optional()?;
+        Ok(match row {
+            Some((policy, version_column)) => TablePolicy {
+                table: table.to_string(),
+                policy: ConflictPolicy::parse(&policy),
+                version_column,
+            },
+            None => TablePolicy {
+                table: table.to_string(),
+                policy: ConflictPolicy::default(),
+                version_column: DEFAULT_VERSION_COLUMN.to_string(),
+            },
+        })
+    })
+}
+
+/// Columnas cifradas de los conflictos
+const SEALED_COLUMNS: [&str; 3] = ["base", "local", "remote"];
+
+/// Contexto del cifrado de una columna: ata el valor a su mutación
+fn sealed_aad(idempotency_key: &str, column: &str) -> String {
+    format!("sync_conflicts:{}:{}", idempotency_key, column)
+}
+
+fn record(
+    store: &OfflineStore,
+    table: &str,
+    mutation: &Mutation,
+    remote: Option<&Value>,
+    fields: &[String],
+) -> Result<i64> {
+    let seal = |column: &str, value: &str| {
+        store.seal_value(value, &sealed_aad(&mutation.idempotency_key, column))
+    };
+    let base = mutation
+        .base
+        .as_ref()
+        .map(|base| seal("base", &base.to_string()))
+        .transpose()?;
+    let local = seal("local", mutation.body.as_deref().unwrap_or("{}"))?;
+    let remote = remote
+        .map(|remote| seal("remote", &remote.to_string()))
+        .transpose()?;
+    store.with_conn(|conn| {
+        conn.execute(
+            "INSERT INTO sync_conflicts
+                (table_name, endpoint, idempotency_key, base, local, remote, fields, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                table,
+                mutation.endpoint,
+                mutation.idempotency_key,
+                base,
+                local,
+                remote,
+                serde_json::to_string(fields).unwrap_or_default(),
+                now_millis(),
+            ],
+        )?;
+        Ok(conn.last_insert_rowid())
+    })
+}
+
+fn list(store: &OfflineStore) -> Result<Vec<SyncConflict>> {
+    store.with_conn(|conn| {
+        let mut stmt = conn.prepare("SELECT * FROM sync_conflicts ORDER BY id")?;
+        let mut rows = stmt.query([])?;
+        let mut conflicts = Vec::new();
+        while let Some(row) = rows.next()? {
+            conflicts.push(SyncConflict::from_row(store, row)?);
+        }
+        Ok(conflicts)
+    })
+}
+
+/// Saca un conflicto de la tabla, dentro de la transacción de quien lo resuelve
+fn take(store: &OfflineStore, conn: &Connection, id: i64) -> Result<SyncConflict> {
+    let conflict = {
+        let mut stmt = conn.prepare("SELECT * FROM sync_conflicts WHERE id = ?1")?;
+        let mut rows = stmt.query(params![id])?;
+        match rows.next()? {
+            Some(row) => SyncConflict::from_row(store, row)?,
+            None => return Err(Error::NotFound(format!("no existe el conflicto {}", id))),
+        }
+    };
+    conn.execute("DELETE FROM sync_conflicts WHERE id = ?1", params![id])?;
+    Ok(conflict)
+}
+
+/// Recifra las columnas cifradas con `f` (ver `OfflineStore::reseal_column`),
+/// que recibe el contexto de cada valor
+pub(crate) fn reseal(
+    store: &OfflineStore,
+    mut f: impl FnMut(&str, &[u8]) -> Result<Option<Vec<u8>>>,
+) -> Result<usize> {
+    let mut changed = 0;
+    for column in SEALED_COLUMNS {
+        changed +=
+            store.reseal_column("sync_conflicts", "idempotency_key", column, |key, value| {
+                f(&sealed_aad(key, column), value)
+            })?;
+    }
+    Ok(changed)
+}
+
+pub(crate) fn emit_count<R: Runtime>(app_handle: &AppHandle<R>, store: &OfflineStore) {
+    let count = store.with_conn(|conn| {
+        Ok(
+            conn.query_row("SELECT COUNT(*) FROM sync_conflicts", [], |row| {
+                row.get::<_, u32>(0)
+            })?,
+        )
+    });
+    if let Ok(count) = count {
+        let _ = app_handle.emit(CONFLICTS_CHANGED_EVENT, count);
+    }
+}
+
+// ============================================
+// PATCH CON CONTROL DE VERSIÓN
+// ============================================
+
+/// Envía un PATCH solo si la fila sigue en la versión de `base`
+async fn guarded_patch(
+    http: &HttpClient,
+    mutation: &Mutation,
+    body: &str,
+    version_column: &str,
+    version: &Value,
+    access_token: &str,
+) -> Result<(SupabaseResponse, Option<Value>)> {
+    let endpoint = with_eq_filter(&mutation.endpoint, version_column, version);
+    let response = send_mutation(
+        http,
+        MutationMethod::Patch,
+        &endpoint,
+        Some(body),
+        access_token,
+        &mutation.idempotency_key,
+        Some("return=representation"),
+    )
+    .await?;
+    let updated = serde_json::from_str::<Vec<Value>>(&response.body)
+        .ok()
+        .and_then(|rows| rows.into_iter().next());
+    Ok((response, updated))
+}
+
+/// Envía un PATCH detectando si la fila cambió desde `mutation.base` y
+/// aplicando la política de su tabla.
+pub(crate) async fn patch_checked(
+    http: &HttpClient,
+    store: &OfflineStore,
+    mutation: &Mutation,
+    access_token: &str,
+) -> Result<PatchOutcome> {
+    let body = mutation.body.as_deref().unwrap_or("{}");
+    let plain = || async {
+        let response = send_mutation(
+            http,
+            MutationMethod::Patch,
+            &mutation.endpoint,
+            Some(body),
+            access_token,
+            &mutation.idempotency_key,
+            None,
+        )
+        .await?;
+        Ok::<_, Error>(PatchOutcome::Sent(response))
+    };
+
+    let Some(table) = table_from_endpoint(&mutation.endpoint) else {
+        return plain().await;
+    };
+    let policy = table_policy(store, table)?;
+    let Some(base_version) = mutation
+        .base
+        .as_ref()
+        .and_then(|base| base.get(&policy.version_column))
+        .filter(|version| !version.is_null())
+    else {
+        return plain().await;
+    };
+
+    let (response, updated) = guarded_patch(
+        http,
+        mutation,
+        body,
+        &policy.version_column,
+        base_version,
+        access_token,
+    )
+    .await?;
+    if !response.is_success() || updated.is_some() {
+        return Ok(PatchOutcome::Sent(response));
+    }
+
+    // Ninguna fila coincidió con la versión base: hay conflicto
+    let response = send_get(http, &mutation.endpoint, access_token).await?;
+    if !response.is_success() {
+        return Ok(PatchOutcome::Sent(response));
+    }
+    let remote = serde_json::from_str::<Vec<Value>>(&response.body)
+        .ok()
+        .and_then(|rows| rows.into_iter().next());
+
+    let Some(remote) = remote else {
+        // La fila ya no existe en el servidor
+        return match policy.policy {
+            ConflictPolicy::ServerWins => Ok(PatchOutcome::Dropped(None)),
+            _ => Ok(PatchOutcome::Conflict(record(
+                store,
+                table,
+                mutation,
+                None,
+                &[],
+            )?)),
+        };
+    };
+
+    match policy.policy {
+        ConflictPolicy::ServerWins => Ok(PatchOutcome::Dropped(Some(remote))),
+        ConflictPolicy::ClientWins => plain().await,
+        ConflictPolicy::FieldMerge => {
+            let empty = Map::new();
+            let base = mutation
+                .base
+                .as_ref()
+                .and_then(Value::as_object)
+                .unwrap_or(&empty);
+            let local: Map<String, Value> = serde_json::from_str(body).map_err(|e| {
+                Error::Validation(format!("el cuerpo del PATCH no es un objeto JSON: {}", e))
+            })?;
+            let remote_fields = remote.as_object().unwrap_or(&empty);
+
+            match merge_fields(base, &local, remote_fields) {
+                Ok(merged) if merged.is_empty() => Ok(PatchOutcome::Dropped(Some(remote))),
+                Ok(merged) => {
+                    let Some(remote_version) = remote.get(&policy.version_column) else {
+                        return Ok(PatchOutcome::Conflict(record(
+                            store,
+                            table,
+                            mutation,
+                            Some(&remote),
+                            &[],
+                        )?));
+                    };
+                    let merged = Value::Object(merged).to_string();
+                    let (response, updated) = guarded_patch(
+                        http,
+                        mutation,
+                        &merged,
+                        &policy.version_column,
+                        remote_version,
+                        access_token,
+                    )
+                    .await?;
+                    if !response.is_success() || updated.is_some() {
+                        Ok(PatchOutcome::Sent(response))
+                    } else {
+                        // Volvió a cambiar entre la lectura y el PATCH
+                        Ok(PatchOutcome::Conflict(record(
+                            store,
+                            table,
+                            mutation,
+                            Some(&remote),
+                            &[],
+                        )?))
+                    }
+                }
+                Err(fields) => Ok(PatchOutcome::Conflict(record(
+                    store,
+                    table,
+                    mutation,
+                    Some(&remote),
+                    &fields,
+                )?)),
+            }
+        }
+    }
+}
+
+// ============================================
+// COMMANDS
+// ============================================
+
+/// Lista los conflictos de sincronización sin resolver
+#[tauri::command]
+pub async fn get_sync_conflicts(store: State<'_, OfflineStore>) -> Result<Vec<SyncConflict>> {
+    list(&store)
+}
+
+/// Resuelve un conflicto conservando el servidor, el cambio local o una
+/// versión fusionada por el usuario. Los dos últimos se envían por la cola.
+#[tauri::command]
+pub async fn resolve_sync_conflict<R: Runtime>(
+    app_handle: AppHandle<R>,
+    store: State<'_, OfflineStore>,
+    outbox: State<'_, Outbox>,
+    id: i64,
+    resolution: ConflictResolution,
+    merged: Option<Value>,
+) -> Result<()> {
+    if resolution == ConflictResolution::Merged && !merged.as_ref().is_some_and(Value::is_object) {
+        return Err(Error::Validation(
+            "la resolución fusionada requiere un objeto `merged`".into(),
+        ));
+    }
+
+    // El conflicto solo desaparece si su cambio quedó en la cola
+    let queued = store.with_conn(|conn| {
+        let tx = conn.transaction()?;
+        let conflict = take(&store, &tx, id)?;
+        let body = match resolution {
+            ConflictResolution::Server => None,
+            ConflictResolution::Client => Some(conflict.local),
+            ConflictResolution::Merged => merged,
+        };
+        if let Some(body) = &body {
+            // Sin `base`: el usuario ya decidió y se sobrescribe el servidor
+            outbox::enqueue_in(
+                &store,
+                &tx,
+                &Mutation {
+                    method: MutationMethod::Patch,
+                    endpoint: conflict.endpoint,
+                    body: Some(body.to_string()),
+                    base: None,
+                    idempotency_key: outbox::new_idempotency_key(),
+                },
+            )?;
+        }
+        tx.commit()?;
+        Ok(body.is_some())
+    })?;
+    if queued {
+        outbox::emit_counts(&app_handle, &store);
+        outbox.wake();
+    }
+    emit_count(&app_handle, &store);
+    Ok(())
+}
+
+/// Lista las políticas de conflicto configuradas
+#[tauri::command]
+pub async fn get_conflict_policies(store: State<'_, OfflineStore>) -> Result<Vec<TablePolicy>> {
+    store.with_conn(|conn| {
+        let mut stmt = conn.prepare(
+            "SELECT table_name, policy, version_column FROM conflict_policies ORDER BY table_name",
+        )?;
+        let policies = stmt
+            .query_map([], |row| {
+                Ok(TablePolicy {
+                    table: row.get(0)?,
+                    policy: ConflictPolicy::parse(&row.get::<_, String>(1)?),
+                    version_column: row.get(2)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(policies)
+    })
+}
+
+/// Configura la política de conflictos de una tabla
+#[tauri::command]
+pub async fn set_conflict_policy(
+    store: State<'_, OfflineStore>,
+    table: String,
+    policy: ConflictPolicy,
+    version_column: Option<String>,
+) -> Result<()> {
+    if table.is_empty() {
+        return Err(Error::Validation("la tabla no puede estar vacía".into()));
+    }
+    let version_column = version_column.unwrap_or_else(|| DEFAULT_VERSION_COLUMN.to_string());
+    // Va en el filtro de cada PATCH con `base`
+    if !is_identifier(&version_column) {
+        return Err(Error::Validation(format!(
+            "columna de versión no válida: {:?}",
+            version_column
+        )));
+    }
+    store.with_conn(|conn| {
+        conn.execute(
+            "INSERT INTO conflict_policies (table_name, policy, version_column)
+             VALUES (?1, ?2, ?3)
+             ON CONFLICT(table_name) DO UPDATE SET
+                policy = excluded.policy,
+                version_column = excluded.version_column",
+            params![table, policy.as_str(), version_column],
+        )?;
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn object(value: Value) -> Map<String, Value> {
+        value.as_object().unwrap().clone()
+    }
+
+    #[test]
+    fn merge_keeps_local_fields_the_server_did_not_touch() {
+        let base = object(json!({ "notas": "a", "peso": 70, "updated_at": "1" }));
+        let local = object(json!({ "notas": "b" }));
+        let remote = object(json!({ "notas": "a", "peso": 72, "updated_at": "2" }));
+        assert_eq!(merge_fields(&base, &local, &remote).unwrap(), local);
+    }
+
+    #[test]
+    fn merge_reports_fields_changed_on_both_sides() {
+        let base = object(json!({ "notas": "a", "peso": 70 }));
+        let local = object(json!({ "notas": "b", "peso": 71 }));
+        let remote = object(json!({ "notas": "c", "peso": 71 }));
+        assert_eq!(
+            merge_fields(&base, &local, &remote).unwrap_err(),
+            vec!["notas".to_string()]
+        );
+    }
+
+    #[test]
+    fn merge_skips_fields_that_already_match() {
+        let base = object(json!({ "estado": "pendiente" }));
+        let local = object(json!({ "estado": "atendida" }));
+        let remote = object(json!({ "estado": "atendida" }));
+        assert!(merge_fields(&base, &local, &remote).unwrap().is_empty());
+    }
+
+    #[test]
+    fn endpoints_map_to_tables_and_version_filters() {
+        assert_eq!(
+            table_from_endpoint("/rest/v1/appointments?id=eq.5"),
+            Some("appointments")
+        );
+        assert_eq!(table_from_endpoint("/rest/v1/rpc/fn"), None);
+        assert_eq!(table_from_endpoint("/auth/v1/user"), None);
+        assert_eq!(
+            with_eq_filter(
+                "/rest/v1/a?id=eq.5",
+                "updated_at",
+                &json!("2024-05-01T10:00:00+00:00")
+            ),
+            "/rest/v1/a?id=eq.5&updated_at=eq.2024-05-01T10%3A00%3A00%2B00%3A00"
+        );
+        assert_eq!(
+            with_eq_filter("/rest/v1/a", "version", &json!(3)),
+            "/rest/v1/a?version=eq.3"
+        );
+    }
+
+    #[test]
+    fn policies_default_to_field_merge_on_updated_at() {
+        let store = OfflineStore::open_in_memory().unwrap();
+        let policy = table_policy(&store, "consultations").unwrap();
+        assert_eq!(policy.policy, ConflictPolicy::FieldMerge);
+        assert_eq!(policy.version_column, "updated_at");
+    }
+
+    #[test]
+    fn conflicts_are_stored_sealed() {
+        let (store, _root) = crate::testing::unlocked_store();
+        let mut mutation = Mutation::new(
+            MutationMethod::Patch,
+            "/rest/v1/citas?id=eq.1".into(),
+            Some(r#"{"notas":"b"}"#.into()),
+            Some("k1".into()),
+        );
+        mutation.base = Some(json!({ "notas": "a", "updated_at": "1" }));
+        let remote = json!({ "notas": "c", "updated_at": "2" });
+        let id = record(&store, "citas", &mutation, Some(&remote), &["notas".into()]).unwrap();
+
+        let sealed = store
+            .with_conn(|conn| {
+                Ok(conn.query_row(
+                    "SELECT typeof(base) || typeof(local) || typeof(remote)
+                     FROM sync_conflicts WHERE id = ?1",
+                    params![id],
+                    |row| row.get::<_, String>(0),
+                )?)
+            })
+            .unwrap();
+        assert_eq!(sealed, "blobblobblob");
+
+        let conflict = store.with_conn(|conn| take(&store, conn, id)).unwrap();
+        assert_eq!(conflict.base, mutation.base);
+        assert_eq!(conflict.local, json!({ "notas": "b" }));
+        assert_eq!(conflict.remote, Some(remote));
+        assert!(list(&store).unwrap().is_empty());
+    }
+}
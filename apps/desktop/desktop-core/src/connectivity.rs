@@ -0,0 +1,287 @@
+// Monitor de conectividad
+// Sondea periódicamente el endpoint de salud de Supabase y mantiene un estado
+// online/degradado/offline con histéresis, para que un sondeo aislado lento o
+// fallido no haga parpadear la interfaz.
+
+use serde::Serialize;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager, Runtime, State};
+use tokio::sync::Notify;
+
+use crate::error::Result;
+use crate::http::HttpClient;
+use crate::outbox::Outbox;
+use crate::store::now_millis;
+
+/// Evento emitido cuando cambia el estado de conectividad
+pub const CONNECTIVITY_CHANGED_EVENT: &str = "connectivity-changed";
+
+/// Endpoint de salud de Supabase (GoTrue responde sin sesión)
+const HEALTH_ENDPOINT: &str = "/auth/v1/health";
+/// Tiempo máximo de un sondeo
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+/// Por encima de esta latencia la conexión se considera degradada
+const DEGRADED_LATENCY: Duration = Duration::from_millis(1_500);
+/// Intervalo entre sondeos con conexión
+const ONLINE_INTERVAL: Duration = Duration::from_secs(30);
+/// Intervalo entre sondeos sin conexión o con conexión degradada
+const OFFLINE_INTERVAL: Duration = Duration::from_secs(10);
+/// Sondeos consecutivos que deben coincidir para cambiar de estado
+const HYSTERESIS: u32 = 2;
+
+/// Estado de la conexión con Supabase
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConnectivityState {
+    Online,
+    /// Supabase responde, pero lento o con errores del servidor
+    Degraded,
+    Offline,
+}
+
+/// Estado que devuelve `get_connectivity_status` y acompaña al evento
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConnectivityStatus {
+    pub state: ConnectivityState,
+    /// Latencia del último sondeo que obtuvo respuesta
+    pub latency_ms: Option<u64>,
+    /// Milisegundos desde epoch del último sondeo; `None` si aún no hubo
+    pub checked_at: Option<i64>,
+}
+
+/// Resultado de un sondeo
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Probe {
+    /// Respuesta 2xx, con su latencia
+    Ok(Duration),
+    /// Respuesta de error del servidor, con su latencia
+    ServerError(Duration),
+    /// Sin respuesta: error de red o timeout
+    Failed,
+}
+
+impl Probe {
+    fn state(self) -> ConnectivityState {
+        match self {
+            Probe::Ok(latency) if latency < DEGRADED_LATENCY => ConnectivityState::Online,
+            Probe::Ok(_) | Probe::ServerError(_) => ConnectivityState::Degraded,
+            Probe::Failed => ConnectivityState::Offline,
+        }
+    }
+
+    fn latency(self) -> Option<Duration> {
+        match self {
+            Probe::Ok(latency) | Probe::ServerError(latency) => Some(latency),
+            Probe::Failed => None,
+        }
+    }
+}
+
+/// Aplica la histéresis: el estado solo cambia tras `HYSTERESIS` sondeos
+/// consecutivos que coinciden en un estado distinto al actual.
+#[derive(Debug)]
+struct Tracker {
+    status: ConnectivityStatus,
+    candidate: Option<(ConnectivityState, u32)>,
+}
+
+impl Default for Tracker {
+    fn default() -> Self {
+        Self {
+            status: ConnectivityStatus {
+                state: ConnectivityState::Offline,
+                latency_ms: None,
+                checked_at: None,
+            },
+            candidate: None,
+        }
+    }
+}
+
+impl Tracker {
+    /// Registra un sondeo y devuelve `true` si cambió el estado
+    fn observe(&mut self, probe: Probe, now: i64) -> bool {
+        let first = self.status.checked_at.is_none();
+        self.status.checked_at = Some(now);
+        if let Some(latency) = probe.latency() {
+            self.status.latency_ms = Some(latency.as_millis() as u64);
+        }
+
+        let observed = probe.state();
+        if observed == self.status.state {
+            self.candidate = None;
+            return false;
+        }
+        // El primer sondeo fija el estado inicial sin esperar confirmación
+        let seen = match self.candidate {
+            Some((state, count)) if state == observed => count + 1,
+            _ => 1,
+        };
+        if first || seen >= HYSTERESIS {
+            self.status.state = observed;
+            self.candidate = None;
+            true
+        } else {
+            self.candidate = Some((observed, seen));
+            false
+        }
+    }
+}
+
+/// Estado compartido del monitor
+#[derive(Default)]
+pub struct ConnectivityMonitor {
+    tracker: Mutex<Tracker>,
+    notify: Notify,
+}
+
+impl ConnectivityMonitor {
+    pub fn status(&self) -> ConnectivityStatus {
+        self.tracker.lock().unwrap().status.clone()
+    }
+
+    pub fn is_online(&self) -> bool {
+        self.status().state != ConnectivityState::Offline
+    }
+
+    /// Adelanta el próximo sondeo
+    pub fn wake(&self) {
+        self.notify.notify_one();
+    }
+}
+
+/// Sondea el endpoint de salud de Supabase. Sin reintentos: la histéresis
+/// ya absorbe los fallos aislados.
+async fn probe(http: &HttpClient) -> Probe {
+    let Ok(config) = http.supabase() else {
+        return Probe::Failed;
+    };
+
+    let started = Instant::now();
+    match http
+        .client()
+        .get(format!("{}{}", config.url, HEALTH_ENDPOINT))
+        .header("apikey", &config.anon_key)
+        .timeout(PROBE_TIMEOUT)
+        .send()
+        .await
+    {
+        Ok(response) if response.status().is_server_error() => {
+            Probe::ServerError(started.elapsed())
+        }
+        // Cualquier otra respuesta demuestra que Supabase es alcanzable
+        Ok(_) => Probe::Ok(started.elapsed()),
+        Err(_) => Probe::Failed,
+    }
+}
+
+/// Sondea una vez, actualiza el estado y avisa si cambió
+async fn check<R: Runtime>(app_handle: &AppHandle<R>) -> ConnectivityStatus {
+    let result = probe(&app_handle.state::<HttpClient>()).await;
+    let monitor = app_handle.state::<ConnectivityMonitor>();
+    let (changed, status) = {
+        let mut tracker = monitor.tracker.lock().unwrap();
+        let changed = tracker.observe(result, now_millis());
+        (changed, tracker.status.clone())
+    };
+
+    if changed {
+        let _ = app_handle.emit(CONNECTIVITY_CHANGED_EVENT, &status);
+        // Al recuperar la conexión se reenvía la cola sin esperar al backoff
+        if status.state != ConnectivityState::Offline {
+            app_handle.state::<Outbox>().wake();
+        }
+    }
+    status
+}
+
+/// Arranca el monitor en segundo plano
+pub(crate) fn spawn_monitor<R: Runtime>(app_handle: AppHandle<R>) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let status = check(&app_handle).await;
+            let wait = match status.state {
+                ConnectivityState::Online => ONLINE_INTERVAL,
+                ConnectivityState::Degraded | ConnectivityState::Offline => OFFLINE_INTERVAL,
+            };
+            let monitor = app_handle.state::<ConnectivityMonitor>();
+            tokio::select! {
+                _ = monitor.notify.notified() => {}
+                _ = tokio::time::sleep(wait) => {}
+            }
+        }
+    });
+}
+
+// ============================================
+// COMMANDS
+// ============================================
+
+/// Verifica la conectividad con Supabase.
+/// Devuelve `true` si el monitor considera la conexión online o degradada.
+#[tauri::command]
+pub async fn check_connectivity(monitor: State<'_, ConnectivityMonitor>) -> Result<bool> {
+    Ok(monitor.is_online())
+}
+
+/// Devuelve el estado de conectividad y la latencia del último sondeo.
+/// Con `refresh` sondea de inmediato en lugar de usar el último resultado.
+#[tauri::command]
+pub async fn get_connectivity_status<R: Runtime>(
+    app_handle: AppHandle<R>,
+    refresh: Option<bool>,
+) -> Result<ConnectivityStatus> {
+    if refresh.unwrap_or(false) {
+        return Ok(check(&app_handle).await);
+    }
+    Ok(app_handle.state::<ConnectivityMonitor>().status())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FAST: Probe = Probe::Ok(Duration::from_millis(80));
+    const SLOW: Probe = Probe::Ok(Duration::from_millis(3_000));
+
+    #[test]
+    fn first_probe_sets_the_initial_state() {
+        let mut tracker = Tracker::default();
+        assert!(tracker.observe(FAST, 1));
+        assert_eq!(tracker.status.state, ConnectivityState::Online);
+        assert_eq!(tracker.status.latency_ms, Some(80));
+    }
+
+    #[test]
+    fn a_single_failure_does_not_flip_the_state() {
+        let mut tracker = Tracker::default();
+        tracker.observe(FAST, 1);
+        assert!(!tracker.observe(Probe::Failed, 2));
+        assert!(!tracker.observe(FAST, 3));
+        assert!(!tracker.observe(Probe::Failed, 4));
+        assert_eq!(tracker.status.state, ConnectivityState::Online);
+
+        assert!(tracker.observe(Probe::Failed, 5));
+        assert_eq!(tracker.status.state, ConnectivityState::Offline);
+        // Se conserva la última latencia conocida
+        assert_eq!(tracker.status.latency_ms, Some(80));
+    }
+
+    #[test]
+    fn slow_or_failing_server_is_degraded() {
+        assert_eq!(SLOW.state(), ConnectivityState::Degraded);
+        assert_eq!(
+            Probe::ServerError(Duration::from_millis(50)).state(),
+            ConnectivityState::Degraded
+        );
+
+        let mut tracker = Tracker::default();
+        tracker.observe(Probe::Failed, 1);
+        assert!(!tracker.observe(SLOW, 2));
+        assert!(tracker.observe(SLOW, 3));
+        assert_eq!(tracker.status.state, ConnectivityState::Degraded);
+        assert_eq!(tracker.status.checked_at, Some(3));
+    }
+}
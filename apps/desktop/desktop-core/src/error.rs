@@ -0,0 +1,165 @@
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+
+/// Error devuelto por todos los comandos del plugin.
+///
+/// Se serializa como `{ code, message, status }` para que el webview pueda
+/// decidir según `code` sin interpretar el mensaje. Los códigos son estables:
+/// `IO`, `NOT_FOUND`, `NETWORK`, `UNCONFIRMED`, `HTTP`, `AUTH`, `VALIDATION`,
+/// `STORAGE`, `QUEUED` y `CONFLICT`.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// Fallo de entrada/salida en disco o del sistema
+    #[error("{0}")]
+    Io(String),
+    /// El recurso solicitado no existe
+    #[error("{0}")]
+    NotFound(String),
+    /// No se pudo completar la petición (sin conexión, DNS, timeout...)
+    #[error("{0}")]
+    Network(String),
+    /// La mutación se envió pero no llegó respuesta: el servidor pudo haberla
+    /// aplicado, así que no se reintenta sola
+    #[error("sin respuesta del servidor; revisa si el cambio se guardó: {0}")]
+    Unconfirmed(String),
+    /// El servidor respondió con un estado HTTP de error
+    #[error("HTTP {status}: {message}")]
+    Http { status: u16, message: String },
+    /// Credenciales ausentes, inválidas o expiradas. `status` es el estado
+    /// HTTP (401) cuando lo rechazó el servidor; `None` si es un error local,
+    /// como no tener sesión o un secreto de almacenamiento incorrecto.
+    #[error("{message}")]
+    Auth {
+        message: String,
+        status: Option<u16>,
+    },
+    /// Los argumentos del comando no son válidos
+    #[error("{0}")]
+    Validation(String),
+    /// Fallo del almacenamiento local de la aplicación
+    #[error("{0}")]
+    Storage(String),
+    /// Sin conexión: la mutación quedó en la cola offline y se enviará después
+    #[error("sin conexión: la operación {idempotency_key} quedó en cola (#{id})")]
+    Queued { id: i64, idempotency_key: String },
+    /// La fila cambió en el servidor y no se pudo fusionar; ver `get_sync_conflicts`
+    #[error("conflicto de sincronización sin resolver (#{id})")]
+    Conflict { id: i64 },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    /// Error de autenticación local, sin respuesta HTTP detrás
+    pub fn auth(message: impl Into<String>) -> Self {
+        Error::Auth {
+            message: message.into(),
+            status: None,
+        }
+    }
+
+    /// Código estable que identifica el tipo de error en el frontend
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::Io(_) => "IO",
+            Error::NotFound(_) => "NOT_FOUND",
+            Error::Network(_) => "NETWORK",
+            Error::Unconfirmed(_) => "UNCONFIRMED",
+            Error::Http { .. } => "HTTP",
+            Error::Auth { .. } => "AUTH",
+            Error::Validation(_) => "VALIDATION",
+            Error::Storage(_) => "STORAGE",
+            Error::Queued { .. } => "QUEUED",
+            Error::Conflict { .. } => "CONFLICT",
+        }
+    }
+
+    /// Estado HTTP asociado, si lo hay
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            Error::Http { status, .. } => Some(*status),
+            Error::Auth { status, .. } => *status,
+            _ => None,
+        }
+    }
+
+    /// Error de una mutación: sin un fallo de conexión o DNS la petición pudo
+    /// llegar al servidor
+    pub(crate) fn unrepeatable(e: reqwest::Error) -> Self {
+        if e.status().is_none() && !e.is_connect() && !e.is_builder() {
+            Error::Unconfirmed(e.to_string())
+        } else {
+            e.into()
+        }
+    }
+
+    /// Error correspondiente a una respuesta HTTP no exitosa. El mensaje se
+    /// toma del cuerpo de error de PostgREST o GoTrue si lo hay. Un 401 pasa
+    /// a `AUTH` conservando el estado, un 404 a `NOT_FOUND` y el resto queda
+    /// como `HTTP`.
+    pub fn from_response(status: reqwest::StatusCode, body: &str) -> Self {
+        let message = serde_json::from_str::<serde_json::Value>(body)
+            .ok()
+            .and_then(|json| {
+                ["message", "msg", "error_description", "error"]
+                    .iter()
+                    .find_map(|field| json.get(field)?.as_str().map(str::to_string))
+            })
+            .or_else(|| Some(body.trim().to_string()).filter(|body| !body.is_empty()))
+            .unwrap_or_else(|| status.canonical_reason().unwrap_or("").to_string());
+
+        match status {
+            reqwest::StatusCode::UNAUTHORIZED => Error::Auth {
+                message,
+                status: Some(status.as_u16()),
+            },
+            reqwest::StatusCode::NOT_FOUND => Error::NotFound(message),
+            _ => Error::Http {
+                status: status.as_u16(),
+                message,
+            },
+        }
+    }
+}
+
+impl Serialize for Error {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("Error", 3)?;
+        state.serialize_field("code", self.code())?;
+        state.serialize_field("message", &self.to_string())?;
+        state.serialize_field("status", &self.status())?;
+        state.end()
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        match e.kind() {
+            std::io::ErrorKind::NotFound => Error::NotFound(e.to_string()),
+            _ => Error::Io(e.to_string()),
+        }
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(e: reqwest::Error) -> Self {
+        match e.status() {
+            Some(status) => Error::Http {
+                status: status.as_u16(),
+                message: e.to_string(),
+            },
+            None => Error::Network(e.to_string()),
+        }
+    }
+}
+
+impl From<rusqlite::Error> for Error {
+    fn from(e: rusqlite::Error) -> Self {
+        Error::Storage(e.to_string())
+    }
+}
+
+impl From<tauri::Error> for Error {
+    fn from(e: tauri::Error) -> Self {
+        Error::Io(e.to_string())
+    }
+}
@@ -0,0 +1,89 @@
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Read};
+use tauri::{AppHandle, Manager, Runtime, State};
+
+use crate::error::{Error, Result};
+use crate::offline::app_data_dir;
+use crate::paths::{ensure_openable, ensure_within, resolve_in};
+use crate::profiles::{ensure_visible, require_active};
+use crate::store::OfflineStore;
+use crate::vault::{is_sealed_file, FileOpener, OPENED_DIR};
+
+/// Guarda un archivo localmente, cifrado con la clave del almacén
+#[tauri::command]
+pub async fn save_file_locally(
+    store: State<'_, OfflineStore>,
+    filename: String,
+    data: Vec<u8>,
+    subfolder: Option<String>,
+) -> Result<String> {
+    let file_path = resolve_in(
+        &require_active(&store)?.dir,
+        subfolder.as_deref(),
+        &filename,
+    )?;
+
+    if let Some(file_dir) = file_path.parent() {
+        fs::create_dir_all(file_dir)?;
+    }
+    fs::write(&file_path, store.vault().seal_file(&data)?)?;
+
+    Ok(file_path.to_string_lossy().to_string())
+}
+
+/// Abre un archivo con la aplicación predeterminada.
+/// Solo se permiten documentos (ver `paths::ensure_openable`) dentro de los
+/// directorios de la aplicación y fuera de los perfiles de otros usuarios. Los
+/// archivos cifrados se descifran antes en una copia dentro de la caché, que
+/// se borra al bloquear el almacenamiento, al cambiar de perfil o al volver a
+/// arrancar la app.
+#[tauri::command]
+pub async fn open_file<R: Runtime>(
+    app_handle: AppHandle<R>,
+    store: State<'_, OfflineStore>,
+    path: String,
+) -> Result<()> {
+    let resolver = app_handle.path();
+    let allowed_roots: Vec<_> = [
+        resolver.app_data_dir(),
+        resolver.app_local_data_dir(),
+        resolver.app_cache_dir(),
+    ]
+    .into_iter()
+    .filter_map(|dir| dir.ok())
+    .collect();
+
+    let path = ensure_within(path.as_ref(), &allowed_roots)?;
+    ensure_openable(&path)?;
+    ensure_visible(&store, &app_data_dir(&app_handle)?, &path)?;
+    let mut head = Vec::new();
+    File::open(&path)?.take(4).read_to_end(&mut head)?;
+    if !is_sealed_file(&head) {
+        return Ok(open::that(path)?);
+    }
+
+    let filename = path
+        .file_name()
+        .ok_or_else(|| Error::Validation("ruta sin nombre de archivo".into()))?;
+    let opened_dir = resolver.app_cache_dir()?.join(OPENED_DIR);
+    fs::create_dir_all(&opened_dir)?;
+    let opened = opened_dir.join(filename);
+    let mut reader = FileOpener::new(store.vault(), BufReader::new(File::open(&path)?))?;
+    reader.copy_to(&mut BufWriter::new(File::create(&opened)?))?;
+    Ok(open::that(opened)?)
+}
+
+/// Lee un archivo local, descifrándolo si hace falta
+#[tauri::command]
+pub async fn read_file_locally(
+    store: State<'_, OfflineStore>,
+    filename: String,
+    subfolder: Option<String>,
+) -> Result<Vec<u8>> {
+    let file_path = resolve_in(
+        &require_active(&store)?.dir,
+        subfolder.as_deref(),
+        &filename,
+    )?;
+    store.vault().open_file(fs::read(file_path)?)
+}
@@ -0,0 +1,103 @@
+// Tareas en segundo plano
+// Registro de las tareas largas en curso (descargas paginadas,
+// transferencias de Storage) con su marca de cancelación. Las tareas la
+// consultan entre página y página o entre bloque y bloque.
+
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::error::{Error, Result};
+use crate::store::OfflineStore;
+
+/// Estado persistido de una tarea
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Running,
+    /// Cancelada por el usuario o interrumpida; se puede reanudar
+    Cancelled,
+    /// Falló; se puede reanudar
+    Failed,
+    Completed,
+}
+
+impl JobStatus {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            JobStatus::Running => "running",
+            JobStatus::Cancelled => "cancelled",
+            JobStatus::Failed => "failed",
+            JobStatus::Completed => "completed",
+        }
+    }
+
+    pub(crate) fn parse(status: &str) -> Self {
+        match status {
+            "running" => JobStatus::Running,
+            "failed" => JobStatus::Failed,
+            "completed" => JobStatus::Completed,
+            _ => JobStatus::Cancelled,
+        }
+    }
+}
+
+/// Tareas en curso en esta sesión, por id
+#[derive(Default)]
+pub struct Jobs {
+    running: Mutex<HashMap<String, Arc<AtomicBool>>>,
+}
+
+impl Jobs {
+    /// Registra una tarea y devuelve su marca de cancelación
+    pub(crate) fn register(&self, id: &str) -> Result<Arc<AtomicBool>> {
+        let mut running = self.running.lock().unwrap();
+        if running.contains_key(id) {
+            return Err(Error::Validation(format!(
+                "la tarea {} ya está en curso",
+                id
+            )));
+        }
+        let cancel = Arc::new(AtomicBool::new(false));
+        running.insert(id.to_string(), cancel.clone());
+        Ok(cancel)
+    }
+
+    pub(crate) fn finish(&self, id: &str) {
+        self.running.lock().unwrap().remove(id);
+    }
+
+    /// Pide cancelar una tarea; `false` si no está en curso
+    pub(crate) fn cancel(&self, id: &str) -> bool {
+        match self.running.lock().unwrap().get(id) {
+            Some(cancel) => {
+                cancel.store(true, Ordering::Relaxed);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub(crate) fn is_running(&self, id: &str) -> bool {
+        self.running.lock().unwrap().contains_key(id)
+    }
+}
+
+/// Usuario dueño del perfil abierto al empezar una tarea. Si cambia, el
+/// avance pertenece a una base ya cerrada y la tarea debe detenerse.
+pub(crate) struct ProfileGuard(Option<String>);
+
+impl ProfileGuard {
+    pub(crate) fn new(store: &OfflineStore) -> Self {
+        Self(store.active_profile().map(|profile| profile.user_id))
+    }
+
+    pub(crate) fn check(&self, store: &OfflineStore) -> Result<()> {
+        if store.active_profile().map(|profile| profile.user_id) == self.0 {
+            Ok(())
+        } else {
+            Err(Error::Storage("el perfil cambió durante la tarea".into()))
+        }
+    }
+}
@@ -0,0 +1,171 @@
+// Backend compartido de las apps de escritorio de Red Salud
+// Médico, corporativo y farmacia registran este plugin para obtener el
+// almacenamiento offline, el proxy de Supabase y las operaciones de archivos.
+
+use tauri::{
+    plugin::{Builder, TauriPlugin},
+    Manager, Runtime,
+};
+
+pub mod cache;
+pub mod config;
+pub mod conflicts;
+pub mod connectivity;
+pub mod error;
+mod files;
+pub mod http;
+pub mod jobs;
+pub mod notifications;
+mod offline;
+pub mod outbox;
+pub mod paged;
+pub mod paths;
+pub mod pharmacy;
+pub mod profiles;
+pub mod query;
+pub mod realtime;
+pub mod response;
+pub mod session;
+pub mod storage;
+mod store;
+mod supabase;
+#[cfg(test)]
+mod testing;
+pub mod vault;
+mod window;
+
+pub use error::{Error, Result};
+pub use response::SupabaseResponse;
+pub use store::{OfflineEntry, OfflineStore};
+
+/// Nombre del plugin. Desde el webview los comandos se invocan como
+/// `plugin:red-salud-desktop-core|<comando>`.
+pub const PLUGIN_NAME: &str = "red-salud-desktop-core";
+
+/// Inicializa el plugin con todos los comandos compartidos
+pub fn init<R: Runtime>() -> TauriPlugin<R> {
+    Builder::new(PLUGIN_NAME)
+        .setup(|app, _api| {
+            let info = app.package_info();
+            let user_agent = format!(
+                "{}/{} {}/{}",
+                info.name,
+                info.version,
+                PLUGIN_NAME,
+                env!("CARGO_PKG_VERSION")
+            );
+            let config = config::AppConfig::load(app.path().app_config_dir().ok().as_deref());
+            // Con una configuración no válida la app arranca, pero el proxy
+            // responde con el error (ver `get_effective_config`)
+            app.manage(
+                http::HttpClient::new(&config.http, &user_agent)?.with_supabase(config.supabase()),
+            );
+            app.manage(config);
+            // Sin perfil abierto hasta que el usuario inicia sesión
+            app.manage(OfflineStore::open_in_memory()?);
+            app.manage(outbox::Outbox::default());
+            app.manage(jobs::Jobs::default());
+            app.manage(connectivity::ConnectivityMonitor::default());
+            app.manage(session::SessionManager::default());
+            app.manage(realtime::RealtimeBridge::default());
+            // Solo la app de farmacia la abre, con `pharmacy::setup`
+            app.manage(pharmacy::PharmacyDb::default());
+            app.manage(pharmacy::fiscal::FiscalPrinterState::default());
+            // Copias descifradas que quedaran de una sesión anterior
+            vault::clear_opened(app);
+            outbox::spawn_worker(app.clone());
+            connectivity::spawn_monitor(app.clone());
+            session::spawn_refresher(app.clone());
+            realtime::spawn_bridge(app.clone());
+            Ok(())
+        })
+        .invoke_handler(tauri::generate_handler![
+            offline::get_offline_data,
+            offline::save_offline_data,
+            offline::delete_offline_data,
+            offline::clear_offline_data,
+            offline::get_offline_keys,
+            connectivity::check_connectivity,
+            connectivity::get_connectivity_status,
+            window::minimize_window,
+            window::maximize_window,
+            window::close_window,
+            supabase::get_supabase_config,
+            config::get_effective_config,
+            session::set_session,
+            session::clear_session,
+            session::get_session_status,
+            session::refresh_session,
+            supabase::supabase_get,
+            supabase::supabase_query,
+            supabase::supabase_post,
+            supabase::supabase_patch,
+            supabase::supabase_delete,
+            outbox::get_outbox,
+            outbox::sync_outbox,
+            outbox::retry_outbox_entry,
+            outbox::discard_outbox_entry,
+            paged::start_paged_fetch,
+            paged::cancel_paged_fetch,
+            paged::resume_paged_fetch,
+            paged::get_paged_fetches,
+            paged::delete_paged_fetch,
+            storage::storage_upload,
+            storage::storage_download,
+            storage::cancel_storage_transfer,
+            storage::resume_storage_transfer,
+            storage::get_storage_transfers,
+            storage::delete_storage_transfer,
+            realtime::realtime_subscribe,
+            realtime::realtime_unsubscribe,
+            realtime::get_realtime_status,
+            notifications::send_notification,
+            notifications::get_notification_preferences,
+            notifications::set_notification_preferences,
+            notifications::get_notification_history,
+            pharmacy::get_pharmacy_schema_version,
+            pharmacy::pos::open_sale,
+            pharmacy::pos::get_sale,
+            pharmacy::pos::get_open_sales,
+            pharmacy::pos::add_sale_item,
+            pharmacy::pos::update_sale_item,
+            pharmacy::pos::remove_sale_item,
+            pharmacy::pos::apply_sale_discount,
+            pharmacy::pos::remove_sale_discount,
+            pharmacy::pos::add_sale_payment,
+            pharmacy::pos::remove_sale_payment,
+            pharmacy::pos::finalize_sale,
+            pharmacy::pos::void_sale,
+            pharmacy::inventory::get_expiring_batches,
+            pharmacy::inventory::consume_stock,
+            pharmacy::rates::get_exchange_rate,
+            pharmacy::rates::get_exchange_rate_history,
+            pharmacy::rates::refresh_exchange_rates,
+            pharmacy::rates::set_exchange_rate,
+            pharmacy::rates::convert_amount,
+            pharmacy::fiscal::list_serial_ports,
+            pharmacy::fiscal::get_fiscal_printer,
+            pharmacy::fiscal::set_fiscal_printer,
+            pharmacy::fiscal::get_fiscal_printer_status,
+            pharmacy::fiscal::print_fiscal_invoice,
+            pharmacy::fiscal::print_fiscal_credit_note,
+            pharmacy::fiscal::print_x_report,
+            pharmacy::fiscal::print_z_report,
+            conflicts::get_sync_conflicts,
+            conflicts::resolve_sync_conflict,
+            conflicts::get_conflict_policies,
+            conflicts::set_conflict_policy,
+            files::save_file_locally,
+            files::open_file,
+            files::read_file_locally,
+            vault::unlock_storage,
+            vault::lock_storage,
+            vault::get_storage_status,
+            vault::change_storage_secret,
+            vault::rotate_storage_key,
+            profiles::list_profiles,
+            profiles::switch_profile,
+            profiles::wipe_profile,
+        ])
+        .build()
+}
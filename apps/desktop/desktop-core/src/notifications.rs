@@ -0,0 +1,674 @@
+// Notificaciones nativas
+// Convierte eventos del dominio (recetas nuevas, lotes por vencer, consultas
+// por comenzar, fallos de sincronización) en notificaciones del sistema.
+// Aplica las preferencias por categoría del usuario y las horas de silencio,
+// y no repite el mismo aviso dentro de la ventana de su categoría.
+
+use rusqlite::{params, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use tauri::{AppHandle, Emitter, Manager, Runtime, State};
+use tauri_plugin_notification::NotificationExt;
+
+use crate::error::{Error, Result};
+use crate::store::{now_millis, OfflineStore};
+
+pub(crate) const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS notification_log (
+        id          INTEGER PRIMARY KEY AUTOINCREMENT,
+        category    TEXT NOT NULL,
+        dedup_key   TEXT,
+        outcome     TEXT NOT NULL,
+        created_at  INTEGER NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS idx_notification_log_dedup
+        ON notification_log(category, dedup_key, created_at);
+
+    CREATE TABLE IF NOT EXISTS notification_preferences (
+        id    INTEGER PRIMARY KEY CHECK (id = 1),
+        value TEXT NOT NULL
+    );
+";
+
+/// Evento con cada notificación mostrada o retenida por las horas de
+/// silencio; el webview lo usa para su centro de avisos y las acciones.
+pub const NOTIFICATION_EVENT: &str = "desktop-notification";
+
+/// Venezuela no usa horario de verano
+const DEFAULT_UTC_OFFSET_MINUTES: i32 = -4 * 60;
+const MINUTES_PER_DAY: i64 = 24 * 60;
+const HISTORY_LIMIT: u32 = 200;
+
+/// Tipo de aviso; cada uno tiene sus preferencias y su ventana de duplicados
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationCategory {
+    /// Receta nueva recibida en la farmacia
+    Prescription,
+    /// Lote cerca de su fecha de vencimiento
+    StockExpiry,
+    /// Consulta o cita por comenzar
+    Appointment,
+    /// Una mutación de la cola offline no se pudo enviar
+    SyncError,
+    General,
+}
+
+impl NotificationCategory {
+    fn as_str(self) -> &'static str {
+        match self {
+            NotificationCategory::Prescription => "prescription",
+            NotificationCategory::StockExpiry => "stock_expiry",
+            NotificationCategory::Appointment => "appointment",
+            NotificationCategory::SyncError => "sync_error",
+            NotificationCategory::General => "general",
+        }
+    }
+
+    fn parse(category: &str) -> Self {
+        match category {
+            "prescription" => NotificationCategory::Prescription,
+            "stock_expiry" => NotificationCategory::StockExpiry,
+            "appointment" => NotificationCategory::Appointment,
+            "sync_error" => NotificationCategory::SyncError,
+            _ => NotificationCategory::General,
+        }
+    }
+
+    /// Tiempo durante el que un aviso con la misma `dedup_key` no se repite
+    fn dedup_window_millis(self) -> i64 {
+        const MINUTE: i64 = 60 * 1000;
+        match self {
+            NotificationCategory::Prescription => 7 * 24 * 60 * MINUTE,
+            NotificationCategory::StockExpiry => 24 * 60 * MINUTE,
+            NotificationCategory::Appointment => 12 * 60 * MINUTE,
+            NotificationCategory::SyncError => 30 * MINUTE,
+            NotificationCategory::General => 10 * MINUTE,
+        }
+    }
+
+    pub(crate) fn title(self) -> &'static str {
+        match self {
+            NotificationCategory::Prescription => "Nueva receta",
+            NotificationCategory::StockExpiry => "Lote por vencer",
+            NotificationCategory::Appointment => "Consulta por comenzar",
+            NotificationCategory::SyncError => "Error de sincronización",
+            NotificationCategory::General => "Red Salud",
+        }
+    }
+
+    fn default_actions(self) -> Vec<NotificationAction> {
+        let action = |id: &str, title: &str| NotificationAction {
+            id: id.into(),
+            title: title.into(),
+        };
+        match self {
+            NotificationCategory::Prescription => vec![action("open", "Ver receta")],
+            NotificationCategory::StockExpiry => vec![action("open", "Ver lote")],
+            NotificationCategory::Appointment => vec![
+                action("open", "Abrir consulta"),
+                action("snooze", "Recordar en 5 minutos"),
+            ],
+            NotificationCategory::SyncError => {
+                vec![action("retry", "Reintentar"), action("open", "Ver cola")]
+            }
+            NotificationCategory::General => Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NotificationAction {
+    pub id: String,
+    pub title: String,
+}
+
+/// Aviso a mostrar
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Notification {
+    pub category: NotificationCategory,
+    pub title: String,
+    pub body: String,
+    /// Identifica el hecho notificado (`receta:123`); sin clave no se deduplica
+    #[serde(default)]
+    pub dedup_key: Option<String>,
+    /// Se muestra aunque sean horas de silencio
+    #[serde(default)]
+    pub urgent: bool,
+    /// Por defecto las de la categoría
+    #[serde(default)]
+    pub actions: Option<Vec<NotificationAction>>,
+    /// Datos para el webview al elegir una acción (ruta, ids...)
+    #[serde(default)]
+    pub data: Value,
+}
+
+impl Notification {
+    pub(crate) fn new(category: NotificationCategory, body: impl Into<String>) -> Self {
+        Self {
+            category,
+            title: category.title().into(),
+            body: body.into(),
+            dedup_key: None,
+            urgent: false,
+            actions: None,
+            data: Value::Null,
+        }
+    }
+
+    pub(crate) fn dedup_key(mut self, key: impl Into<String>) -> Self {
+        self.dedup_key = Some(key.into());
+        self
+    }
+}
+
+/// Preferencias de una categoría
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CategoryPreference {
+    #[serde(default = "enabled")]
+    pub enabled: bool,
+    #[serde(default = "enabled")]
+    pub sound: bool,
+    /// Se muestra también en horas de silencio
+    #[serde(default)]
+    pub bypass_quiet_hours: bool,
+}
+
+fn enabled() -> bool {
+    true
+}
+
+impl Default for CategoryPreference {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            sound: true,
+            bypass_quiet_hours: false,
+        }
+    }
+}
+
+/// Franja en hora local (`HH:MM`) en la que no se muestran avisos. Puede
+/// cruzar la medianoche (`22:00`–`07:00`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QuietHours {
+    pub start: String,
+    pub end: String,
+}
+
+fn parse_time(value: &str) -> Result<i64> {
+    let parsed = value.split_once(':').and_then(|(hours, minutes)| {
+        let hours: i64 = hours.parse().ok()?;
+        let minutes: i64 = minutes.parse().ok()?;
+        ((0..24).contains(&hours) && (0..60).contains(&minutes) && value.len() == 5)
+            .then_some(hours * 60 + minutes)
+    });
+    parsed.ok_or_else(|| Error::Validation(format!("hora no válida (HH:MM): {:?}", value)))
+}
+
+impl QuietHours {
+    /// Si `minute` (minuto del día) cae dentro de la franja
+    fn contains(&self, minute: i64) -> Result<bool> {
+        let (start, end) = (parse_time(&self.start)?, parse_time(&self.end)?);
+        Ok(if start <= end {
+            start <= minute && minute < end
+        } else {
+            minute >= start || minute < end
+        })
+    }
+}
+
+/// Preferencias de notificación del usuario (del perfil abierto)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NotificationPreferences {
+    /// Las categorías ausentes usan los valores por defecto
+    #[serde(default)]
+    pub categories: HashMap<NotificationCategory, CategoryPreference>,
+    #[serde(default)]
+    pub quiet_hours: Option<QuietHours>,
+    /// Desfase de la hora local respecto a UTC, para las horas de silencio
+    #[serde(default = "default_utc_offset")]
+    pub utc_offset_minutes: i32,
+}
+
+fn default_utc_offset() -> i32 {
+    DEFAULT_UTC_OFFSET_MINUTES
+}
+
+impl Default for NotificationPreferences {
+    fn default() -> Self {
+        Self {
+            categories: HashMap::new(),
+            quiet_hours: None,
+            utc_offset_minutes: DEFAULT_UTC_OFFSET_MINUTES,
+        }
+    }
+}
+
+impl NotificationPreferences {
+    fn category(&self, category: NotificationCategory) -> CategoryPreference {
+        self.categories.get(&category).copied().unwrap_or_default()
+    }
+
+    fn validate(&self) -> Result<()> {
+        if let Some(quiet_hours) = &self.quiet_hours {
+            quiet_hours.contains(0)?;
+        }
+        if self.utc_offset_minutes.abs() > 14 * 60 {
+            return Err(Error::Validation(format!(
+                "desfase horario no válido: {}",
+                self.utc_offset_minutes
+            )));
+        }
+        Ok(())
+    }
+
+    fn is_quiet(&self, now_millis: i64) -> Result<bool> {
+        let Some(quiet_hours) = &self.quiet_hours else {
+            return Ok(false);
+        };
+        let local = now_millis / 60_000 + self.utc_offset_minutes as i64;
+        quiet_hours.contains(local.rem_euclid(MINUTES_PER_DAY))
+    }
+}
+
+/// Qué se hizo con un aviso
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationOutcome {
+    Delivered,
+    /// La categoría está desactivada
+    Disabled,
+    /// Horas de silencio: solo llega al webview
+    QuietHours,
+    /// Ya se avisó dentro de la ventana de la categoría
+    Duplicate,
+}
+
+impl NotificationOutcome {
+    fn as_str(self) -> &'static str {
+        match self {
+            NotificationOutcome::Delivered => "delivered",
+            NotificationOutcome::Disabled => "disabled",
+            NotificationOutcome::QuietHours => "quiet_hours",
+            NotificationOutcome::Duplicate => "duplicate",
+        }
+    }
+
+    fn parse(outcome: &str) -> Self {
+        match outcome {
+            "delivered" => NotificationOutcome::Delivered,
+            "quiet_hours" => NotificationOutcome::QuietHours,
+            "duplicate" => NotificationOutcome::Duplicate,
+            _ => NotificationOutcome::Disabled,
+        }
+    }
+
+    /// Si el aviso llegó al usuario de alguna forma
+    fn was_sent(self) -> bool {
+        matches!(
+            self,
+            NotificationOutcome::Delivered | NotificationOutcome::QuietHours
+        )
+    }
+}
+
+/// Entrada del historial. No guarda título ni cuerpo: pueden llevar datos
+/// de pacientes y el registro no está cifrado.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NotificationLogEntry {
+    pub id: i64,
+    pub category: NotificationCategory,
+    pub dedup_key: Option<String>,
+    pub outcome: NotificationOutcome,
+    pub created_at: i64,
+}
+
+/// Lo que recibe el webview en `desktop-notification`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NotificationEvent {
+    #[serde(flatten)]
+    pub notification: Notification,
+    pub actions: Vec<NotificationAction>,
+    pub outcome: NotificationOutcome,
+}
+
+// ============================================
+// PERSISTENCIA
+// ============================================
+
+pub(crate) fn load_preferences(store: &OfflineStore) -> Result<NotificationPreferences> {
+    let value: Option<String> = store.with_conn(|conn| {
+        Ok(conn
+            .query_row(
+                "SELECT value FROM notification_preferences WHERE id = 1",
+                [],
+                |row| row.get(0),
+            )
+            .optional()?)
+    })?;
+    Ok(value
+        .and_then(|value| serde_json::from_str(&value).ok())
+        .unwrap_or_default())
+}
+
+fn save_preferences(store: &OfflineStore, preferences: &NotificationPreferences) -> Result<()> {
+    let value = serde_json::to_string(preferences)
+        .map_err(|e| Error::Storage(format!("preferencias no válidas: {}", e)))?;
+    store.with_conn(|conn| {
+        conn.execute(
+            "INSERT INTO notification_preferences (id, value) VALUES (1, ?1)
+             ON CONFLICT(id) DO UPDATE SET value = excluded.value",
+            params![value],
+        )?;
+        Ok(())
+    })
+}
+
+/// Decide qué hacer con el aviso y lo registra. La consulta de duplicados y
+/// el registro van en la misma transacción para que dos avisos simultáneos
+/// no pasen ambos.
+pub(crate) fn record(
+    store: &OfflineStore,
+    preferences: &NotificationPreferences,
+    notification: &Notification,
+    now: i64,
+) -> Result<NotificationOutcome> {
+    let category = notification.category;
+    let preference = preferences.category(category);
+    let quiet =
+        !notification.urgent && !preference.bypass_quiet_hours && preferences.is_quiet(now)?;
+
+    store.with_conn(|conn| {
+        let tx = conn.transaction()?;
+        let last_sent: Option<i64> = match &notification.dedup_key {
+            Some(key) => tx.query_row(
+                "SELECT MAX(created_at) FROM notification_log
+                 WHERE category = ?1 AND dedup_key = ?2 AND outcome IN ('delivered', 'quiet_hours')",
+                params![category.as_str(), key],
+                |row| row.get(0),
+            )?,
+            None => None,
+        };
+        let outcome = if !preference.enabled {
+            NotificationOutcome::Disabled
+        } else if last_sent.is_some_and(|last| now - last < category.dedup_window_millis()) {
+            NotificationOutcome::Duplicate
+        } else if quiet {
+            NotificationOutcome::QuietHours
+        } else {
+            NotificationOutcome::Delivered
+        };
+        tx.execute(
+            "INSERT INTO notification_log (category, dedup_key, outcome, created_at)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![
+                category.as_str(),
+                notification.dedup_key,
+                outcome.as_str(),
+                now
+            ],
+        )?;
+        tx.commit()?;
+        Ok(outcome)
+    })
+}
+
+fn history(store: &OfflineStore, limit: u32) -> Result<Vec<NotificationLogEntry>> {
+    store.with_conn(|conn| {
+        let mut stmt = conn.prepare(
+            "SELECT id, category, dedup_key, outcome, created_at FROM notification_log
+             ORDER BY id DESC LIMIT ?1",
+        )?;
+        let entries = stmt
+            .query_map(params![limit.min(HISTORY_LIMIT)], |row| {
+                let category: String = row.get(1)?;
+                let outcome: String = row.get(3)?;
+                Ok(NotificationLogEntry {
+                    id: row.get(0)?,
+                    category: NotificationCategory::parse(&category),
+                    dedup_key: row.get(2)?,
+                    outcome: NotificationOutcome::parse(&outcome),
+                    created_at: row.get(4)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(entries)
+    })
+}
+
+// ============================================
+// ENVÍO
+// ============================================
+
+/// Aplica las preferencias y muestra el aviso si corresponde. Los avisos
+/// mostrados o retenidos por las horas de silencio se reenvían al webview.
+pub(crate) fn notify<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    notification: Notification,
+) -> Result<NotificationOutcome> {
+    let store = app_handle.state::<OfflineStore>();
+    let preferences = load_preferences(&store)?;
+    let outcome = record(&store, &preferences, &notification, now_millis())?;
+
+    if outcome == NotificationOutcome::Delivered {
+        let mut builder = app_handle
+            .notification()
+            .builder()
+            .title(&notification.title)
+            .body(&notification.body)
+            .group(notification.category.as_str())
+            .action_type_id(notification.category.as_str())
+            .extra("data", &notification.data);
+        if !preferences.category(notification.category).sound {
+            builder = builder.silent();
+        }
+        builder
+            .show()
+            .map_err(|e| Error::Io(format!("no se pudo mostrar la notificación: {}", e)))?;
+    }
+    if outcome.was_sent() {
+        let actions = notification
+            .actions
+            .clone()
+            .unwrap_or_else(|| notification.category.default_actions());
+        let _ = app_handle.emit(
+            NOTIFICATION_EVENT,
+            NotificationEvent {
+                notification,
+                actions,
+                outcome,
+            },
+        );
+    }
+    Ok(outcome)
+}
+
+/// Reemplaza `{columna}` por los valores de `record`
+pub(crate) fn fill_template(template: &str, record: &Value) -> String {
+    let mut filled = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let Some(end) = rest[start..].find('}') else {
+            break;
+        };
+        filled.push_str(&rest[..start]);
+        let column = &rest[start + 1..start + end];
+        match record.get(column) {
+            Some(Value::String(value)) => filled.push_str(value),
+            Some(Value::Null) | None => {}
+            Some(value) => filled.push_str(&value.to_string()),
+        }
+        rest = &rest[start + end + 1..];
+    }
+    filled.push_str(rest);
+    filled
+}
+
+// ============================================
+// COMMANDS
+// ============================================
+
+/// Muestra un aviso del webview (por ejemplo, una consulta por comenzar)
+/// aplicando las preferencias, las horas de silencio y la deduplicación
+#[tauri::command]
+pub async fn send_notification<R: Runtime>(
+    app_handle: AppHandle<R>,
+    notification: Notification,
+) -> Result<NotificationOutcome> {
+    if notification.title.trim().is_empty() {
+        return Err(Error::Validation("el aviso necesita un título".into()));
+    }
+    notify(&app_handle, notification)
+}
+
+#[tauri::command]
+pub async fn get_notification_preferences(
+    store: State<'_, OfflineStore>,
+) -> Result<NotificationPreferences> {
+    load_preferences(&store)
+}
+
+#[tauri::command]
+pub async fn set_notification_preferences(
+    store: State<'_, OfflineStore>,
+    preferences: NotificationPreferences,
+) -> Result<()> {
+    preferences.validate()?;
+    save_preferences(&store, &preferences)
+}
+
+/// Últimos avisos, del más reciente al más antiguo
+#[tauri::command]
+pub async fn get_notification_history(
+    store: State<'_, OfflineStore>,
+    limit: Option<u32>,
+) -> Result<Vec<NotificationLogEntry>> {
+    history(&store, limit.unwrap_or(50))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::unlocked_store;
+    use serde_json::json;
+
+    /// 2026-01-01 12:00 en Caracas (16:00 UTC)
+    const NOON: i64 = 1_767_283_200_000;
+    const HOUR: i64 = 60 * 60 * 1000;
+
+    fn expiry(batch: &str) -> Notification {
+        Notification::new(NotificationCategory::StockExpiry, "Vence en 10 días")
+            .dedup_key(format!("lote:{}", batch))
+    }
+
+    #[test]
+    fn quiet_hours_wrap_midnight_in_local_time() {
+        let preferences = NotificationPreferences {
+            quiet_hours: Some(QuietHours {
+                start: "22:00".into(),
+                end: "07:00".into(),
+            }),
+            ..Default::default()
+        };
+        assert!(!preferences.is_quiet(NOON).unwrap());
+        assert!(preferences.is_quiet(NOON + 10 * HOUR).unwrap());
+        assert!(preferences.is_quiet(NOON + 18 * HOUR).unwrap());
+        assert!(!preferences.is_quiet(NOON + 19 * HOUR).unwrap());
+
+        let invalid = NotificationPreferences {
+            quiet_hours: Some(QuietHours {
+                start: "25:00".into(),
+                end: "7:00".into(),
+            }),
+            ..Default::default()
+        };
+        assert!(invalid.validate().is_err());
+    }
+
+    #[test]
+    fn duplicates_are_suppressed_within_the_category_window() {
+        let (store, _root) = unlocked_store();
+        let preferences = NotificationPreferences::default();
+
+        let first = record(&store, &preferences, &expiry("L1"), NOON).unwrap();
+        let again = record(&store, &preferences, &expiry("L1"), NOON + HOUR).unwrap();
+        let other = record(&store, &preferences, &expiry("L2"), NOON + HOUR).unwrap();
+        let next_day = record(&store, &preferences, &expiry("L1"), NOON + 25 * HOUR).unwrap();
+        assert_eq!(first, NotificationOutcome::Delivered);
+        assert_eq!(again, NotificationOutcome::Duplicate);
+        assert_eq!(other, NotificationOutcome::Delivered);
+        assert_eq!(next_day, NotificationOutcome::Delivered);
+
+        let log = history(&store, 10).unwrap();
+        assert_eq!(log.len(), 4);
+        assert_eq!(log[0].dedup_key.as_deref(), Some("lote:L1"));
+        assert_eq!(log[2].outcome, NotificationOutcome::Duplicate);
+    }
+
+    #[test]
+    fn preferences_disable_categories_and_hold_back_during_quiet_hours() {
+        let (store, _root) = unlocked_store();
+        let mut preferences = NotificationPreferences {
+            quiet_hours: Some(QuietHours {
+                start: "11:00".into(),
+                end: "13:00".into(),
+            }),
+            ..Default::default()
+        };
+        preferences.categories.insert(
+            NotificationCategory::SyncError,
+            CategoryPreference {
+                enabled: false,
+                ..Default::default()
+            },
+        );
+        preferences.categories.insert(
+            NotificationCategory::Appointment,
+            CategoryPreference {
+                bypass_quiet_hours: true,
+                ..Default::default()
+            },
+        );
+        save_preferences(&store, &preferences).unwrap();
+        let preferences = load_preferences(&store).unwrap();
+
+        let sync = Notification::new(NotificationCategory::SyncError, "No se pudo enviar");
+        let appointment = Notification::new(NotificationCategory::Appointment, "En 10 minutos");
+        let mut urgent = expiry("L1");
+        urgent.urgent = true;
+        assert_eq!(
+            record(&store, &preferences, &sync, NOON).unwrap(),
+            NotificationOutcome::Disabled
+        );
+        assert_eq!(
+            record(&store, &preferences, &expiry("L1"), NOON).unwrap(),
+            NotificationOutcome::QuietHours
+        );
+        // Retenido en silencio también cuenta como avisado
+        assert_eq!(
+            record(&store, &preferences, &urgent, NOON).unwrap(),
+            NotificationOutcome::Duplicate
+        );
+        assert_eq!(
+            record(&store, &preferences, &appointment, NOON).unwrap(),
+            NotificationOutcome::Delivered
+        );
+    }
+
+    #[test]
+    fn templates_take_values_from_the_record() {
+        let record = json!({ "paciente": "Ana Pérez", "items": 3, "nota": null });
+        assert_eq!(
+            fill_template("Receta de {paciente} ({items} ítems){nota}", &record),
+            "Receta de Ana Pérez (3 ítems)"
+        );
+        assert_eq!(fill_template("Sin cierre {x", &record), "Sin cierre {x");
+    }
+}
@@ -0,0 +1,51 @@
+use std::path::PathBuf;
+use tauri::{AppHandle, Manager, Runtime, State};
+
+use crate::error::{Error, Result};
+use crate::paths::validate_key;
+use crate::store::OfflineStore;
+
+/// Directorio de datos de la aplicación
+pub(crate) fn app_data_dir<R: Runtime>(app_handle: &AppHandle<R>) -> Result<PathBuf> {
+    app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| Error::Storage(e.to_string()))
+}
+
+/// Obtiene datos almacenados offline
+#[tauri::command]
+pub async fn get_offline_data(
+    store: State<'_, OfflineStore>,
+    key: String,
+) -> Result<Option<String>> {
+    store.get(validate_key(&key)?)
+}
+
+/// Guarda datos para uso offline
+#[tauri::command]
+pub async fn save_offline_data(
+    store: State<'_, OfflineStore>,
+    key: String,
+    data: String,
+) -> Result<()> {
+    store.set(validate_key(&key)?, &data, None)
+}
+
+/// Elimina datos almacenados offline
+#[tauri::command]
+pub async fn delete_offline_data(store: State<'_, OfflineStore>, key: String) -> Result<()> {
+    store.delete(validate_key(&key)?)
+}
+
+/// Limpia todos los datos almacenados offline
+#[tauri::command]
+pub async fn clear_offline_data(store: State<'_, OfflineStore>) -> Result<()> {
+    store.clear()
+}
+
+/// Obtiene todas las claves de datos almacenados offline
+#[tauri::command]
+pub async fn get_offline_keys(store: State<'_, OfflineStore>) -> Result<Vec<String>> {
+    store.keys()
+}
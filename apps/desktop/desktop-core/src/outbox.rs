@@ -0,0 +1,798 @@
+// Cola de mutaciones offline (outbox)
+// Las mutaciones que fallan por red se guardan en SQLite y se reenvían en
+// orden cuando vuelve la conectividad, cada una con su clave de idempotencia.
+// El cuerpo y la fila base llevan datos de pacientes: se guardan cifrados.
+
+use reqwest::StatusCode;
+use rusqlite::{params, Connection, Row};
+use serde::Serialize;
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager, Runtime, State};
+use tokio::sync::Notify;
+
+use crate::conflicts::{self, patch_checked, PatchOutcome};
+use crate::error::{Error, Result};
+use crate::http::HttpClient;
+use crate::notifications::{self, Notification, NotificationCategory};
+use crate::response::SupabaseResponse;
+use crate::session;
+use crate::store::{now_millis, OfflineStore};
+use crate::supabase::{send_mutation, MutationMethod};
+
+pub(crate) const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS outbox (
+        id              INTEGER PRIMARY KEY AUTOINCREMENT,
+        idempotency_key TEXT NOT NULL UNIQUE,
+        method          TEXT NOT NULL,
+        endpoint        TEXT NOT NULL,
+        body            TEXT,
+        status          TEXT NOT NULL DEFAULT 'pending',
+        attempts        INTEGER NOT NULL DEFAULT 0,
+        next_attempt_at INTEGER NOT NULL,
+        last_error      TEXT,
+        created_at      INTEGER NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS idx_outbox_status ON outbox (status, id);
+";
+
+/// Evento con el tamaño de la cola tras cada cambio
+pub const OUTBOX_CHANGED_EVENT: &str = "outbox-changed";
+/// Evento con el resultado de cada reenvío
+pub const OUTBOX_SYNC_EVENT: &str = "outbox-sync-result";
+
+/// Espera máxima entre revisiones de la cola
+const IDLE_WAIT: Duration = Duration::from_secs(30);
+/// Primer reintento tras un fallo; se duplica en cada intento
+const BASE_BACKOFF_MS: i64 = 5_000;
+const MAX_BACKOFF_MS: i64 = 10 * 60 * 1000;
+
+/// Estado de una entrada de la cola
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutboxStatus {
+    /// Esperando envío o reintento
+    Pending,
+    /// Rechazada por el servidor; requiere acción del usuario
+    Failed,
+}
+
+impl OutboxStatus {
+    fn as_str(self) -> &'static str {
+        match self {
+            OutboxStatus::Pending => "pending",
+            OutboxStatus::Failed => "failed",
+        }
+    }
+}
+
+/// Mutación a enviar a Supabase
+#[derive(Debug, Clone)]
+pub(crate) struct Mutation {
+    pub method: MutationMethod,
+    pub endpoint: String,
+    pub body: Option<String>,
+    /// Fila sobre la que se basó un PATCH, para detectar conflictos
+    pub base: Option<serde_json::Value>,
+    pub idempotency_key: String,
+}
+
+impl Mutation {
+    pub fn new(
+        method: MutationMethod,
+        endpoint: String,
+        body: Option<String>,
+        idempotency_key: Option<String>,
+    ) -> Self {
+        Self {
+            method,
+            endpoint,
+            body,
+            base: None,
+            idempotency_key: idempotency_key.unwrap_or_else(new_idempotency_key),
+        }
+    }
+}
+
+/// Mutación guardada en la cola
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OutboxEntry {
+    pub id: i64,
+    pub idempotency_key: String,
+    pub method: MutationMethod,
+    pub endpoint: String,
+    pub body: Option<String>,
+    pub base: Option<serde_json::Value>,
+    pub status: OutboxStatus,
+    pub attempts: u32,
+    pub next_attempt_at: i64,
+    pub last_error: Option<String>,
+    pub created_at: i64,
+}
+
+impl OutboxEntry {
+    fn mutation(&self) -> Mutation {
+        Mutation {
+            method: self.method,
+            endpoint: self.endpoint.clone(),
+            body: self.body.clone(),
+            base: self.base.clone(),
+            idempotency_key: self.idempotency_key.clone(),
+        }
+    }
+
+    fn from_row(store: &OfflineStore, row: &Row<'_>) -> Result<Self> {
+        let idempotency_key: String = row.get("idempotency_key")?;
+        let method: String = row.get("method")?;
+        let status: String = row.get("status")?;
+        let body = store.open_value(row.get("body")?, &sealed_aad(&idempotency_key, "body"))?;
+        let base = store.open_value(row.get("base")?, &sealed_aad(&idempotency_key, "base"))?;
+        Ok(Self {
+            id: row.get("id")?,
+            idempotency_key,
+            method: MutationMethod::parse(&method).unwrap_or(MutationMethod::Post),
+            endpoint: row.get("endpoint")?,
+            body,
+            base: base.and_then(|base| serde_json::from_str(&base).ok()),
+            status: if status == "failed" {
+                OutboxStatus::Failed
+            } else {
+                OutboxStatus::Pending
+            },
+            attempts: row.get("attempts")?,
+            next_attempt_at: row.get("next_attempt_at")?,
+            last_error: row.get("last_error")?,
+            created_at: row.get("created_at")?,
+        })
+    }
+}
+
+/// Tamaño de la cola
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct OutboxCounts {
+    pub pending: u32,
+    pub failed: u32,
+}
+
+/// Resultado de reenviar una entrada
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OutboxSyncResult {
+    pub id: i64,
+    pub idempotency_key: String,
+    pub method: MutationMethod,
+    pub endpoint: String,
+    pub ok: bool,
+    /// La entrada sigue en cola y se reintentará
+    pub retrying: bool,
+    pub status: Option<u16>,
+    pub error: Option<String>,
+}
+
+/// Estado compartido de la cola: despierta al worker y guarda en memoria el
+/// último token de acceso visto (nunca se persiste en disco).
+#[derive(Default)]
+pub struct Outbox {
+    notify: Notify,
+    access_token: Mutex<Option<String>>,
+}
+
+impl Outbox {
+    /// Pide al worker que revise la cola ahora
+    pub fn wake(&self) {
+        self.notify.notify_one();
+    }
+
+    pub fn set_access_token(&self, token: &str) {
+        if let Ok(mut current) = self.access_token.lock() {
+            *current = Some(token.to_string());
+        }
+    }
+
+    /// Olvida el token al cerrar la sesión, para que el worker no reenvíe
+    /// con él la cola del siguiente perfil
+    pub fn clear_access_token(&self) {
+        if let Ok(mut current) = self.access_token.lock() {
+            *current = None;
+        }
+    }
+
+    fn access_token(&self) -> Option<String> {
+        self.access_token
+            .lock()
+            .ok()
+            .and_then(|token| token.clone())
+    }
+}
+
+pub fn new_idempotency_key() -> String {
+    uuid::Uuid::new_v4().to_string()
+}
+
+// ============================================
+// PERSISTENCIA
+// ============================================
+
+/// Columnas cifradas de la cola
+const SEALED_COLUMNS: [&str; 2] = ["body", "base"];
+
+/// Contexto del cifrado de una columna: ata el valor a su mutación
+fn sealed_aad(idempotency_key: &str, column: &str) -> String {
+    format!("outbox:{}:{}", idempotency_key, column)
+}
+
+pub(crate) fn enqueue(store: &OfflineStore, mutation: &Mutation) -> Result<i64> {
+    store.with_conn(|conn| enqueue_in(store, conn, mutation))
+}
+
+/// Encola con una conexión ya tomada, para hacerlo dentro de una transacción
+pub(crate) fn enqueue_in(
+    store: &OfflineStore,
+    conn: &Connection,
+    mutation: &Mutation,
+) -> Result<i64> {
+    let key = &mutation.idempotency_key;
+    let body = mutation
+        .body
+        .as_deref()
+        .map(|body| store.seal_value(body, &sealed_aad(key, "body")))
+        .transpose()?;
+    let base = mutation
+        .base
+        .as_ref()
+        .map(|base| store.seal_value(&base.to_string(), &sealed_aad(key, "base")))
+        .transpose()?;
+    let now = now_millis();
+    conn.execute(
+        "INSERT INTO outbox
+            (idempotency_key, method, endpoint, body, base, next_attempt_at, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?6)",
+        params![
+            mutation.idempotency_key,
+            mutation.method.as_str(),
+            mutation.endpoint,
+            body,
+            base,
+            now
+        ],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+fn list(store: &OfflineStore) -> Result<Vec<OutboxEntry>> {
+    store.with_conn(|conn| {
+        let mut stmt = conn.prepare("SELECT * FROM outbox ORDER BY id")?;
+        let mut rows = stmt.query([])?;
+        let mut entries = Vec::new();
+        while let Some(row) = rows.next()? {
+            entries.push(OutboxEntry::from_row(store, row)?);
+        }
+        Ok(entries)
+    })
+}
+
+/// Primera entrada pendiente; las siguientes esperan detrás de ella
+fn next_pending(store: &OfflineStore) -> Result<Option<OutboxEntry>> {
+    store.with_conn(|conn| {
+        let mut stmt =
+            conn.prepare("SELECT * FROM outbox WHERE status = 'pending' ORDER BY id LIMIT 1")?;
+        let mut rows = stmt.query([])?;
+        rows.next()?
+            .map(|row| OutboxEntry::from_row(store, row))
+            .transpose()
+    })
+}
+
+/// Recifra las columnas cifradas con `f` (ver `OfflineStore::reseal_column`),
+/// que recibe el contexto de cada valor
+pub(crate) fn reseal(
+    store: &OfflineStore,
+    mut f: impl FnMut(&str, &[u8]) -> Result<Option<Vec<u8>>>,
+) -> Result<usize> {
+    let mut changed = 0;
+    for column in SEALED_COLUMNS {
+        changed += store.reseal_column("outbox", "idempotency_key", column, |key, value| {
+            f(&sealed_aad(key, column), value)
+        })?;
+    }
+    Ok(changed)
+}
+
+pub(crate) fn counts(store: &OfflineStore) -> Result<OutboxCounts> {
+    store.with_conn(|conn| {
+        Ok(conn.query_row(
+            "SELECT
+                COALESCE(SUM(status = 'pending'), 0),
+                COALESCE(SUM(status = 'failed'), 0)
+             FROM outbox",
+            [],
+            |row| {
+                Ok(OutboxCounts {
+                    pending: row.get(0)?,
+                    failed: row.get(1)?,
+                })
+            },
+        )?)
+    })
+}
+
+fn remove(store: &OfflineStore, id: i64) -> Result<()> {
+    store.with_conn(|conn| {
+        conn.execute("DELETE FROM outbox WHERE id = ?1", params![id])?;
+        Ok(())
+    })
+}
+
+fn mark(
+    store: &OfflineStore,
+    id: i64,
+    status: OutboxStatus,
+    attempts: u32,
+    next_attempt_at: i64,
+    last_error: Option<&str>,
+) -> Result<()> {
+    store.with_conn(|conn| {
+        let updated = conn.execute(
+            "UPDATE outbox SET status = ?2, attempts = ?3, next_attempt_at = ?4, last_error = ?5
+             WHERE id = ?1",
+            params![id, status.as_str(), attempts, next_attempt_at, last_error],
+        )?;
+        if updated == 0 {
+            return Err(Error::NotFound(format!(
+                "no existe la entrada {} en la cola",
+                id
+            )));
+        }
+        Ok(())
+    })
+}
+
+/// Espera antes del intento número `attempts`
+fn backoff_millis(attempts: u32) -> i64 {
+    let exponent = attempts.saturating_sub(1).min(16);
+    (BASE_BACKOFF_MS << exponent).min(MAX_BACKOFF_MS)
+}
+
+// ============================================
+// ENVÍO Y SINCRONIZACIÓN
+// ============================================
+
+/// Envía una mutación, comprobando conflictos si es un PATCH con `base`.
+/// Devuelve la respuesta sea cual sea su estado, o `Error::Conflict` si
+/// quedó un conflicto sin resolver.
+async fn send(
+    http: &HttpClient,
+    store: &OfflineStore,
+    mutation: &Mutation,
+    access_token: &str,
+) -> Result<SupabaseResponse> {
+    if mutation.method == MutationMethod::Patch && mutation.base.is_some() {
+        return match patch_checked(http, store, mutation, access_token).await? {
+            PatchOutcome::Sent(response) => Ok(response),
+            // Ganó el servidor: se devuelve la fila vigente como resultado
+            PatchOutcome::Dropped(remote) => Ok(SupabaseResponse::ok(
+                remote
+                    .map(|row| format!("[{}]", row))
+                    .unwrap_or_else(|| "[]".into()),
+            )),
+            PatchOutcome::Conflict(id) => Err(Error::Conflict { id }),
+        };
+    }
+    send_mutation(
+        http,
+        mutation.method,
+        &mutation.endpoint,
+        mutation.body.as_deref(),
+        access_token,
+        &mutation.idempotency_key,
+        None,
+    )
+    .await
+}
+
+/// Envía una mutación; si no hay red (o ya hay otras en cola) la guarda en
+/// la cola y devuelve `Error::Queued`. Los estados no 2xx se devuelven como
+/// error. Solo se encola si la petición no llegó a salir (`NETWORK`): sin
+/// respuesta después de enviarla (`UNCONFIRMED`) el servidor pudo haberla
+/// aplicado y reenviarla la duplicaría.
+///
+/// El token (el explícito del webview o el de la sesión) solo se resuelve si
+/// la mutación se va a enviar ya: si no se puede refrescar por falta de red
+/// también va a la cola, y el worker pide uno nuevo al reenviarla.
+pub(crate) async fn send_or_enqueue<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    mutation: Mutation,
+    access_token: Option<String>,
+) -> Result<SupabaseResponse> {
+    let outbox = app_handle.state::<Outbox>();
+    let http = app_handle.state::<HttpClient>();
+    let store = app_handle.state::<OfflineStore>();
+
+    // Si ya hay mutaciones en cola, esta va detrás para respetar el orden
+    if counts(&store)?.pending == 0 {
+        match session::resolve_token(app_handle, access_token).await {
+            Ok(access_token) => {
+                outbox.set_access_token(&access_token);
+                match send(&http, &store, &mutation, &access_token).await {
+                    Err(Error::Network(_)) => {}
+                    Err(Error::Conflict { id }) => {
+                        conflicts::emit_count(app_handle, &store);
+                        return Err(Error::Conflict { id });
+                    }
+                    other => return other.and_then(SupabaseResponse::into_result),
+                }
+            }
+            Err(e) if session::is_transient(&e) => {}
+            Err(e) => return Err(e),
+        }
+    } else if let Some(token) = access_token.filter(|token| !token.is_empty()) {
+        outbox.set_access_token(&token);
+    }
+
+    let id = enqueue(&store, &mutation)?;
+    emit_counts(app_handle, &store);
+    outbox.wake();
+    Err(Error::Queued {
+        id,
+        idempotency_key: mutation.idempotency_key,
+    })
+}
+
+/// Qué hacer con una entrada tras intentar enviarla
+enum Outcome {
+    Done(StatusCode),
+    Retry(Option<StatusCode>, String),
+    Fail(Option<StatusCode>, String),
+    Conflict(String),
+}
+
+fn classify(result: Result<SupabaseResponse>) -> Outcome {
+    let response = match result {
+        Ok(response) => response,
+        Err(Error::Network(message)) => return Outcome::Retry(None, message),
+        // Pudo haberse aplicado: queda fallida para revisarla antes de reintentar
+        Err(e @ Error::Unconfirmed(_)) => return Outcome::Fail(None, e.to_string()),
+        Err(e @ Error::Conflict { .. }) => return Outcome::Conflict(e.to_string()),
+        Err(e) => {
+            return Outcome::Fail(
+                e.status().and_then(|s| StatusCode::from_u16(s).ok()),
+                e.to_string(),
+            )
+        }
+    };
+    let status = response.status_code();
+    match status {
+        _ if status.is_success() => Outcome::Done(status),
+        // Token vencido, timeout, rate limit o fallo del servidor: se reintenta
+        StatusCode::UNAUTHORIZED | StatusCode::REQUEST_TIMEOUT | StatusCode::TOO_MANY_REQUESTS => {
+            Outcome::Retry(Some(status), response.body)
+        }
+        _ if status.is_server_error() => Outcome::Retry(Some(status), response.body),
+        _ => Outcome::Fail(Some(status), response.body),
+    }
+}
+
+/// Reenvía en orden las entradas vencidas. Devuelve cuánto esperar hasta la
+/// próxima revisión.
+async fn process_due<R: Runtime>(app_handle: &AppHandle<R>) -> Result<Duration> {
+    let http = app_handle.state::<HttpClient>();
+    let store = app_handle.state::<OfflineStore>();
+    let outbox = app_handle.state::<Outbox>();
+
+    loop {
+        let Some(entry) = next_pending(&store)? else {
+            return Ok(IDLE_WAIT);
+        };
+        let now = now_millis();
+        if entry.next_attempt_at > now {
+            return Ok(Duration::from_millis((entry.next_attempt_at - now) as u64).min(IDLE_WAIT));
+        }
+        // Sin token no se puede enviar; se espera a que haya sesión
+        let Some(access_token) = session::access_token(app_handle)
+            .await
+            .ok()
+            .or_else(|| outbox.access_token())
+        else {
+            return Ok(IDLE_WAIT);
+        };
+
+        let result = send(&http, &store, &entry.mutation(), &access_token).await;
+
+        let attempts = entry.attempts + 1;
+        let (ok, retrying, status, error) = match classify(result) {
+            Outcome::Done(status) => {
+                remove(&store, entry.id)?;
+                (true, false, Some(status), None)
+            }
+            Outcome::Retry(status, error) => {
+                mark(
+                    &store,
+                    entry.id,
+                    OutboxStatus::Pending,
+                    attempts,
+                    now + backoff_millis(attempts),
+                    Some(&error),
+                )?;
+                (false, true, status, Some(error))
+            }
+            // El conflicto ya quedó registrado aparte; sale de la cola
+            Outcome::Conflict(error) => {
+                remove(&store, entry.id)?;
+                conflicts::emit_count(app_handle, &store);
+                (false, false, None, Some(error))
+            }
+            Outcome::Fail(status, error) => {
+                mark(
+                    &store,
+                    entry.id,
+                    OutboxStatus::Failed,
+                    attempts,
+                    now,
+                    Some(&error),
+                )?;
+                (false, false, status, Some(error))
+            }
+        };
+
+        // Un solo aviso por ventana aunque fallen varias mutaciones seguidas
+        if let Some(error) = error.as_ref().filter(|_| !ok && !retrying) {
+            let _ = notifications::notify(
+                app_handle,
+                Notification::new(
+                    NotificationCategory::SyncError,
+                    format!("Un cambio pendiente no se pudo sincronizar: {}", error),
+                )
+                .dedup_key("outbox"),
+            );
+        }
+
+        let _ = app_handle.emit(
+            OUTBOX_SYNC_EVENT,
+            OutboxSyncResult {
+                id: entry.id,
+                idempotency_key: entry.idempotency_key,
+                method: entry.method,
+                endpoint: entry.endpoint,
+                ok,
+                retrying,
+                status: status.map(|s| s.as_u16()),
+                error,
+            },
+        );
+        emit_counts(app_handle, &store);
+
+        if retrying {
+            return Ok(Duration::from_millis(backoff_millis(attempts) as u64).min(IDLE_WAIT));
+        }
+    }
+}
+
+/// Arranca el worker que vacía la cola en segundo plano
+pub(crate) fn spawn_worker<R: Runtime>(app_handle: AppHandle<R>) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let wait = process_due(&app_handle).await.unwrap_or(IDLE_WAIT);
+            let outbox = app_handle.state::<Outbox>();
+            tokio::select! {
+                _ = outbox.notify.notified() => {}
+                _ = tokio::time::sleep(wait) => {}
+            }
+        }
+    });
+}
+
+pub(crate) fn emit_counts<R: Runtime>(app_handle: &AppHandle<R>, store: &OfflineStore) {
+    if let Ok(counts) = counts(store) {
+        let _ = app_handle.emit(OUTBOX_CHANGED_EVENT, counts);
+    }
+}
+
+// ============================================
+// COMMANDS
+// ============================================
+
+/// Lista las mutaciones en cola
+#[tauri::command]
+pub async fn get_outbox(store: State<'_, OfflineStore>) -> Result<Vec<OutboxEntry>> {
+    list(&store)
+}
+
+/// Fuerza una sincronización inmediata de la cola
+#[tauri::command]
+pub async fn sync_outbox(outbox: State<'_, Outbox>) -> Result<()> {
+    outbox.wake();
+    Ok(())
+}
+
+/// Vuelve a poner en cola una entrada fallida
+#[tauri::command]
+pub async fn retry_outbox_entry<R: Runtime>(
+    app_handle: AppHandle<R>,
+    store: State<'_, OfflineStore>,
+    outbox: State<'_, Outbox>,
+    id: i64,
+) -> Result<()> {
+    mark(&store, id, OutboxStatus::Pending, 0, now_millis(), None)?;
+    emit_counts(&app_handle, &store);
+    outbox.wake();
+    Ok(())
+}
+
+/// Descarta una entrada de la cola sin enviarla
+#[tauri::command]
+pub async fn discard_outbox_entry<R: Runtime>(
+    app_handle: AppHandle<R>,
+    store: State<'_, OfflineStore>,
+    id: i64,
+) -> Result<()> {
+    remove(&store, id)?;
+    emit_counts(&app_handle, &store);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{client, unlocked_store};
+    use red_salud_supabase_mock::{MockResponse, MockSupabase};
+    use rusqlite::types::Value;
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap() {
+        assert_eq!(backoff_millis(1), 5_000);
+        assert_eq!(backoff_millis(2), 10_000);
+        assert_eq!(backoff_millis(3), 20_000);
+        assert_eq!(backoff_millis(50), MAX_BACKOFF_MS);
+    }
+
+    #[test]
+    fn classify_retries_transient_failures_only() {
+        let ok = |status: u16| {
+            Ok(SupabaseResponse {
+                status,
+                ..SupabaseResponse::ok(String::new())
+            })
+        };
+        assert!(matches!(classify(ok(201)), Outcome::Done(_)));
+        for status in [401, 408, 429, 500, 503] {
+            assert!(
+                matches!(classify(ok(status)), Outcome::Retry(..)),
+                "{status}"
+            );
+        }
+        for status in [400, 403, 404, 422] {
+            assert!(
+                matches!(classify(ok(status)), Outcome::Fail(..)),
+                "{status}"
+            );
+        }
+        assert!(matches!(
+            classify(Err(Error::Network("sin red".into()))),
+            Outcome::Retry(None, _)
+        ));
+        assert!(matches!(
+            classify(Err(Error::Unconfirmed("timeout".into()))),
+            Outcome::Fail(None, _)
+        ));
+    }
+
+    #[test]
+    fn entries_are_replayed_in_insertion_order() {
+        let store = OfflineStore::open_in_memory().unwrap();
+        let mutation = |method, endpoint: &str, key: &str| {
+            Mutation::new(method, endpoint.into(), None, Some(key.into()))
+        };
+        let first = enqueue(&store, &mutation(MutationMethod::Post, "/rest/v1/a", "k1")).unwrap();
+        enqueue(
+            &store,
+            &mutation(MutationMethod::Delete, "/rest/v1/b", "k2"),
+        )
+        .unwrap();
+        assert_eq!(next_pending(&store).unwrap().unwrap().id, first);
+
+        mark(&store, first, OutboxStatus::Failed, 1, 0, Some("400")).unwrap();
+        let next = next_pending(&store).unwrap().unwrap();
+        assert_eq!(next.idempotency_key, "k2");
+        assert_eq!(next.method, MutationMethod::Delete);
+
+        let counts = counts(&store).unwrap();
+        assert_eq!((counts.pending, counts.failed), (1, 1));
+        assert!(enqueue(&store, &mutation(MutationMethod::Post, "/x", "k1")).is_err());
+    }
+
+    #[test]
+    fn the_token_is_forgotten_on_logout() {
+        let outbox = Outbox::default();
+        outbox.set_access_token("jwt-a");
+        assert_eq!(outbox.access_token().as_deref(), Some("jwt-a"));
+        outbox.clear_access_token();
+        assert_eq!(outbox.access_token(), None);
+    }
+
+    #[tokio::test]
+    async fn replays_keep_the_idempotency_key_until_the_server_accepts() {
+        let mut mock = MockSupabase::start();
+        mock.on("POST", "/rest/v1/citas")
+            .once(MockResponse::new(503))
+            .always(MockResponse::json(201, "[]"));
+        let http = client(&mock);
+        let store = OfflineStore::open_in_memory().unwrap();
+        let mutation = Mutation::new(
+            MutationMethod::Post,
+            "/rest/v1/citas".into(),
+            Some("{}".into()),
+            None,
+        );
+
+        let first = send(&http, &store, &mutation, "jwt").await;
+        assert!(matches!(classify(first), Outcome::Retry(..)));
+        let second = send(&http, &store, &mutation, "jwt").await;
+        assert!(matches!(classify(second), Outcome::Done(_)));
+        let keys: Vec<_> = mock
+            .requests_to("POST", "/rest/v1/citas")
+            .iter()
+            .map(|request| request.header("idempotency-key").map(str::to_string))
+            .collect();
+        assert_eq!(keys, vec![Some(mutation.idempotency_key.clone()); 2]);
+
+        // Sin conexiones abiertas al servidor caído la petición no sale
+        mock.stop();
+        let http = client(&mock);
+        let offline = send(&http, &store, &mutation, "jwt").await;
+        assert!(matches!(classify(offline), Outcome::Retry(None, _)));
+    }
+
+    #[test]
+    fn bodies_are_sealed_and_follow_key_rotation() {
+        let (store, root) = unlocked_store();
+        // Entrada encolada antes de que la cola se cifrara
+        store
+            .with_conn(|conn| {
+                conn.execute(
+                    "INSERT INTO outbox
+                        (idempotency_key, method, endpoint, body, next_attempt_at, created_at)
+                     VALUES ('k0', 'POST', '/rest/v1/citas', '{\"id\":1}', 0, 0)",
+                    [],
+                )?;
+                Ok(())
+            })
+            .unwrap();
+        let mut mutation = Mutation::new(
+            MutationMethod::Patch,
+            "/rest/v1/citas?id=eq.1".into(),
+            Some(r#"{"notas":"alergia"}"#.into()),
+            Some("k1".into()),
+        );
+        mutation.base = Some(serde_json::json!({ "updated_at": "1" }));
+        enqueue(&store, &mutation).unwrap();
+
+        let raw = |key: &str, column: &str| -> Value {
+            store
+                .with_conn(|conn| {
+                    Ok(conn.query_row(
+                        &format!("SELECT {} FROM outbox WHERE idempotency_key = ?1", column),
+                        params![key],
+                        |row| row.get(0),
+                    )?)
+                })
+                .unwrap()
+        };
+        assert!(matches!(raw("k1", "body"), Value::Blob(_)));
+        assert!(matches!(raw("k1", "base"), Value::Blob(_)));
+        assert!(matches!(raw("k0", "body"), Value::Text(_)));
+
+        // La rotación cifra lo que quedaba en claro y recifra lo demás
+        let report = crate::vault::rotate(&store, root.path(), "secreto").unwrap();
+        assert_eq!(report.entries, 3);
+        assert!(matches!(raw("k0", "body"), Value::Blob(_)));
+
+        let entries = list(&store).unwrap();
+        assert_eq!(entries[0].body.as_deref(), Some(r#"{"id":1}"#));
+        assert_eq!(entries[1].body, mutation.body);
+        assert_eq!(entries[1].base, mutation.base);
+
+        store.vault().lock();
+        assert_eq!(list(&store).unwrap_err().code(), "AUTH");
+    }
+}
@@ -0,0 +1,640 @@
+// Descarga paginada de tablas grandes
+// Recorre una consulta con `Range`/`Content-Range` y guarda cada página en
+// el almacén offline según llega, sin cargar la tabla entera en memoria.
+// El avance queda registrado en SQLite: una descarga cancelada, fallida o
+// interrumpida al cerrar la app se reanuda desde la última página guardada.
+
+use reqwest::StatusCode;
+use rusqlite::{params, OptionalExtension, Row};
+use serde::Serialize;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tauri::{AppHandle, Emitter, Manager, Runtime, State};
+
+use crate::error::{Error, Result};
+use crate::http::HttpClient;
+use crate::jobs::{JobStatus, Jobs, ProfileGuard};
+use crate::paths::validate_key;
+use crate::query::{Order, Query};
+use crate::response::SupabaseResponse;
+use crate::session::resolve_token;
+use crate::store::{now_millis, OfflineStore};
+use crate::supabase::endpoint_url;
+
+pub(crate) const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS paged_fetches (
+        id          TEXT PRIMARY KEY,
+        cache_key   TEXT NOT NULL UNIQUE,
+        endpoint    TEXT NOT NULL,
+        page_size   INTEGER NOT NULL,
+        rows_done   INTEGER NOT NULL DEFAULT 0,
+        pages       INTEGER NOT NULL DEFAULT 0,
+        total       INTEGER,
+        status      TEXT NOT NULL,
+        error       TEXT,
+        updated_at  INTEGER NOT NULL
+    );
+";
+
+/// Evento con el avance de una descarga tras cada página
+pub const PAGED_FETCH_PROGRESS_EVENT: &str = "paged-fetch-progress";
+
+const DEFAULT_PAGE_SIZE: u64 = 1_000;
+const MAX_PAGE_SIZE: u64 = 10_000;
+
+/// Descarga paginada. Las filas de la página `n` quedan en la clave
+/// `{cache_key}/{n}` del almacén offline, como un array JSON.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PagedFetch {
+    pub id: String,
+    pub cache_key: String,
+    pub endpoint: String,
+    pub page_size: u64,
+    pub rows_done: u64,
+    pub pages: u64,
+    /// Total de filas según `Content-Range`, si el servidor lo informa
+    pub total: Option<u64>,
+    pub status: JobStatus,
+    pub error: Option<String>,
+    pub updated_at: i64,
+}
+
+impl PagedFetch {
+    fn from_row(row: &Row<'_>) -> rusqlite::Result<Self> {
+        let status: String = row.get("status")?;
+        Ok(Self {
+            id: row.get("id")?,
+            cache_key: row.get("cache_key")?,
+            endpoint: row.get("endpoint")?,
+            page_size: row.get("page_size")?,
+            rows_done: row.get("rows_done")?,
+            pages: row.get("pages")?,
+            total: row.get("total")?,
+            status: JobStatus::parse(&status),
+            error: row.get("error")?,
+            updated_at: row.get("updated_at")?,
+        })
+    }
+}
+
+/// Clave del almacén offline con las filas de una página
+pub fn page_key(cache_key: &str, page: u64) -> String {
+    format!("{}/{}", cache_key, page)
+}
+
+// ============================================
+// PERSISTENCIA
+// ============================================
+
+fn save(store: &OfflineStore, fetch: &PagedFetch) -> Result<()> {
+    store.with_conn(|conn| {
+        conn.execute(
+            "INSERT INTO paged_fetches
+                (id, cache_key, endpoint, page_size, rows_done, pages, total, status, error, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+             ON CONFLICT(id) DO UPDATE SET
+                rows_done = excluded.rows_done,
+                pages = excluded.pages,
+                total = excluded.total,
+                status = excluded.status,
+                error = excluded.error,
+                updated_at = excluded.updated_at",
+            params![
+                fetch.id,
+                fetch.cache_key,
+                fetch.endpoint,
+                fetch.page_size,
+                fetch.rows_done,
+                fetch.pages,
+                fetch.total,
+                fetch.status.as_str(),
+                fetch.error,
+                fetch.updated_at
+            ],
+        )?;
+        Ok(())
+    })
+}
+
+fn get(store: &OfflineStore, id: &str) -> Result<PagedFetch> {
+    store
+        .with_conn(|conn| {
+            Ok(conn
+                .query_row(
+                    "SELECT * FROM paged_fetches WHERE id = ?1",
+                    params![id],
+                    PagedFetch::from_row,
+                )
+                .optional()?)
+        })?
+        .ok_or_else(|| Error::NotFound(format!("no existe la descarga {}", id)))
+}
+
+fn find_by_key(store: &OfflineStore, cache_key: &str) -> Result<Option<PagedFetch>> {
+    store.with_conn(|conn| {
+        Ok(conn
+            .query_row(
+                "SELECT * FROM paged_fetches WHERE cache_key = ?1",
+                params![cache_key],
+                PagedFetch::from_row,
+            )
+            .optional()?)
+    })
+}
+
+fn list(store: &OfflineStore) -> Result<Vec<PagedFetch>> {
+    store.with_conn(|conn| {
+        let mut stmt = conn.prepare("SELECT * FROM paged_fetches ORDER BY updated_at DESC")?;
+        let fetches = stmt
+            .query_map([], PagedFetch::from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(fetches)
+    })
+}
+
+/// Borra la descarga y las páginas que guardó
+fn remove(store: &OfflineStore, fetch: &PagedFetch) -> Result<()> {
+    for page in 0..fetch.pages {
+        store.delete(&page_key(&fetch.cache_key, page))?;
+    }
+    store.with_conn(|conn| {
+        conn.execute("DELETE FROM paged_fetches WHERE id = ?1", params![fetch.id])?;
+        Ok(())
+    })
+}
+
+// ============================================
+// DESCARGA
+// ============================================
+
+/// Resultado de pedir una página
+enum Page {
+    Rows {
+        body: String,
+        rows: u64,
+        total: Option<u64>,
+    },
+    /// El servidor no tiene filas a partir de este punto (416)
+    End,
+}
+
+/// `Content-Range` de PostgREST: `0-999/5000`, `0-999/*` o `*/0`.
+/// Devuelve las filas de la página y el total, si se conoce.
+fn parse_content_range(value: &str) -> Option<(u64, Option<u64>)> {
+    let (range, total) = value.trim().split_once('/')?;
+    let total = match total {
+        "*" => None,
+        total => Some(total.parse().ok()?),
+    };
+    let rows = match range.split_once('-') {
+        Some((from, to)) => (to.parse::<u64>().ok()? + 1).checked_sub(from.parse().ok()?)?,
+        None if range == "*" => 0,
+        None => return None,
+    };
+    Some((rows, total))
+}
+
+async fn fetch_page(http: &HttpClient, fetch: &PagedFetch, access_token: &str) -> Result<Page> {
+    let config = http.supabase()?;
+    let from = fetch.rows_done;
+    let to = from + fetch.page_size - 1;
+    let mut request = http
+        .client()
+        .get(endpoint_url(&config, &fetch.endpoint)?)
+        .header("apikey", &config.anon_key)
+        .header("Authorization", format!("Bearer {}", access_token))
+        .header("Range-Unit", "items")
+        .header("Range", format!("{}-{}", from, to));
+    // El total solo se pide una vez: contar en cada página es caro
+    if fetch.total.is_none() {
+        request = request.header("Prefer", "count=exact");
+    }
+
+    let response = SupabaseResponse::read(http.send(request).await?).await?;
+    if response.status_code() == StatusCode::RANGE_NOT_SATISFIABLE {
+        return Ok(Page::End);
+    }
+    let response = response.into_result()?;
+    let (rows, total) = match response
+        .content_range
+        .as_deref()
+        .and_then(parse_content_range)
+    {
+        Some(parsed) => parsed,
+        None => {
+            let rows: Vec<serde::de::IgnoredAny> = serde_json::from_str(&response.body)
+                .map_err(|e| Error::Validation(format!("la respuesta no es una lista: {}", e)))?;
+            (rows.len() as u64, None)
+        }
+    };
+    Ok(Page::Rows {
+        body: response.body,
+        rows,
+        total,
+    })
+}
+
+/// Descarga las páginas que faltan hasta terminar, fallar o ser cancelada,
+/// guardando el avance tras cada una. `access_token` se consulta en cada
+/// página para usar siempre el token vigente.
+pub(crate) async fn run<T, F>(
+    http: &HttpClient,
+    store: &OfflineStore,
+    fetch: &mut PagedFetch,
+    cancel: &AtomicBool,
+    mut access_token: T,
+    mut on_progress: impl FnMut(&PagedFetch),
+) -> Result<()>
+where
+    T: FnMut() -> F,
+    F: Future<Output = Result<String>>,
+{
+    // Cada escritura comprueba el perfil: entre las esperas de red pudo
+    // abrirse el de otro usuario
+    let profile = ProfileGuard::new(store);
+    let checkpoint = |fetch: &PagedFetch| -> Result<()> {
+        profile.check(store)?;
+        save(store, fetch)
+    };
+
+    loop {
+        profile.check(store)?;
+        if cancel.load(Ordering::Relaxed) {
+            fetch.status = JobStatus::Cancelled;
+            fetch.updated_at = now_millis();
+            checkpoint(fetch)?;
+            on_progress(fetch);
+            return Ok(());
+        }
+
+        let result = match access_token().await {
+            Ok(token) => fetch_page(http, fetch, &token).await,
+            Err(e) => Err(e),
+        };
+        let finished = result.and_then(|page| match page {
+            Page::Rows { body, rows, total } => {
+                profile.check(store)?;
+                store.set(&page_key(&fetch.cache_key, fetch.pages), &body, None)?;
+                fetch.pages += 1;
+                fetch.rows_done += rows;
+                fetch.error = None;
+                fetch.total = total.or(fetch.total);
+                Ok(rows < fetch.page_size
+                    || fetch.total.is_some_and(|total| fetch.rows_done >= total))
+            }
+            Page::End => Ok(true),
+        });
+
+        fetch.updated_at = now_millis();
+        match finished {
+            Ok(finished) => {
+                if finished {
+                    fetch.status = JobStatus::Completed;
+                    fetch.total = Some(fetch.rows_done);
+                }
+                checkpoint(fetch)?;
+                on_progress(fetch);
+                if finished {
+                    return Ok(());
+                }
+            }
+            Err(e) => {
+                fetch.status = JobStatus::Failed;
+                fetch.error = Some(e.to_string());
+                checkpoint(fetch)?;
+                on_progress(fetch);
+                return Err(e);
+            }
+        }
+    }
+}
+
+fn spawn<R: Runtime>(
+    app_handle: AppHandle<R>,
+    mut fetch: PagedFetch,
+    cancel: Arc<AtomicBool>,
+    access_token: Option<String>,
+) {
+    tauri::async_runtime::spawn(async move {
+        let http = app_handle.state::<HttpClient>();
+        let store = app_handle.state::<OfflineStore>();
+        let _ = run(
+            &http,
+            &store,
+            &mut fetch,
+            &cancel,
+            || resolve_token(&app_handle, access_token.clone()),
+            |fetch| {
+                let _ = app_handle.emit(PAGED_FETCH_PROGRESS_EVENT, fetch);
+            },
+        )
+        .await;
+        app_handle.state::<Jobs>().finish(&fetch.id);
+    });
+}
+
+// ============================================
+// COMMANDS
+// ============================================
+
+/// Empieza a descargar una consulta completa por páginas de `page_size`
+/// filas (1000 por defecto). Cada página se guarda en `{cache_key}/{n}` y se
+/// emite `paged-fetch-progress`. Una descarga previa con la misma clave se
+/// reemplaza.
+///
+/// Sin `order` se ordena por `id` para que las páginas no se solapen.
+#[tauri::command]
+pub async fn start_paged_fetch<R: Runtime>(
+    app_handle: AppHandle<R>,
+    store: State<'_, OfflineStore>,
+    jobs: State<'_, Jobs>,
+    mut query: Query,
+    cache_key: String,
+    page_size: Option<u64>,
+    access_token: Option<String>,
+) -> Result<PagedFetch> {
+    validate_key(&cache_key)?;
+    let page_size = page_size.unwrap_or(DEFAULT_PAGE_SIZE);
+    if !(1..=MAX_PAGE_SIZE).contains(&page_size) {
+        return Err(Error::Validation(format!(
+            "el tamaño de página debe estar entre 1 y {}",
+            MAX_PAGE_SIZE
+        )));
+    }
+    if query.limit.is_some() || query.offset.is_some() || query.range.is_some() {
+        return Err(Error::Validation(
+            "la descarga paginada controla limit, offset y range".into(),
+        ));
+    }
+    if query.order.is_empty() {
+        query.order.push(Order {
+            column: "id".into(),
+            ascending: true,
+            nulls_first: None,
+        });
+    }
+    let endpoint = query.endpoint()?;
+
+    if let Some(previous) = find_by_key(&store, &cache_key)? {
+        if jobs.is_running(&previous.id) {
+            return Err(Error::Validation(format!(
+                "ya hay una descarga en curso para {}",
+                cache_key
+            )));
+        }
+        remove(&store, &previous)?;
+    }
+
+    let fetch = PagedFetch {
+        id: uuid::Uuid::new_v4().to_string(),
+        cache_key,
+        endpoint,
+        page_size,
+        rows_done: 0,
+        pages: 0,
+        total: None,
+        status: JobStatus::Running,
+        error: None,
+        updated_at: now_millis(),
+    };
+    save(&store, &fetch)?;
+    let cancel = jobs.register(&fetch.id)?;
+    spawn(app_handle, fetch.clone(), cancel, access_token);
+    Ok(fetch)
+}
+
+/// Pide cancelar una descarga; se detiene al terminar la página en curso
+#[tauri::command]
+pub async fn cancel_paged_fetch(jobs: State<'_, Jobs>, id: String) -> Result<()> {
+    if jobs.cancel(&id) {
+        Ok(())
+    } else {
+        Err(Error::NotFound(format!(
+            "la descarga {} no está en curso",
+            id
+        )))
+    }
+}
+
+/// Reanuda una descarga cancelada o fallida desde la última página guardada
+#[tauri::command]
+pub async fn resume_paged_fetch<R: Runtime>(
+    app_handle: AppHandle<R>,
+    store: State<'_, OfflineStore>,
+    jobs: State<'_, Jobs>,
+    id: String,
+    access_token: Option<String>,
+) -> Result<PagedFetch> {
+    let mut fetch = get(&store, &id)?;
+    if fetch.status == JobStatus::Completed {
+        return Ok(fetch);
+    }
+    let cancel = jobs.register(&fetch.id)?;
+    fetch.status = JobStatus::Running;
+    fetch.error = None;
+    fetch.updated_at = now_millis();
+    if let Err(e) = save(&store, &fetch) {
+        jobs.finish(&fetch.id);
+        return Err(e);
+    }
+    spawn(app_handle, fetch.clone(), cancel, access_token);
+    Ok(fetch)
+}
+
+/// Lista las descargas del perfil. Las que figuran en curso pero no tienen
+/// tarea activa (la app se cerró a medias) se informan como canceladas.
+#[tauri::command]
+pub async fn get_paged_fetches(
+    store: State<'_, OfflineStore>,
+    jobs: State<'_, Jobs>,
+) -> Result<Vec<PagedFetch>> {
+    let mut list = list(&store)?;
+    for fetch in &mut list {
+        if fetch.status == JobStatus::Running && !jobs.is_running(&fetch.id) {
+            fetch.status = JobStatus::Cancelled;
+        }
+    }
+    Ok(list)
+}
+
+/// Borra una descarga y sus páginas
+#[tauri::command]
+pub async fn delete_paged_fetch(
+    store: State<'_, OfflineStore>,
+    jobs: State<'_, Jobs>,
+    id: String,
+) -> Result<()> {
+    if jobs.is_running(&id) {
+        return Err(Error::Validation(format!(
+            "la descarga {} sigue en curso; cancélala antes",
+            id
+        )));
+    }
+    remove(&store, &get(&store, &id)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{client, profile_store, unlocked_store};
+    use crate::{profiles, vault};
+    use red_salud_supabase_mock::{MockResponse, MockSupabase};
+
+    const PRODUCTS: &str = "/rest/v1/products";
+
+    fn new_fetch(page_size: u64) -> PagedFetch {
+        PagedFetch {
+            id: "f1".into(),
+            cache_key: "products".into(),
+            endpoint: format!("{}?select=*&order=id.asc", PRODUCTS),
+            page_size,
+            rows_done: 0,
+            pages: 0,
+            total: None,
+            status: JobStatus::Running,
+            error: None,
+            updated_at: 0,
+        }
+    }
+
+    fn page(range: &str, body: &str) -> MockResponse {
+        MockResponse::json(206, body).header("content-range", range)
+    }
+
+    #[test]
+    fn content_range_gives_rows_and_total() {
+        assert_eq!(
+            parse_content_range("0-999/5000"),
+            Some((1_000, Some(5_000)))
+        );
+        assert_eq!(parse_content_range("10-14/*"), Some((5, None)));
+        assert_eq!(parse_content_range("*/0"), Some((0, Some(0))));
+        assert_eq!(parse_content_range("basura"), None);
+        assert_eq!(parse_content_range("9-2/10"), None);
+    }
+
+    #[tokio::test]
+    async fn walks_every_page_into_the_store() {
+        let mock = MockSupabase::start();
+        mock.on("GET", PRODUCTS)
+            .once(page("0-1/5", "[1,2]"))
+            .once(page("2-3/5", "[3,4]"))
+            .once(page("4-4/5", "[5]"));
+        let http = client(&mock);
+        let (store, _root) = unlocked_store();
+        let mut fetch = new_fetch(2);
+        let mut progress = Vec::new();
+
+        run(
+            &http,
+            &store,
+            &mut fetch,
+            &AtomicBool::new(false),
+            || async { Ok("jwt".to_string()) },
+            |fetch| progress.push((fetch.rows_done, fetch.total)),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(fetch.status, JobStatus::Completed);
+        assert_eq!((fetch.rows_done, fetch.pages), (5, 3));
+        assert_eq!(progress, vec![(2, Some(5)), (4, Some(5)), (5, Some(5))]);
+        assert_eq!(store.get("products/2").unwrap().as_deref(), Some("[5]"));
+        assert_eq!(get(&store, "f1").unwrap().status, JobStatus::Completed);
+
+        let requests = mock.requests_to("GET", PRODUCTS);
+        let ranges: Vec<_> = requests.iter().map(|r| r.header("range")).collect();
+        assert_eq!(ranges, [Some("0-1"), Some("2-3"), Some("4-5")]);
+        assert_eq!(requests[0].header("prefer"), Some("count=exact"));
+        assert_eq!(requests[1].header("prefer"), None);
+        assert_eq!(requests[0].header("range-unit"), Some("items"));
+    }
+
+    #[tokio::test]
+    async fn rows_are_not_written_into_a_profile_opened_mid_request() {
+        let mock = MockSupabase::start();
+        mock.on("GET", PRODUCTS).always(page("0-1/2", "[1,2]"));
+        let http = client(&mock);
+        let (store, root) = profile_store();
+        let mut fetch = new_fetch(2);
+        save(&store, &fetch).unwrap();
+
+        // Otro usuario abre su perfil mientras se pide la página
+        let switch = || {
+            let profile = profiles::activate(&store, root.path(), "u2", None).unwrap();
+            vault::unlock(&store, &profile.dir, "otro", 1_000).unwrap();
+            async { Ok("jwt".to_string()) }
+        };
+        let error = run(
+            &http,
+            &store,
+            &mut fetch,
+            &AtomicBool::new(false),
+            switch,
+            |_| {},
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(error.code(), "STORAGE");
+        assert_eq!(store.get("products/0").unwrap(), None);
+        assert!(get(&store, "f1").is_err());
+    }
+
+    #[tokio::test]
+    async fn cancelled_and_failed_fetches_resume_where_they_stopped() {
+        let mock = MockSupabase::start();
+        mock.on("GET", PRODUCTS)
+            .once(page("0-1/6", "[1,2]"))
+            .once(MockResponse::postgrest_error(
+                400,
+                "PGRST103",
+                "rango no válido",
+            ))
+            .once(page("2-3/6", "[3,4]"))
+            .once(page("4-5/6", "[5,6]"));
+        let http = client(&mock);
+        let (store, _root) = unlocked_store();
+        let mut fetch = new_fetch(2);
+        let token = || async { Ok("jwt".to_string()) };
+
+        // Se cancela al terminar la primera página
+        let cancel = AtomicBool::new(false);
+        run(&http, &store, &mut fetch, &cancel, token, |_| {
+            cancel.store(true, Ordering::Relaxed)
+        })
+        .await
+        .unwrap();
+        assert_eq!(fetch.status, JobStatus::Cancelled);
+        assert_eq!(get(&store, "f1").unwrap().rows_done, 2);
+
+        let cancel = AtomicBool::new(false);
+        let error = run(&http, &store, &mut fetch, &cancel, token, |_| {})
+            .await
+            .unwrap_err();
+        assert_eq!(error.status(), Some(400));
+        assert_eq!(fetch.status, JobStatus::Failed);
+        assert_eq!(fetch.rows_done, 2);
+
+        let mut resumed = get(&store, "f1").unwrap();
+        run(&http, &store, &mut resumed, &cancel, token, |_| {})
+            .await
+            .unwrap();
+        assert_eq!(resumed.status, JobStatus::Completed);
+        assert_eq!((resumed.rows_done, resumed.pages), (6, 3));
+        let ranges: Vec<_> = mock
+            .requests_to("GET", PRODUCTS)
+            .iter()
+            .map(|r| r.header("range").map(str::to_string))
+            .collect();
+        assert_eq!(
+            ranges[1..],
+            [Some("2-3".into()), Some("2-3".into()), Some("4-5".into())]
+        );
+
+        remove(&store, &resumed).unwrap();
+        assert!(store.get("products/0").unwrap().is_none());
+        assert!(list(&store).unwrap().is_empty());
+    }
+}
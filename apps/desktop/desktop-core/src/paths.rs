@@ -0,0 +1,368 @@
+// Capa de seguridad de rutas
+// Todo nombre de archivo, subcarpeta o clave que llega desde el webview pasa
+// por aquí antes de tocar el disco, para que un webview comprometido no pueda
+// salir de los directorios de la aplicación con `../` o rutas absolutas.
+
+use std::path::{Path, PathBuf};
+
+use crate::error::{Error, Result};
+
+/// Longitud máxima de una clave offline
+const MAX_KEY_LEN: usize = 256;
+
+/// Longitud máxima de un componente de ruta (límite habitual de los sistemas de archivos)
+const MAX_NAME_LEN: usize = 255;
+
+/// Nombres reservados por Windows, con o sin extensión
+const WINDOWS_RESERVED: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Tipos de documento que `open_file` entrega a la aplicación predeterminada.
+/// Lo demás (`.exe`, `.bat`, `.lnk`, `.js`...) el sistema lo ejecutaría.
+const OPENABLE_EXTENSIONS: &[&str] = &[
+    "pdf", "html", "htm", "txt", "csv", "png", "jpg", "jpeg", "gif", "webp", "docx", "xlsx", "odt",
+    "ods",
+];
+
+/// Entradas internas de `app_data_dir` que los comandos de archivos no pueden tocar
+pub(crate) const APP_RESERVED: &[&str] = &[
+    "offline.db",
+    "offline.db-wal",
+    "offline.db-shm",
+    "farmacia.db",
+    "farmacia.db-wal",
+    "farmacia.db-shm",
+    "offline_data",
+    "offline_data.migrated",
+    "profiles",
+    "profile.json",
+    "transfers",
+];
+
+/// Valida una clave del almacén offline
+pub fn validate_key(key: &str) -> Result<&str> {
+    if key.is_empty() {
+        return Err(Error::Validation("la clave no puede estar vacía".into()));
+    }
+    if key.len() > MAX_KEY_LEN {
+        return Err(Error::Validation(format!(
+            "la clave supera los {} bytes",
+            MAX_KEY_LEN
+        )));
+    }
+    if key.chars().any(char::is_control) {
+        return Err(Error::Validation(
+            "la clave contiene caracteres de control".into(),
+        ));
+    }
+    Ok(key)
+}
+
+/// Valida un único componente de ruta (nombre de archivo o carpeta)
+pub fn validate_file_name(name: &str) -> Result<&str> {
+    if name.is_empty() {
+        return Err(Error::Validation("el nombre no puede estar vacío".into()));
+    }
+    if name.len() > MAX_NAME_LEN {
+        return Err(Error::Validation(format!(
+            "el nombre supera los {} bytes",
+            MAX_NAME_LEN
+        )));
+    }
+    if name == "." || name == ".." {
+        return Err(Error::Validation(format!("nombre no permitido: {}", name)));
+    }
+    if let Some(c) = name.chars().find(|c| {
+        c.is_control() || matches!(c, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|')
+    }) {
+        return Err(Error::Validation(format!(
+            "el nombre contiene un carácter no permitido: {:?}",
+            c
+        )));
+    }
+    // Windows ignora el punto y el espacio finales, lo que permite suplantar nombres
+    if name.ends_with('.') || name.ends_with(' ') {
+        return Err(Error::Validation(
+            "el nombre no puede terminar en punto o espacio".into(),
+        ));
+    }
+    let stem = name.split('.').next().unwrap_or(name).trim_end();
+    if WINDOWS_RESERVED
+        .iter()
+        .any(|reserved| reserved.eq_ignore_ascii_case(stem))
+    {
+        return Err(Error::Validation(format!("nombre reservado: {}", name)));
+    }
+    Ok(name)
+}
+
+/// Convierte una subcarpeta relativa en una ruta segura.
+///
+/// Acepta separadores `/` o `\` para anidar carpetas, pero cada componente
+/// debe ser un nombre válido: no se permiten `..`, rutas absolutas ni
+/// prefijos de unidad.
+pub fn validate_subfolder(subfolder: &str) -> Result<PathBuf> {
+    let mut path = PathBuf::new();
+    for part in subfolder.split(['/', '\\']) {
+        if part.is_empty() {
+            // Barras iniciales o duplicadas indican una ruta absoluta o mal formada
+            return Err(Error::Validation(format!(
+                "subcarpeta no válida: {}",
+                subfolder
+            )));
+        }
+        path.push(validate_file_name(part)?);
+    }
+    if path.as_os_str().is_empty() {
+        return Err(Error::Validation(
+            "la subcarpeta no puede estar vacía".into(),
+        ));
+    }
+    Ok(path)
+}
+
+/// Resuelve `subfolder/filename` dentro de `root`, rechazando cualquier
+/// intento de escapar de él o de pisar archivos internos de la aplicación.
+pub fn resolve_in(root: &Path, subfolder: Option<&str>, filename: &str) -> Result<PathBuf> {
+    let filename = validate_file_name(filename)?;
+    let relative = match subfolder {
+        Some(sub) => validate_subfolder(sub)?,
+        None => PathBuf::from("files"),
+    };
+
+    let first = relative
+        .components()
+        .next()
+        .and_then(|c| c.as_os_str().to_str())
+        .unwrap_or_default();
+    if APP_RESERVED
+        .iter()
+        .any(|reserved| reserved.eq_ignore_ascii_case(first))
+    {
+        return Err(Error::Validation(format!(
+            "la carpeta {} es interna de la aplicación",
+            first
+        )));
+    }
+
+    Ok(root.join(relative).join(filename))
+}
+
+/// Comprueba que `path` exista y quede, tras resolver enlaces, dentro de
+/// alguno de los directorios permitidos. Devuelve la ruta canónica.
+pub fn ensure_within(path: &Path, allowed_roots: &[PathBuf]) -> Result<PathBuf> {
+    let canonical = path.canonicalize().map_err(|e| match e.kind() {
+        std::io::ErrorKind::NotFound => Error::NotFound(format!("no existe: {}", path.display())),
+        _ => Error::from(e),
+    })?;
+
+    let inside = allowed_roots.iter().any(|root| {
+        root.canonicalize()
+            .map(|root| canonical.starts_with(root))
+            .unwrap_or(false)
+    });
+    if !inside {
+        return Err(Error::Validation(format!(
+            "la ruta está fuera de los directorios de la aplicación: {}",
+            path.display()
+        )));
+    }
+    Ok(canonical)
+}
+
+/// Comprueba que `path` sea un documento que se puede abrir sin ejecutarlo
+pub fn ensure_openable(path: &Path) -> Result<()> {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default();
+    if !OPENABLE_EXTENSIONS
+        .iter()
+        .any(|allowed| allowed.eq_ignore_ascii_case(extension))
+    {
+        return Err(Error::Validation(format!(
+            "no se pueden abrir archivos de este tipo: {}",
+            path.display()
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn code(result: Result<impl std::fmt::Debug>) -> &'static str {
+        result.expect_err("se esperaba un error").code()
+    }
+
+    #[test]
+    fn keys_reject_empty_long_and_control_chars() {
+        assert_eq!(code(validate_key("")), "VALIDATION");
+        assert_eq!(
+            code(validate_key(&"a".repeat(MAX_KEY_LEN + 1))),
+            "VALIDATION"
+        );
+        assert_eq!(code(validate_key("citas\n")), "VALIDATION");
+        assert_eq!(validate_key("citas_2024-05").unwrap(), "citas_2024-05");
+        // Las claves ya no son rutas: los puntos y barras son datos válidos
+        assert!(validate_key("../pacientes/5").is_ok());
+    }
+
+    #[test]
+    fn file_names_reject_traversal_and_separators() {
+        for name in [
+            "",
+            ".",
+            "..",
+            "../x.pdf",
+            "a/b.pdf",
+            "a\\b.pdf",
+            "/etc/passwd",
+        ] {
+            assert_eq!(code(validate_file_name(name)), "VALIDATION", "{name}");
+        }
+    }
+
+    #[test]
+    fn file_names_reject_windows_specific_tricks() {
+        for name in [
+            "C:evil.pdf",
+            "receta.pdf:stream",
+            "receta.pdf.",
+            "receta.pdf ",
+            "CON",
+            "con.txt",
+            "Lpt1.pdf",
+            "nul .pdf",
+            "a*b",
+            "a\u{0}b",
+        ] {
+            assert_eq!(code(validate_file_name(name)), "VALIDATION", "{name}");
+        }
+        assert!(validate_file_name("console.log").is_ok());
+        assert!(validate_file_name("receta_5_1700000000.html").is_ok());
+    }
+
+    #[test]
+    fn subfolders_reject_absolute_and_parent_components() {
+        for sub in [
+            "",
+            "/",
+            "/etc",
+            "\\\\server\\share",
+            "C:\\Windows",
+            "recetas/../..",
+            "a//b",
+        ] {
+            assert_eq!(code(validate_subfolder(sub)), "VALIDATION", "{sub}");
+        }
+        assert_eq!(
+            validate_subfolder("recetas/2024").unwrap(),
+            PathBuf::from("recetas").join("2024")
+        );
+        assert_eq!(
+            validate_subfolder("recetas\\2024").unwrap(),
+            PathBuf::from("recetas").join("2024")
+        );
+    }
+
+    #[test]
+    fn resolve_in_defaults_to_files_and_blocks_internal_entries() {
+        let root = Path::new("/datos");
+        assert_eq!(
+            resolve_in(root, None, "a.pdf").unwrap(),
+            root.join("files").join("a.pdf")
+        );
+        assert_eq!(
+            resolve_in(root, Some("recetas"), "a.pdf").unwrap(),
+            root.join("recetas").join("a.pdf")
+        );
+        assert_eq!(
+            code(resolve_in(root, Some("offline_data"), "a.json")),
+            "VALIDATION"
+        );
+        assert_eq!(
+            code(resolve_in(root, Some("OFFLINE.DB"), "x")),
+            "VALIDATION"
+        );
+        assert_eq!(
+            code(resolve_in(root, Some(".."), "offline.db")),
+            "VALIDATION"
+        );
+        assert_eq!(code(resolve_in(root, None, "../offline.db")), "VALIDATION");
+    }
+
+    #[test]
+    fn only_documents_can_be_opened() {
+        for name in [
+            "receta.pdf",
+            "historia.HTML",
+            "foto.jpeg",
+            "/datos/files/informe.xlsx",
+        ] {
+            assert!(ensure_openable(Path::new(name)).is_ok(), "{name}");
+        }
+        for name in [
+            "x.bat",
+            "x.exe",
+            "x.lnk",
+            "x.cmd",
+            "x.ps1",
+            "x.js",
+            "x.pdf.exe",
+            "sin_extension",
+        ] {
+            assert_eq!(
+                code(ensure_openable(Path::new(name))),
+                "VALIDATION",
+                "{name}"
+            );
+        }
+    }
+
+    #[test]
+    fn ensure_within_confines_to_allowed_roots() {
+        let tmp = tempfile::tempdir().unwrap();
+        let app_dir = tmp.path().join("app");
+        let outside = tmp.path().join("fuera");
+        fs::create_dir_all(app_dir.join("files")).unwrap();
+        fs::create_dir_all(&outside).unwrap();
+        let inside_file = app_dir.join("files").join("a.pdf");
+        let outside_file = outside.join("b.pdf");
+        fs::write(&inside_file, b"a").unwrap();
+        fs::write(&outside_file, b"b").unwrap();
+
+        let roots = vec![app_dir.clone()];
+        assert!(ensure_within(&inside_file, &roots).is_ok());
+        assert_eq!(code(ensure_within(&outside_file, &roots)), "VALIDATION");
+        assert_eq!(
+            code(ensure_within(
+                &app_dir.join("files/../../fuera/b.pdf"),
+                &roots
+            )),
+            "VALIDATION"
+        );
+        assert_eq!(
+            code(ensure_within(&app_dir.join("nada.pdf"), &roots)),
+            "NOT_FOUND"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn ensure_within_resolves_symlinks() {
+        let tmp = tempfile::tempdir().unwrap();
+        let app_dir = tmp.path().join("app");
+        fs::create_dir_all(&app_dir).unwrap();
+        let target = tmp.path().join("secreto.txt");
+        fs::write(&target, b"x").unwrap();
+        let link = app_dir.join("enlace.txt");
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+
+        assert_eq!(code(ensure_within(&link, &[app_dir])), "VALIDATION");
+    }
+}
@@ -0,0 +1,1324 @@
+// Impresora fiscal
+// Las facturas del punto de venta solo tienen validez ante el SENIAT si las
+// emite una impresora fiscal homologada. Este módulo habla con ella por
+// puerto serie o TCP con el juego de comandos HKA (`protocol`), arma la
+// factura o la nota de crédito a partir de lo guardado en la base local y
+// anota el número que asignó la impresora. Con `backend: "simulated"` todo
+// corre contra una impresora en memoria (`simulator`), sin el equipo.
+
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager, Runtime, State};
+
+use super::pos::{self, Currency, PaymentMethod, SaleStatus};
+use super::{local_date, PharmacyDb};
+use crate::error::{Error, Result};
+use crate::store::now_millis;
+
+pub mod protocol;
+pub mod simulator;
+pub mod transport;
+
+use protocol::{
+    digits, frame, printable, read_byte, read_frame, Counters, DailyTotals, FiscalMemory, Link,
+    PrinterError, PrinterMode, PrinterState, RawStatus, ACK, DISCOUNT_DIGITS, ENQ, NAK,
+    PAYMENT_DIGITS, PRICE_DIGITS, QUANTITY_DIGITS,
+};
+use simulator::Simulator;
+pub use transport::PrinterConnection;
+
+/// Evento con el estado de la impresora cuando cambia
+pub const FISCAL_PRINTER_STATUS_EVENT: &str = "fiscal-printer-status";
+
+/// Espera de cada respuesta
+const REPLY_TIMEOUT: Duration = Duration::from_secs(3);
+/// Los reportes X y Z imprimen varias páginas antes de contestar
+const REPORT_TIMEOUT: Duration = Duration::from_secs(30);
+/// Reenvíos de un comando que llegó dañado
+const RETRIES: usize = 3;
+const STATUS_POLL_INTERVAL: Duration = Duration::from_secs(30);
+/// Serial de la impresora simulada
+const SIMULATOR_SERIAL: &str = "SIM0000001";
+
+// ============================================
+// DOCUMENTOS
+// ============================================
+
+/// Cliente de la factura; sin él la impresora emite a consumidor final
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Customer {
+    pub rif: String,
+    pub name: String,
+}
+
+impl Customer {
+    fn validate(&self) -> Result<()> {
+        let rif: String = self.rif.chars().filter(|c| *c != '-').collect();
+        let valid = rif
+            .strip_prefix(['V', 'E', 'J', 'P', 'G'])
+            .is_some_and(|number| {
+                (7..=10).contains(&number.len()) && number.bytes().all(|b| b.is_ascii_digit())
+            });
+        if !valid {
+            return Err(Error::Validation(format!("RIF no válido: {}", self.rif)));
+        }
+        if self.name.trim().is_empty() {
+            return Err(Error::Validation("falta el nombre del cliente".into()));
+        }
+        Ok(())
+    }
+}
+
+/// Renglón en bolívares: la impresora no conoce la tasa
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FiscalItem {
+    pub description: String,
+    /// Centésimas (16 = 16 %); 0 es exento
+    pub iva_rate: i64,
+    /// Céntimos de bolívar
+    pub unit_price: i64,
+    /// Milésimas
+    pub quantity: i64,
+    /// Céntimos de bolívar sobre el renglón
+    pub discount: i64,
+}
+
+/// Pago en bolívares con el código de medio de pago de la impresora
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FiscalPayment {
+    pub code: u8,
+    pub amount: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct FiscalInvoice {
+    pub customer: Option<Customer>,
+    pub items: Vec<FiscalItem>,
+    pub payments: Vec<FiscalPayment>,
+    pub comments: Vec<String>,
+}
+
+/// Nota de crédito; la impresora exige los datos de la factura de origen
+#[derive(Debug, Clone)]
+pub struct FiscalCreditNote {
+    pub customer: Option<Customer>,
+    pub invoice_number: String,
+    /// `YYYY-MM-DD`
+    pub invoice_date: String,
+    pub printer_serial: String,
+    pub items: Vec<FiscalItem>,
+    pub comments: Vec<String>,
+}
+
+/// Número que asignó la impresora a un documento
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Printed {
+    pub number: String,
+    pub serial: String,
+    /// Reporte Z que cerrará el día del documento
+    pub z_number: u32,
+}
+
+/// Carácter de la alícuota en los comandos de factura
+fn tax_class(iva_rate: i64) -> Result<usize> {
+    match iva_rate {
+        0 => Ok(0),
+        16 => Ok(1),
+        8 => Ok(2),
+        31 => Ok(3),
+        other => Err(Error::Validation(format!(
+            "la impresora fiscal no tiene programada la alícuota de {} %",
+            other
+        ))),
+    }
+}
+
+/// Código del medio de pago. Del 20 en adelante son divisas y causan IGTF.
+pub fn payment_code(method: PaymentMethod, currency: Currency) -> u8 {
+    match (method, currency) {
+        (PaymentMethod::Cash, Currency::Ves) => 1,
+        (PaymentMethod::Card, _) => 9,
+        (PaymentMethod::Biopago, _) => 10,
+        (PaymentMethod::PagoMovil, _) => 11,
+        (PaymentMethod::Transfer, Currency::Ves) => 12,
+        (PaymentMethod::Cash, Currency::Usd) => 20,
+        (PaymentMethod::Zelle, _) => 21,
+        (PaymentMethod::Crypto, _) => 22,
+        (PaymentMethod::Transfer, Currency::Usd) => 23,
+    }
+}
+
+fn item_command(prefix: &str, item: &FiscalItem) -> Result<String> {
+    let description = printable(&item.description);
+    if description.is_empty() {
+        return Err(Error::Validation("el renglón no tiene descripción".into()));
+    }
+    Ok(format!(
+        "{}{}{}{}",
+        prefix,
+        digits(item.unit_price, PRICE_DIGITS, "precio")?,
+        digits(item.quantity, QUANTITY_DIGITS, "cantidad")?,
+        description
+    ))
+}
+
+/// Descuento por monto sobre el último renglón. `p-` es el porcentual.
+fn discount_command(discount: i64) -> Result<String> {
+    Ok(format!(
+        "q-{}",
+        digits(discount, DISCOUNT_DIGITS, "descuento")?
+    ))
+}
+
+fn customer_commands(customer: &Option<Customer>) -> Result<Vec<String>> {
+    let Some(customer) = customer else {
+        return Ok(Vec::new());
+    };
+    customer.validate()?;
+    Ok(vec![
+        format!("iR*{}", printable(&customer.rif)),
+        format!("iS*{}", printable(&customer.name)),
+    ])
+}
+
+// ============================================
+// CONTROLADOR
+// ============================================
+
+/// Controlador de una impresora fiscal sobre cualquier `Link`
+pub struct FiscalPrinter {
+    link: Box<dyn Link>,
+}
+
+impl FiscalPrinter {
+    pub fn new(mut link: Box<dyn Link>) -> Result<Self> {
+        link.set_timeout(REPLY_TIMEOUT)?;
+        Ok(Self { link })
+    }
+
+    pub fn open(connection: &PrinterConnection, simulator: &Arc<Mutex<Simulator>>) -> Result<Self> {
+        Self::new(transport::open(connection, simulator)?)
+    }
+
+    fn not_responding() -> Error {
+        Error::Network("la impresora fiscal no responde".into())
+    }
+
+    /// Envía un comando y espera ACK. Si llega NAK, el byte de error dice si
+    /// la impresora lo rechazó o si llegó dañado y hay que reenviarlo. Sin
+    /// respuesta no se reenvía: el comando pudo haberse ejecutado.
+    fn send(&mut self, command: &str) -> Result<()> {
+        let framed = frame(command.as_bytes());
+        for _ in 0..RETRIES {
+            self.link.write_all(&framed)?;
+            self.link.flush()?;
+            match read_byte(self.link.as_mut())? {
+                Some(ACK) => return Ok(()),
+                Some(NAK) => match self.raw_status()?.error {
+                    PrinterError::None => continue,
+                    error => return Err(Error::Validation(error.message().into())),
+                },
+                _ => return Err(Self::not_responding()),
+            }
+        }
+        Err(Error::Io(
+            "la línea de la impresora fiscal tiene demasiado ruido".into(),
+        ))
+    }
+
+    /// Envía una consulta (`S1`, `S2`) y lee la trama de datos de respuesta
+    fn query(&mut self, command: &[u8]) -> Result<Vec<u8>> {
+        for _ in 0..RETRIES {
+            self.link.write_all(command)?;
+            self.link.flush()?;
+            if let Some(data) = read_frame(self.link.as_mut())? {
+                return Ok(data);
+            }
+        }
+        Err(Self::not_responding())
+    }
+
+    pub fn raw_status(&mut self) -> Result<RawStatus> {
+        RawStatus::decode(&self.query(&[ENQ])?)
+    }
+
+    pub fn counters(&mut self) -> Result<Counters> {
+        let data = self.query(&frame(b"S1"))?;
+        Counters::decode(&String::from_utf8_lossy(&data))
+    }
+
+    pub fn totals(&mut self) -> Result<DailyTotals> {
+        let data = self.query(&frame(b"S2"))?;
+        DailyTotals::decode(&String::from_utf8_lossy(&data))
+    }
+
+    /// Deja la impresora sin documento abierto, o explica por qué no puede
+    /// imprimir. Un documento que quedó a medias se anula.
+    fn ready(&mut self) -> Result<()> {
+        let status = self.raw_status()?;
+        // Los rechazos son del último comando, no impiden imprimir
+        if status.error != PrinterError::None && !status.error.is_rejection() {
+            return Err(Error::Validation(status.error.message().into()));
+        }
+        if status.memory == FiscalMemory::Full {
+            return Err(Error::Validation(
+                PrinterError::FiscalMemoryFull.message().into(),
+            ));
+        }
+        match status.state {
+            PrinterState::Idle => Ok(()),
+            PrinterState::FiscalDocument => self.send("7"),
+            PrinterState::NonFiscalDocument => Err(Error::Validation(
+                "la impresora fiscal tiene un documento no fiscal abierto".into(),
+            )),
+        }
+    }
+
+    /// Corre los comandos de un documento; si alguno falla lo anula. Un
+    /// comando sin respuesta pudo haberse ejecutado: si el contador del
+    /// documento (`number`) avanzó, la impresora lo cerró y numeró y se
+    /// devuelve como impreso para no emitirlo dos veces.
+    fn document(
+        &mut self,
+        number: fn(&Counters) -> u32,
+        commands: impl FnOnce(&mut Self) -> Result<()>,
+    ) -> Result<Counters> {
+        self.ready()?;
+        let before = self.counters()?;
+        if let Err(e) = commands(self) {
+            if let Ok(after) = self.counters() {
+                if number(&after) > number(&before) {
+                    return Ok(after);
+                }
+            }
+            let _ = self.send("7");
+            return Err(e);
+        }
+        self.counters()
+    }
+
+    fn pay(&mut self, payments: &[FiscalPayment]) -> Result<()> {
+        let Some((last, partial)) = payments.split_last() else {
+            return self.send("101");
+        };
+        for payment in partial {
+            self.send(&format!(
+                "2{:02}{}",
+                payment.code,
+                digits(payment.amount, PAYMENT_DIGITS, "pago")?
+            ))?;
+            // Con vuelto un pago parcial puede cerrar la factura
+            if self.raw_status()?.state == PrinterState::Idle {
+                return Ok(());
+            }
+        }
+        self.send(&format!("1{:02}", last.code))
+    }
+
+    pub fn print_invoice(&mut self, invoice: &FiscalInvoice) -> Result<Printed> {
+        if invoice.items.is_empty() {
+            return Err(Error::Validation("la factura no tiene renglones".into()));
+        }
+        let counters = self.document(
+            |counters| counters.last_invoice,
+            |printer| {
+                for command in customer_commands(&invoice.customer)? {
+                    printer.send(&command)?;
+                }
+                for item in &invoice.items {
+                    let class = [" ", "!", "\"", "#"][tax_class(item.iva_rate)?];
+                    printer.send(&item_command(class, item)?)?;
+                    if item.discount > 0 {
+                        printer.send(&discount_command(item.discount)?)?;
+                    }
+                }
+                for comment in &invoice.comments {
+                    printer.send(&format!("@{}", printable(comment)))?;
+                }
+                printer.send("3")?;
+                printer.pay(&invoice.payments)
+            },
+        )?;
+        Ok(Printed {
+            number: format!("{:08}", counters.last_invoice),
+            serial: counters.serial,
+            z_number: counters.z_number + 1,
+        })
+    }
+
+    pub fn print_credit_note(&mut self, note: &FiscalCreditNote) -> Result<Printed> {
+        if note.items.is_empty() {
+            return Err(Error::Validation(
+                "la nota de crédito no tiene renglones".into(),
+            ));
+        }
+        let invoice_number: u64 = note.invoice_number.parse().map_err(|_| {
+            Error::Validation(format!(
+                "número de factura no válido: {}",
+                note.invoice_number
+            ))
+        })?;
+        let date = match note.invoice_date.split('-').collect::<Vec<_>>().as_slice() {
+            [year, month, day] => format!("{}-{}-{}", day, month, year),
+            _ => {
+                return Err(Error::Validation(format!(
+                    "fecha de factura no válida: {}",
+                    note.invoice_date
+                )))
+            }
+        };
+        let counters = self.document(
+            |counters| counters.last_credit_note,
+            |printer| {
+                for command in customer_commands(&note.customer)? {
+                    printer.send(&command)?;
+                }
+                printer.send(&format!("iF*{:011}", invoice_number))?;
+                printer.send(&format!("iD*{}", date))?;
+                printer.send(&format!("iI*{}", printable(&note.printer_serial)))?;
+                for item in &note.items {
+                    let class = format!("d{}", tax_class(item.iva_rate)?);
+                    printer.send(&item_command(&class, item)?)?;
+                    if item.discount > 0 {
+                        printer.send(&discount_command(item.discount)?)?;
+                    }
+                }
+                for comment in &note.comments {
+                    printer.send(&format!("@{}", printable(comment)))?;
+                }
+                printer.send("3")?;
+                // El reintegro se registra como efectivo en bolívares
+                printer.send("101")
+            },
+        )?;
+        Ok(Printed {
+            number: format!("{:08}", counters.last_credit_note),
+            serial: counters.serial,
+            z_number: counters.z_number + 1,
+        })
+    }
+
+    fn report(&mut self, command: &str) -> Result<()> {
+        self.ready()?;
+        self.link.set_timeout(REPORT_TIMEOUT)?;
+        let result = self.send(command);
+        self.link.set_timeout(REPLY_TIMEOUT)?;
+        result
+    }
+
+    /// Reporte X: acumulados del día sin cerrarlo
+    pub fn x_report(&mut self) -> Result<(DailyTotals, Counters)> {
+        self.report("I0X")?;
+        Ok((self.totals()?, self.counters()?))
+    }
+
+    /// Reporte Z: cierra el día y reinicia los acumulados. Devuelve los del
+    /// día cerrado.
+    pub fn z_report(&mut self) -> Result<(DailyTotals, Counters)> {
+        let totals = self.totals()?;
+        self.report("I0Z")?;
+        Ok((totals, self.counters()?))
+    }
+
+    pub fn status(&mut self, now: i64) -> PrinterStatus {
+        let result = self
+            .raw_status()
+            .and_then(|raw| Ok((raw, self.counters()?)));
+        match result {
+            Ok((raw, counters)) => PrinterStatus {
+                connected: true,
+                mode: Some(raw.mode),
+                memory: Some(raw.memory),
+                state: Some(raw.state),
+                error: Some(raw.error),
+                message: status_message(&raw).map(Into::into),
+                counters: Some(counters),
+                checked_at: now,
+            },
+            Err(e) => PrinterStatus::offline(e, now),
+        }
+    }
+}
+
+/// Lo que conviene avisar en la barra: el error o, si no hay, el modo de
+/// prueba y la memoria fiscal por llenarse
+fn status_message(raw: &RawStatus) -> Option<&'static str> {
+    if raw.error != PrinterError::None {
+        Some(raw.error.message())
+    } else if raw.mode == PrinterMode::Test {
+        Some("la impresora fiscal está en modo de prueba: sus documentos no tienen validez fiscal")
+    } else {
+        match raw.memory {
+            FiscalMemory::Available => None,
+            FiscalMemory::AlmostFull => Some("la memoria fiscal está casi llena"),
+            FiscalMemory::Full => Some(PrinterError::FiscalMemoryFull.message()),
+        }
+    }
+}
+
+/// Estado de la impresora para la barra del punto de venta
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PrinterStatus {
+    pub connected: bool,
+    pub mode: Option<PrinterMode>,
+    pub memory: Option<FiscalMemory>,
+    pub state: Option<PrinterState>,
+    pub error: Option<PrinterError>,
+    pub message: Option<String>,
+    pub counters: Option<Counters>,
+    pub checked_at: i64,
+}
+
+impl PrinterStatus {
+    fn offline(error: Error, now: i64) -> Self {
+        Self {
+            connected: false,
+            mode: None,
+            memory: None,
+            state: None,
+            error: None,
+            message: Some(error.to_string()),
+            counters: None,
+            checked_at: now,
+        }
+    }
+
+    fn same_as(&self, other: &PrinterStatus) -> bool {
+        PrinterStatus {
+            checked_at: other.checked_at,
+            ..self.clone()
+        } == *other
+    }
+}
+
+// ============================================
+// BASE LOCAL
+// ============================================
+
+/// Fila de `fiscal_documents`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FiscalDocument {
+    pub id: i64,
+    pub document_type: String,
+    pub invoice_id: Option<String>,
+    pub number: Option<String>,
+    pub printer_serial: String,
+    pub z_number: u32,
+    pub printed_at: i64,
+}
+
+/// Reporte X o Z con los acumulados que informó la impresora
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FiscalReport {
+    pub document: FiscalDocument,
+    pub totals: DailyTotals,
+}
+
+pub(crate) fn load_connection(conn: &Connection) -> Result<Option<PrinterConnection>> {
+    let json: Option<String> = conn
+        .query_row(
+            "SELECT connection FROM fiscal_printer WHERE id = 1",
+            [],
+            |row| row.get(0),
+        )
+        .optional()?;
+    json.map(|json| serde_json::from_str(&json).map_err(|e| Error::Storage(e.to_string())))
+        .transpose()
+}
+
+pub(crate) fn save_connection(
+    conn: &Connection,
+    connection: Option<&PrinterConnection>,
+    now: i64,
+) -> Result<()> {
+    match connection {
+        Some(connection) => {
+            connection.validate()?;
+            let json =
+                serde_json::to_string(connection).map_err(|e| Error::Storage(e.to_string()))?;
+            conn.execute(
+                "INSERT INTO fiscal_printer (id, connection, updated_at) VALUES (1, ?1, ?2)
+                 ON CONFLICT (id) DO UPDATE SET connection = ?1, updated_at = ?2",
+                params![json, now],
+            )?;
+        }
+        None => {
+            conn.execute("DELETE FROM fiscal_printer", [])?;
+        }
+    }
+    Ok(())
+}
+
+fn load_document(conn: &Connection, id: i64) -> Result<FiscalDocument> {
+    Ok(conn.query_row(
+        "SELECT id, document_type, invoice_id, number, printer_serial, z_number, printed_at
+         FROM fiscal_documents WHERE id = ?1",
+        [id],
+        |row| {
+            Ok(FiscalDocument {
+                id: row.get(0)?,
+                document_type: row.get(1)?,
+                invoice_id: row.get(2)?,
+                number: row.get(3)?,
+                printer_serial: row.get(4)?,
+                z_number: row.get(5)?,
+                printed_at: row.get(6)?,
+            })
+        },
+    )?)
+}
+
+fn insert_document(
+    conn: &Connection,
+    document_type: &str,
+    invoice_id: Option<&str>,
+    number: Option<&str>,
+    serial: &str,
+    z_number: u32,
+    now: i64,
+) -> Result<FiscalDocument> {
+    conn.execute(
+        "INSERT INTO fiscal_documents (document_type, invoice_id, number, printer_serial,
+                                       z_number, printed_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![document_type, invoice_id, number, serial, z_number, now],
+    )?;
+    load_document(conn, conn.last_insert_rowid())
+}
+
+/// Factura fiscal de una venta finalizada, con los montos en bolívares
+pub(crate) fn invoice_for_sale(
+    conn: &Connection,
+    sale_id: &str,
+    customer: Option<Customer>,
+    now: i64,
+) -> Result<(String, FiscalInvoice)> {
+    let sale = pos::load_sale(conn, sale_id, now)?;
+    let invoice_id = match (&sale.status, &sale.invoice_id) {
+        (SaleStatus::Finalized, Some(invoice_id)) => invoice_id.clone(),
+        _ => {
+            return Err(Error::Validation(
+                "solo se imprimen ventas finalizadas".into(),
+            ))
+        }
+    };
+    ensure_not_printed(conn, &invoice_id)?;
+    let items = sale
+        .lines
+        .iter()
+        .map(|line| FiscalItem {
+            description: line.product_name.clone(),
+            iva_rate: line.iva_rate,
+            unit_price: line.unit_price_ves,
+            quantity: line.quantity * 1000,
+            // Lo que la impresora resta para llegar a la base de la venta
+            discount: (line.unit_price_ves * line.quantity - line.total_ves).max(0),
+        })
+        .collect();
+    let payments = sale
+        .payments
+        .iter()
+        .map(|payment| FiscalPayment {
+            code: payment_code(payment.method, payment.currency),
+            amount: payment.amount_ves,
+        })
+        .collect();
+    let comments = sale
+        .invoice_number
+        .iter()
+        .map(|number| format!("Venta {}", number))
+        .chain(std::iter::once(format!("Tasa BCV {}", sale.exchange_rate)))
+        .collect();
+    Ok((
+        invoice_id,
+        FiscalInvoice {
+            customer,
+            items,
+            payments,
+            comments,
+        },
+    ))
+}
+
+fn ensure_not_printed(conn: &Connection, invoice_id: &str) -> Result<()> {
+    let control: Option<String> = conn
+        .query_row(
+            "SELECT fiscal_control_number FROM invoices WHERE id = ?1",
+            [invoice_id],
+            |row| row.get(0),
+        )
+        .optional()?
+        .ok_or_else(|| Error::NotFound(format!("factura {}", invoice_id)))?;
+    match control {
+        Some(number) => Err(Error::Validation(format!(
+            "la factura ya se imprimió con el número fiscal {}",
+            number
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Anota el número fiscal en la factura y el documento impreso
+pub(crate) fn record_invoice(
+    conn: &mut Connection,
+    invoice_id: &str,
+    printed: &Printed,
+    now: i64,
+) -> Result<FiscalDocument> {
+    let tx = conn.transaction()?;
+    tx.execute(
+        "UPDATE invoices SET fiscal_control_number = ?2, updated_at = ?3 WHERE id = ?1",
+        params![invoice_id, printed.number, now],
+    )?;
+    let document = insert_document(
+        &tx,
+        "invoice",
+        Some(invoice_id),
+        Some(&printed.number),
+        &printed.serial,
+        printed.z_number,
+        now,
+    )?;
+    tx.commit()?;
+    Ok(document)
+}
+
+/// Nota de crédito por el total de una factura ya impresa
+pub(crate) fn credit_note_for_invoice(
+    conn: &Connection,
+    invoice_id: &str,
+    reason: &str,
+    customer: Option<Customer>,
+) -> Result<FiscalCreditNote> {
+    if reason.trim().is_empty() {
+        return Err(Error::Validation(
+            "falta el motivo de la nota de crédito".into(),
+        ));
+    }
+    let (status, control, created_at): (String, Option<String>, i64) = conn
+        .query_row(
+            "SELECT status, fiscal_control_number, created_at FROM invoices WHERE id = ?1",
+            [invoice_id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .optional()?
+        .ok_or_else(|| Error::NotFound(format!("factura {}", invoice_id)))?;
+    let Some(invoice_number) = control else {
+        return Err(Error::Validation(
+            "la factura no se imprimió en la impresora fiscal".into(),
+        ));
+    };
+    if status != "paid" {
+        return Err(Error::Validation(format!(
+            "no se puede emitir una nota de crédito de una factura en estado {}",
+            status
+        )));
+    }
+    let printer_serial: String = conn.query_row(
+        "SELECT printer_serial FROM fiscal_documents
+         WHERE invoice_id = ?1 AND document_type = 'invoice'
+         ORDER BY id DESC LIMIT 1",
+        [invoice_id],
+        |row| row.get(0),
+    )?;
+    let mut stmt = conn.prepare(
+        "SELECT product_name, iva_rate, unit_price_ves, quantity, total_ves
+         FROM invoice_items WHERE invoice_id = ?1 ORDER BY created_at, rowid",
+    )?;
+    let items = stmt
+        .query_map([invoice_id], |row| {
+            let unit_price: i64 = row.get(2)?;
+            // Centésimas de unidad a milésimas
+            let quantity: i64 = row.get(3)?;
+            let total: i64 = row.get(4)?;
+            Ok(FiscalItem {
+                description: row.get(0)?,
+                iva_rate: row.get(1)?,
+                unit_price,
+                quantity: quantity * 10,
+                discount: (unit_price * quantity / 100 - total).max(0),
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(FiscalCreditNote {
+        customer,
+        invoice_number,
+        invoice_date: local_date(created_at),
+        printer_serial,
+        items,
+        comments: vec![format!("Motivo: {}", reason.trim())],
+    })
+}
+
+/// Marca la factura como reintegrada y anota la nota de crédito
+pub(crate) fn record_credit_note(
+    conn: &mut Connection,
+    invoice_id: &str,
+    printed: &Printed,
+    now: i64,
+) -> Result<FiscalDocument> {
+    let tx = conn.transaction()?;
+    tx.execute(
+        "UPDATE invoices SET status = 'refunded', updated_at = ?2 WHERE id = ?1",
+        params![invoice_id, now],
+    )?;
+    let document = insert_document(
+        &tx,
+        "credit_note",
+        Some(invoice_id),
+        Some(&printed.number),
+        &printed.serial,
+        printed.z_number,
+        now,
+    )?;
+    tx.commit()?;
+    Ok(document)
+}
+
+// ============================================
+// ESTADO
+// ============================================
+
+/// Impresora compartida por los comandos y el sondeo de estado. Los
+/// documentos no se intercalan: cada uso toma `busy` de principio a fin.
+pub struct FiscalPrinterState {
+    busy: Mutex<()>,
+    simulator: Arc<Mutex<Simulator>>,
+    status: Mutex<Option<PrinterStatus>>,
+}
+
+impl Default for FiscalPrinterState {
+    fn default() -> Self {
+        Self {
+            busy: Mutex::new(()),
+            simulator: Arc::new(Mutex::new(Simulator::new(SIMULATOR_SERIAL))),
+            status: Mutex::new(None),
+        }
+    }
+}
+
+impl FiscalPrinterState {
+    fn open(&self, db: &PharmacyDb) -> Result<FiscalPrinter> {
+        let connection = db
+            .with_conn(|conn| load_connection(conn))?
+            .ok_or_else(|| Error::NotFound("no hay impresora fiscal configurada".into()))?;
+        FiscalPrinter::open(&connection, &self.simulator)
+    }
+
+    /// Abre la impresora y corre `f` sin que nadie más la use
+    fn with_printer<T>(
+        &self,
+        db: &PharmacyDb,
+        f: impl FnOnce(&mut FiscalPrinter) -> Result<T>,
+    ) -> Result<T> {
+        let _busy = self
+            .busy
+            .lock()
+            .map_err(|_| Error::Io("la impresora fiscal quedó bloqueada".into()))?;
+        f(&mut self.open(db)?)
+    }
+
+    /// Consulta el estado y lo guarda. `None` si la impresora está ocupada
+    /// o no hay ninguna configurada.
+    fn poll(&self, db: &PharmacyDb, wait: bool) -> Result<Option<PrinterStatus>> {
+        let _busy = match (self.busy.try_lock(), wait) {
+            (Ok(guard), _) => guard,
+            (Err(_), false) => return Ok(None),
+            (Err(_), true) => self
+                .busy
+                .lock()
+                .map_err(|_| Error::Io("la impresora fiscal quedó bloqueada".into()))?,
+        };
+        if db.with_conn(|conn| load_connection(conn))?.is_none() {
+            return Ok(None);
+        }
+        let now = now_millis();
+        let status = match self.open(db) {
+            Ok(mut printer) => printer.status(now),
+            Err(e) => PrinterStatus::offline(e, now),
+        };
+        *self.status.lock().map_err(|_| lock_error())? = Some(status.clone());
+        Ok(Some(status))
+    }
+}
+
+fn lock_error() -> Error {
+    Error::Io("el estado de la impresora fiscal quedó bloqueado".into())
+}
+
+/// Corre `f` fuera del hilo de los comandos: la impresora tarda segundos
+async fn blocking<R: Runtime, T: Send + 'static>(
+    app_handle: AppHandle<R>,
+    f: impl FnOnce(&PharmacyDb, &FiscalPrinterState) -> Result<T> + Send + 'static,
+) -> Result<T> {
+    tauri::async_runtime::spawn_blocking(move || {
+        f(
+            &app_handle.state::<PharmacyDb>(),
+            &app_handle.state::<FiscalPrinterState>(),
+        )
+    })
+    .await?
+}
+
+/// Sondea la impresora configurada y avisa al webview cuando cambia su
+/// estado (sin papel, desconectada, documento abierto)
+pub(crate) fn spawn_status_poll<R: Runtime>(app_handle: AppHandle<R>) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let previous = app_handle
+                .state::<FiscalPrinterState>()
+                .status
+                .lock()
+                .ok()
+                .and_then(|status| status.clone());
+            let polled = blocking(app_handle.clone(), |db, printer| printer.poll(db, false)).await;
+            if let Ok(Some(status)) = polled {
+                if !previous.is_some_and(|previous| previous.same_as(&status)) {
+                    let _ = app_handle.emit(FISCAL_PRINTER_STATUS_EVENT, &status);
+                }
+            }
+            tokio::time::sleep(STATUS_POLL_INTERVAL).await;
+        }
+    });
+}
+
+// ============================================
+// COMMANDS
+// ============================================
+
+/// Puertos serie disponibles para la impresora
+#[tauri::command]
+pub async fn list_serial_ports() -> Result<Vec<String>> {
+    transport::list_serial_ports()
+}
+
+/// Impresora configurada, si hay alguna
+#[tauri::command]
+pub async fn get_fiscal_printer(db: State<'_, PharmacyDb>) -> Result<Option<PrinterConnection>> {
+    db.with_conn(|conn| load_connection(conn))
+}
+
+/// Configura la impresora; `null` la quita
+#[tauri::command]
+pub async fn set_fiscal_printer(
+    db: State<'_, PharmacyDb>,
+    printer: State<'_, FiscalPrinterState>,
+    connection: Option<PrinterConnection>,
+) -> Result<Option<PrinterConnection>> {
+    db.with_conn(|conn| save_connection(conn, connection.as_ref(), now_millis()))?;
+    *printer.status.lock().map_err(|_| lock_error())? = None;
+    Ok(connection)
+}
+
+/// Último estado conocido; con `refresh` consulta la impresora
+#[tauri::command]
+pub async fn get_fiscal_printer_status<R: Runtime>(
+    app_handle: AppHandle<R>,
+    refresh: Option<bool>,
+) -> Result<PrinterStatus> {
+    let known = app_handle
+        .state::<FiscalPrinterState>()
+        .status
+        .lock()
+        .map_err(|_| lock_error())?
+        .clone();
+    if let (Some(status), false) = (known, refresh.unwrap_or(false)) {
+        return Ok(status);
+    }
+    blocking(app_handle, |db, printer| printer.poll(db, true))
+        .await?
+        .ok_or_else(|| Error::NotFound("no hay impresora fiscal configurada".into()))
+}
+
+/// Imprime la factura fiscal de una venta finalizada y guarda su número
+#[tauri::command]
+pub async fn print_fiscal_invoice<R: Runtime>(
+    app_handle: AppHandle<R>,
+    sale_id: String,
+    customer: Option<Customer>,
+) -> Result<FiscalDocument> {
+    blocking(app_handle, move |db, printer| {
+        printer.with_printer(db, |device| {
+            let now = now_millis();
+            let (invoice_id, invoice) =
+                db.with_conn(|conn| invoice_for_sale(conn, &sale_id, customer, now))?;
+            let printed = device.print_invoice(&invoice)?;
+            db.with_conn(|conn| record_invoice(conn, &invoice_id, &printed, now_millis()))
+        })
+    })
+    .await
+}
+
+/// Anula una factura impresa con una nota de crédito por el total
+#[tauri::command]
+pub async fn print_fiscal_credit_note<R: Runtime>(
+    app_handle: AppHandle<R>,
+    invoice_id: String,
+    reason: String,
+    customer: Option<Customer>,
+) -> Result<FiscalDocument> {
+    blocking(app_handle, move |db, printer| {
+        printer.with_printer(db, |device| {
+            let note =
+                db.with_conn(|conn| credit_note_for_invoice(conn, &invoice_id, &reason, customer))?;
+            let printed = device.print_credit_note(&note)?;
+            db.with_conn(|conn| record_credit_note(conn, &invoice_id, &printed, now_millis()))
+        })
+    })
+    .await
+}
+
+/// Reporte X: acumulados del día sin cerrarlo
+#[tauri::command]
+pub async fn print_x_report<R: Runtime>(app_handle: AppHandle<R>) -> Result<FiscalReport> {
+    blocking(app_handle, |db, printer| {
+        printer.with_printer(db, |device| {
+            let (totals, counters) = device.x_report()?;
+            let document = db.with_conn(|conn| {
+                insert_document(
+                    conn,
+                    "x_report",
+                    None,
+                    None,
+                    &counters.serial,
+                    counters.z_number + 1,
+                    now_millis(),
+                )
+            })?;
+            Ok(FiscalReport { document, totals })
+        })
+    })
+    .await
+}
+
+/// Reporte Z: cierra el día fiscal
+#[tauri::command]
+pub async fn print_z_report<R: Runtime>(app_handle: AppHandle<R>) -> Result<FiscalReport> {
+    blocking(app_handle, |db, printer| {
+        printer.with_printer(db, |device| {
+            let (totals, counters) = device.z_report()?;
+            let number = format!("{:04}", counters.z_number);
+            let document = db.with_conn(|conn| {
+                insert_document(
+                    conn,
+                    "z_report",
+                    None,
+                    Some(&number),
+                    &counters.serial,
+                    counters.z_number,
+                    now_millis(),
+                )
+            })?;
+            Ok(FiscalReport { document, totals })
+        })
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pharmacy::pos::NewPayment;
+    use crate::pharmacy::schema::{migrate, MIGRATIONS};
+    use simulator::SimulatorLink;
+
+    /// 2026-03-07 12:00 en Caracas, sábado
+    const NOW: i64 = 1_772_899_200_000;
+
+    fn simulated() -> (Arc<Mutex<Simulator>>, FiscalPrinter) {
+        let simulator = Arc::new(Mutex::new(Simulator::new(SIMULATOR_SERIAL)));
+        let printer = FiscalPrinter::new(Box::new(SimulatorLink(simulator.clone()))).unwrap();
+        (simulator, printer)
+    }
+
+    fn item(description: &str, iva_rate: i64, unit_price: i64, quantity: i64) -> FiscalItem {
+        FiscalItem {
+            description: description.into(),
+            iva_rate,
+            unit_price,
+            quantity,
+            discount: 0,
+        }
+    }
+
+    fn invoice(payments: Vec<FiscalPayment>) -> FiscalInvoice {
+        FiscalInvoice {
+            customer: Some(Customer {
+                rif: "V-12345678-9".into(),
+                name: "María Pérez".into(),
+            }),
+            items: vec![
+                FiscalItem {
+                    discount: 4_000,
+                    ..item("Losartán 50 mg", 16, 40_000, 2_000)
+                },
+                item("Suero oral", 0, 20_000, 1_000),
+            ],
+            payments,
+            comments: vec!["Gracias por su compra".into()],
+        }
+    }
+
+    #[test]
+    fn prints_invoices_with_iva_and_igtf() {
+        let (simulator, mut printer) = simulated();
+        // Bases: 760,00 al 16 % y 200,00 exento; IVA 121,60; total 1.081,60.
+        // 400,00 en divisas causan 12,00 de IGTF.
+        let payments = vec![
+            FiscalPayment {
+                code: 20,
+                amount: 40_000,
+            },
+            FiscalPayment { code: 1, amount: 0 },
+        ];
+        let printed = printer.print_invoice(&invoice(payments)).unwrap();
+        assert_eq!(printed.number, "00000001");
+        assert_eq!(printed.serial, SIMULATOR_SERIAL);
+        assert_eq!(printed.z_number, 1);
+
+        let totals = printer.totals().unwrap();
+        assert_eq!(totals.bases, [76_000, 0, 0]);
+        assert_eq!(totals.taxes, [12_160, 0, 0]);
+        assert_eq!(totals.exempt, 20_000);
+        assert_eq!(totals.igtf, 1_200);
+        assert_eq!(totals.total(), 109_360);
+
+        let simulator = simulator.lock().unwrap();
+        assert!(simulator.journal.contains(&"iR*V-12345678-9".to_string()));
+        assert!(simulator
+            .journal
+            .contains(&"Losartan 50 mg 80000".to_string()));
+        assert!(simulator.journal.contains(&"PAGO 01 69360".to_string()));
+        assert_eq!(simulator.journal.last().unwrap(), "TOTAL 109360");
+    }
+
+    #[test]
+    fn discounts_are_sent_as_amounts() {
+        let (simulator, mut printer) = simulated();
+        printer.print_invoice(&invoice(Vec::new())).unwrap();
+        {
+            let simulator = simulator.lock().unwrap();
+            let item = simulator
+                .received
+                .iter()
+                .position(|command| command == "!000004000000002000Losartan 50 mg")
+                .unwrap();
+            assert_eq!(simulator.received[item + 1], "q-000004000");
+            assert!(simulator.journal.contains(&"DESCUENTO -4000".to_string()));
+        }
+
+        // `p-` es porcentual con dos decimales: 10,00 % de 800,00
+        printer.send("!000004000000002000Losartan 50 mg").unwrap();
+        printer.send("p-1000").unwrap();
+        printer.send("7").unwrap();
+        let simulator = simulator.lock().unwrap();
+        assert!(simulator.journal.contains(&"DESCUENTO -8000".to_string()));
+    }
+
+    #[test]
+    fn failures_cancel_the_document() {
+        let (simulator, mut printer) = simulated();
+
+        simulator.lock().unwrap().paper_out = true;
+        let err = printer.print_invoice(&invoice(Vec::new())).unwrap_err();
+        assert_eq!(err.to_string(), "la impresora fiscal no tiene papel");
+        simulator.lock().unwrap().paper_out = false;
+
+        // Una precio fuera de rango se detecta antes de enviarlo y la
+        // factura ya abierta se anula
+        let mut too_big = invoice(Vec::new());
+        too_big.items[1].unit_price = 100_000_000_000;
+        assert!(printer.print_invoice(&too_big).is_err());
+        assert_eq!(printer.raw_status().unwrap().state, PrinterState::Idle);
+        assert!(simulator
+            .lock()
+            .unwrap()
+            .journal
+            .contains(&"DOCUMENTO ANULADO".to_string()));
+
+        // Sin respuesta no se reenvía, porque el comando pudo ejecutarse
+        simulator.lock().unwrap().drop_replies = 1;
+        let err = printer.print_invoice(&invoice(Vec::new())).unwrap_err();
+        assert!(matches!(err, Error::Network(_)));
+        assert_eq!(printer.raw_status().unwrap().state, PrinterState::Idle);
+        assert_eq!(printer.counters().unwrap().invoices_today, 0);
+
+        // Un comando dañado en la línea se reenvía
+        simulator.lock().unwrap().garble_commands = 2;
+        let printed = printer.print_invoice(&invoice(Vec::new())).unwrap();
+        assert_eq!(printed.number, "00000001");
+        assert_eq!(printer.counters().unwrap().invoices_today, 1);
+    }
+
+    #[test]
+    fn fiscal_memory_and_test_mode_reach_the_status() {
+        let (simulator, mut printer) = simulated();
+        let status = printer.status(NOW);
+        assert_eq!(status.mode, Some(PrinterMode::Fiscal));
+        assert_eq!(status.message, None);
+
+        simulator.lock().unwrap().memory = FiscalMemory::AlmostFull;
+        let status = printer.status(NOW);
+        assert_eq!(status.memory, Some(FiscalMemory::AlmostFull));
+        assert_eq!(
+            status.message.as_deref(),
+            Some("la memoria fiscal está casi llena")
+        );
+        printer.print_invoice(&invoice(Vec::new())).unwrap();
+
+        simulator.lock().unwrap().memory = FiscalMemory::Full;
+        let err = printer.print_invoice(&invoice(Vec::new())).unwrap_err();
+        assert_eq!(err.to_string(), "la memoria fiscal está llena");
+        assert_eq!(printer.counters().unwrap().invoices_today, 1);
+
+        simulator.lock().unwrap().mode = PrinterMode::Test;
+        let status = printer.status(NOW);
+        assert_eq!(status.mode, Some(PrinterMode::Test));
+        assert!(status.message.unwrap().contains("modo de prueba"));
+    }
+
+    #[test]
+    fn a_lost_ack_after_closing_keeps_the_printed_number() {
+        let (simulator, mut printer) = simulated();
+        // La factura se cierra con el pago pero su ACK no llega
+        simulator.lock().unwrap().drop_reply_to = Some("101".into());
+        let printed = printer.print_invoice(&invoice(Vec::new())).unwrap();
+        assert_eq!(printed.number, "00000001");
+        assert_eq!(printer.counters().unwrap().invoices_today, 1);
+        assert!(!simulator
+            .lock()
+            .unwrap()
+            .journal
+            .contains(&"DOCUMENTO ANULADO".to_string()));
+
+        // Igual con la nota de crédito
+        let invoice = invoice(Vec::new());
+        let note = FiscalCreditNote {
+            customer: invoice.customer.clone(),
+            invoice_number: printed.number,
+            invoice_date: "2026-03-07".into(),
+            printer_serial: printed.serial,
+            items: invoice.items.clone(),
+            comments: Vec::new(),
+        };
+        simulator.lock().unwrap().drop_reply_to = Some("101".into());
+        let credit = printer.print_credit_note(&note).unwrap();
+        assert_eq!(credit.number, "00000001");
+        assert_eq!(printer.counters().unwrap().credit_notes_today, 1);
+    }
+
+    #[test]
+    fn credit_notes_and_daily_reports() {
+        let (simulator, mut printer) = simulated();
+        let invoice = invoice(Vec::new());
+        let printed = printer.print_invoice(&invoice).unwrap();
+
+        let note = FiscalCreditNote {
+            customer: invoice.customer.clone(),
+            invoice_number: printed.number.clone(),
+            invoice_date: "2026-03-07".into(),
+            printer_serial: printed.serial.clone(),
+            items: invoice.items.clone(),
+            comments: vec!["Motivo: devolución".into()],
+        };
+        let credit = printer.print_credit_note(&note).unwrap();
+        assert_eq!(credit.number, "00000001");
+        {
+            let simulator = simulator.lock().unwrap();
+            assert!(simulator.journal.contains(&"iF*00000000001".to_string()));
+            assert!(simulator.journal.contains(&"iD*07-03-2026".to_string()));
+        }
+
+        let (totals, counters) = printer.x_report().unwrap();
+        assert_eq!(totals.total(), 108_160);
+        assert_eq!(totals.credit_notes, 108_160);
+        assert_eq!(counters.z_number, 0);
+
+        let (closed, counters) = printer.z_report().unwrap();
+        assert_eq!(closed, totals);
+        assert_eq!(counters.z_number, 1);
+        assert_eq!(counters.invoices_today, 0);
+        assert_eq!(counters.last_invoice, 1);
+        assert_eq!(printer.totals().unwrap(), DailyTotals::default());
+        assert_eq!(printer.print_invoice(&invoice).unwrap().z_number, 2);
+    }
+
+    #[test]
+    fn prints_finalized_sales_once() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.pragma_update(None, "foreign_keys", "ON").unwrap();
+        migrate(&mut conn, MIGRATIONS).unwrap();
+        conn.execute_batch(
+            "INSERT INTO products (id, sku, name, sale_price_usd, iva_rate, iva_exempt,
+                                   created_at, updated_at)
+             VALUES ('p-a', 'A', 'Losartán 50 mg', 1000, 16, 0, 0, 0);
+             INSERT INTO batches (id, product_id, lot_number, expiry_date, warehouse_id,
+                                  quantity, received_at, created_at, updated_at)
+             VALUES ('a-1', 'p-a', 'A1', '2027-01-31', 'w1', 5, 1, 0, 0);",
+        )
+        .unwrap();
+        let sale = pos::create(
+            &conn,
+            &pos::NewSale {
+                pharmacy_id: "f1".into(),
+                warehouse_id: "w1".into(),
+                user_id: "u1".into(),
+                patient_id: None,
+                exchange_rate: Some("40".into()),
+            },
+            NOW,
+        )
+        .unwrap();
+        pos::add_item(&mut conn, &sale.id, "p-a", None, 2, NOW).unwrap();
+        let payment = NewPayment {
+            method: PaymentMethod::Zelle,
+            currency: Currency::Usd,
+            amount: 2_400,
+            reference: None,
+        };
+        pos::add_payment(&mut conn, &sale.id, &payment, NOW).unwrap();
+        assert!(invoice_for_sale(&conn, &sale.id, None, NOW).is_err());
+        pos::finalize(&mut conn, &sale.id, NOW).unwrap();
+
+        let (invoice_id, invoice) = invoice_for_sale(&conn, &sale.id, None, NOW).unwrap();
+        assert_eq!(
+            invoice.items,
+            vec![item("Losartán 50 mg", 16, 40_000, 2_000)]
+        );
+        assert_eq!(
+            invoice.payments,
+            vec![FiscalPayment {
+                code: 21,
+                amount: 96_000
+            }]
+        );
+
+        let (_, mut printer) = simulated();
+        let printed = printer.print_invoice(&invoice).unwrap();
+        let document = record_invoice(&mut conn, &invoice_id, &printed, NOW).unwrap();
+        assert_eq!(document.number.as_deref(), Some("00000001"));
+        let control: String = conn
+            .query_row(
+                "SELECT fiscal_control_number FROM invoices WHERE id = ?1",
+                [&invoice_id],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(control, "00000001");
+        assert!(invoice_for_sale(&conn, &sale.id, None, NOW).is_err());
+
+        let note = credit_note_for_invoice(&conn, &invoice_id, "Devolución", None).unwrap();
+        assert_eq!(note.items, invoice.items);
+        assert_eq!(note.printer_serial, SIMULATOR_SERIAL);
+        let printed = printer.print_credit_note(&note).unwrap();
+        record_credit_note(&mut conn, &invoice_id, &printed, NOW).unwrap();
+        assert!(credit_note_for_invoice(&conn, &invoice_id, "Devolución", None).is_err());
+    }
+}
@@ -0,0 +1,587 @@
+// Protocolo de las impresoras fiscales
+// Variante del juego de comandos de The Factory HKA que comparten la mayoría
+// de los equipos homologados en Venezuela. Cada comando viaja como
+// `STX comando ETX LRC`, donde el LRC es el XOR del comando y el ETX. La
+// impresora contesta ACK si lo aceptó o NAK si lo rechazó; ENQ pide el byte
+// de estado y el de error, y las consultas (`S1`, `S2`) contestan con una
+// trama de datos con el mismo formato.
+
+use serde::Serialize;
+use std::io::{ErrorKind, Read, Write};
+use std::time::Duration;
+
+use crate::error::{Error, Result};
+
+pub const STX: u8 = 0x02;
+pub const ETX: u8 = 0x03;
+pub const ENQ: u8 = 0x05;
+pub const ACK: u8 = 0x06;
+pub const NAK: u8 = 0x15;
+
+/// Largo máximo de una descripción o un comentario
+pub const TEXT_WIDTH: usize = 40;
+/// Precio unitario: 8 enteros y 2 decimales
+pub const PRICE_DIGITS: usize = 10;
+/// Cantidad: 5 enteros y 3 decimales
+pub const QUANTITY_DIGITS: usize = 8;
+/// Descuento por monto (`q-`) sobre el último renglón: 7 enteros y 2 decimales
+pub const DISCOUNT_DIGITS: usize = 9;
+/// Descuento porcentual (`p-`) sobre el último renglón: 2 enteros y 2 decimales
+pub const PERCENT_DIGITS: usize = 4;
+/// Pago parcial: 10 enteros y 2 decimales
+pub const PAYMENT_DIGITS: usize = 12;
+/// Montos de las consultas: 12 enteros y 2 decimales
+pub const TOTAL_DIGITS: usize = 14;
+
+/// Conexión con la impresora: puerto serie, socket TCP o el simulador
+pub trait Link: Read + Write + Send {
+    /// Tiempo máximo de espera de cada lectura
+    fn set_timeout(&mut self, timeout: Duration) -> Result<()>;
+}
+
+/// Modo de trabajo. En modo de prueba (entrenamiento) los documentos no
+/// tienen validez fiscal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PrinterMode {
+    Test,
+    Fiscal,
+}
+
+/// Ocupación de la memoria fiscal; solo se informa en modo fiscal
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FiscalMemory {
+    Available,
+    AlmostFull,
+    Full,
+}
+
+/// Transacción en curso. La impresora no distingue una factura de una nota
+/// de crédito ni si ya recibió pagos.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PrinterState {
+    Idle,
+    /// Factura o nota de crédito abierta
+    FiscalDocument,
+    NonFiscalDocument,
+}
+
+/// Tabla de estados del manual de HKA: primer byte de la respuesta a ENQ
+const STATUS_CODES: [(u8, PrinterMode, FiscalMemory, PrinterState); 12] = {
+    use FiscalMemory::*;
+    use PrinterMode::*;
+    use PrinterState::*;
+    [
+        (0x40, Test, Available, Idle),
+        (0x41, Test, Available, FiscalDocument),
+        (0x42, Test, Available, NonFiscalDocument),
+        (0x60, Fiscal, Available, Idle),
+        (0x61, Fiscal, Available, FiscalDocument),
+        (0x62, Fiscal, Available, NonFiscalDocument),
+        (0x64, Fiscal, AlmostFull, Idle),
+        (0x65, Fiscal, AlmostFull, FiscalDocument),
+        (0x66, Fiscal, AlmostFull, NonFiscalDocument),
+        (0x68, Fiscal, Full, Idle),
+        (0x69, Fiscal, Full, FiscalDocument),
+        (0x6A, Fiscal, Full, NonFiscalDocument),
+    ]
+};
+
+/// Error de la impresora según el segundo byte de la respuesta a ENQ, con
+/// los códigos del manual de HKA
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PrinterError {
+    None,
+    PaperOut,
+    /// Falla mecánica en la entrega de papel
+    Mechanical,
+    PaperOutAndMechanical,
+    /// Comando con un valor inválido
+    InvalidValue,
+    InvalidRate,
+    /// No hay directivas (encabezado fiscal) asignadas
+    NoDirectives,
+    InvalidCommand,
+    /// Rechazo fiscal, por ejemplo al pasar 24 horas sin reporte Z
+    FiscalError,
+    FiscalMemoryError,
+    FiscalMemoryFull,
+    /// Buffer de recepción lleno; hay que reiniciar la impresora
+    BufferFull,
+}
+
+impl PrinterError {
+    pub fn code(self) -> u8 {
+        match self {
+            PrinterError::None => 0x40,
+            PrinterError::PaperOut => 0x41,
+            PrinterError::Mechanical => 0x42,
+            PrinterError::PaperOutAndMechanical => 0x43,
+            PrinterError::InvalidValue => 0x50,
+            PrinterError::InvalidRate => 0x54,
+            PrinterError::NoDirectives => 0x58,
+            PrinterError::InvalidCommand => 0x5C,
+            PrinterError::FiscalError => 0x60,
+            PrinterError::FiscalMemoryError => 0x64,
+            PrinterError::FiscalMemoryFull => 0x6C,
+            PrinterError::BufferFull => 0x70,
+        }
+    }
+
+    fn from_code(code: u8) -> Result<Self> {
+        match code {
+            0x40 => Ok(PrinterError::None),
+            0x41 => Ok(PrinterError::PaperOut),
+            0x42 => Ok(PrinterError::Mechanical),
+            0x43 => Ok(PrinterError::PaperOutAndMechanical),
+            0x50 => Ok(PrinterError::InvalidValue),
+            0x54 => Ok(PrinterError::InvalidRate),
+            0x58 => Ok(PrinterError::NoDirectives),
+            0x5C => Ok(PrinterError::InvalidCommand),
+            0x60 => Ok(PrinterError::FiscalError),
+            0x64 => Ok(PrinterError::FiscalMemoryError),
+            0x6C => Ok(PrinterError::FiscalMemoryFull),
+            0x70 => Ok(PrinterError::BufferFull),
+            other => Err(Error::Validation(format!(
+                "error de impresora desconocido: {:#04x}",
+                other
+            ))),
+        }
+    }
+
+    /// Si es el rechazo del último comando y no impide seguir imprimiendo
+    pub fn is_rejection(self) -> bool {
+        matches!(
+            self,
+            PrinterError::InvalidValue
+                | PrinterError::InvalidRate
+                | PrinterError::NoDirectives
+                | PrinterError::InvalidCommand
+        )
+    }
+
+    pub fn message(self) -> &'static str {
+        match self {
+            PrinterError::None => "sin errores",
+            PrinterError::PaperOut => "la impresora fiscal no tiene papel",
+            PrinterError::Mechanical => "la impresora fiscal tiene una falla mecánica",
+            PrinterError::PaperOutAndMechanical => {
+                "la impresora fiscal no tiene papel y tiene una falla mecánica"
+            }
+            PrinterError::InvalidValue => "la impresora fiscal rechazó un dato del comando",
+            PrinterError::InvalidRate => "la impresora fiscal no tiene programada esa alícuota",
+            PrinterError::NoDirectives => "la impresora fiscal no tiene el encabezado fiscal",
+            PrinterError::InvalidCommand => "la impresora fiscal rechazó el comando",
+            PrinterError::FiscalError => {
+                "la impresora fiscal no puede facturar; puede faltar el reporte Z del día"
+            }
+            PrinterError::FiscalMemoryError => "la memoria fiscal tiene una falla",
+            PrinterError::FiscalMemoryFull => "la memoria fiscal está llena",
+            PrinterError::BufferFull => "la impresora fiscal está saturada; hay que reiniciarla",
+        }
+    }
+}
+
+/// Respuesta a ENQ
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RawStatus {
+    pub mode: PrinterMode,
+    pub memory: FiscalMemory,
+    pub state: PrinterState,
+    pub error: PrinterError,
+}
+
+impl RawStatus {
+    /// Bytes de estado y de error. La memoria fiscal se ignora en modo de
+    /// prueba, que no la informa.
+    pub fn encode(self) -> [u8; 2] {
+        let mut status = 0x40;
+        if self.mode == PrinterMode::Fiscal {
+            status |= 0x20;
+            status |= match self.memory {
+                FiscalMemory::Available => 0x00,
+                FiscalMemory::AlmostFull => 0x04,
+                FiscalMemory::Full => 0x08,
+            };
+        }
+        status |= match self.state {
+            PrinterState::Idle => 0x00,
+            PrinterState::FiscalDocument => 0x01,
+            PrinterState::NonFiscalDocument => 0x02,
+        };
+        [status, self.error.code()]
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        let [status, error] = data else {
+            return Err(Error::Validation(
+                "respuesta de estado de la impresora no válida".into(),
+            ));
+        };
+        let (_, mode, memory, state) = STATUS_CODES
+            .iter()
+            .find(|(code, ..)| code == status)
+            .copied()
+            .ok_or_else(|| {
+                Error::Validation(format!("estado de impresora desconocido: {:#04x}", status))
+            })?;
+        Ok(RawStatus {
+            mode,
+            memory,
+            state,
+            error: PrinterError::from_code(*error)?,
+        })
+    }
+}
+
+/// Contadores de `S1`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Counters {
+    pub last_invoice: u32,
+    pub invoices_today: u32,
+    pub last_credit_note: u32,
+    pub credit_notes_today: u32,
+    /// Último reporte Z emitido
+    pub z_number: u32,
+    pub serial: String,
+}
+
+/// Largos de los campos de `S1`, en orden
+const COUNTER_WIDTHS: [usize; 5] = [8, 5, 8, 5, 4];
+const SERIAL_WIDTH: usize = 10;
+
+impl Counters {
+    pub fn encode(&self) -> String {
+        format!(
+            "S1{:08}{:05}{:08}{:05}{:04}{:<10}",
+            self.last_invoice,
+            self.invoices_today,
+            self.last_credit_note,
+            self.credit_notes_today,
+            self.z_number,
+            self.serial
+        )
+    }
+
+    pub fn decode(data: &str) -> Result<Self> {
+        let invalid = || Error::Validation(format!("respuesta S1 no válida: {:?}", data));
+        let mut rest = data.strip_prefix("S1").ok_or_else(invalid)?;
+        let mut numbers = [0u32; 5];
+        for (number, width) in numbers.iter_mut().zip(COUNTER_WIDTHS) {
+            let field = rest.get(..width).ok_or_else(invalid)?;
+            *number = field.parse().map_err(|_| invalid())?;
+            rest = &rest[width..];
+        }
+        if rest.len() > SERIAL_WIDTH {
+            return Err(invalid());
+        }
+        let [last_invoice, invoices_today, last_credit_note, credit_notes_today, z_number] =
+            numbers;
+        Ok(Counters {
+            last_invoice,
+            invoices_today,
+            last_credit_note,
+            credit_notes_today,
+            z_number,
+            serial: rest.trim_end().to_string(),
+        })
+    }
+}
+
+/// Acumulados del día en céntimos de bolívar (`S2`), desde el último Z
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DailyTotals {
+    pub exempt: i64,
+    /// Base imponible y IVA de cada alícuota (general, reducida, adicional)
+    pub bases: [i64; 3],
+    pub taxes: [i64; 3],
+    pub igtf: i64,
+    /// Total de las notas de crédito, que no se restan de lo anterior
+    pub credit_notes: i64,
+}
+
+impl DailyTotals {
+    fn fields(&self) -> [i64; 9] {
+        [
+            self.exempt,
+            self.bases[0],
+            self.taxes[0],
+            self.bases[1],
+            self.taxes[1],
+            self.bases[2],
+            self.taxes[2],
+            self.igtf,
+            self.credit_notes,
+        ]
+    }
+
+    pub fn encode(&self) -> String {
+        let mut data = String::from("S2");
+        for value in self.fields() {
+            data.push_str(&format!("{:0width$}", value, width = TOTAL_DIGITS));
+        }
+        data
+    }
+
+    pub fn decode(data: &str) -> Result<Self> {
+        let invalid = || Error::Validation(format!("respuesta S2 no válida: {:?}", data));
+        let digits = data.strip_prefix("S2").ok_or_else(invalid)?;
+        if digits.len() != TOTAL_DIGITS * 9 {
+            return Err(invalid());
+        }
+        let mut values = [0i64; 9];
+        for (i, value) in values.iter_mut().enumerate() {
+            *value = digits[i * TOTAL_DIGITS..(i + 1) * TOTAL_DIGITS]
+                .parse()
+                .map_err(|_| invalid())?;
+        }
+        let [exempt, base1, tax1, base2, tax2, base3, tax3, igtf, credit_notes] = values;
+        Ok(DailyTotals {
+            exempt,
+            bases: [base1, base2, base3],
+            taxes: [tax1, tax2, tax3],
+            igtf,
+            credit_notes,
+        })
+    }
+
+    /// Total facturado: bases, IVA e IGTF
+    pub fn total(&self) -> i64 {
+        self.exempt + self.bases.iter().sum::<i64>() + self.taxes.iter().sum::<i64>() + self.igtf
+    }
+}
+
+/// XOR de todos los bytes
+pub fn lrc(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0, |acc, byte| acc ^ byte)
+}
+
+/// `STX data ETX LRC`
+pub fn frame(data: &[u8]) -> Vec<u8> {
+    let mut framed = Vec::with_capacity(data.len() + 3);
+    framed.push(STX);
+    framed.extend_from_slice(data);
+    framed.push(ETX);
+    framed.push(lrc(&framed[1..]));
+    framed
+}
+
+/// Número en ceros a la izquierda con `digits` cifras
+pub fn digits(value: i64, digits: usize, field: &str) -> Result<String> {
+    let text = format!("{:0width$}", value, width = digits);
+    if value < 0 || text.len() > digits {
+        return Err(Error::Validation(format!(
+            "{} fuera del rango de la impresora fiscal: {}",
+            field, value
+        )));
+    }
+    Ok(text)
+}
+
+/// Texto que acepta la impresora: ASCII imprimible, sin el separador `|`
+/// y cortado a `TEXT_WIDTH` caracteres
+pub fn printable(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            'á' | 'à' | 'ä' => 'a',
+            'é' | 'è' | 'ë' => 'e',
+            'í' | 'ì' | 'ï' => 'i',
+            'ó' | 'ò' | 'ö' => 'o',
+            'ú' | 'ù' | 'ü' => 'u',
+            'Á' | 'À' | 'Ä' => 'A',
+            'É' | 'È' | 'Ë' => 'E',
+            'Í' | 'Ì' | 'Ï' => 'I',
+            'Ó' | 'Ò' | 'Ö' => 'O',
+            'Ú' | 'Ù' | 'Ü' => 'U',
+            'ñ' => 'n',
+            'Ñ' => 'N',
+            c if c.is_ascii_graphic() || c == ' ' => c,
+            _ => ' ',
+        })
+        .filter(|c| *c != '|')
+        .take(TEXT_WIDTH)
+        .collect::<String>()
+        .trim_end()
+        .to_string()
+}
+
+fn is_timeout(e: &std::io::Error) -> bool {
+    matches!(e.kind(), ErrorKind::TimedOut | ErrorKind::WouldBlock)
+}
+
+/// Lee un byte; `None` si se agotó el tiempo de espera
+pub fn read_byte(link: &mut dyn Link) -> Result<Option<u8>> {
+    let mut byte = [0u8; 1];
+    loop {
+        match link.read(&mut byte) {
+            Ok(1) => return Ok(Some(byte[0])),
+            Ok(_) => return Ok(None),
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) if is_timeout(&e) => return Ok(None),
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+/// Lee una trama de datos. `None` si no llegó completa o el LRC no coincide.
+pub fn read_frame(link: &mut dyn Link) -> Result<Option<Vec<u8>>> {
+    // Lo que llegue antes del STX es ruido de la línea
+    loop {
+        match read_byte(link)? {
+            Some(STX) => break,
+            Some(_) => continue,
+            None => return Ok(None),
+        }
+    }
+    let mut data = Vec::new();
+    loop {
+        match read_byte(link)? {
+            Some(ETX) => break,
+            Some(byte) => data.push(byte),
+            None => return Ok(None),
+        }
+    }
+    let Some(received) = read_byte(link)? else {
+        return Ok(None);
+    };
+    let mut checked = data.clone();
+    checked.push(ETX);
+    Ok((lrc(&checked) == received).then_some(data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    /// Línea que entrega bytes ya recibidos
+    struct Received(Cursor<Vec<u8>>);
+
+    impl Read for Received {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.0.read(buf)
+        }
+    }
+
+    impl Write for Received {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Link for Received {
+        fn set_timeout(&mut self, _timeout: Duration) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    fn enq_response(bytes: &[u8]) -> Result<RawStatus> {
+        let mut link = Received(Cursor::new(bytes.to_vec()));
+        let data = read_frame(&mut link)?.expect("trama completa");
+        RawStatus::decode(&data)
+    }
+
+    #[test]
+    fn decodes_enq_responses() {
+        // Modo fiscal, en espera y sin errores
+        assert_eq!(
+            enq_response(&[STX, 0x60, 0x40, ETX, 0x23]).unwrap(),
+            RawStatus {
+                mode: PrinterMode::Fiscal,
+                memory: FiscalMemory::Available,
+                state: PrinterState::Idle,
+                error: PrinterError::None,
+            }
+        );
+        // Modo fiscal con una factura abierta y sin papel
+        assert_eq!(
+            enq_response(&[STX, 0x61, 0x41, ETX, 0x23]).unwrap(),
+            RawStatus {
+                mode: PrinterMode::Fiscal,
+                memory: FiscalMemory::Available,
+                state: PrinterState::FiscalDocument,
+                error: PrinterError::PaperOut,
+            }
+        );
+        // Modo de prueba con un documento no fiscal abierto: 0x42 no es una
+        // nota de crédito
+        assert_eq!(
+            enq_response(&[STX, 0x42, 0x40, ETX, 0x01]).unwrap(),
+            RawStatus {
+                mode: PrinterMode::Test,
+                memory: FiscalMemory::Available,
+                state: PrinterState::NonFiscalDocument,
+                error: PrinterError::None,
+            }
+        );
+        // Memoria fiscal casi llena, y llena tras rechazar un comando
+        let status = enq_response(&[STX, 0x64, 0x5C, ETX, 0x3B]).unwrap();
+        assert_eq!(status.memory, FiscalMemory::AlmostFull);
+        assert_eq!(status.error, PrinterError::InvalidCommand);
+        let status = enq_response(&[STX, 0x68, 0x6C, ETX, 0x07]).unwrap();
+        assert_eq!(status.memory, FiscalMemory::Full);
+        assert_eq!(status.error, PrinterError::FiscalMemoryFull);
+
+        // Bytes fuera de las tablas
+        assert!(enq_response(&[STX, 0x43, 0x40, ETX, 0x00]).is_err());
+        assert!(enq_response(&[STX, 0x60, 0x44, ETX, 0x27]).is_err());
+
+        // Todo estado de la tabla sobrevive a encode y decode
+        for (code, mode, memory, state) in STATUS_CODES {
+            let status = RawStatus {
+                mode,
+                memory,
+                state,
+                error: PrinterError::FiscalError,
+            };
+            assert_eq!(status.encode(), [code, 0x60]);
+            assert_eq!(RawStatus::decode(&status.encode()).unwrap(), status);
+        }
+    }
+
+    #[test]
+    fn frames_and_fixed_width_fields() {
+        // El LRC cubre el comando y el ETX, no el STX
+        assert_eq!(
+            frame(b"I0X"),
+            vec![STX, b'I', b'0', b'X', ETX, b'I' ^ b'0' ^ b'X' ^ ETX]
+        );
+        assert_eq!(digits(1234, 10, "precio").unwrap(), "0000001234");
+        assert!(digits(10_000_000_000, 10, "precio").is_err());
+        assert!(digits(-1, 10, "precio").is_err());
+        assert_eq!(
+            printable("Acetaminofén | niños 120 mg"),
+            "Acetaminofen  ninos 120 mg"
+        );
+        assert_eq!(printable(&"x".repeat(50)).len(), TEXT_WIDTH);
+
+        let counters = Counters {
+            last_invoice: 42,
+            invoices_today: 3,
+            last_credit_note: 1,
+            credit_notes_today: 0,
+            z_number: 7,
+            serial: "Z1B0000001".into(),
+        };
+        assert_eq!(Counters::decode(&counters.encode()).unwrap(), counters);
+        assert!(Counters::decode("S1000").is_err());
+        let totals = DailyTotals {
+            exempt: 500,
+            bases: [10_000, 0, 0],
+            taxes: [1_600, 0, 0],
+            igtf: 363,
+            credit_notes: 0,
+        };
+        assert_eq!(DailyTotals::decode(&totals.encode()).unwrap(), totals);
+        assert_eq!(totals.total(), 12_463);
+    }
+}
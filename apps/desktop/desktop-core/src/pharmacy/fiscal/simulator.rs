@@ -0,0 +1,494 @@
+// Impresora fiscal simulada
+// Implementa en memoria el mismo protocolo que las impresoras reales:
+// verifica el LRC, mantiene el documento abierto, calcula IVA e IGTF, lleva
+// los contadores y acumulados del día y los reinicia con el reporte Z. Sirve
+// para las pruebas y para trabajar sin el equipo conectado; lo "impreso"
+// queda en `journal`.
+
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use super::protocol::{
+    frame, lrc, Counters, DailyTotals, FiscalMemory, Link, PrinterError, PrinterMode, PrinterState,
+    RawStatus, ACK, DISCOUNT_DIGITS, ENQ, ETX, NAK, PAYMENT_DIGITS, PERCENT_DIGITS, STX,
+};
+use crate::error::Result;
+use crate::pharmacy::money::{mul_div, percent_of};
+
+/// Alícuotas programadas: general, reducida y adicional
+const TAX_RATES: [i64; 3] = [16, 8, 31];
+/// IGTF en centésimas de punto
+const IGTF_RATE: i64 = 300;
+/// Medios de pago en divisas, que causan IGTF
+const FOREIGN_PAYMENTS: std::ops::RangeInclusive<u8> = 20..=24;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Invoice,
+    CreditNote,
+}
+
+/// Documento fiscal en curso
+#[derive(Debug)]
+struct Document {
+    kind: Kind,
+    /// Bases por clase: exento y las tres alícuotas
+    bases: [i64; 4],
+    /// Base del último renglón, a la que aplica un descuento
+    last_item: Option<(usize, i64)>,
+    /// Todo lo entregado, IGTF incluido
+    paid: i64,
+    /// Parte del total ya cubierta por los pagos
+    covered: i64,
+    igtf: i64,
+}
+
+impl Document {
+    fn new(kind: Kind) -> Self {
+        Self {
+            kind,
+            bases: [0; 4],
+            last_item: None,
+            paid: 0,
+            covered: 0,
+            igtf: 0,
+        }
+    }
+
+    fn taxes(&self) -> [i64; 3] {
+        let mut taxes = [0; 3];
+        for (i, tax) in taxes.iter_mut().enumerate() {
+            *tax = percent_of(self.bases[i + 1], TAX_RATES[i] * 100);
+        }
+        taxes
+    }
+
+    /// Bases más IVA, sin IGTF
+    fn due(&self) -> i64 {
+        self.bases.iter().sum::<i64>() + self.taxes().iter().sum::<i64>()
+    }
+}
+
+/// Estado de la impresora simulada
+#[derive(Debug)]
+pub struct Simulator {
+    input: Vec<u8>,
+    output: VecDeque<u8>,
+    error: PrinterError,
+    header: Vec<String>,
+    document: Option<Document>,
+    counters: Counters,
+    daily: DailyTotals,
+    /// Modo fiscal salvo que se pruebe el de entrenamiento
+    pub mode: PrinterMode,
+    pub memory: FiscalMemory,
+    /// Sin papel: rechaza todo lo que imprime
+    pub paper_out: bool,
+    /// ACK o NAK que se pierden después de ejecutar el comando
+    pub drop_replies: u32,
+    /// Comando cuyo ACK o NAK se pierde la próxima vez que llegue
+    pub drop_reply_to: Option<String>,
+    /// Comandos que llegan con el LRC dañado, como con ruido en la línea
+    pub garble_commands: u32,
+    /// Texto impreso, una entrada por línea
+    pub journal: Vec<String>,
+    /// Comandos recibidos íntegros, tal como llegaron
+    pub received: Vec<String>,
+}
+
+impl Simulator {
+    pub fn new(serial: &str) -> Self {
+        Self {
+            input: Vec::new(),
+            output: VecDeque::new(),
+            error: PrinterError::None,
+            header: Vec::new(),
+            document: None,
+            counters: Counters {
+                last_invoice: 0,
+                invoices_today: 0,
+                last_credit_note: 0,
+                credit_notes_today: 0,
+                z_number: 0,
+                serial: serial.to_string(),
+            },
+            daily: DailyTotals::default(),
+            mode: PrinterMode::Fiscal,
+            memory: FiscalMemory::Available,
+            paper_out: false,
+            drop_replies: 0,
+            drop_reply_to: None,
+            garble_commands: 0,
+            journal: Vec::new(),
+            received: Vec::new(),
+        }
+    }
+
+    pub fn counters(&self) -> &Counters {
+        &self.counters
+    }
+
+    pub fn daily(&self) -> &DailyTotals {
+        &self.daily
+    }
+
+    fn state(&self) -> PrinterState {
+        match &self.document {
+            None => PrinterState::Idle,
+            Some(_) => PrinterState::FiscalDocument,
+        }
+    }
+
+    fn reply(&mut self, bytes: &[u8]) {
+        self.output.extend(bytes);
+    }
+
+    /// Procesa lo recibido hasta donde haya comandos completos
+    fn receive(&mut self, bytes: &[u8]) {
+        self.input.extend_from_slice(bytes);
+        loop {
+            let Some(start) = self.input.iter().position(|b| *b == STX || *b == ENQ) else {
+                self.input.clear();
+                return;
+            };
+            self.input.drain(..start);
+            if self.input[0] == ENQ {
+                self.input.remove(0);
+                // Sin papel lo avisa aunque todavía no haya fallado un comando
+                let error = match self.error {
+                    PrinterError::None if self.paper_out => PrinterError::PaperOut,
+                    error => error,
+                };
+                let status = RawStatus {
+                    mode: self.mode,
+                    memory: self.memory,
+                    state: self.state(),
+                    error,
+                };
+                self.reply(&frame(&status.encode()));
+                continue;
+            }
+            let Some(end) = self.input.iter().position(|b| *b == ETX) else {
+                return;
+            };
+            if self.input.len() < end + 2 {
+                return;
+            }
+            let received: Vec<u8> = self.input.drain(..end + 2).collect();
+            let garbled = self.garble_commands > 0;
+            self.garble_commands = self.garble_commands.saturating_sub(1);
+            if garbled || lrc(&received[1..=end]) != received[end + 1] {
+                // Error de transmisión: se pide reenviar sin marcar error
+                self.reply(&[NAK]);
+                continue;
+            }
+            let command = String::from_utf8_lossy(&received[1..end]).to_string();
+            self.received.push(command.clone());
+            self.execute(&command);
+        }
+    }
+
+    fn execute(&mut self, command: &str) {
+        match command {
+            "S1" => {
+                let data = self.counters.encode();
+                self.reply(&frame(data.as_bytes()));
+            }
+            "S2" => {
+                let data = self.daily.encode();
+                self.reply(&frame(data.as_bytes()));
+            }
+            _ => {
+                let result = self.run(command);
+                self.error = match result {
+                    Ok(()) => PrinterError::None,
+                    Err(error) => error,
+                };
+                if self.drop_reply_to.as_deref() == Some(command) {
+                    self.drop_reply_to = None;
+                } else if self.drop_replies > 0 {
+                    self.drop_replies -= 1;
+                } else {
+                    self.reply(&[if result.is_ok() { ACK } else { NAK }]);
+                }
+            }
+        }
+    }
+
+    fn run(&mut self, command: &str) -> std::result::Result<(), PrinterError> {
+        let printing = !command.starts_with('i') && command != "7";
+        if printing && self.paper_out {
+            return Err(PrinterError::PaperOut);
+        }
+        if printing && self.memory == FiscalMemory::Full {
+            return Err(PrinterError::FiscalMemoryFull);
+        }
+        let bytes = command.as_bytes();
+        match bytes.first().copied() {
+            Some(b'i') if bytes.get(2) == Some(&b'*') && self.document.is_none() => {
+                self.header.push(command.to_string());
+                Ok(())
+            }
+            Some(b'@') => {
+                self.journal.push(command[1..].to_string());
+                Ok(())
+            }
+            Some(b' ' | b'!' | b'"' | b'#') => {
+                let class = b" !\"#".iter().position(|c| *c == bytes[0]).unwrap_or(0);
+                self.item(Kind::Invoice, class, &command[1..])
+            }
+            Some(b'd') => {
+                let class = match bytes.get(1) {
+                    Some(digit @ b'0'..=b'3') => (digit - b'0') as usize,
+                    _ => return Err(PrinterError::InvalidCommand),
+                };
+                self.item(Kind::CreditNote, class, &command[2..])
+            }
+            Some(b'p' | b'q') if bytes.get(1) == Some(&b'-') => {
+                let fields = &command[2..];
+                let document = self.document.as_mut().ok_or(PrinterError::InvalidCommand)?;
+                let (class, base) = document.last_item.ok_or(PrinterError::InvalidCommand)?;
+                let amount = if bytes[0] == b'p' {
+                    // Porcentaje con dos decimales: p-1000 es el 10,00 %
+                    if fields.len() != PERCENT_DIGITS {
+                        return Err(PrinterError::InvalidValue);
+                    }
+                    percent_of(base, number(fields)?)
+                } else {
+                    if fields.len() != DISCOUNT_DIGITS {
+                        return Err(PrinterError::InvalidValue);
+                    }
+                    number(fields)?
+                };
+                if amount > base {
+                    return Err(PrinterError::InvalidValue);
+                }
+                document.bases[class] -= amount;
+                document.last_item = Some((class, base - amount));
+                self.journal.push(format!("DESCUENTO -{}", amount));
+                Ok(())
+            }
+            Some(b'3') if command == "3" => {
+                let document = self.document.as_ref().ok_or(PrinterError::InvalidCommand)?;
+                let subtotal = document.due();
+                self.journal.push(format!("SUBTOTAL {}", subtotal));
+                Ok(())
+            }
+            Some(b'2') => {
+                let (code, amount) = command[1..]
+                    .split_at_checked(2)
+                    .ok_or(PrinterError::InvalidValue)?;
+                if amount.len() != PAYMENT_DIGITS {
+                    return Err(PrinterError::InvalidValue);
+                }
+                self.pay(payment_code(code)?, Some(number(amount)?))
+            }
+            Some(b'1') => self.pay(payment_code(&command[1..])?, None),
+            Some(b'7') if command == "7" => {
+                if self.document.take().is_some() {
+                    self.journal.push("DOCUMENTO ANULADO".into());
+                }
+                self.header.clear();
+                Ok(())
+            }
+            Some(b'I') if command == "I0X" || command == "I0Z" => {
+                if self.document.is_some() {
+                    return Err(PrinterError::InvalidCommand);
+                }
+                if command == "I0X" {
+                    self.journal
+                        .push(format!("REPORTE X {}", self.daily.total()));
+                } else {
+                    self.counters.z_number += 1;
+                    self.journal.push(format!(
+                        "REPORTE Z {:04} {}",
+                        self.counters.z_number,
+                        self.daily.total()
+                    ));
+                    self.daily = DailyTotals::default();
+                    self.counters.invoices_today = 0;
+                    self.counters.credit_notes_today = 0;
+                }
+                Ok(())
+            }
+            _ => Err(PrinterError::InvalidCommand),
+        }
+    }
+
+    fn item(
+        &mut self,
+        kind: Kind,
+        class: usize,
+        fields: &str,
+    ) -> std::result::Result<(), PrinterError> {
+        if fields.len() < 18 {
+            return Err(PrinterError::InvalidValue);
+        }
+        let price = number(&fields[..10])?;
+        let quantity = number(&fields[10..18])?;
+        let description = &fields[18..];
+        if quantity == 0 || description.trim().is_empty() {
+            return Err(PrinterError::InvalidValue);
+        }
+        match &self.document {
+            Some(document) if document.kind != kind || document.paid > 0 => {
+                return Err(PrinterError::InvalidCommand)
+            }
+            Some(_) => {}
+            None => {
+                // La nota de crédito exige la factura, la fecha y el serial de origen
+                if kind == Kind::CreditNote
+                    && !["iF*", "iD*", "iI*"]
+                        .iter()
+                        .all(|prefix| self.header.iter().any(|line| line.starts_with(prefix)))
+                {
+                    return Err(PrinterError::InvalidValue);
+                }
+                self.journal.push(match kind {
+                    Kind::Invoice => "FACTURA".to_string(),
+                    Kind::CreditNote => "NOTA DE CREDITO".to_string(),
+                });
+                self.journal.append(&mut self.header);
+                self.document = Some(Document::new(kind));
+            }
+        }
+        let base = mul_div(price, quantity, 1000);
+        let document = self.document.as_mut().ok_or(PrinterError::InvalidCommand)?;
+        document.bases[class] += base;
+        document.last_item = Some((class, base));
+        self.journal.push(format!("{} {}", description, base));
+        Ok(())
+    }
+
+    /// Pago parcial, o el saldo con `amount` vacío, que cierra el documento.
+    /// Lo pagado en divisas causa IGTF sobre la parte que cubre del total.
+    fn pay(&mut self, code: u8, amount: Option<i64>) -> std::result::Result<(), PrinterError> {
+        let document = self.document.as_mut().ok_or(PrinterError::InvalidCommand)?;
+        if document.last_item.is_none() {
+            return Err(PrinterError::InvalidCommand);
+        }
+        let foreign = FOREIGN_PAYMENTS.contains(&code);
+        let due = document.due();
+        let closing = amount.is_none();
+        let amount = amount.unwrap_or_else(|| {
+            let rest = due - document.covered;
+            let igtf = if foreign {
+                percent_of(rest, IGTF_RATE)
+            } else {
+                0
+            };
+            (due + document.igtf + igtf - document.paid).max(0)
+        });
+        let applied = amount.min(due - document.covered);
+        document.covered += applied;
+        if foreign {
+            document.igtf += percent_of(applied, IGTF_RATE);
+        }
+        document.paid += amount;
+        self.journal.push(format!("PAGO {:02} {}", code, amount));
+        if closing || (document.covered >= due && document.paid >= due + document.igtf) {
+            self.close();
+        }
+        Ok(())
+    }
+
+    fn close(&mut self) {
+        let Some(document) = self.document.take() else {
+            return;
+        };
+        let taxes = document.taxes();
+        let igtf = document.igtf;
+        let total = document.due() + igtf;
+        match document.kind {
+            Kind::Invoice => {
+                self.daily.exempt += document.bases[0];
+                for (i, tax) in taxes.iter().enumerate() {
+                    self.daily.bases[i] += document.bases[i + 1];
+                    self.daily.taxes[i] += tax;
+                }
+                self.daily.igtf += igtf;
+                self.counters.last_invoice += 1;
+                self.counters.invoices_today += 1;
+            }
+            Kind::CreditNote => {
+                self.daily.credit_notes += total;
+                self.counters.last_credit_note += 1;
+                self.counters.credit_notes_today += 1;
+            }
+        }
+        if igtf > 0 {
+            self.journal.push(format!("IGTF {}", igtf));
+        }
+        self.journal.push(format!("TOTAL {}", total));
+        if document.paid > total {
+            self.journal
+                .push(format!("VUELTO {}", document.paid - total));
+        }
+        self.header.clear();
+    }
+}
+
+fn number(text: &str) -> std::result::Result<i64, PrinterError> {
+    if text.is_empty() || !text.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(PrinterError::InvalidValue);
+    }
+    text.parse().map_err(|_| PrinterError::InvalidValue)
+}
+
+fn payment_code(text: &str) -> std::result::Result<u8, PrinterError> {
+    match number(text)? {
+        code @ 1..=24 if text.len() == 2 => Ok(code as u8),
+        _ => Err(PrinterError::InvalidValue),
+    }
+}
+
+/// Conexión con un simulador compartido: la impresora conserva su estado
+/// entre conexiones, como una real
+#[derive(Clone)]
+pub struct SimulatorLink(pub Arc<Mutex<Simulator>>);
+
+impl Write for SimulatorLink {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        self.0
+            .lock()
+            .map_err(|_| io::Error::other("simulador bloqueado"))?
+            .receive(bytes);
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Read for SimulatorLink {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut simulator = self
+            .0
+            .lock()
+            .map_err(|_| io::Error::other("simulador bloqueado"))?;
+        if simulator.output.is_empty() {
+            // Responde al escribir: si no hay nada, no va a llegar
+            return Err(io::ErrorKind::TimedOut.into());
+        }
+        let mut read = 0;
+        while read < buf.len() {
+            match simulator.output.pop_front() {
+                Some(byte) => {
+                    buf[read] = byte;
+                    read += 1;
+                }
+                None => break,
+            }
+        }
+        Ok(read)
+    }
+}
+
+impl Link for SimulatorLink {
+    fn set_timeout(&mut self, _timeout: Duration) -> Result<()> {
+        Ok(())
+    }
+}
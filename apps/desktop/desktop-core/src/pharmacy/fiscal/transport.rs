@@ -0,0 +1,132 @@
+// Conexión con la impresora fiscal
+// Puerto serie (RS-232 o adaptador USB), socket TCP de los modelos con red o
+// el simulador en memoria. El controlador solo ve un `Link`.
+
+use serde::{Deserialize, Serialize};
+use std::io::ErrorKind;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use super::protocol::Link;
+use super::simulator::{Simulator, SimulatorLink};
+use crate::error::{Error, Result};
+
+/// Velocidad de fábrica de las impresoras HKA
+pub const DEFAULT_BAUD_RATE: u32 = 9600;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Dónde está la impresora, guardado como JSON en `fiscal_printer`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "backend", rename_all = "snake_case")]
+pub enum PrinterConnection {
+    Serial {
+        port: String,
+        #[serde(rename = "baudRate", default = "default_baud_rate")]
+        baud_rate: u32,
+    },
+    Tcp {
+        host: String,
+        port: u16,
+    },
+    /// Impresora simulada, para pruebas y demostraciones
+    Simulated,
+}
+
+fn default_baud_rate() -> u32 {
+    DEFAULT_BAUD_RATE
+}
+
+impl PrinterConnection {
+    pub fn validate(&self) -> Result<()> {
+        match self {
+            PrinterConnection::Serial { port, baud_rate } => {
+                if port.trim().is_empty() {
+                    return Err(Error::Validation("falta el puerto serie".into()));
+                }
+                if ![2400, 4800, 9600, 19200, 38400, 57600, 115200].contains(baud_rate) {
+                    return Err(Error::Validation(format!(
+                        "velocidad no soportada: {}",
+                        baud_rate
+                    )));
+                }
+            }
+            PrinterConnection::Tcp { host, port } => {
+                if host.trim().is_empty() || *port == 0 {
+                    return Err(Error::Validation(
+                        "la dirección de la impresora no es válida".into(),
+                    ));
+                }
+            }
+            PrinterConnection::Simulated => {}
+        }
+        Ok(())
+    }
+}
+
+impl Link for TcpStream {
+    fn set_timeout(&mut self, timeout: Duration) -> Result<()> {
+        self.set_read_timeout(Some(timeout))?;
+        self.set_write_timeout(Some(timeout))?;
+        Ok(())
+    }
+}
+
+impl Link for Box<dyn serialport::SerialPort> {
+    fn set_timeout(&mut self, timeout: Duration) -> Result<()> {
+        serialport::SerialPort::set_timeout(self.as_mut(), timeout).map_err(serial_error)
+    }
+}
+
+fn serial_error(e: serialport::Error) -> Error {
+    match e.kind() {
+        serialport::ErrorKind::NoDevice => Error::NotFound(format!("puerto serie: {}", e)),
+        serialport::ErrorKind::Io(kind) => std::io::Error::new(kind, e.description).into(),
+        _ => Error::Io(format!("puerto serie: {}", e)),
+    }
+}
+
+/// Abre la conexión. El simulador se comparte para que conserve su estado.
+pub fn open(
+    connection: &PrinterConnection,
+    simulator: &Arc<Mutex<Simulator>>,
+) -> Result<Box<dyn Link>> {
+    match connection {
+        PrinterConnection::Serial { port, baud_rate } => {
+            // 8 bits, paridad par y un bit de parada
+            let port = serialport::new(port, *baud_rate)
+                .data_bits(serialport::DataBits::Eight)
+                .parity(serialport::Parity::Even)
+                .stop_bits(serialport::StopBits::One)
+                .flow_control(serialport::FlowControl::None)
+                .timeout(CONNECT_TIMEOUT)
+                .open()
+                .map_err(serial_error)?;
+            Ok(Box::new(port))
+        }
+        PrinterConnection::Tcp { host, port } => {
+            let address = (host.as_str(), *port)
+                .to_socket_addrs()?
+                .next()
+                .ok_or_else(|| Error::Network(format!("no se encontró {}", host)))?;
+            let stream =
+                TcpStream::connect_timeout(&address, CONNECT_TIMEOUT).map_err(|e| {
+                    match e.kind() {
+                        ErrorKind::TimedOut | ErrorKind::ConnectionRefused => Error::Network(
+                            format!("la impresora fiscal en {}:{} no responde", host, port),
+                        ),
+                        _ => e.into(),
+                    }
+                })?;
+            stream.set_nodelay(true)?;
+            Ok(Box::new(stream))
+        }
+        PrinterConnection::Simulated => Ok(Box::new(SimulatorLink(simulator.clone()))),
+    }
+}
+
+/// Puertos serie del equipo, para elegir la impresora
+pub fn list_serial_ports() -> Result<Vec<String>> {
+    let ports = serialport::available_ports().map_err(serial_error)?;
+    Ok(ports.into_iter().map(|port| port.port_name).collect())
+}
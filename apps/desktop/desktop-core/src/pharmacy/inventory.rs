@@ -0,0 +1,480 @@
+// Inventario por lote
+// Toda salida de existencias (ventas y entregas) pasa por aquí: se asigna
+// por lote con FEFO (primero el que vence antes), nunca de lotes vencidos ni
+// fuera de las zonas vendibles, y cada lote tocado deja su movimiento en
+// `inventory_movements`. También alimenta las alertas de vencimiento.
+
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::time::Duration;
+use tauri::{AppHandle, Manager, Runtime, State};
+
+use super::{local_date, PharmacyDb};
+use crate::error::{Error, Result};
+use crate::notifications::{self, Notification, NotificationCategory};
+use crate::store::now_millis;
+
+/// Zonas de las que se puede vender, para las consultas y para `sellable`
+const SELLABLE_ZONES: &str = "('available', 'approved')";
+/// Días de anticipación de los avisos de vencimiento
+pub const EXPIRY_ALERT_DAYS: u32 = 30;
+/// Cada cuánto se revisan los vencimientos; la deduplicación de
+/// `StockExpiry` evita repetir el aviso de un lote más de una vez al día
+const EXPIRY_CHECK_INTERVAL: Duration = Duration::from_secs(6 * 60 * 60);
+
+/// Unidades tomadas de un lote
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Allocation {
+    pub batch_id: String,
+    pub lot_number: String,
+    pub expiry_date: String,
+    pub quantity: i64,
+}
+
+/// Salida de existencias de un producto
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Consumption {
+    pub pharmacy_id: String,
+    /// Solo se toma de los lotes de este almacén
+    pub warehouse_id: String,
+    pub product_id: String,
+    /// Lote concreto; sin él se asigna con FEFO
+    pub batch_id: Option<String>,
+    /// Unidades enteras
+    pub quantity: i64,
+    pub unit_price_usd: Option<i64>,
+    /// `factura`, `nota_credito`, `orden_compra` o `ajuste_manual`
+    pub document_type: Option<String>,
+    pub document_id: Option<String>,
+    pub notes: Option<String>,
+    pub user_id: Option<String>,
+}
+
+/// Lote con existencias que vence dentro del plazo consultado
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExpiringBatch {
+    pub batch_id: String,
+    pub product_id: String,
+    pub product_name: String,
+    pub sku: String,
+    pub lot_number: String,
+    pub expiry_date: String,
+    pub quantity: i64,
+    /// Negativo si ya venció
+    pub days_left: i64,
+    pub expired: bool,
+}
+
+fn sellable(zone: &str) -> bool {
+    matches!(zone, "available" | "approved")
+}
+
+/// Comprueba que un lote pedido explícitamente esté en el almacén y se
+/// pueda vender hoy
+pub(crate) fn check_batch(
+    conn: &Connection,
+    warehouse_id: &str,
+    product_id: &str,
+    batch_id: &str,
+    today: &str,
+) -> Result<()> {
+    let batch: Option<(String, String, String)> = conn
+        .query_row(
+            "SELECT lot_number, expiry_date, zone FROM batches
+             WHERE id = ?1 AND product_id = ?2 AND warehouse_id = ?3",
+            params![batch_id, product_id, warehouse_id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .optional()?;
+    let Some((lot, expiry, zone)) = batch else {
+        return Err(Error::NotFound(format!(
+            "lote {} del producto en el almacén",
+            batch_id
+        )));
+    };
+    if expiry.as_str() < today {
+        return Err(Error::Validation(format!(
+            "el lote {} venció el {} y no se puede vender",
+            lot, expiry
+        )));
+    }
+    if !sellable(&zone) {
+        return Err(Error::Validation(format!(
+            "el lote {} está en la zona {}",
+            lot, zone
+        )));
+    }
+    Ok(())
+}
+
+/// Unidades vendibles hoy de un producto (o de uno de sus lotes) en un almacén
+pub(crate) fn available(
+    conn: &Connection,
+    warehouse_id: &str,
+    product_id: &str,
+    batch_id: Option<&str>,
+    today: &str,
+) -> Result<i64> {
+    Ok(conn.query_row(
+        &format!(
+            "SELECT COALESCE(SUM(quantity), 0) FROM batches
+             WHERE product_id = ?1 AND (?2 IS NULL OR id = ?2) AND warehouse_id = ?4
+               AND expiry_date >= ?3 AND zone IN {}",
+            SELLABLE_ZONES
+        ),
+        params![product_id, batch_id, today, warehouse_id],
+        |row| row.get(0),
+    )?)
+}
+
+/// Lotes del almacén de los que saldrían `quantity` unidades, sin tocar nada
+pub(crate) fn allocate(
+    conn: &Connection,
+    warehouse_id: &str,
+    product_id: &str,
+    batch_id: Option<&str>,
+    quantity: i64,
+    today: &str,
+) -> Result<Vec<Allocation>> {
+    if quantity <= 0 {
+        return Err(Error::Validation(
+            "la cantidad debe ser mayor que cero".into(),
+        ));
+    }
+    if let Some(batch_id) = batch_id {
+        check_batch(conn, warehouse_id, product_id, batch_id, today)?;
+    }
+    let mut stmt = conn.prepare(&format!(
+        "SELECT id, lot_number, expiry_date, quantity FROM batches
+         WHERE product_id = ?1 AND (?2 IS NULL OR id = ?2) AND warehouse_id = ?4
+           AND quantity > 0 AND expiry_date >= ?3 AND zone IN {}
+         ORDER BY expiry_date, received_at, id",
+        SELLABLE_ZONES
+    ))?;
+    let batches = stmt
+        .query_map(params![product_id, batch_id, today, warehouse_id], |row| {
+            Ok(Allocation {
+                batch_id: row.get(0)?,
+                lot_number: row.get(1)?,
+                expiry_date: row.get(2)?,
+                quantity: row.get(3)?,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    let mut remaining = quantity;
+    let mut allocations = Vec::new();
+    for mut batch in batches {
+        if remaining == 0 {
+            break;
+        }
+        batch.quantity = batch.quantity.min(remaining);
+        remaining -= batch.quantity;
+        allocations.push(batch);
+    }
+    if remaining > 0 {
+        return Err(Error::Validation(format!(
+            "stock insuficiente: faltan {} unidades vendibles",
+            remaining
+        )));
+    }
+    Ok(allocations)
+}
+
+/// Descuenta existencias por lote y registra un movimiento `salida` por
+/// cada lote. No abre transacción: quien llama decide su alcance.
+pub(crate) fn consume(
+    conn: &Connection,
+    consumption: &Consumption,
+    now: i64,
+) -> Result<Vec<Allocation>> {
+    let allocations = allocate(
+        conn,
+        &consumption.warehouse_id,
+        &consumption.product_id,
+        consumption.batch_id.as_deref(),
+        consumption.quantity,
+        &local_date(now),
+    )?;
+    for allocation in &allocations {
+        conn.execute(
+            "UPDATE batches SET quantity = quantity - ?2, updated_at = ?3 WHERE id = ?1",
+            params![allocation.batch_id, allocation.quantity, now],
+        )?;
+        conn.execute(
+            "INSERT INTO inventory_movements (id, pharmacy_id, product_id, batch_id,
+                                              movement_type, quantity, unit_price_usd,
+                                              document_type, document_id, notes, created_by,
+                                              created_at)
+             VALUES (?1, ?2, ?3, ?4, 'salida', ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+            params![
+                uuid::Uuid::new_v4().to_string(),
+                consumption.pharmacy_id,
+                consumption.product_id,
+                allocation.batch_id,
+                allocation.quantity * 100,
+                consumption.unit_price_usd,
+                consumption.document_type,
+                consumption.document_id,
+                consumption.notes,
+                consumption.user_id,
+                now
+            ],
+        )?;
+    }
+    Ok(allocations)
+}
+
+/// Lotes con existencias que vencen dentro de `days` días, incluidos los ya
+/// vencidos, del más urgente al menos
+pub(crate) fn expiring(conn: &Connection, days: u32, today: &str) -> Result<Vec<ExpiringBatch>> {
+    let mut stmt = conn.prepare(
+        "SELECT b.id, b.product_id, p.name, p.sku, b.lot_number, b.expiry_date, b.quantity,
+                CAST(julianday(b.expiry_date) - julianday(?1) AS INTEGER)
+         FROM batches b JOIN products p ON p.id = b.product_id
+         WHERE b.quantity > 0 AND b.expiry_date <= date(?1, '+' || ?2 || ' days')
+         ORDER BY b.expiry_date, p.name",
+    )?;
+    let batches = stmt
+        .query_map(params![today, days], |row| {
+            let days_left: i64 = row.get(7)?;
+            Ok(ExpiringBatch {
+                batch_id: row.get(0)?,
+                product_id: row.get(1)?,
+                product_name: row.get(2)?,
+                sku: row.get(3)?,
+                lot_number: row.get(4)?,
+                expiry_date: row.get(5)?,
+                quantity: row.get(6)?,
+                days_left,
+                expired: days_left < 0,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(batches)
+}
+
+/// Aviso de vencimiento de un lote; los vencidos son urgentes
+fn expiry_notification(batch: &ExpiringBatch) -> Notification {
+    let body = match batch.days_left {
+        days if days < 0 => format!(
+            "El lote {} de {} venció el {} ({} unidades)",
+            batch.lot_number, batch.product_name, batch.expiry_date, batch.quantity
+        ),
+        0 => format!(
+            "El lote {} de {} vence hoy ({} unidades)",
+            batch.lot_number, batch.product_name, batch.quantity
+        ),
+        days => format!(
+            "El lote {} de {} vence en {} días ({} unidades)",
+            batch.lot_number, batch.product_name, days, batch.quantity
+        ),
+    };
+    Notification {
+        urgent: batch.expired,
+        data: json!({ "batchId": batch.batch_id, "productId": batch.product_id }),
+        ..Notification::new(NotificationCategory::StockExpiry, body)
+            .dedup_key(format!("lote:{}", batch.batch_id))
+    }
+}
+
+/// Revisa periódicamente los vencimientos y avisa de cada lote afectado
+pub(crate) fn spawn_expiry_watch<R: Runtime>(app_handle: AppHandle<R>) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let now = now_millis();
+            let batches = app_handle
+                .state::<PharmacyDb>()
+                .with_conn(|conn| expiring(conn, EXPIRY_ALERT_DAYS, &local_date(now)));
+            for batch in batches.unwrap_or_default() {
+                let _ = notifications::notify(&app_handle, expiry_notification(&batch));
+            }
+            tokio::time::sleep(EXPIRY_CHECK_INTERVAL).await;
+        }
+    });
+}
+
+// ============================================
+// COMMANDS
+// ============================================
+
+/// Lotes que vencen dentro de `days` días (30 por defecto), para las alertas
+#[tauri::command]
+pub async fn get_expiring_batches(
+    db: State<'_, PharmacyDb>,
+    days: Option<u32>,
+) -> Result<Vec<ExpiringBatch>> {
+    let today = local_date(now_millis());
+    db.with_conn(|conn| expiring(conn, days.unwrap_or(EXPIRY_ALERT_DAYS), &today))
+}
+
+/// Descuenta existencias fuera del punto de venta (entregas, mermas) con
+/// la misma asignación FEFO que las ventas
+#[tauri::command]
+pub async fn consume_stock(
+    db: State<'_, PharmacyDb>,
+    consumption: Consumption,
+) -> Result<Vec<Allocation>> {
+    db.with_conn(|conn| {
+        let tx = conn.transaction()?;
+        let allocations = consume(&tx, &consumption, now_millis())?;
+        tx.commit()?;
+        Ok(allocations)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pharmacy::schema::{migrate, MIGRATIONS};
+
+    const TODAY: &str = "2026-03-07";
+
+    fn pharmacy() -> Connection {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.pragma_update(None, "foreign_keys", "ON").unwrap();
+        migrate(&mut conn, MIGRATIONS).unwrap();
+        conn.execute_batch(
+            "INSERT INTO products (id, sku, name, created_at, updated_at)
+             VALUES ('p1', 'AMX', 'Amoxicilina 500 mg', 0, 0);
+             INSERT INTO batches (id, product_id, lot_number, expiry_date, warehouse_id, zone,
+                                  quantity, received_at, created_at, updated_at)
+             VALUES ('old', 'p1', 'L0', '2026-03-06', 'w1', 'available', 9, 1, 0, 0),
+                    ('late', 'p1', 'L1', '2027-06-30', 'w1', 'available', 5, 2, 0, 0),
+                    ('soon', 'p1', 'L2', '2026-03-20', 'w1', 'available', 2, 3, 0, 0),
+                    ('held', 'p1', 'L3', '2026-03-10', 'w1', 'quarantine', 7, 4, 0, 0);",
+        )
+        .unwrap();
+        conn
+    }
+
+    fn consumption(quantity: i64, batch_id: Option<&str>) -> Consumption {
+        Consumption {
+            pharmacy_id: "f1".into(),
+            warehouse_id: "w1".into(),
+            product_id: "p1".into(),
+            batch_id: batch_id.map(Into::into),
+            quantity,
+            unit_price_usd: None,
+            document_type: Some("factura".into()),
+            document_id: Some("inv-1".into()),
+            notes: None,
+            user_id: None,
+        }
+    }
+
+    fn quantity(conn: &Connection, batch: &str) -> i64 {
+        conn.query_row(
+            "SELECT quantity FROM batches WHERE id = ?1",
+            params![batch],
+            |row| row.get(0),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn allocates_first_expired_first_out_skipping_unsellable_batches() {
+        let conn = pharmacy();
+        // Ni el vencido ni el de cuarentena cuentan
+        assert_eq!(available(&conn, "w1", "p1", None, TODAY).unwrap(), 7);
+
+        let plan = allocate(&conn, "w1", "p1", None, 4, TODAY).unwrap();
+        let taken: Vec<_> = plan
+            .iter()
+            .map(|a| (a.batch_id.as_str(), a.quantity))
+            .collect();
+        assert_eq!(taken, [("soon", 2), ("late", 2)]);
+
+        let err = allocate(&conn, "w1", "p1", None, 8, TODAY).unwrap_err();
+        assert!(matches!(err, Error::Validation(ref m) if m.contains("faltan 1")));
+        let err = allocate(&conn, "w1", "p1", Some("old"), 1, TODAY).unwrap_err();
+        assert!(matches!(err, Error::Validation(ref m) if m.contains("venció")));
+        let err = allocate(&conn, "w1", "p1", Some("held"), 1, TODAY).unwrap_err();
+        assert!(matches!(err, Error::Validation(ref m) if m.contains("quarantine")));
+    }
+
+    #[test]
+    fn consume_records_one_movement_per_batch() {
+        let conn = pharmacy();
+        // 2026-03-07 12:00 en Caracas
+        let now = 1_772_899_200_000;
+        let taken = consume(&conn, &consumption(3, None), now).unwrap();
+        assert_eq!(taken.len(), 2);
+        assert_eq!((quantity(&conn, "soon"), quantity(&conn, "late")), (0, 4));
+
+        let mut stmt = conn
+            .prepare(
+                "SELECT batch_id, quantity, document_id FROM inventory_movements
+                 WHERE movement_type = 'salida' ORDER BY batch_id DESC",
+            )
+            .unwrap();
+        let movements: Vec<(String, i64, String)> = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(
+            movements,
+            [
+                ("soon".to_string(), 200, "inv-1".to_string()),
+                ("late".to_string(), 100, "inv-1".to_string())
+            ]
+        );
+
+        // Un lote pedido sin existencias suficientes no descuenta nada
+        assert!(consume(&conn, &consumption(5, Some("late")), now).is_err());
+        assert_eq!(quantity(&conn, "late"), 4);
+    }
+
+    #[test]
+    fn stock_only_comes_from_the_requested_warehouse() {
+        let conn = pharmacy();
+        conn.execute_batch(
+            "INSERT INTO batches (id, product_id, lot_number, expiry_date, warehouse_id, zone,
+                                  quantity, received_at, created_at, updated_at)
+             VALUES ('other', 'p1', 'L9', '2026-03-08', 'w2', 'available', 10, 5, 0, 0);",
+        )
+        .unwrap();
+        assert_eq!(available(&conn, "w1", "p1", None, TODAY).unwrap(), 7);
+        assert_eq!(available(&conn, "w2", "p1", None, TODAY).unwrap(), 10);
+
+        // El lote de w2 vence antes, pero una salida de w1 no lo toca
+        let plan = allocate(&conn, "w1", "p1", None, 3, TODAY).unwrap();
+        assert!(plan.iter().all(|a| a.batch_id != "other"));
+        let err = allocate(&conn, "w1", "p1", None, 8, TODAY).unwrap_err();
+        assert!(matches!(err, Error::Validation(ref m) if m.contains("faltan 1")));
+        let err = allocate(&conn, "w1", "p1", Some("other"), 1, TODAY).unwrap_err();
+        assert!(matches!(err, Error::NotFound(_)), "{err:?}");
+
+        let now = 1_772_899_200_000;
+        let mut from_w2 = consumption(4, None);
+        from_w2.warehouse_id = "w2".into();
+        consume(&conn, &from_w2, now).unwrap();
+        assert_eq!(quantity(&conn, "other"), 6);
+        assert_eq!((quantity(&conn, "soon"), quantity(&conn, "late")), (2, 5));
+    }
+
+    #[test]
+    fn lists_batches_expiring_within_days() {
+        let conn = pharmacy();
+        let batches = expiring(&conn, 14, TODAY).unwrap();
+        let summary: Vec<_> = batches
+            .iter()
+            .map(|b| (b.batch_id.as_str(), b.days_left, b.expired))
+            .collect();
+        assert_eq!(
+            summary,
+            [("old", -1, true), ("held", 3, false), ("soon", 13, false)]
+        );
+
+        let notification = expiry_notification(&batches[0]);
+        assert!(notification.urgent);
+        assert_eq!(notification.dedup_key.as_deref(), Some("lote:old"));
+        assert!(notification.body.contains("venció el 2026-03-06"));
+        assert!(expiry_notification(&batches[2]).body.contains("en 13 días"));
+    }
+}
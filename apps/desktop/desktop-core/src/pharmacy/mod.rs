@@ -0,0 +1,177 @@
+// Base local de la farmacia
+// El punto de venta trabaja sobre su propia base SQLite (`farmacia.db`), que
+// la app de farmacia abre al arrancar con `pharmacy::setup`. Las demás apps
+// no la abren y sus comandos responden con un error.
+
+use rusqlite::Connection;
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager, Runtime, State};
+
+use crate::error::{Error, Result};
+
+pub mod fiscal;
+pub mod inventory;
+pub mod money;
+pub mod pos;
+pub mod rates;
+pub mod schema;
+
+pub use schema::{AppliedMigration, SchemaStatus};
+
+/// Nombre del archivo de la base dentro de `app_data_dir`
+pub const DB_FILE: &str = "farmacia.db";
+
+/// Hora de Venezuela (UTC-4, sin horario de verano): las fechas de
+/// vencimiento y las vigencias de las promociones son de calendario local
+const UTC_OFFSET_MILLIS: i64 = -4 * 60 * 60 * 1000;
+const DAY_MILLIS: i64 = 24 * 60 * 60 * 1000;
+
+/// Días desde epoch en hora local
+fn local_days(now: i64) -> i64 {
+    (now + UTC_OFFSET_MILLIS).div_euclid(DAY_MILLIS)
+}
+
+/// Fecha local `YYYY-MM-DD`
+pub(crate) fn local_date(now: i64) -> String {
+    // Algoritmo `civil_from_days` de Howard Hinnant
+    let z = local_days(now) + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Día de la semana local, 0 = domingo
+pub(crate) fn local_weekday(now: i64) -> u32 {
+    // El 1 de enero de 1970 fue jueves
+    (local_days(now) + 4).rem_euclid(7) as u32
+}
+
+/// Hora local `HH:MM`
+pub(crate) fn local_time(now: i64) -> String {
+    let minutes = (now + UTC_OFFSET_MILLIS).rem_euclid(DAY_MILLIS) / 60_000;
+    format!("{:02}:{:02}", minutes / 60, minutes % 60)
+}
+
+/// Conexión a la base local de la farmacia, cerrada hasta `open`
+#[derive(Default)]
+pub struct PharmacyDb {
+    conn: Mutex<Option<Connection>>,
+}
+
+impl PharmacyDb {
+    /// Abre (o crea) la base en `db_path` y la lleva a la última versión del esquema
+    pub fn open(&self, db_path: &Path) -> Result<SchemaStatus> {
+        if let Some(parent) = db_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        self.init(Connection::open(db_path)?)
+    }
+
+    fn init(&self, mut conn: Connection) -> Result<SchemaStatus> {
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        conn.pragma_update(None, "foreign_keys", "ON")?;
+        let status = schema::migrate(&mut conn, schema::MIGRATIONS)?;
+        *self.lock()? = Some(conn);
+        Ok(status)
+    }
+
+    fn lock(&self) -> Result<std::sync::MutexGuard<'_, Option<Connection>>> {
+        self.conn
+            .lock()
+            .map_err(|_| Error::Storage("la base de farmacia quedó bloqueada".into()))
+    }
+
+    /// Ejecuta `f` con la conexión bloqueada; una transacción dentro de `f`
+    /// es atómica respecto a los demás comandos
+    pub(crate) fn with_conn<T>(&self, f: impl FnOnce(&mut Connection) -> Result<T>) -> Result<T> {
+        match self.lock()?.as_mut() {
+            Some(conn) => f(conn),
+            None => Err(Error::Storage("la base de farmacia no está abierta".into())),
+        }
+    }
+
+    /// Versión del esquema y migraciones aplicadas
+    pub fn schema_status(&self) -> Result<SchemaStatus> {
+        self.with_conn(|conn| schema::status(conn, schema::MIGRATIONS))
+    }
+}
+
+/// Abre la base de la farmacia en `app_data_dir` y arranca la revisión de
+/// vencimientos, tasas e impresora fiscal. La app de farmacia lo llama desde
+/// su `setup`; si una migración aplicada no coincide con la de esta versión,
+/// el arranque falla en lugar de trabajar sobre otro esquema.
+pub fn setup<R: Runtime>(app: &AppHandle<R>) -> Result<SchemaStatus> {
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| Error::Io(e.to_string()))?;
+    let status = app.state::<PharmacyDb>().open(&dir.join(DB_FILE))?;
+    inventory::spawn_expiry_watch(app.clone());
+    rates::spawn_rate_refresh(app.clone());
+    fiscal::spawn_status_poll(app.clone());
+    Ok(status)
+}
+
+// ============================================
+// COMMANDS
+// ============================================
+
+/// Versión del esquema local de la farmacia
+#[tauri::command]
+pub async fn get_pharmacy_schema_version(db: State<'_, PharmacyDb>) -> Result<SchemaStatus> {
+    db.schema_status()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn local_calendar_is_caracas_time() {
+        // 2026-03-01 02:30 UTC es todavía 28 de febrero en Caracas
+        let now = 1_772_332_200_000;
+        assert_eq!(local_date(now), "2026-02-28");
+        assert_eq!(local_time(now), "22:30");
+        assert_eq!(local_weekday(now), 6);
+        assert_eq!(local_date(0), "1969-12-31");
+    }
+
+    #[test]
+    fn reopening_keeps_data_and_version() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(DB_FILE);
+
+        let db = PharmacyDb::default();
+        assert!(db.schema_status().is_err());
+        let status = db.open(&path).unwrap();
+        assert_eq!(status.version, status.latest);
+        db.with_conn(|conn| {
+            conn.execute(
+                "INSERT INTO suppliers (id, name, created_at, updated_at)
+                 VALUES ('s1', 'Droguería Central', 0, 0)",
+                [],
+            )?;
+            Ok(())
+        })
+        .unwrap();
+        drop(db);
+
+        let reopened = PharmacyDb::default();
+        let again = reopened.open(&path).unwrap();
+        assert_eq!(again.migrations, status.migrations);
+        let suppliers: i64 = reopened
+            .with_conn(|conn| {
+                Ok(conn.query_row("SELECT COUNT(*) FROM suppliers", [], |row| row.get(0))?)
+            })
+            .unwrap();
+        assert_eq!(suppliers, 1);
+    }
+}
@@ -0,0 +1,131 @@
+// Aritmética de montos
+// Los montos y tasas viajan como enteros escalados (ver `schema`): nunca se
+// usa f64 para no arrastrar errores de redondeo en facturas fiscales. Los
+// productos se calculan en i128 y se redondean a la mitad alejándose de cero.
+
+use crate::error::{Error, Result};
+
+/// Escala de los montos (céntimos)
+pub const MONEY_SCALE: u32 = 2;
+/// Escala de las tasas de cambio (millonésimas)
+pub const RATE_SCALE: u32 = 6;
+/// Denominador de los porcentajes en centésimas de punto (1600 = 16 %)
+pub const PERCENT_SCALE: i64 = 10_000;
+
+/// Divide redondeando a la mitad alejándose de cero
+pub fn div_round(numerator: i128, denominator: i128) -> i64 {
+    debug_assert!(denominator > 0);
+    let half = denominator / 2;
+    let rounded = if numerator >= 0 {
+        (numerator + half) / denominator
+    } else {
+        (numerator - half) / denominator
+    };
+    rounded as i64
+}
+
+/// `amount * numerator / denominator`, redondeado
+pub fn mul_div(amount: i64, numerator: i64, denominator: i64) -> i64 {
+    div_round(amount as i128 * numerator as i128, denominator as i128)
+}
+
+/// Porcentaje de un monto; `rate` en centésimas de punto
+pub fn percent_of(amount: i64, rate: i64) -> i64 {
+    mul_div(amount, rate, PERCENT_SCALE)
+}
+
+/// Convierte céntimos de USD a céntimos de VES con una tasa en millonésimas
+pub fn usd_to_ves(cents: i64, rate: i64) -> i64 {
+    mul_div(cents, rate, 10i64.pow(RATE_SCALE))
+}
+
+/// Lee un decimal como `"36.52"` escalado por 10^`scale`. Rechaza más
+/// decimales de los que admite la escala en lugar de redondearlos.
+pub fn parse_decimal(text: &str, scale: u32) -> Result<i64> {
+    let invalid = || Error::Validation(format!("número no válido: {:?}", text));
+    let text = text.trim();
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text),
+    };
+    let (whole, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+    if whole.is_empty()
+        || fraction.len() > scale as usize
+        || !whole
+            .bytes()
+            .chain(fraction.bytes())
+            .all(|b| b.is_ascii_digit())
+    {
+        return Err(invalid());
+    }
+    let padded = format!("{}{:0<width$}", whole, fraction, width = scale as usize);
+    let value: i64 = padded.parse().map_err(|_| invalid())?;
+    Ok(if negative { -value } else { value })
+}
+
+/// Como `parse_decimal`, pero redondea los decimales que sobran en lugar de
+/// rechazarlos; el BCV publica sus tasas con ocho
+pub fn parse_decimal_rounded(text: &str, scale: u32) -> Result<i64> {
+    let digits = text
+        .trim()
+        .split_once('.')
+        .map_or(0, |(_, fraction)| fraction.len() as u32);
+    if digits <= scale {
+        return parse_decimal(text, scale);
+    }
+    if digits > 18 {
+        return Err(Error::Validation(format!("número no válido: {:?}", text)));
+    }
+    let value = parse_decimal(text, digits)?;
+    Ok(div_round(value as i128, 10i128.pow(digits - scale)))
+}
+
+/// Escribe un entero escalado como decimal con `scale` cifras
+pub fn format_decimal(value: i64, scale: u32) -> String {
+    let factor = 10i64.pow(scale);
+    let sign = if value < 0 { "-" } else { "" };
+    let value = value.unsigned_abs();
+    if scale == 0 {
+        return format!("{}{}", sign, value);
+    }
+    format!(
+        "{}{}.{:0width$}",
+        sign,
+        value / factor as u64,
+        value % factor as u64,
+        width = scale as usize
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rounds_half_away_from_zero_without_floats() {
+        assert_eq!(div_round(5, 10), 1);
+        assert_eq!(div_round(4, 10), 0);
+        assert_eq!(div_round(-5, 10), -1);
+        // 16 % de 0,05 USD = 0,008 → 0,01
+        assert_eq!(percent_of(5, 1600), 1);
+        // 1,00 USD a 36,525 Bs/USD = 36,525 Bs → 36,53
+        assert_eq!(usd_to_ves(100, 36_525_000), 3653);
+
+        assert_eq!(parse_decimal("36.52", RATE_SCALE).unwrap(), 36_520_000);
+        assert_eq!(parse_decimal("7", MONEY_SCALE).unwrap(), 700);
+        assert_eq!(parse_decimal("-0.5", MONEY_SCALE).unwrap(), -50);
+        assert!(parse_decimal("1.234", MONEY_SCALE).is_err());
+        assert!(parse_decimal("1,5", MONEY_SCALE).is_err());
+        assert!(parse_decimal(".5", MONEY_SCALE).is_err());
+        assert_eq!(
+            parse_decimal_rounded("36.52345650", RATE_SCALE).unwrap(),
+            36_523_457
+        );
+        assert_eq!(
+            parse_decimal_rounded("36.5", RATE_SCALE).unwrap(),
+            36_500_000
+        );
+        assert_eq!(format_decimal(36_520_000, RATE_SCALE), "36.520000");
+        assert_eq!(format_decimal(-5, MONEY_SCALE), "-0.05");
+    }
+}
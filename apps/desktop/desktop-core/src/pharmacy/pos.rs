@@ -0,0 +1,1602 @@
+// Punto de venta
+// Las ventas en curso viven en la base local (`pos_sales` y sus tablas), así
+// que un cierre inesperado no pierde el carrito. Los totales se recalculan en
+// cada cambio a partir de los precios en USD del catálogo: combos, descuentos
+// por producto y por orden, IVA por línea, conversión a bolívares con la tasa
+// de la venta e IGTF sobre lo pagado en divisas. Al cerrar, la factura, sus
+// líneas y los movimientos de inventario se escriben en una sola transacción.
+
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+use tauri::State;
+
+use super::inventory::{self, Consumption};
+use super::money::{
+    format_decimal, mul_div, parse_decimal, percent_of, usd_to_ves, MONEY_SCALE, RATE_SCALE,
+};
+use super::rates;
+use super::{local_date, local_time, local_weekday, PharmacyDb};
+use crate::error::{Error, Result};
+use crate::store::now_millis;
+
+/// IGTF sobre los pagos en divisas: 3 %, en centésimas de punto
+pub const IGTF_RATE: i64 = 300;
+/// Prefijo de la numeración local de facturas
+const INVOICE_PREFIX: &str = "POS";
+
+/// Datos para abrir una venta
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NewSale {
+    pub pharmacy_id: String,
+    pub warehouse_id: String,
+    pub user_id: String,
+    pub patient_id: Option<String>,
+    /// Bolívares por dólar, como decimal (`"36.52"`). Sin ella se usa la
+    /// tasa vigente; si difiere de la vigente queda como tasa manual del día.
+    pub exchange_rate: Option<String>,
+}
+
+/// Forma de pago; `mixed` solo aparece en la factura
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PaymentMethod {
+    Cash,
+    Card,
+    PagoMovil,
+    Zelle,
+    Biopago,
+    Crypto,
+    Transfer,
+}
+
+impl PaymentMethod {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PaymentMethod::Cash => "cash",
+            PaymentMethod::Card => "card",
+            PaymentMethod::PagoMovil => "pago_movil",
+            PaymentMethod::Zelle => "zelle",
+            PaymentMethod::Biopago => "biopago",
+            PaymentMethod::Crypto => "crypto",
+            PaymentMethod::Transfer => "transfer",
+        }
+    }
+
+    fn parse(value: &str) -> Result<Self> {
+        serde_json::from_value(json!(value))
+            .map_err(|_| Error::Storage(format!("forma de pago desconocida: {}", value)))
+    }
+}
+
+/// Moneda de un pago
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum Currency {
+    Usd,
+    Ves,
+}
+
+impl Currency {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Currency::Usd => "USD",
+            Currency::Ves => "VES",
+        }
+    }
+
+    fn parse(value: &str) -> Result<Self> {
+        match value {
+            "USD" => Ok(Currency::Usd),
+            "VES" => Ok(Currency::Ves),
+            other => Err(Error::Storage(format!("moneda desconocida: {}", other))),
+        }
+    }
+}
+
+/// Pago que registra el cajero
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NewPayment {
+    pub method: PaymentMethod,
+    pub currency: Currency,
+    /// Céntimos de `currency`
+    pub amount: i64,
+    pub reference: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SaleStatus {
+    Open,
+    Finalized,
+    Voided,
+}
+
+impl SaleStatus {
+    fn parse(value: &str) -> Result<Self> {
+        match value {
+            "open" => Ok(SaleStatus::Open),
+            "finalized" => Ok(SaleStatus::Finalized),
+            "voided" => Ok(SaleStatus::Voided),
+            other => Err(Error::Storage(format!(
+                "estado de venta desconocido: {}",
+                other
+            ))),
+        }
+    }
+}
+
+/// Línea de la venta con sus montos en céntimos
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SaleLine {
+    pub id: i64,
+    pub product_id: String,
+    pub batch_id: Option<String>,
+    pub product_name: String,
+    pub generic_name: Option<String>,
+    pub unit_type: String,
+    pub quantity: i64,
+    pub unit_price_usd: i64,
+    pub unit_price_ves: i64,
+    /// Centésimas (16 = 16 %)
+    pub iva_rate: i64,
+    /// Precio por cantidad, antes de descuentos
+    pub gross_usd: i64,
+    pub discount_usd: i64,
+    /// Base imponible de la línea
+    pub total_usd: i64,
+    pub total_ves: i64,
+    pub iva_usd: i64,
+    pub iva_ves: i64,
+    pub combo_id: Option<String>,
+}
+
+/// Descuento aplicado por el cajero; `amount_usd` es 0 si hoy no aplica
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AppliedDiscount {
+    pub id: String,
+    pub name: String,
+    pub amount_usd: i64,
+}
+
+/// Combo activo que el carrito completa `sets` veces
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AppliedCombo {
+    pub id: String,
+    pub name: String,
+    pub sets: i64,
+    pub amount_usd: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Payment {
+    pub id: i64,
+    pub method: PaymentMethod,
+    pub currency: Currency,
+    pub amount: i64,
+    /// Equivalente en céntimos de bolívar con la tasa de la venta
+    pub amount_ves: i64,
+    pub reference: Option<String>,
+}
+
+/// Venta con sus totales en céntimos
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Sale {
+    pub id: String,
+    pub status: SaleStatus,
+    pub pharmacy_id: String,
+    pub warehouse_id: String,
+    pub user_id: String,
+    pub patient_id: Option<String>,
+    pub exchange_rate: String,
+    /// Fila de `exchange_rates` con la que se factura y su fecha valor
+    pub exchange_rate_id: Option<i64>,
+    pub exchange_rate_date: Option<String>,
+    pub invoice_id: Option<String>,
+    pub invoice_number: Option<String>,
+    pub lines: Vec<SaleLine>,
+    pub combos: Vec<AppliedCombo>,
+    pub discounts: Vec<AppliedDiscount>,
+    pub payments: Vec<Payment>,
+    pub subtotal_usd: i64,
+    pub subtotal_ves: i64,
+    pub discount_usd: i64,
+    pub iva_usd: i64,
+    pub iva_ves: i64,
+    pub igtf_usd: i64,
+    pub igtf_ves: i64,
+    pub total_usd: i64,
+    pub total_ves: i64,
+    pub paid_ves: i64,
+    /// Lo que falta por cobrar
+    pub balance_ves: i64,
+    pub change_ves: i64,
+    pub created_at: i64,
+}
+
+// ============================================
+// REGLAS DE PRECIO
+// ============================================
+
+/// Fila de `discounts`
+#[derive(Debug, Clone)]
+struct DiscountRule {
+    id: String,
+    name: String,
+    percentage: bool,
+    value: i64,
+    applicable_to: String,
+    applicable_ids: Vec<String>,
+    start_date: Option<String>,
+    end_date: Option<String>,
+    days_of_week: Vec<String>,
+    start_time: Option<String>,
+    end_time: Option<String>,
+    min_quantity: Option<i64>,
+    max_quantity: Option<i64>,
+    /// Centésimas (50 = 50 %)
+    max_percentage: Option<i64>,
+    max_amount_usd: Option<i64>,
+    is_active: bool,
+}
+
+const DISCOUNT_COLUMNS: &str = "d.id, d.name, d.discount_type, d.value, d.applicable_to,
+    d.applicable_ids, d.start_date, d.end_date, d.days_of_week, d.start_time, d.end_time,
+    d.min_quantity, d.max_quantity, d.max_discount_percentage, d.max_discount_amount_usd,
+    d.is_active";
+
+/// Nombres aceptados en `days_of_week`, desde el domingo
+const WEEKDAYS: [[&str; 3]; 7] = [
+    ["sunday", "domingo", "domingo"],
+    ["monday", "lunes", "lunes"],
+    ["tuesday", "martes", "martes"],
+    ["wednesday", "miercoles", "miércoles"],
+    ["thursday", "jueves", "jueves"],
+    ["friday", "viernes", "viernes"],
+    ["saturday", "sabado", "sábado"],
+];
+
+impl DiscountRule {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        let list = |index: usize| -> rusqlite::Result<Vec<String>> {
+            let text: String = row.get(index)?;
+            serde_json::from_str(&text).map_err(|e| {
+                rusqlite::Error::FromSqlConversionFailure(
+                    index,
+                    rusqlite::types::Type::Text,
+                    Box::new(e),
+                )
+            })
+        };
+        Ok(Self {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            percentage: row.get::<_, String>(2)? == "percentage",
+            value: row.get(3)?,
+            applicable_to: row.get(4)?,
+            applicable_ids: list(5)?,
+            start_date: row.get(6)?,
+            end_date: row.get(7)?,
+            days_of_week: list(8)?,
+            start_time: row.get(9)?,
+            end_time: row.get(10)?,
+            min_quantity: row.get(11)?,
+            max_quantity: row.get(12)?,
+            max_percentage: row.get(13)?,
+            max_amount_usd: row.get(14)?,
+            is_active: row.get(15)?,
+        })
+    }
+
+    /// Por qué el descuento no está vigente en `now`, si no lo está
+    fn unavailable(&self, now: i64) -> Option<String> {
+        if !self.is_active {
+            return Some(format!("el descuento {} está inactivo", self.name));
+        }
+        let today = local_date(now);
+        if self.start_date.as_ref().is_some_and(|start| today < *start)
+            || self.end_date.as_ref().is_some_and(|end| today > *end)
+        {
+            return Some(format!("el descuento {} no está vigente hoy", self.name));
+        }
+        if !self.days_of_week.is_empty() {
+            let names = WEEKDAYS[local_weekday(now) as usize];
+            let today = |day: &String| names.contains(&day.trim().to_lowercase().as_str());
+            if !self.days_of_week.iter().any(today) {
+                return Some(format!("el descuento {} no aplica este día", self.name));
+            }
+        }
+        if let (Some(start), Some(end)) = (&self.start_time, &self.end_time) {
+            let time = local_time(now);
+            let (start, end) = (prefix(start, 5), prefix(end, 5));
+            let inside = if start <= end {
+                start <= time.as_str() && time.as_str() < end
+            } else {
+                start <= time.as_str() || time.as_str() < end
+            };
+            if !inside {
+                return Some(format!("el descuento {} no aplica a esta hora", self.name));
+            }
+        }
+        None
+    }
+
+    fn is_line_discount(&self) -> bool {
+        matches!(
+            self.applicable_to.as_str(),
+            "product" | "category" | "brand"
+        )
+    }
+
+    fn matches_line(&self, line: &PricedLine) -> bool {
+        let key = match self.applicable_to.as_str() {
+            "product" => Some(&line.line.product_id),
+            "category" => Some(&line.category),
+            "brand" => line.brand.as_ref(),
+            _ => None,
+        };
+        key.is_some_and(|key| self.applicable_ids.contains(key))
+    }
+
+    /// Monto del descuento sobre `base` para `quantity` unidades, con los topes
+    fn amount(&self, base: i64, quantity: i64) -> i64 {
+        let amount = if self.percentage {
+            percent_of(base, self.value)
+        } else if self.is_line_discount() {
+            self.value * quantity
+        } else {
+            self.value
+        };
+        let mut amount = amount.min(base);
+        if let Some(max) = self.max_percentage {
+            amount = amount.min(percent_of(base, max * 100));
+        }
+        amount
+    }
+}
+
+fn prefix(text: &str, len: usize) -> &str {
+    text.get(..len).unwrap_or(text)
+}
+
+/// Combo activo con sus productos
+#[derive(Debug, Clone)]
+struct ComboRule {
+    id: String,
+    name: String,
+    /// Centésimas (15 = 15 %)
+    discount_percentage: i64,
+    items: Vec<(String, i64)>,
+}
+
+/// Línea con los datos de catálogo necesarios para el precio
+#[derive(Debug, Clone)]
+struct PricedLine {
+    line: SaleLine,
+    category: String,
+    brand: Option<String>,
+    iva_exempt: bool,
+}
+
+/// Cabecera de `pos_sales`
+#[derive(Debug, Clone)]
+struct SaleHeader {
+    id: String,
+    status: SaleStatus,
+    pharmacy_id: String,
+    warehouse_id: String,
+    user_id: String,
+    patient_id: Option<String>,
+    exchange_rate: i64,
+    exchange_rate_id: Option<i64>,
+    exchange_rate_date: Option<String>,
+    invoice_id: Option<String>,
+    invoice_number: Option<String>,
+    summary: Option<String>,
+    created_at: i64,
+}
+
+/// Calcula los totales de la venta. Función pura: todo lo que necesita
+/// viene de la base en `load_sale`.
+fn price(
+    header: &SaleHeader,
+    mut lines: Vec<PricedLine>,
+    combos: &[ComboRule],
+    discounts: &[DiscountRule],
+    payments: Vec<Payment>,
+    now: i64,
+) -> Sale {
+    for priced in &mut lines {
+        let line = &mut priced.line;
+        line.gross_usd = line.unit_price_usd * line.quantity;
+        line.discount_usd = 0;
+        line.combo_id = None;
+    }
+
+    // Combos: cada juego completo descuenta su porcentaje sobre sus unidades
+    let mut free: HashMap<String, i64> = HashMap::new();
+    for priced in &lines {
+        *free.entry(priced.line.product_id.clone()).or_default() += priced.line.quantity;
+    }
+    let mut applied_combos = Vec::new();
+    for combo in combos {
+        let sets = combo
+            .items
+            .iter()
+            .map(|(product, quantity)| free.get(product).copied().unwrap_or(0) / quantity)
+            .min()
+            .unwrap_or(0);
+        if sets == 0 {
+            continue;
+        }
+        let mut amount = 0;
+        for (product, quantity) in &combo.items {
+            let mut units = sets * quantity;
+            *free.get_mut(product).unwrap() -= units;
+            for priced in lines.iter_mut().filter(|l| &l.line.product_id == product) {
+                let line = &mut priced.line;
+                let taken = units.min(line.quantity);
+                if taken == 0 {
+                    continue;
+                }
+                let discount =
+                    percent_of(line.unit_price_usd * taken, combo.discount_percentage * 100);
+                line.discount_usd += discount;
+                line.combo_id = Some(combo.id.clone());
+                amount += discount;
+                units -= taken;
+            }
+        }
+        applied_combos.push(AppliedCombo {
+            id: combo.id.clone(),
+            name: combo.name.clone(),
+            sets,
+            amount_usd: amount,
+        });
+    }
+
+    // Descuentos del cajero: primero los de línea y luego los de orden,
+    // siempre sobre lo que queda después de los anteriores
+    let units: i64 = lines.iter().map(|l| l.line.quantity).sum();
+    let mut applied_discounts = Vec::new();
+    for rule in discounts {
+        let mut amount = 0;
+        if rule.unavailable(now).is_none() {
+            if rule.is_line_discount() {
+                for priced in lines.iter_mut().filter(|l| rule.matches_line(l)) {
+                    let line = &mut priced.line;
+                    if rule.min_quantity.is_some_and(|min| line.quantity < min) {
+                        continue;
+                    }
+                    let quantity = rule
+                        .max_quantity
+                        .map_or(line.quantity, |max| line.quantity.min(max));
+                    let base = mul_div(line.gross_usd - line.discount_usd, quantity, line.quantity);
+                    let mut discount = rule.amount(base, quantity);
+                    if let Some(max) = rule.max_amount_usd {
+                        discount = discount.min(max - amount);
+                    }
+                    line.discount_usd += discount;
+                    amount += discount;
+                }
+            } else if order_applies(rule, header, units) {
+                let base: i64 = lines
+                    .iter()
+                    .map(|l| l.line.gross_usd - l.line.discount_usd)
+                    .sum();
+                let mut total = rule.amount(base, units);
+                if let Some(max) = rule.max_amount_usd {
+                    total = total.min(max);
+                }
+                amount = total;
+                spread(&mut lines, total, base);
+            }
+        }
+        applied_discounts.push(AppliedDiscount {
+            id: rule.id.clone(),
+            name: rule.name.clone(),
+            amount_usd: amount,
+        });
+    }
+
+    let rate = header.exchange_rate;
+    let mut sale = Sale {
+        id: header.id.clone(),
+        status: header.status,
+        pharmacy_id: header.pharmacy_id.clone(),
+        warehouse_id: header.warehouse_id.clone(),
+        user_id: header.user_id.clone(),
+        patient_id: header.patient_id.clone(),
+        exchange_rate: format_decimal(rate, RATE_SCALE),
+        exchange_rate_id: header.exchange_rate_id,
+        exchange_rate_date: header.exchange_rate_date.clone(),
+        invoice_id: header.invoice_id.clone(),
+        invoice_number: header.invoice_number.clone(),
+        lines: Vec::with_capacity(lines.len()),
+        combos: applied_combos,
+        discounts: applied_discounts,
+        payments,
+        subtotal_usd: 0,
+        subtotal_ves: 0,
+        discount_usd: 0,
+        iva_usd: 0,
+        iva_ves: 0,
+        igtf_usd: 0,
+        igtf_ves: 0,
+        total_usd: 0,
+        total_ves: 0,
+        paid_ves: 0,
+        balance_ves: 0,
+        change_ves: 0,
+        created_at: header.created_at,
+    };
+    for priced in lines {
+        let mut line = priced.line;
+        let iva_rate = if priced.iva_exempt { 0 } else { line.iva_rate };
+        line.unit_price_ves = usd_to_ves(line.unit_price_usd, rate);
+        line.total_usd = line.gross_usd - line.discount_usd;
+        line.total_ves = usd_to_ves(line.total_usd, rate);
+        line.iva_usd = percent_of(line.total_usd, iva_rate * 100);
+        line.iva_ves = percent_of(line.total_ves, iva_rate * 100);
+        sale.subtotal_usd += line.total_usd;
+        sale.subtotal_ves += line.total_ves;
+        sale.discount_usd += line.discount_usd;
+        sale.iva_usd += line.iva_usd;
+        sale.iva_ves += line.iva_ves;
+        sale.lines.push(line);
+    }
+
+    // IGTF sobre lo pagado en divisas, hasta el monto de la factura
+    let due_usd = sale.subtotal_usd + sale.iva_usd;
+    let due_ves = sale.subtotal_ves + sale.iva_ves;
+    let foreign = sale.payments.iter().filter(|p| p.currency == Currency::Usd);
+    let foreign_usd: i64 = foreign.clone().map(|p| p.amount).sum();
+    let foreign_ves: i64 = foreign.map(|p| p.amount_ves).sum();
+    sale.igtf_usd = percent_of(foreign_usd.min(due_usd), IGTF_RATE);
+    sale.igtf_ves = percent_of(foreign_ves.min(due_ves), IGTF_RATE);
+    sale.total_usd = due_usd + sale.igtf_usd;
+    sale.total_ves = due_ves + sale.igtf_ves;
+    sale.paid_ves = sale.payments.iter().map(|p| p.amount_ves).sum();
+    sale.balance_ves = (sale.total_ves - sale.paid_ves).max(0);
+    sale.change_ves = (sale.paid_ves - sale.total_ves).max(0);
+    sale
+}
+
+fn order_applies(rule: &DiscountRule, header: &SaleHeader, units: i64) -> bool {
+    let eligible = match rule.applicable_to.as_str() {
+        "customer" => header
+            .patient_id
+            .as_ref()
+            .is_some_and(|id| rule.applicable_ids.contains(id)),
+        "warehouse" => rule.applicable_ids.contains(&header.warehouse_id),
+        _ => true,
+    };
+    eligible
+        && rule.min_quantity.is_none_or(|min| units >= min)
+        && rule.max_quantity.is_none_or(|max| units <= max)
+}
+
+/// Reparte un descuento de orden entre las líneas en proporción a su base,
+/// para que el IVA se calcule sobre lo cobrado; el resto del redondeo va a
+/// la última línea con base
+fn spread(lines: &mut [PricedLine], total: i64, base: i64) {
+    if base <= 0 || total <= 0 {
+        return;
+    }
+    let mut remaining = total;
+    let last = lines
+        .iter()
+        .rposition(|l| l.line.gross_usd > l.line.discount_usd);
+    for (index, priced) in lines.iter_mut().enumerate() {
+        let line = &mut priced.line;
+        let net = line.gross_usd - line.discount_usd;
+        if net <= 0 {
+            continue;
+        }
+        let share = if Some(index) == last {
+            remaining.min(net)
+        } else {
+            (total as i128 * net as i128 / base as i128) as i64
+        };
+        line.discount_usd += share;
+        remaining -= share;
+    }
+}
+
+// ============================================
+// PERSISTENCIA
+// ============================================
+
+fn load_header(conn: &Connection, id: &str) -> Result<SaleHeader> {
+    conn.query_row(
+        "SELECT s.id, s.status, s.pharmacy_id, s.warehouse_id, s.user_id, s.patient_id,
+                s.exchange_rate, s.invoice_id, i.invoice_number, s.summary, s.created_at,
+                s.exchange_rate_id, r.effective_date
+         FROM pos_sales s
+         LEFT JOIN invoices i ON i.id = s.invoice_id
+         LEFT JOIN exchange_rates r ON r.id = s.exchange_rate_id
+         WHERE s.id = ?1",
+        params![id],
+        |row| {
+            Ok((
+                row.get::<_, String>(1)?,
+                SaleHeader {
+                    id: row.get(0)?,
+                    status: SaleStatus::Open,
+                    pharmacy_id: row.get(2)?,
+                    warehouse_id: row.get(3)?,
+                    user_id: row.get(4)?,
+                    patient_id: row.get(5)?,
+                    exchange_rate: row.get(6)?,
+                    exchange_rate_id: row.get(11)?,
+                    exchange_rate_date: row.get(12)?,
+                    invoice_id: row.get(7)?,
+                    invoice_number: row.get(8)?,
+                    summary: row.get(9)?,
+                    created_at: row.get(10)?,
+                },
+            ))
+        },
+    )
+    .optional()?
+    .ok_or_else(|| Error::NotFound(format!("venta {}", id)))
+    .and_then(|(status, mut header)| {
+        header.status = SaleStatus::parse(&status)?;
+        Ok(header)
+    })
+}
+
+/// Venta con los precios y reglas vigentes en `now`. Las ventas cerradas
+/// devuelven el resumen congelado al cerrarlas.
+pub(crate) fn load_sale(conn: &Connection, id: &str, now: i64) -> Result<Sale> {
+    let header = load_header(conn, id)?;
+    if let Some(summary) = &header.summary {
+        let mut sale: Sale =
+            serde_json::from_str(summary).map_err(|e| Error::Storage(e.to_string()))?;
+        sale.status = header.status;
+        return Ok(sale);
+    }
+
+    let mut stmt = conn.prepare(
+        "SELECT i.id, i.product_id, i.batch_id, i.quantity, p.name, p.generic_name, p.unit_type,
+                p.sale_price_usd, p.iva_rate, p.category, p.brand, p.iva_exempt
+         FROM pos_sale_items i JOIN products p ON p.id = i.product_id
+         WHERE i.sale_id = ?1 ORDER BY i.id",
+    )?;
+    let lines = stmt
+        .query_map(params![id], |row| {
+            Ok(PricedLine {
+                line: SaleLine {
+                    id: row.get(0)?,
+                    product_id: row.get(1)?,
+                    batch_id: row.get(2)?,
+                    product_name: row.get(4)?,
+                    generic_name: row.get(5)?,
+                    unit_type: row.get(6)?,
+                    quantity: row.get(3)?,
+                    unit_price_usd: row.get(7)?,
+                    unit_price_ves: 0,
+                    iva_rate: row.get(8)?,
+                    gross_usd: 0,
+                    discount_usd: 0,
+                    total_usd: 0,
+                    total_ves: 0,
+                    iva_usd: 0,
+                    iva_ves: 0,
+                    combo_id: None,
+                },
+                category: row.get(9)?,
+                brand: row.get(10)?,
+                iva_exempt: row.get(11)?,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    let mut stmt = conn.prepare(
+        "SELECT c.id, c.name, c.discount_percentage, i.product_id, i.quantity
+         FROM combos c JOIN combo_items i ON i.combo_id = c.id
+         WHERE c.is_active = 1
+         ORDER BY c.discount_percentage DESC, c.id",
+    )?;
+    let mut combos: Vec<ComboRule> = Vec::new();
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, i64>(2)?,
+            row.get::<_, String>(3)?,
+            row.get::<_, i64>(4)?,
+        ))
+    })?;
+    for row in rows {
+        let (combo_id, name, discount_percentage, product_id, quantity) = row?;
+        match combos.last_mut() {
+            Some(combo) if combo.id == combo_id => combo.items.push((product_id, quantity)),
+            _ => combos.push(ComboRule {
+                id: combo_id,
+                name,
+                discount_percentage,
+                items: vec![(product_id, quantity)],
+            }),
+        }
+    }
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM pos_sale_discounts s JOIN discounts d ON d.id = s.discount_id
+         WHERE s.sale_id = ?1 ORDER BY s.rowid",
+        DISCOUNT_COLUMNS
+    ))?;
+    let discounts = stmt
+        .query_map(params![id], DiscountRule::from_row)?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    let mut stmt = conn.prepare(
+        "SELECT id, method, currency, amount, reference FROM pos_payments
+         WHERE sale_id = ?1 ORDER BY id",
+    )?;
+    let payments = stmt
+        .query_map(params![id], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, i64>(3)?,
+                row.get::<_, Option<String>>(4)?,
+            ))
+        })?
+        .map(|row| {
+            let (id, method, currency, amount, reference) = row?;
+            let currency = Currency::parse(&currency)?;
+            Ok(Payment {
+                id,
+                method: PaymentMethod::parse(&method)?,
+                currency,
+                amount,
+                amount_ves: match currency {
+                    Currency::Usd => usd_to_ves(amount, header.exchange_rate),
+                    Currency::Ves => amount,
+                },
+                reference,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(price(&header, lines, &combos, &discounts, payments, now))
+}
+
+/// Venta abierta o el error que explica por qué no se puede modificar
+fn ensure_open(conn: &Connection, id: &str, now: i64) -> Result<SaleHeader> {
+    let header = load_header(conn, id)?;
+    if header.status != SaleStatus::Open {
+        return Err(Error::Validation(format!(
+            "la venta {} ya está cerrada",
+            id
+        )));
+    }
+    conn.execute(
+        "UPDATE pos_sales SET updated_at = ?2 WHERE id = ?1",
+        params![id, now],
+    )?;
+    Ok(header)
+}
+
+/// Comprueba que haya existencias vendibles hoy en el almacén de la venta
+/// para `quantity` unidades más de lo que ya tiene
+fn check_stock(
+    conn: &Connection,
+    sale: &SaleHeader,
+    product_id: &str,
+    batch_id: Option<&str>,
+    quantity: i64,
+    now: i64,
+) -> Result<()> {
+    let today = local_date(now);
+    if let Some(batch_id) = batch_id {
+        inventory::check_batch(conn, &sale.warehouse_id, product_id, batch_id, &today)?;
+    }
+    let in_sale: i64 = conn.query_row(
+        "SELECT COALESCE(SUM(quantity), 0) FROM pos_sale_items
+         WHERE sale_id = ?1 AND product_id = ?2 AND (?3 IS NULL OR batch_id IS ?3)",
+        params![sale.id, product_id, batch_id],
+        |row| row.get(0),
+    )?;
+    let available = inventory::available(conn, &sale.warehouse_id, product_id, batch_id, &today)?;
+    if in_sale + quantity > available {
+        return Err(Error::Validation(format!(
+            "stock insuficiente: disponibles {}, en la venta {}",
+            available, in_sale
+        )));
+    }
+    Ok(())
+}
+
+pub(crate) fn create(conn: &Connection, sale: &NewSale, now: i64) -> Result<Sale> {
+    for (field, value) in [
+        ("pharmacyId", &sale.pharmacy_id),
+        ("warehouseId", &sale.warehouse_id),
+        ("userId", &sale.user_id),
+    ] {
+        if value.trim().is_empty() {
+            return Err(Error::Validation(format!("{} es obligatorio", field)));
+        }
+    }
+    let today = local_date(now);
+    let rate = match &sale.exchange_rate {
+        Some(text) => rates::stamp(
+            conn,
+            rates::Currency::Usd,
+            parse_decimal(text, RATE_SCALE)?,
+            &today,
+            now,
+        )?,
+        None => rates::current(conn, rates::Currency::Usd, &today, now)?,
+    };
+    let id = uuid::Uuid::new_v4().to_string();
+    conn.execute(
+        "INSERT INTO pos_sales (id, pharmacy_id, warehouse_id, user_id, patient_id,
+                                exchange_rate, exchange_rate_id, created_at, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?8)",
+        params![
+            id,
+            sale.pharmacy_id,
+            sale.warehouse_id,
+            sale.user_id,
+            sale.patient_id,
+            rate.value,
+            rate.id,
+            now
+        ],
+    )?;
+    load_sale(conn, &id, now)
+}
+
+pub(crate) fn add_item(
+    conn: &mut Connection,
+    sale_id: &str,
+    product_id: &str,
+    batch_id: Option<&str>,
+    quantity: u32,
+    now: i64,
+) -> Result<Sale> {
+    if quantity == 0 {
+        return Err(Error::Validation(
+            "la cantidad debe ser mayor que cero".into(),
+        ));
+    }
+    let tx = conn.transaction()?;
+    let header = ensure_open(&tx, sale_id, now)?;
+    let exists: bool = tx.query_row(
+        "SELECT EXISTS (SELECT 1 FROM products WHERE id = ?1)",
+        params![product_id],
+        |row| row.get(0),
+    )?;
+    if !exists {
+        return Err(Error::NotFound(format!("producto {}", product_id)));
+    }
+    check_stock(&tx, &header, product_id, batch_id, quantity.into(), now)?;
+
+    let line: Option<i64> = tx
+        .query_row(
+            "SELECT id FROM pos_sale_items
+             WHERE sale_id = ?1 AND product_id = ?2 AND batch_id IS ?3",
+            params![sale_id, product_id, batch_id],
+            |row| row.get(0),
+        )
+        .optional()?;
+    match line {
+        Some(line) => tx.execute(
+            "UPDATE pos_sale_items SET quantity = quantity + ?2 WHERE id = ?1",
+            params![line, quantity],
+        )?,
+        None => tx.execute(
+            "INSERT INTO pos_sale_items (sale_id, product_id, batch_id, quantity, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![sale_id, product_id, batch_id, quantity, now],
+        )?,
+    };
+    let sale = load_sale(&tx, sale_id, now)?;
+    tx.commit()?;
+    Ok(sale)
+}
+
+/// Cambia la cantidad de una línea; con 0 la quita
+pub(crate) fn update_item(
+    conn: &mut Connection,
+    sale_id: &str,
+    line_id: i64,
+    quantity: u32,
+    now: i64,
+) -> Result<Sale> {
+    let tx = conn.transaction()?;
+    let header = ensure_open(&tx, sale_id, now)?;
+    let line: Option<(String, Option<String>, i64)> = tx
+        .query_row(
+            "SELECT product_id, batch_id, quantity FROM pos_sale_items
+             WHERE id = ?1 AND sale_id = ?2",
+            params![line_id, sale_id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .optional()?;
+    let Some((product_id, batch_id, current)) = line else {
+        return Err(Error::NotFound(format!("línea {} de la venta", line_id)));
+    };
+    if quantity == 0 {
+        tx.execute("DELETE FROM pos_sale_items WHERE id = ?1", params![line_id])?;
+    } else {
+        let extra = i64::from(quantity) - current;
+        if extra > 0 {
+            check_stock(&tx, &header, &product_id, batch_id.as_deref(), extra, now)?;
+        }
+        tx.execute(
+            "UPDATE pos_sale_items SET quantity = ?2 WHERE id = ?1",
+            params![line_id, quantity],
+        )?;
+    }
+    let sale = load_sale(&tx, sale_id, now)?;
+    tx.commit()?;
+    Ok(sale)
+}
+
+pub(crate) fn apply_discount(
+    conn: &mut Connection,
+    sale_id: &str,
+    discount_id: &str,
+    now: i64,
+) -> Result<Sale> {
+    let tx = conn.transaction()?;
+    ensure_open(&tx, sale_id, now)?;
+    let rule = tx
+        .query_row(
+            &format!(
+                "SELECT {} FROM discounts d WHERE d.id = ?1",
+                DISCOUNT_COLUMNS
+            ),
+            params![discount_id],
+            DiscountRule::from_row,
+        )
+        .optional()?
+        .ok_or_else(|| Error::NotFound(format!("descuento {}", discount_id)))?;
+    if let Some(reason) = rule.unavailable(now) {
+        return Err(Error::Validation(reason));
+    }
+    tx.execute(
+        "INSERT INTO pos_sale_discounts (sale_id, discount_id) VALUES (?1, ?2)
+         ON CONFLICT DO NOTHING",
+        params![sale_id, discount_id],
+    )?;
+    let sale = load_sale(&tx, sale_id, now)?;
+    tx.commit()?;
+    Ok(sale)
+}
+
+pub(crate) fn add_payment(
+    conn: &mut Connection,
+    sale_id: &str,
+    payment: &NewPayment,
+    now: i64,
+) -> Result<Sale> {
+    if payment.amount <= 0 {
+        return Err(Error::Validation(
+            "el monto del pago debe ser positivo".into(),
+        ));
+    }
+    let tx = conn.transaction()?;
+    ensure_open(&tx, sale_id, now)?;
+    tx.execute(
+        "INSERT INTO pos_payments (sale_id, method, currency, amount, reference, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+            sale_id,
+            payment.method.as_str(),
+            payment.currency.as_str(),
+            payment.amount,
+            payment.reference,
+            now
+        ],
+    )?;
+    let sale = load_sale(&tx, sale_id, now)?;
+    tx.commit()?;
+    Ok(sale)
+}
+
+/// Borra una fila hija de una venta abierta (línea, descuento o pago)
+fn remove_child(
+    conn: &mut Connection,
+    sale_id: &str,
+    sql: &str,
+    child: &dyn rusqlite::ToSql,
+    what: &str,
+    now: i64,
+) -> Result<Sale> {
+    let tx = conn.transaction()?;
+    ensure_open(&tx, sale_id, now)?;
+    if tx.execute(sql, params![sale_id, child])? == 0 {
+        return Err(Error::NotFound(format!("{} de la venta", what)));
+    }
+    let sale = load_sale(&tx, sale_id, now)?;
+    tx.commit()?;
+    Ok(sale)
+}
+
+/// Siguiente número de la secuencia `name`
+fn next_sequence(conn: &Connection, name: &str) -> Result<i64> {
+    Ok(conn.query_row(
+        "INSERT INTO pos_sequences (name, value) VALUES (?1, 1)
+         ON CONFLICT(name) DO UPDATE SET value = value + 1
+         RETURNING value",
+        params![name],
+        |row| row.get(0),
+    )?)
+}
+
+/// Cierra la venta: factura, líneas, movimientos de inventario y descuento
+/// de existencias en una sola transacción. Si algo falla no queda nada escrito.
+pub(crate) fn finalize(conn: &mut Connection, sale_id: &str, now: i64) -> Result<Sale> {
+    let tx = conn.transaction()?;
+    ensure_open(&tx, sale_id, now)?;
+    let mut sale = load_sale(&tx, sale_id, now)?;
+    if sale.lines.is_empty() {
+        return Err(Error::Validation("la venta no tiene productos".into()));
+    }
+    if sale.balance_ves > 0 {
+        return Err(Error::Validation(format!(
+            "faltan {} Bs por cobrar",
+            format_decimal(sale.balance_ves, MONEY_SCALE)
+        )));
+    }
+
+    let mut methods = sale.payments.iter().map(|p| p.method.as_str());
+    let payment_method = match methods.next() {
+        Some(first) if methods.all(|m| m == first) => first,
+        Some(_) => "mixed",
+        None => PaymentMethod::Cash.as_str(),
+    };
+    let rate = parse_decimal(&sale.exchange_rate, RATE_SCALE)?;
+    let invoice_id = uuid::Uuid::new_v4().to_string();
+    let invoice_number = format!(
+        "{}-{:08}",
+        INVOICE_PREFIX,
+        next_sequence(&tx, INVOICE_PREFIX)?
+    );
+    // IGTF, descuentos y vuelto no tienen columna propia en `invoices`
+    let details = json!({
+        "payments": sale.payments,
+        "discountUsd": sale.discount_usd,
+        "combos": sale.combos,
+        "discounts": sale.discounts,
+        "igtfUsd": sale.igtf_usd,
+        "igtfVes": sale.igtf_ves,
+        "paidVes": sale.paid_ves,
+        "changeVes": sale.change_ves,
+    });
+    tx.execute(
+        "INSERT INTO invoices (id, invoice_number, patient_id, warehouse_id, user_id, status,
+                               subtotal_usd, subtotal_ves, iva_usd, iva_ves, total_usd, total_ves,
+                               payment_method, payment_details, exchange_rate,
+                               exchange_rate_id, created_at, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, 'paid', ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15,
+                 ?16, ?16)",
+        params![
+            invoice_id,
+            invoice_number,
+            sale.patient_id,
+            sale.warehouse_id,
+            sale.user_id,
+            sale.subtotal_usd,
+            sale.subtotal_ves,
+            sale.iva_usd,
+            sale.iva_ves,
+            sale.total_usd,
+            sale.total_ves,
+            payment_method,
+            details.to_string(),
+            rate,
+            sale.exchange_rate_id,
+            now
+        ],
+    )?;
+
+    for line in &sale.lines {
+        let consumption = Consumption {
+            pharmacy_id: sale.pharmacy_id.clone(),
+            warehouse_id: sale.warehouse_id.clone(),
+            product_id: line.product_id.clone(),
+            batch_id: line.batch_id.clone(),
+            quantity: line.quantity,
+            unit_price_usd: Some(line.unit_price_usd),
+            document_type: Some("factura".into()),
+            document_id: Some(invoice_id.clone()),
+            notes: None,
+            user_id: Some(sale.user_id.clone()),
+        };
+        let taken = inventory::consume(&tx, &consumption, now).map_err(|e| match e {
+            Error::Validation(message) => {
+                Error::Validation(format!("{}: {}", line.product_name, message))
+            }
+            other => other,
+        })?;
+        // Con un solo lote la línea lo referencia; si no, queda en los movimientos
+        let batch_id = match taken.as_slice() {
+            [allocation] => Some(allocation.batch_id.clone()),
+            _ => None,
+        };
+        tx.execute(
+            "INSERT INTO invoice_items (id, invoice_id, product_id, batch_id, product_name,
+                                        generic_name, quantity, unit_type, unit_price_usd,
+                                        unit_price_ves, total_usd, total_ves, iva_rate, iva_usd,
+                                        iva_ves, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)",
+            params![
+                uuid::Uuid::new_v4().to_string(),
+                invoice_id,
+                line.product_id,
+                batch_id,
+                line.product_name,
+                line.generic_name,
+                line.quantity * 100,
+                line.unit_type,
+                line.unit_price_usd,
+                line.unit_price_ves,
+                line.total_usd,
+                line.total_ves,
+                line.iva_rate,
+                line.iva_usd,
+                line.iva_ves,
+                now
+            ],
+        )?;
+    }
+
+    sale.status = SaleStatus::Finalized;
+    sale.invoice_id = Some(invoice_id.clone());
+    sale.invoice_number = Some(invoice_number);
+    let summary = serde_json::to_string(&sale).map_err(|e| Error::Storage(e.to_string()))?;
+    tx.execute(
+        "UPDATE pos_sales SET status = 'finalized', invoice_id = ?2, summary = ?3 WHERE id = ?1",
+        params![sale_id, invoice_id, summary],
+    )?;
+    tx.commit()?;
+    Ok(sale)
+}
+
+/// Anula una venta abierta; no toca el inventario porque aún no se descontó
+pub(crate) fn void(conn: &mut Connection, sale_id: &str, now: i64) -> Result<Sale> {
+    let tx = conn.transaction()?;
+    ensure_open(&tx, sale_id, now)?;
+    tx.execute(
+        "UPDATE pos_sales SET status = 'voided' WHERE id = ?1",
+        params![sale_id],
+    )?;
+    let sale = load_sale(&tx, sale_id, now)?;
+    tx.commit()?;
+    Ok(sale)
+}
+
+// ============================================
+// COMMANDS
+// ============================================
+
+/// Abre una venta vacía con la tasa de cambio vigente o la indicada
+#[tauri::command]
+pub async fn open_sale(db: State<'_, PharmacyDb>, sale: NewSale) -> Result<Sale> {
+    db.with_conn(|conn| create(conn, &sale, now_millis()))
+}
+
+#[tauri::command]
+pub async fn get_sale(db: State<'_, PharmacyDb>, id: String) -> Result<Sale> {
+    db.with_conn(|conn| load_sale(conn, &id, now_millis()))
+}
+
+/// Ventas abiertas, para retomarlas después de reiniciar la app
+#[tauri::command]
+pub async fn get_open_sales(db: State<'_, PharmacyDb>) -> Result<Vec<Sale>> {
+    db.with_conn(|conn| {
+        let now = now_millis();
+        let mut stmt =
+            conn.prepare("SELECT id FROM pos_sales WHERE status = 'open' ORDER BY created_at")?;
+        let ids = stmt
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        ids.iter().map(|id| load_sale(conn, id, now)).collect()
+    })
+}
+
+/// Agrega unidades de un producto; sin `batchId` se descuentan al cerrar
+/// de los lotes que vencen antes
+#[tauri::command]
+pub async fn add_sale_item(
+    db: State<'_, PharmacyDb>,
+    sale_id: String,
+    product_id: String,
+    batch_id: Option<String>,
+    quantity: u32,
+) -> Result<Sale> {
+    db.with_conn(|conn| {
+        add_item(
+            conn,
+            &sale_id,
+            &product_id,
+            batch_id.as_deref(),
+            quantity,
+            now_millis(),
+        )
+    })
+}
+
+/// Cambia la cantidad de una línea; con 0 la quita
+#[tauri::command]
+pub async fn update_sale_item(
+    db: State<'_, PharmacyDb>,
+    sale_id: String,
+    line_id: i64,
+    quantity: u32,
+) -> Result<Sale> {
+    db.with_conn(|conn| update_item(conn, &sale_id, line_id, quantity, now_millis()))
+}
+
+#[tauri::command]
+pub async fn remove_sale_item(
+    db: State<'_, PharmacyDb>,
+    sale_id: String,
+    line_id: i64,
+) -> Result<Sale> {
+    db.with_conn(|conn| {
+        remove_child(
+            conn,
+            &sale_id,
+            "DELETE FROM pos_sale_items WHERE sale_id = ?1 AND id = ?2",
+            &line_id,
+            "línea",
+            now_millis(),
+        )
+    })
+}
+
+/// Aplica un descuento de la tabla `discounts`; los combos se aplican solos
+#[tauri::command]
+pub async fn apply_sale_discount(
+    db: State<'_, PharmacyDb>,
+    sale_id: String,
+    discount_id: String,
+) -> Result<Sale> {
+    db.with_conn(|conn| apply_discount(conn, &sale_id, &discount_id, now_millis()))
+}
+
+#[tauri::command]
+pub async fn remove_sale_discount(
+    db: State<'_, PharmacyDb>,
+    sale_id: String,
+    discount_id: String,
+) -> Result<Sale> {
+    db.with_conn(|conn| {
+        remove_child(
+            conn,
+            &sale_id,
+            "DELETE FROM pos_sale_discounts WHERE sale_id = ?1 AND discount_id = ?2",
+            &discount_id,
+            "descuento",
+            now_millis(),
+        )
+    })
+}
+
+#[tauri::command]
+pub async fn add_sale_payment(
+    db: State<'_, PharmacyDb>,
+    sale_id: String,
+    payment: NewPayment,
+) -> Result<Sale> {
+    db.with_conn(|conn| add_payment(conn, &sale_id, &payment, now_millis()))
+}
+
+#[tauri::command]
+pub async fn remove_sale_payment(
+    db: State<'_, PharmacyDb>,
+    sale_id: String,
+    payment_id: i64,
+) -> Result<Sale> {
+    db.with_conn(|conn| {
+        remove_child(
+            conn,
+            &sale_id,
+            "DELETE FROM pos_payments WHERE sale_id = ?1 AND id = ?2",
+            &payment_id,
+            "pago",
+            now_millis(),
+        )
+    })
+}
+
+/// Cierra la venta y escribe la factura; devuelve el resumen congelado
+#[tauri::command]
+pub async fn finalize_sale(db: State<'_, PharmacyDb>, sale_id: String) -> Result<Sale> {
+    db.with_conn(|conn| finalize(conn, &sale_id, now_millis()))
+}
+
+#[tauri::command]
+pub async fn void_sale(db: State<'_, PharmacyDb>, sale_id: String) -> Result<Sale> {
+    db.with_conn(|conn| void(conn, &sale_id, now_millis()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pharmacy::schema::{migrate, MIGRATIONS};
+
+    /// 2026-03-07 12:00 en Caracas, sábado
+    const NOW: i64 = 1_772_899_200_000;
+
+    fn pharmacy() -> Connection {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.pragma_update(None, "foreign_keys", "ON").unwrap();
+        migrate(&mut conn, MIGRATIONS).unwrap();
+        conn.execute_batch(
+            "INSERT INTO products (id, sku, name, sale_price_usd, iva_rate, iva_exempt,
+                                   created_at, updated_at)
+             VALUES ('p-a', 'A', 'Losartán 50 mg', 1000, 16, 0, 0, 0),
+                    ('p-b', 'B', 'Suero oral', 500, 16, 1, 0, 0);
+             INSERT INTO batches (id, product_id, lot_number, expiry_date, warehouse_id,
+                                  quantity, received_at, created_at, updated_at)
+             VALUES ('a-1', 'p-a', 'A1', '2027-01-31', 'w1', 1, 1, 0, 0),
+                    ('a-2', 'p-a', 'A2', '2026-12-31', 'w1', 5, 2, 0, 0),
+                    ('b-1', 'p-b', 'B1', '2027-06-30', 'w1', 10, 1, 0, 0);",
+        )
+        .unwrap();
+        conn
+    }
+
+    fn new_sale(conn: &Connection) -> Sale {
+        let sale = NewSale {
+            pharmacy_id: "f1".into(),
+            warehouse_id: "w1".into(),
+            user_id: "u1".into(),
+            patient_id: None,
+            exchange_rate: Some("40".into()),
+        };
+        create(conn, &sale, NOW).unwrap()
+    }
+
+    fn pay(
+        conn: &mut Connection,
+        sale: &str,
+        method: PaymentMethod,
+        currency: Currency,
+        amount: i64,
+    ) -> Sale {
+        let payment = NewPayment {
+            method,
+            currency,
+            amount,
+            reference: None,
+        };
+        add_payment(conn, sale, &payment, NOW).unwrap()
+    }
+
+    fn count(conn: &Connection, sql: &str) -> i64 {
+        conn.query_row(sql, [], |row| row.get(0)).unwrap()
+    }
+
+    #[test]
+    fn prices_combos_discounts_iva_and_igtf() {
+        let mut conn = pharmacy();
+        conn.execute_batch(
+            "INSERT INTO combos (id, name, discount_percentage, created_at, updated_at)
+             VALUES ('c1', 'Tensión + suero', 10, 0, 0);
+             INSERT INTO combo_items (id, combo_id, product_id, quantity, created_at)
+             VALUES ('ci1', 'c1', 'p-a', 1, 0), ('ci2', 'c1', 'p-b', 1, 0);
+             INSERT INTO discounts (id, name, discount_type, value, applicable_to,
+                                    created_at, updated_at)
+             VALUES ('d1', 'Cliente frecuente', 'percentage', 500, 'order', 0, 0);",
+        )
+        .unwrap();
+        let sale = new_sale(&conn);
+        add_item(&mut conn, &sale.id, "p-a", None, 3, NOW).unwrap();
+        add_item(&mut conn, &sale.id, "p-b", None, 1, NOW).unwrap();
+        let sale = apply_discount(&mut conn, &sale.id, "d1", NOW).unwrap();
+
+        // Combo: 10 % de una unidad de cada producto (1,00 + 0,50)
+        assert_eq!(sale.combos[0].sets, 1);
+        assert_eq!(sale.combos[0].amount_usd, 150);
+        // 5 % de 33,50 repartido en proporción: 1,45 y 0,23
+        assert_eq!(sale.discounts[0].amount_usd, 168);
+        let [a, b] = sale.lines.as_slice() else {
+            panic!()
+        };
+        assert_eq!((a.discount_usd, a.total_usd, a.iva_usd), (245, 2755, 441));
+        assert_eq!(
+            (a.total_ves, a.iva_ves, a.unit_price_ves),
+            (110_200, 17_632, 40_000)
+        );
+        // Exento de IVA
+        assert_eq!((b.total_usd, b.iva_usd, b.total_ves), (427, 0, 17_080));
+        assert_eq!(sale.subtotal_usd, 3182);
+        assert_eq!(sale.discount_usd, 318);
+        assert_eq!(sale.total_ves, 144_912);
+
+        // 20 USD en Zelle pagan 3 % de IGTF; el resto en bolívares deja vuelto
+        let sale = pay(
+            &mut conn,
+            &sale.id,
+            PaymentMethod::Zelle,
+            Currency::Usd,
+            2000,
+        );
+        assert_eq!((sale.igtf_usd, sale.igtf_ves), (60, 2400));
+        assert_eq!(sale.total_ves, 147_312);
+        assert_eq!(sale.balance_ves, 67_312);
+        let sale = pay(
+            &mut conn,
+            &sale.id,
+            PaymentMethod::PagoMovil,
+            Currency::Ves,
+            70_000,
+        );
+        assert_eq!((sale.balance_ves, sale.change_ves), (0, 2688));
+    }
+
+    #[test]
+    fn finalize_writes_invoice_and_stock_atomically() {
+        let mut conn = pharmacy();
+        let sale = new_sale(&conn);
+        add_item(&mut conn, &sale.id, "p-a", None, 2, NOW).unwrap();
+        let err = finalize(&mut conn, &sale.id, NOW).unwrap_err();
+        assert!(matches!(err, Error::Validation(ref m) if m.contains("por cobrar")));
+
+        pay(
+            &mut conn,
+            &sale.id,
+            PaymentMethod::Cash,
+            Currency::Ves,
+            100_000,
+        );
+        let done = finalize(&mut conn, &sale.id, NOW).unwrap();
+        assert_eq!(done.status, SaleStatus::Finalized);
+        assert_eq!(done.invoice_number.as_deref(), Some("POS-00000001"));
+        let (total_ves, method): (i64, String) = conn
+            .query_row(
+                "SELECT total_ves, payment_method FROM invoices",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!((total_ves, method.as_str()), (92_800, "cash"));
+        assert_eq!(
+            count(&conn, "SELECT exchange_rate_id FROM invoices"),
+            done.exchange_rate_id.unwrap()
+        );
+        assert_eq!(count(&conn, "SELECT quantity FROM invoice_items"), 200);
+        // Primero el lote que vence antes, aunque se recibió después
+        assert_eq!(
+            count(&conn, "SELECT quantity FROM batches WHERE id = 'a-1'"),
+            1
+        );
+        assert_eq!(
+            count(&conn, "SELECT quantity FROM batches WHERE id = 'a-2'"),
+            3
+        );
+        assert_eq!(
+            count(
+                &conn,
+                "SELECT COUNT(*) FROM invoice_items WHERE batch_id = 'a-2'"
+            ),
+            1
+        );
+        assert_eq!(count(&conn, "SELECT COUNT(*) FROM inventory_movements"), 1);
+
+        // Cerrada: el resumen no cambia con el catálogo ni admite cambios
+        conn.execute("UPDATE products SET sale_price_usd = 9999", [])
+            .unwrap();
+        assert_eq!(load_sale(&conn, &sale.id, NOW).unwrap().total_ves, 92_800);
+        assert!(add_item(&mut conn, &sale.id, "p-b", None, 1, NOW).is_err());
+
+        // Si el inventario bajó entre tanto, no se escribe nada
+        let second = new_sale(&conn);
+        add_item(&mut conn, &second.id, "p-a", None, 4, NOW).unwrap();
+        pay(
+            &mut conn,
+            &second.id,
+            PaymentMethod::Card,
+            Currency::Ves,
+            100_000_000,
+        );
+        conn.execute("UPDATE batches SET quantity = 2 WHERE id = 'a-2'", [])
+            .unwrap();
+        let err = finalize(&mut conn, &second.id, NOW).unwrap_err();
+        assert!(matches!(err, Error::Validation(ref m) if m.contains("insuficiente")));
+        assert_eq!(count(&conn, "SELECT COUNT(*) FROM invoices"), 1);
+        assert_eq!(
+            count(&conn, "SELECT quantity FROM batches WHERE id = 'a-2'"),
+            2
+        );
+        assert_eq!(count(&conn, "SELECT COUNT(*) FROM inventory_movements"), 1);
+        assert_eq!(
+            load_sale(&conn, &second.id, NOW).unwrap().status,
+            SaleStatus::Open
+        );
+    }
+
+    #[test]
+    fn sales_are_stamped_with_the_rate_they_use() {
+        let conn = pharmacy();
+        let mut sale = NewSale {
+            pharmacy_id: "f1".into(),
+            warehouse_id: "w1".into(),
+            user_id: "u1".into(),
+            patient_id: None,
+            exchange_rate: None,
+        };
+        assert!(matches!(create(&conn, &sale, NOW), Err(Error::NotFound(_))));
+
+        let bcv = rates::record(
+            &conn,
+            rates::Currency::Usd,
+            36_520_000,
+            "2026-03-06",
+            rates::RateSource::Bcv,
+            NOW,
+        )
+        .unwrap();
+        let opened = create(&conn, &sale, NOW).unwrap();
+        assert_eq!(opened.exchange_rate, "36.520000");
+        assert_eq!(opened.exchange_rate_id, Some(bcv.id));
+        assert_eq!(opened.exchange_rate_date.as_deref(), Some("2026-03-06"));
+
+        // La misma tasa escrita a mano no duplica el historial; otra distinta
+        // queda como tasa manual del día
+        sale.exchange_rate = Some("36.52".into());
+        assert_eq!(
+            create(&conn, &sale, NOW).unwrap().exchange_rate_id,
+            Some(bcv.id)
+        );
+        sale.exchange_rate = Some("37".into());
+        let manual = create(&conn, &sale, NOW).unwrap();
+        assert_ne!(manual.exchange_rate_id, Some(bcv.id));
+        assert_eq!(manual.exchange_rate_date.as_deref(), Some("2026-03-07"));
+        sale.exchange_rate = Some("0".into());
+        assert!(create(&conn, &sale, NOW).is_err());
+    }
+
+    #[test]
+    fn rejects_unavailable_discounts_and_missing_stock() {
+        let mut conn = pharmacy();
+        conn.execute_batch(
+            "INSERT INTO discounts (id, name, discount_type, value, applicable_to,
+                                    applicable_ids, end_date, days_of_week, created_at, updated_at)
+             VALUES ('old', 'Carnaval', 'percentage', 1000, 'order', '[]', '2026-02-17', '[]', 0, 0),
+                    ('mon', 'Lunes', 'percentage', 1000, 'order', '[]', NULL, '[\"lunes\"]', 0, 0),
+                    ('sat', 'Sábado', 'fixed_amount', 50, 'product', '[\"p-b\"]', NULL,
+                     '[\"Saturday\"]', 0, 0);",
+        )
+        .unwrap();
+        let sale = new_sale(&conn);
+        for id in ["old", "mon"] {
+            let err = apply_discount(&mut conn, &sale.id, id, NOW).unwrap_err();
+            assert!(matches!(err, Error::Validation(_)), "{}", id);
+        }
+        add_item(&mut conn, &sale.id, "p-b", None, 2, NOW).unwrap();
+        let sale = apply_discount(&mut conn, &sale.id, "sat", NOW).unwrap();
+        // Monto fijo por unidad
+        assert_eq!(sale.discount_usd, 100);
+
+        // Un lunes el descuento deja de aplicar aunque siga en la venta
+        let monday = NOW + 2 * 24 * 60 * 60 * 1000;
+        let sale = load_sale(&conn, &sale.id, monday).unwrap();
+        assert_eq!((sale.discount_usd, sale.discounts[0].amount_usd), (0, 0));
+
+        let err = add_item(&mut conn, &sale.id, "p-a", Some("a-1"), 2, NOW).unwrap_err();
+        assert!(matches!(err, Error::Validation(ref m) if m.contains("stock")));
+        let line = sale.lines[0].id;
+        let err = update_item(&mut conn, &sale.id, line, 11, NOW).unwrap_err();
+        assert!(matches!(err, Error::Validation(_)));
+        let sale = update_item(&mut conn, &sale.id, line, 0, NOW).unwrap();
+        assert!(sale.lines.is_empty());
+    }
+}
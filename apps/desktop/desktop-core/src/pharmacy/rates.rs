@@ -0,0 +1,754 @@
+// Tasas de cambio
+// Las farmacias fijan los precios en USD y cobran en VES a la tasa oficial
+// del BCV. Las tasas se piden al servicio `services/bcv-rate` (o a otro con
+// la misma respuesta, según `ratesUrl`) y se guardan con su fecha de
+// vigencia: `exchange_rates` es a la vez caché e historial, y cada venta y
+// factura queda ligada a la fila que usó. Sin conexión se sigue con la
+// última tasa conocida, marcada como desactualizada.
+
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
+use std::time::Duration;
+use tauri::{AppHandle, Manager, Runtime, State};
+
+use super::money::{format_decimal, mul_div, parse_decimal, parse_decimal_rounded, RATE_SCALE};
+use super::{local_date, PharmacyDb};
+use crate::config::AppConfig;
+use crate::error::{Error, Result};
+use crate::http::HttpClient;
+use crate::store::now_millis;
+
+/// Cada cuánto se revisa si toca consultar el servicio
+const REFRESH_INTERVAL: Duration = Duration::from_secs(60 * 60);
+/// Antigüedad de la última consulta a partir de la cual se vuelve a pedir;
+/// el BCV publica una vez por día hábil
+const REFRESH_AFTER_MILLIS: i64 = 4 * 60 * 60 * 1000;
+/// Una tasa que el origen no confirma en este plazo se marca desactualizada
+const STALE_AFTER_MILLIS: i64 = 24 * 60 * 60 * 1000;
+/// Un bolívar por bolívar, en millonésimas
+const UNIT_RATE: i64 = 1_000_000;
+
+/// Moneda de un monto; el BCV publica USD y EUR contra VES
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum Currency {
+    Usd,
+    Eur,
+    Ves,
+}
+
+impl Currency {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Currency::Usd => "USD",
+            Currency::Eur => "EUR",
+            Currency::Ves => "VES",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "USD" => Some(Currency::Usd),
+            "EUR" => Some(Currency::Eur),
+            "VES" => Some(Currency::Ves),
+            _ => None,
+        }
+    }
+}
+
+/// Origen de una tasa
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RateSource {
+    Bcv,
+    Manual,
+}
+
+impl RateSource {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RateSource::Bcv => "bcv",
+            RateSource::Manual => "manual",
+        }
+    }
+
+    fn parse(value: &str) -> Result<Self> {
+        match value {
+            "bcv" => Ok(RateSource::Bcv),
+            "manual" => Ok(RateSource::Manual),
+            other => Err(Error::Storage(format!(
+                "origen de tasa desconocido: {}",
+                other
+            ))),
+        }
+    }
+}
+
+/// Fila del historial de tasas
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExchangeRate {
+    pub id: i64,
+    pub currency: Currency,
+    /// Bolívares por unidad, como decimal (`"36.520000"`)
+    pub rate: String,
+    pub effective_date: String,
+    pub source: RateSource,
+    pub fetched_at: i64,
+    pub checked_at: i64,
+    /// El origen no la confirma desde hace más de un día
+    pub stale: bool,
+    /// `rate` en millonésimas
+    #[serde(skip)]
+    pub value: i64,
+}
+
+/// Monto convertido y las tasas que se usaron
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Conversion {
+    /// Céntimos de `to`
+    pub amount: i64,
+    pub from: Currency,
+    pub to: Currency,
+    pub rates: Vec<ExchangeRate>,
+}
+
+/// Tasa leída del servicio
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct FetchedRate {
+    pub currency: Currency,
+    pub value: i64,
+    pub effective_date: String,
+}
+
+/// Respuesta de `GET /rates`. La tasa se lee del texto JSON sin pasar por
+/// f64; el servicio la manda como número y otros orígenes como texto.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RatesResponse<'a> {
+    #[serde(default)]
+    success: bool,
+    #[serde(default, borrow)]
+    rates: Vec<RateEntry<'a>>,
+    error: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RateEntry<'a> {
+    currency: String,
+    #[serde(borrow)]
+    rate: &'a RawValue,
+    /// Fecha valor, si el origen la publica. `services/bcv-rate` solo manda
+    /// el momento de la consulta, así que rige desde el día en que se pidió.
+    effective_date: Option<String>,
+}
+
+const COLUMNS: &str = "id, currency, rate, effective_date, source, fetched_at, checked_at";
+
+fn from_row(row: &Row, now: i64) -> rusqlite::Result<(ExchangeRate, String, String)> {
+    let value: i64 = row.get(2)?;
+    let checked_at: i64 = row.get(6)?;
+    Ok((
+        ExchangeRate {
+            id: row.get(0)?,
+            currency: Currency::Usd,
+            rate: format_decimal(value, RATE_SCALE),
+            effective_date: row.get(3)?,
+            source: RateSource::Bcv,
+            fetched_at: row.get(5)?,
+            checked_at,
+            stale: now - checked_at > STALE_AFTER_MILLIS,
+            value,
+        },
+        row.get(1)?,
+        row.get(4)?,
+    ))
+}
+
+fn finish((mut rate, currency, source): (ExchangeRate, String, String)) -> Result<ExchangeRate> {
+    rate.currency = Currency::parse(&currency)
+        .ok_or_else(|| Error::Storage(format!("moneda desconocida: {}", currency)))?;
+    rate.source = RateSource::parse(&source)?;
+    Ok(rate)
+}
+
+fn foreign(currency: Currency) -> Result<()> {
+    match currency {
+        Currency::Ves => Err(Error::Validation(
+            "las tasas se expresan en bolívares por USD o EUR".into(),
+        )),
+        _ => Ok(()),
+    }
+}
+
+fn check_date(conn: &Connection, date: &str) -> Result<()> {
+    let valid: bool = conn.query_row("SELECT date(?1) IS ?1", params![date], |row| row.get(0))?;
+    if valid {
+        Ok(())
+    } else {
+        Err(Error::Validation(format!("fecha no válida: {}", date)))
+    }
+}
+
+fn load(conn: &Connection, id: i64, now: i64) -> Result<ExchangeRate> {
+    let row = conn.query_row(
+        &format!("SELECT {} FROM exchange_rates WHERE id = ?1", COLUMNS),
+        params![id],
+        |row| from_row(row, now),
+    )?;
+    finish(row)
+}
+
+/// Guarda una tasa en el historial. Si ya estaba, solo anota que el origen
+/// la confirmó: las filas no cambian porque hay ventas que las referencian.
+pub(crate) fn record(
+    conn: &Connection,
+    currency: Currency,
+    value: i64,
+    effective_date: &str,
+    source: RateSource,
+    now: i64,
+) -> Result<ExchangeRate> {
+    foreign(currency)?;
+    if value <= 0 {
+        return Err(Error::Validation(
+            "la tasa de cambio debe ser positiva".into(),
+        ));
+    }
+    check_date(conn, effective_date)?;
+    let id: i64 = conn.query_row(
+        "INSERT INTO exchange_rates (currency, rate, effective_date, source, fetched_at, checked_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?5)
+         ON CONFLICT (currency, effective_date, source, rate)
+         DO UPDATE SET checked_at = excluded.checked_at
+         RETURNING id",
+        params![currency.as_str(), value, effective_date, source.as_str(), now],
+        |row| row.get(0),
+    )?;
+    load(conn, id, now)
+}
+
+/// Tasa vigente en `today`: la más reciente con fecha valor hasta ese día.
+/// Entre varias del mismo día gana la última que apareció, así una tasa
+/// manual no se pierde porque el servicio vuelva a confirmar la anterior.
+pub(crate) fn current(
+    conn: &Connection,
+    currency: Currency,
+    today: &str,
+    now: i64,
+) -> Result<ExchangeRate> {
+    foreign(currency)?;
+    let row = conn
+        .query_row(
+            &format!(
+                "SELECT {} FROM exchange_rates
+                 WHERE currency = ?1 AND effective_date <= ?2
+                 ORDER BY effective_date DESC, fetched_at DESC, id DESC
+                 LIMIT 1",
+                COLUMNS
+            ),
+            params![currency.as_str(), today],
+            |row| from_row(row, now),
+        )
+        .optional()?
+        .ok_or_else(|| {
+            Error::NotFound(format!(
+                "no hay una tasa de {} registrada; actualícela o ingrésela a mano",
+                currency.as_str()
+            ))
+        })?;
+    finish(row)
+}
+
+/// Fila para una tasa indicada al abrir una venta: la vigente si coincide
+/// con ella y, si no, una nueva tasa manual del día
+pub(crate) fn stamp(
+    conn: &Connection,
+    currency: Currency,
+    value: i64,
+    today: &str,
+    now: i64,
+) -> Result<ExchangeRate> {
+    match current(conn, currency, today, now) {
+        Ok(rate) if rate.value == value => Ok(rate),
+        Ok(_) | Err(Error::NotFound(_)) => {
+            record(conn, currency, value, today, RateSource::Manual, now)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Historial de una moneda entre dos fechas, de la más reciente a la más antigua
+pub(crate) fn history(
+    conn: &Connection,
+    currency: Currency,
+    from: Option<&str>,
+    to: Option<&str>,
+    now: i64,
+) -> Result<Vec<ExchangeRate>> {
+    foreign(currency)?;
+    for date in from.iter().chain(to.iter()) {
+        check_date(conn, date)?;
+    }
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM exchange_rates
+         WHERE currency = ?1
+           AND (?2 IS NULL OR effective_date >= ?2)
+           AND (?3 IS NULL OR effective_date <= ?3)
+         ORDER BY effective_date DESC, fetched_at DESC, id DESC",
+        COLUMNS
+    ))?;
+    let rows = stmt
+        .query_map(params![currency.as_str(), from, to], |row| {
+            from_row(row, now)
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    rows.into_iter().map(finish).collect()
+}
+
+/// Convierte céntimos entre monedas con una sola división redondeada. Sin
+/// tasa se entiende VES.
+fn convert_cents(amount: i64, from: Option<i64>, to: Option<i64>) -> i64 {
+    mul_div(amount, from.unwrap_or(UNIT_RATE), to.unwrap_or(UNIT_RATE))
+}
+
+/// Convierte un monto con las tasas vigentes en `today`
+pub(crate) fn convert(
+    conn: &Connection,
+    amount: i64,
+    from: Currency,
+    to: Currency,
+    today: &str,
+    now: i64,
+) -> Result<Conversion> {
+    let rate = |currency: Currency| -> Result<Option<ExchangeRate>> {
+        match currency {
+            Currency::Ves => Ok(None),
+            _ if from == to => Ok(None),
+            _ => current(conn, currency, today, now).map(Some),
+        }
+    };
+    let from_rate = rate(from)?;
+    let to_rate = rate(to)?;
+    Ok(Conversion {
+        amount: convert_cents(
+            amount,
+            from_rate.as_ref().map(|r| r.value),
+            to_rate.as_ref().map(|r| r.value),
+        ),
+        from,
+        to,
+        rates: from_rate.into_iter().chain(to_rate).collect(),
+    })
+}
+
+/// Lee la respuesta del servicio. Las monedas que no sean USD o EUR se ignoran.
+pub(crate) fn parse_rates(body: &str, today: &str) -> Result<Vec<FetchedRate>> {
+    let response: RatesResponse = serde_json::from_str(body)
+        .map_err(|e| Error::Validation(format!("respuesta de tasas no válida: {}", e)))?;
+    if !response.success {
+        return Err(Error::Validation(format!(
+            "el servicio de tasas no respondió tasas: {}",
+            response.error.as_deref().unwrap_or("sin detalle")
+        )));
+    }
+    let mut rates = Vec::new();
+    for entry in response.rates {
+        let currency = match Currency::parse(&entry.currency.trim().to_ascii_uppercase()) {
+            Some(Currency::Ves) | None => continue,
+            Some(currency) => currency,
+        };
+        let text = match serde_json::from_str::<String>(entry.rate.get()) {
+            // El BCV escribe la coma decimal
+            Ok(text) => text.replace(',', "."),
+            Err(_) => entry.rate.get().to_string(),
+        };
+        let value = parse_decimal_rounded(&text, RATE_SCALE)?;
+        if value <= 0 {
+            return Err(Error::Validation(format!(
+                "tasa de {} no válida: {}",
+                currency.as_str(),
+                text
+            )));
+        }
+        let effective_date = entry
+            .effective_date
+            .map(|date| date.chars().take(10).collect())
+            .unwrap_or_else(|| today.to_string());
+        rates.push(FetchedRate {
+            currency,
+            value,
+            effective_date,
+        });
+    }
+    if rates.is_empty() {
+        return Err(Error::Validation(
+            "el servicio de tasas no devolvió USD ni EUR".into(),
+        ));
+    }
+    Ok(rates)
+}
+
+/// Pide las tasas a `GET {base}/rates`
+pub(crate) async fn fetch(http: &HttpClient, base: &str, today: &str) -> Result<Vec<FetchedRate>> {
+    let url = format!("{}/rates", base.trim_end_matches('/'));
+    let response = http.send(http.client().get(url)).await?;
+    let status = response.status();
+    let body = response.text().await?;
+    if !status.is_success() {
+        return Err(Error::from_response(status, &body));
+    }
+    parse_rates(&body, today)
+}
+
+/// Guarda lo que devolvió el servicio en una sola transacción
+pub(crate) fn save_fetched(
+    conn: &mut Connection,
+    fetched: &[FetchedRate],
+    now: i64,
+) -> Result<Vec<ExchangeRate>> {
+    let tx = conn.transaction()?;
+    let saved = fetched
+        .iter()
+        .map(|rate| {
+            record(
+                &tx,
+                rate.currency,
+                rate.value,
+                &rate.effective_date,
+                RateSource::Bcv,
+                now,
+            )
+        })
+        .collect::<Result<Vec<_>>>()?;
+    tx.commit()?;
+    Ok(saved)
+}
+
+/// Si la última consulta al servicio es más vieja que `REFRESH_AFTER_MILLIS`
+fn needs_refresh(conn: &Connection, now: i64) -> Result<bool> {
+    let last: Option<i64> = conn.query_row(
+        "SELECT MAX(checked_at) FROM exchange_rates WHERE source = 'bcv'",
+        [],
+        |row| row.get(0),
+    )?;
+    Ok(last.is_none_or(|last| now - last >= REFRESH_AFTER_MILLIS))
+}
+
+/// Consulta el servicio configurado y guarda sus tasas
+async fn refresh<R: Runtime>(app_handle: &AppHandle<R>) -> Result<Vec<ExchangeRate>> {
+    let base = app_handle.state::<AppConfig>().rates_url()?;
+    let today = local_date(now_millis());
+    let fetched = fetch(&app_handle.state::<HttpClient>(), &base, &today).await?;
+    app_handle
+        .state::<PharmacyDb>()
+        .with_conn(|conn| save_fetched(conn, &fetched, now_millis()))
+}
+
+/// Mantiene las tasas al día mientras la app está abierta. Los fallos se
+/// ignoran: se reintenta en la próxima vuelta y mientras tanto rige la
+/// última tasa guardada.
+pub(crate) fn spawn_rate_refresh<R: Runtime>(app_handle: AppHandle<R>) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let due = app_handle
+                .state::<PharmacyDb>()
+                .with_conn(|conn| needs_refresh(conn, now_millis()));
+            if due.unwrap_or(false) {
+                let _ = refresh(&app_handle).await;
+            }
+            tokio::time::sleep(REFRESH_INTERVAL).await;
+        }
+    });
+}
+
+// ============================================
+// COMMANDS
+// ============================================
+
+/// Tasa vigente de una moneda (USD por defecto)
+#[tauri::command]
+pub async fn get_exchange_rate(
+    db: State<'_, PharmacyDb>,
+    currency: Option<Currency>,
+) -> Result<ExchangeRate> {
+    let now = now_millis();
+    db.with_conn(|conn| {
+        current(
+            conn,
+            currency.unwrap_or(Currency::Usd),
+            &local_date(now),
+            now,
+        )
+    })
+}
+
+/// Historial de tasas entre dos fechas (`YYYY-MM-DD`), ambas opcionales
+#[tauri::command]
+pub async fn get_exchange_rate_history(
+    db: State<'_, PharmacyDb>,
+    currency: Option<Currency>,
+    from: Option<String>,
+    to: Option<String>,
+) -> Result<Vec<ExchangeRate>> {
+    db.with_conn(|conn| {
+        history(
+            conn,
+            currency.unwrap_or(Currency::Usd),
+            from.as_deref(),
+            to.as_deref(),
+            now_millis(),
+        )
+    })
+}
+
+/// Consulta ahora el servicio de tasas, sin esperar a la próxima vuelta
+#[tauri::command]
+pub async fn refresh_exchange_rates<R: Runtime>(app: AppHandle<R>) -> Result<Vec<ExchangeRate>> {
+    refresh(&app).await
+}
+
+/// Registra una tasa a mano, p. ej. si el servicio no responde. Sin fecha
+/// rige desde hoy.
+#[tauri::command]
+pub async fn set_exchange_rate(
+    db: State<'_, PharmacyDb>,
+    currency: Currency,
+    rate: String,
+    effective_date: Option<String>,
+) -> Result<ExchangeRate> {
+    let now = now_millis();
+    let value = parse_decimal(&rate, RATE_SCALE)?;
+    let date = effective_date.unwrap_or_else(|| local_date(now));
+    db.with_conn(|conn| record(conn, currency, value, &date, RateSource::Manual, now))
+}
+
+/// Convierte céntimos de una moneda a otra con las tasas vigentes
+#[tauri::command]
+pub async fn convert_amount(
+    db: State<'_, PharmacyDb>,
+    amount: i64,
+    from: Currency,
+    to: Currency,
+) -> Result<Conversion> {
+    let now = now_millis();
+    db.with_conn(|conn| convert(conn, amount, from, to, &local_date(now), now))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pharmacy::schema::{migrate, MIGRATIONS};
+    use crate::testing::client;
+    use red_salud_supabase_mock::{MockResponse, MockSupabase};
+
+    /// Sábado 2026-03-07 12:00 en Caracas
+    const NOW: i64 = 1_772_899_200_000;
+    const TODAY: &str = "2026-03-07";
+    const HOUR: i64 = 60 * 60 * 1000;
+
+    fn db() -> Connection {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.pragma_update(None, "foreign_keys", "ON").unwrap();
+        migrate(&mut conn, MIGRATIONS).unwrap();
+        conn
+    }
+
+    #[test]
+    fn reads_rates_without_floats() {
+        let body = r#"{"success": true, "rates": [
+            {"currency": "USD", "rate": 36.52345650, "date": "2026-03-07T15:00:00.000Z"},
+            {"currency": "EUR", "rate": "39,81", "effectiveDate": "2026-03-09"},
+            {"currency": "CNY", "rate": 5.1}
+        ]}"#;
+        let rates = parse_rates(body, TODAY).unwrap();
+        assert_eq!(
+            rates,
+            vec![
+                FetchedRate {
+                    currency: Currency::Usd,
+                    value: 36_523_457,
+                    effective_date: TODAY.into(),
+                },
+                FetchedRate {
+                    currency: Currency::Eur,
+                    value: 39_810_000,
+                    effective_date: "2026-03-09".into(),
+                },
+            ]
+        );
+
+        let err = parse_rates(r#"{"success": false, "error": "BCV caído"}"#, TODAY).unwrap_err();
+        assert!(err.to_string().contains("BCV caído"));
+        assert!(parse_rates(
+            r#"{"success": true, "rates": [{"currency": "USD", "rate": -1}]}"#,
+            TODAY
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn keeps_history_and_picks_the_effective_rate() {
+        let conn = db();
+        let old = record(
+            &conn,
+            Currency::Usd,
+            36_000_000,
+            "2026-03-05",
+            RateSource::Bcv,
+            NOW - 48 * HOUR,
+        )
+        .unwrap();
+        // Publicada hoy con fecha valor del lunes: aún no rige
+        record(
+            &conn,
+            Currency::Usd,
+            37_000_000,
+            "2026-03-09",
+            RateSource::Bcv,
+            NOW,
+        )
+        .unwrap();
+        let rate = current(&conn, Currency::Usd, TODAY, NOW).unwrap();
+        assert_eq!(rate.id, old.id);
+        assert!(rate.stale);
+
+        // Confirmarla no crea otra fila ni cambia su valor
+        let again = record(
+            &conn,
+            Currency::Usd,
+            36_000_000,
+            "2026-03-05",
+            RateSource::Bcv,
+            NOW,
+        )
+        .unwrap();
+        assert_eq!(again.id, old.id);
+        assert_eq!(again.fetched_at, NOW - 48 * HOUR);
+        assert!(!again.stale);
+
+        // Una tasa manual posterior gana aunque el servicio confirme la suya
+        let manual = record(
+            &conn,
+            Currency::Usd,
+            36_500_000,
+            TODAY,
+            RateSource::Manual,
+            NOW + HOUR,
+        )
+        .unwrap();
+        record(
+            &conn,
+            Currency::Usd,
+            36_000_000,
+            "2026-03-05",
+            RateSource::Bcv,
+            NOW + 2 * HOUR,
+        )
+        .unwrap();
+        assert_eq!(
+            current(&conn, Currency::Usd, TODAY, NOW).unwrap().id,
+            manual.id
+        );
+        assert_eq!(
+            current(&conn, Currency::Usd, "2026-03-09", NOW)
+                .unwrap()
+                .rate,
+            "37.000000"
+        );
+
+        let all = history(&conn, Currency::Usd, Some("2026-03-01"), Some(TODAY), NOW).unwrap();
+        assert_eq!(
+            all.iter().map(|r| r.id).collect::<Vec<_>>(),
+            vec![manual.id, old.id]
+        );
+        assert!(matches!(
+            current(&conn, Currency::Eur, TODAY, NOW),
+            Err(Error::NotFound(_))
+        ));
+        assert!(record(
+            &conn,
+            Currency::Usd,
+            1,
+            "2026-02-30",
+            RateSource::Manual,
+            NOW
+        )
+        .is_err());
+        assert!(record(&conn, Currency::Ves, 1, TODAY, RateSource::Manual, NOW).is_err());
+    }
+
+    #[test]
+    fn converts_with_a_single_rounding() {
+        let conn = db();
+        record(
+            &conn,
+            Currency::Usd,
+            36_525_000,
+            TODAY,
+            RateSource::Bcv,
+            NOW,
+        )
+        .unwrap();
+        record(
+            &conn,
+            Currency::Eur,
+            39_810_000,
+            TODAY,
+            RateSource::Bcv,
+            NOW,
+        )
+        .unwrap();
+
+        // 1,00 USD = 36,525 Bs → 36,53
+        let ves = convert(&conn, 100, Currency::Usd, Currency::Ves, TODAY, NOW).unwrap();
+        assert_eq!(ves.amount, 3653);
+        assert_eq!(ves.rates.len(), 1);
+        // 100,00 Bs / 36,525 = 2,7378… USD → 2,74
+        assert_eq!(
+            convert(&conn, 10_000, Currency::Ves, Currency::Usd, TODAY, NOW)
+                .unwrap()
+                .amount,
+            274
+        );
+        // 10,00 USD a EUR: 365,25 / 39,81 = 9,1748… → 9,17
+        let eur = convert(&conn, 1000, Currency::Usd, Currency::Eur, TODAY, NOW).unwrap();
+        assert_eq!(eur.amount, 917);
+        assert_eq!(eur.rates.len(), 2);
+        assert_eq!(
+            convert(&conn, 1000, Currency::Usd, Currency::Usd, TODAY, NOW)
+                .unwrap()
+                .amount,
+            1000
+        );
+    }
+
+    #[tokio::test]
+    async fn fetches_from_the_rates_service() {
+        let mock = MockSupabase::start();
+        mock.on("GET", "/rates")
+            .once(MockResponse::json(
+                200,
+                r#"{"success":true,"rates":[{"currency":"USD","rate":36.52,"date":"2026-03-07T16:00:00.000Z"}]}"#,
+            ))
+            .always(MockResponse::json(
+                503,
+                r#"{"success":false,"error":"No se pudieron obtener las tasas"}"#,
+            ));
+        let http = client(&mock);
+
+        let fetched = fetch(&http, &mock.url(), TODAY).await.unwrap();
+        let mut conn = db();
+        assert!(needs_refresh(&conn, NOW).unwrap());
+        let saved = save_fetched(&mut conn, &fetched, NOW).unwrap();
+        assert_eq!(saved[0].rate, "36.520000");
+        assert!(!needs_refresh(&conn, NOW + HOUR).unwrap());
+        assert!(needs_refresh(&conn, NOW + 4 * HOUR).unwrap());
+
+        let err = fetch(&http, &mock.url(), TODAY).await.unwrap_err();
+        assert!(err.to_string().contains("No se pudieron"));
+    }
+}
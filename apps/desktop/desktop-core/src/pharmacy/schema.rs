@@ -0,0 +1,569 @@
+// Esquema local de la farmacia
+// Réplica en SQLite de las tablas de farmacia de Supabase (`suppliers`,
+// `products`, `batches`, `inventory_movements`, `invoices` e
+// `invoice_items`) para que el punto de venta siga funcionando sin conexión.
+//
+// Las columnas conservan los nombres de Postgres. Los tipos se traducen así:
+// UUID y DATE (`YYYY-MM-DD`) pasan a TEXT, TIMESTAMPTZ a milisegundos desde
+// epoch, BOOLEAN a 0/1, los enums a TEXT con CHECK y los arrays y JSONB a
+// JSON en texto. Cada NUMERIC(p, s) se guarda como INTEGER multiplicado por
+// 10^s para no perder precisión: los montos en céntimos, `iva_rate` en
+// centésimas (16 = 16 %), `exchange_rate` en millonésimas y las cantidades
+// fraccionables en centésimas. Almacenes, usuarios y pacientes no se
+// replican, así que sus ids no llevan clave foránea.
+//
+// Una migración aplicada no se edita nunca: su checksum quedó guardado en
+// `schema_migrations` y la base se niega a abrir si no coincide. Los cambios
+// van en una migración nueva al final de `MIGRATIONS`.
+
+use rusqlite::{params, Connection};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use crate::error::{Error, Result};
+use crate::store::now_millis;
+
+/// Migración del esquema local
+#[derive(Debug, Clone, Copy)]
+pub struct Migration {
+    /// Consecutiva desde 1
+    pub version: u32,
+    pub name: &'static str,
+    pub sql: &'static str,
+}
+
+impl Migration {
+    /// SHA-256 del SQL en hexadecimal
+    pub fn checksum(&self) -> String {
+        ring::digest::digest(&ring::digest::SHA256, self.sql.as_bytes())
+            .as_ref()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+}
+
+/// Migración registrada en la base
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AppliedMigration {
+    pub version: u32,
+    pub name: String,
+    pub checksum: String,
+    /// Milisegundos desde epoch
+    pub applied_at: i64,
+}
+
+/// Versión del esquema local
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SchemaStatus {
+    /// Última migración aplicada (0 con la base vacía)
+    pub version: u32,
+    /// Última migración que conoce esta versión de la app
+    pub latest: u32,
+    pub migrations: Vec<AppliedMigration>,
+}
+
+const MIGRATIONS_TABLE: &str = "
+    CREATE TABLE IF NOT EXISTS schema_migrations (
+        version    INTEGER PRIMARY KEY NOT NULL,
+        name       TEXT NOT NULL,
+        checksum   TEXT NOT NULL,
+        applied_at INTEGER NOT NULL
+    );
+";
+
+const CATALOG: &str = "
+    CREATE TABLE suppliers (
+        id             TEXT PRIMARY KEY NOT NULL,
+        name           TEXT NOT NULL,
+        rif            TEXT UNIQUE,
+        contact_person TEXT,
+        email          TEXT,
+        phone          TEXT,
+        address        TEXT,
+        payment_terms  TEXT,
+        credit_limit   INTEGER NOT NULL DEFAULT 0,
+        balance        INTEGER NOT NULL DEFAULT 0,
+        is_active      INTEGER NOT NULL DEFAULT 1 CHECK (is_active IN (0, 1)),
+        created_at     INTEGER NOT NULL,
+        updated_at     INTEGER NOT NULL
+    );
+
+    CREATE TABLE products (
+        id                    TEXT PRIMARY KEY NOT NULL,
+        sku                   TEXT NOT NULL UNIQUE,
+        barcode               TEXT UNIQUE,
+        name                  TEXT NOT NULL,
+        generic_name          TEXT,
+        active_ingredient     TEXT,
+        description           TEXT,
+        product_type          TEXT NOT NULL DEFAULT 'medicine' CHECK (product_type IN
+                                  ('medicine', 'supply', 'food', 'cosmetic', 'equipment')),
+        category              TEXT NOT NULL DEFAULT 'other' CHECK (category IN
+                                  ('analgesic', 'antibiotic', 'antihypertensive', 'diabetes',
+                                   'cardiovascular', 'vitamins', 'dermatology', 'respiratory',
+                                   'gastrointestinal', 'psychotropic', 'controlled', 'other')),
+        manufacturer          TEXT,
+        brand                 TEXT,
+        cost_price_usd        INTEGER NOT NULL DEFAULT 0,
+        cost_price_ves        INTEGER NOT NULL DEFAULT 0,
+        sale_price_usd        INTEGER NOT NULL DEFAULT 0,
+        sale_price_ves        INTEGER NOT NULL DEFAULT 0,
+        wholesale_price_usd   INTEGER,
+        wholesale_price_ves   INTEGER,
+        iva_rate              INTEGER NOT NULL DEFAULT 16 CHECK (iva_rate BETWEEN 0 AND 100),
+        iva_exempt            INTEGER NOT NULL DEFAULT 0 CHECK (iva_exempt IN (0, 1)),
+        min_stock             INTEGER NOT NULL DEFAULT 10,
+        max_stock             INTEGER NOT NULL DEFAULT 100,
+        reorder_point         INTEGER NOT NULL DEFAULT 20,
+        unit_type             TEXT NOT NULL DEFAULT 'unit' CHECK (unit_type IN
+                                  ('unit', 'box', 'blister', 'pack', 'bottle', 'tube')),
+        units_per_box         INTEGER NOT NULL DEFAULT 1,
+        allow_fractional_sale INTEGER NOT NULL DEFAULT 0 CHECK (allow_fractional_sale IN (0, 1)),
+        requires_prescription INTEGER NOT NULL DEFAULT 0 CHECK (requires_prescription IN (0, 1)),
+        controlled_substance  INTEGER NOT NULL DEFAULT 0 CHECK (controlled_substance IN (0, 1)),
+        psychotropic          INTEGER NOT NULL DEFAULT 0 CHECK (psychotropic IN (0, 1)),
+        refrigerated          INTEGER NOT NULL DEFAULT 0 CHECK (refrigerated IN (0, 1)),
+        image_url             TEXT,
+        tags                  TEXT NOT NULL DEFAULT '[]',
+        created_at            INTEGER NOT NULL,
+        updated_at            INTEGER NOT NULL,
+        created_by            TEXT
+    );
+    CREATE INDEX idx_products_name ON products (name);
+";
+
+const INVENTORY: &str = "
+    CREATE TABLE batches (
+        id                 TEXT PRIMARY KEY NOT NULL,
+        product_id         TEXT NOT NULL REFERENCES products (id) ON DELETE CASCADE,
+        lot_number         TEXT NOT NULL,
+        expiry_date        TEXT NOT NULL CHECK (date(expiry_date) IS expiry_date),
+        manufacturing_date TEXT CHECK (date(manufacturing_date) IS manufacturing_date),
+        warehouse_id       TEXT NOT NULL,
+        location           TEXT,
+        zone               TEXT NOT NULL DEFAULT 'available' CHECK (zone IN
+                               ('available', 'quarantine', 'rejected', 'approved', 'damaged')),
+        -- Sin existencias negativas: el punto de venta descuenta por lote
+        quantity           INTEGER NOT NULL DEFAULT 0 CHECK (quantity >= 0),
+        original_quantity  INTEGER NOT NULL DEFAULT 0,
+        received_at        INTEGER,
+        supplier_id        TEXT REFERENCES suppliers (id),
+        created_at         INTEGER NOT NULL,
+        updated_at         INTEGER NOT NULL,
+        UNIQUE (lot_number, warehouse_id)
+    );
+    CREATE INDEX idx_batches_product_expiry ON batches (product_id, expiry_date);
+
+    CREATE TABLE inventory_movements (
+        id             TEXT PRIMARY KEY NOT NULL,
+        pharmacy_id    TEXT NOT NULL,
+        product_id     TEXT NOT NULL REFERENCES products (id),
+        batch_id       TEXT REFERENCES batches (id),
+        movement_type  TEXT NOT NULL CHECK (movement_type IN
+                           ('entrada', 'salida', 'ajuste', 'devolucion', 'perdida', 'expiracion')),
+        quantity       INTEGER NOT NULL,
+        unit_price_usd INTEGER,
+        document_type  TEXT CHECK (document_type IN
+                           ('factura', 'nota_credito', 'orden_compra', 'ajuste_manual')),
+        document_id    TEXT,
+        notes          TEXT,
+        created_by     TEXT,
+        created_at     INTEGER NOT NULL
+    );
+    CREATE INDEX idx_inventory_movements_product_id ON inventory_movements (product_id);
+    CREATE INDEX idx_inventory_movements_type ON inventory_movements (movement_type);
+    CREATE INDEX idx_inventory_movements_date ON inventory_movements (created_at);
+";
+
+const SALES: &str = "
+    CREATE TABLE invoices (
+        id                    TEXT PRIMARY KEY NOT NULL,
+        invoice_number        TEXT NOT NULL UNIQUE,
+        fiscal_control_number TEXT UNIQUE,
+        patient_id            TEXT,
+        warehouse_id          TEXT NOT NULL,
+        user_id               TEXT NOT NULL,
+        status                TEXT NOT NULL DEFAULT 'draft' CHECK (status IN
+                                  ('draft', 'pending', 'paid', 'cancelled', 'refunded')),
+        subtotal_usd          INTEGER NOT NULL DEFAULT 0,
+        subtotal_ves          INTEGER NOT NULL DEFAULT 0,
+        iva_usd               INTEGER NOT NULL DEFAULT 0,
+        iva_ves               INTEGER NOT NULL DEFAULT 0,
+        total_usd             INTEGER NOT NULL DEFAULT 0,
+        total_ves             INTEGER NOT NULL DEFAULT 0,
+        payment_method        TEXT NOT NULL CHECK (payment_method IN
+                                  ('cash', 'card', 'pago_movil', 'zelle', 'biopago', 'crypto',
+                                   'transfer', 'mixed')),
+        payment_details       TEXT,
+        exchange_rate         INTEGER NOT NULL DEFAULT 1000000,
+        notes                 TEXT,
+        created_at            INTEGER NOT NULL,
+        updated_at            INTEGER NOT NULL
+    );
+    CREATE INDEX idx_invoices_created_at ON invoices (created_at);
+
+    CREATE TABLE invoice_items (
+        id             TEXT PRIMARY KEY NOT NULL,
+        invoice_id     TEXT NOT NULL REFERENCES invoices (id) ON DELETE CASCADE,
+        product_id     TEXT NOT NULL REFERENCES products (id),
+        batch_id       TEXT REFERENCES batches (id),
+        product_name   TEXT NOT NULL,
+        generic_name   TEXT,
+        quantity       INTEGER NOT NULL CHECK (quantity > 0),
+        unit_type      TEXT NOT NULL CHECK (unit_type IN
+                           ('unit', 'box', 'blister', 'pack', 'bottle', 'tube')),
+        unit_price_usd INTEGER NOT NULL,
+        unit_price_ves INTEGER NOT NULL,
+        total_usd      INTEGER NOT NULL,
+        total_ves      INTEGER NOT NULL,
+        iva_rate       INTEGER NOT NULL DEFAULT 16,
+        iva_usd        INTEGER NOT NULL DEFAULT 0,
+        iva_ves        INTEGER NOT NULL DEFAULT 0,
+        created_at     INTEGER NOT NULL
+    );
+    CREATE INDEX idx_invoice_items_invoice_id ON invoice_items (invoice_id);
+";
+
+const PROMOTIONS: &str = "
+    CREATE TABLE discounts (
+        id                      TEXT PRIMARY KEY NOT NULL,
+        name                    TEXT NOT NULL,
+        description             TEXT,
+        -- `value` es un porcentaje (1000 = 10 %) o un monto en céntimos de USD
+        discount_type           TEXT NOT NULL CHECK (discount_type IN ('percentage', 'fixed_amount')),
+        value                   INTEGER NOT NULL CHECK (value >= 0),
+        applicable_to           TEXT NOT NULL CHECK (applicable_to IN
+                                    ('product', 'category', 'brand', 'order', 'customer', 'warehouse')),
+        applicable_ids          TEXT NOT NULL DEFAULT '[]',
+        start_date              TEXT CHECK (date(start_date) IS start_date),
+        end_date                TEXT CHECK (date(end_date) IS end_date),
+        days_of_week            TEXT NOT NULL DEFAULT '[]',
+        start_time              TEXT,
+        end_time                TEXT,
+        min_quantity            INTEGER,
+        max_quantity            INTEGER,
+        max_discount_percentage INTEGER,
+        max_discount_amount_usd INTEGER,
+        max_discount_amount_ves INTEGER,
+        is_active               INTEGER NOT NULL DEFAULT 1 CHECK (is_active IN (0, 1)),
+        created_at              INTEGER NOT NULL,
+        updated_at              INTEGER NOT NULL
+    );
+
+    CREATE TABLE combos (
+        id                  TEXT PRIMARY KEY NOT NULL,
+        name                TEXT NOT NULL,
+        description         TEXT,
+        discount_percentage INTEGER NOT NULL CHECK (discount_percentage BETWEEN 0 AND 100),
+        is_active           INTEGER NOT NULL DEFAULT 1 CHECK (is_active IN (0, 1)),
+        created_at          INTEGER NOT NULL,
+        updated_at          INTEGER NOT NULL
+    );
+
+    CREATE TABLE combo_items (
+        id         TEXT PRIMARY KEY NOT NULL,
+        combo_id   TEXT NOT NULL REFERENCES combos (id) ON DELETE CASCADE,
+        product_id TEXT NOT NULL REFERENCES products (id),
+        quantity   INTEGER NOT NULL DEFAULT 1 CHECK (quantity > 0),
+        created_at INTEGER NOT NULL
+    );
+    CREATE INDEX idx_combo_items_combo_id ON combo_items (combo_id);
+";
+
+/// Ventas en curso del punto de venta; solo existen en la base local
+const POINT_OF_SALE: &str = "
+    CREATE TABLE pos_sales (
+        id            TEXT PRIMARY KEY NOT NULL,
+        pharmacy_id   TEXT NOT NULL,
+        warehouse_id  TEXT NOT NULL,
+        user_id       TEXT NOT NULL,
+        patient_id    TEXT,
+        exchange_rate INTEGER NOT NULL CHECK (exchange_rate > 0),
+        status        TEXT NOT NULL DEFAULT 'open' CHECK (status IN ('open', 'finalized', 'voided')),
+        invoice_id    TEXT REFERENCES invoices (id),
+        -- Resumen congelado al cerrar la venta
+        summary       TEXT,
+        created_at    INTEGER NOT NULL,
+        updated_at    INTEGER NOT NULL
+    );
+    CREATE INDEX idx_pos_sales_status ON pos_sales (status);
+
+    CREATE TABLE pos_sale_items (
+        id         INTEGER PRIMARY KEY AUTOINCREMENT,
+        sale_id    TEXT NOT NULL REFERENCES pos_sales (id) ON DELETE CASCADE,
+        product_id TEXT NOT NULL REFERENCES products (id),
+        batch_id   TEXT REFERENCES batches (id),
+        quantity   INTEGER NOT NULL CHECK (quantity > 0),
+        created_at INTEGER NOT NULL
+    );
+    CREATE INDEX idx_pos_sale_items_sale_id ON pos_sale_items (sale_id);
+
+    CREATE TABLE pos_sale_discounts (
+        sale_id     TEXT NOT NULL REFERENCES pos_sales (id) ON DELETE CASCADE,
+        discount_id TEXT NOT NULL REFERENCES discounts (id),
+        PRIMARY KEY (sale_id, discount_id)
+    );
+
+    CREATE TABLE pos_payments (
+        id         INTEGER PRIMARY KEY AUTOINCREMENT,
+        sale_id    TEXT NOT NULL REFERENCES pos_sales (id) ON DELETE CASCADE,
+        method     TEXT NOT NULL CHECK (method IN
+                       ('cash', 'card', 'pago_movil', 'zelle', 'biopago', 'crypto', 'transfer')),
+        currency   TEXT NOT NULL CHECK (currency IN ('USD', 'VES')),
+        amount     INTEGER NOT NULL CHECK (amount > 0),
+        reference  TEXT,
+        created_at INTEGER NOT NULL
+    );
+    CREATE INDEX idx_pos_payments_sale_id ON pos_payments (sale_id);
+
+    CREATE TABLE pos_sequences (
+        name  TEXT PRIMARY KEY NOT NULL,
+        value INTEGER NOT NULL
+    );
+";
+
+/// Historial de tasas del BCV y manuales; ventas y facturas guardan la que usaron
+const EXCHANGE_RATES: &str = "
+    CREATE TABLE exchange_rates (
+        id             INTEGER PRIMARY KEY AUTOINCREMENT,
+        currency       TEXT NOT NULL CHECK (currency IN ('USD', 'EUR')),
+        -- Bolívares por unidad, en millonésimas
+        rate           INTEGER NOT NULL CHECK (rate > 0),
+        effective_date TEXT NOT NULL CHECK (date(effective_date) IS effective_date),
+        source         TEXT NOT NULL CHECK (source IN ('bcv', 'manual')),
+        -- Primera vez que se vio la tasa y última vez que el origen la confirmó
+        fetched_at     INTEGER NOT NULL,
+        checked_at     INTEGER NOT NULL,
+        UNIQUE (currency, effective_date, source, rate)
+    );
+    CREATE INDEX idx_exchange_rates_currency_date ON exchange_rates (currency, effective_date);
+
+    ALTER TABLE pos_sales ADD COLUMN exchange_rate_id INTEGER REFERENCES exchange_rates (id);
+    ALTER TABLE invoices ADD COLUMN exchange_rate_id INTEGER REFERENCES exchange_rates (id);
+";
+
+/// Impresora fiscal configurada y documentos que emitió
+const FISCAL_PRINTER: &str = "
+    CREATE TABLE fiscal_printer (
+        id         INTEGER PRIMARY KEY CHECK (id = 1),
+        -- `PrinterConnection` en JSON
+        connection TEXT NOT NULL,
+        updated_at INTEGER NOT NULL
+    );
+
+    CREATE TABLE fiscal_documents (
+        id             INTEGER PRIMARY KEY AUTOINCREMENT,
+        document_type  TEXT NOT NULL CHECK (document_type IN
+                           ('invoice', 'credit_note', 'x_report', 'z_report')),
+        invoice_id     TEXT REFERENCES invoices (id),
+        -- Número que asignó la impresora; vacío en los reportes X
+        number         TEXT,
+        printer_serial TEXT NOT NULL,
+        z_number       INTEGER NOT NULL,
+        printed_at     INTEGER NOT NULL
+    );
+    CREATE INDEX idx_fiscal_documents_invoice_id ON fiscal_documents (invoice_id);
+";
+
+/// Migraciones en orden; solo se agregan al final
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "catalogo",
+        sql: CATALOG,
+    },
+    Migration {
+        version: 2,
+        name: "inventario",
+        sql: INVENTORY,
+    },
+    Migration {
+        version: 3,
+        name: "ventas",
+        sql: SALES,
+    },
+    Migration {
+        version: 4,
+        name: "promociones",
+        sql: PROMOTIONS,
+    },
+    Migration {
+        version: 5,
+        name: "punto_de_venta",
+        sql: POINT_OF_SALE,
+    },
+    Migration {
+        version: 6,
+        name: "tasas_de_cambio",
+        sql: EXCHANGE_RATES,
+    },
+    Migration {
+        version: 7,
+        name: "impresora_fiscal",
+        sql: FISCAL_PRINTER,
+    },
+];
+
+/// Verifica los checksums de las migraciones aplicadas y aplica las
+/// pendientes, cada una en su transacción
+pub(crate) fn migrate(conn: &mut Connection, migrations: &[Migration]) -> Result<SchemaStatus> {
+    conn.execute_batch(MIGRATIONS_TABLE)?;
+    let applied = applied(conn)?;
+
+    let known: BTreeMap<u32, &Migration> = migrations.iter().map(|m| (m.version, m)).collect();
+    for record in &applied {
+        let Some(migration) = known.get(&record.version) else {
+            return Err(Error::Storage(format!(
+                "la base de farmacia tiene la migración {} ({}), que esta versión de la app no conoce",
+                record.version, record.name
+            )));
+        };
+        if migration.checksum() != record.checksum {
+            return Err(Error::Storage(format!(
+                "la migración {} ({}) cambió después de aplicarse",
+                record.version, record.name
+            )));
+        }
+    }
+
+    let current = applied.last().map(|m| m.version).unwrap_or(0);
+    for (version, migration) in known.range(..=current) {
+        if !applied.iter().any(|m| m.version == *version) {
+            return Err(Error::Storage(format!(
+                "falta la migración {} ({}) en la base de farmacia",
+                version, migration.name
+            )));
+        }
+    }
+    for migration in known.range(current + 1..).map(|(_, m)| m) {
+        let tx = conn.transaction()?;
+        tx.execute_batch(migration.sql)?;
+        tx.execute(
+            "INSERT INTO schema_migrations (version, name, checksum, applied_at)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![
+                migration.version,
+                migration.name,
+                migration.checksum(),
+                now_millis()
+            ],
+        )?;
+        tx.commit()?;
+    }
+
+    status(conn, migrations)
+}
+
+/// Versión del esquema y migraciones aplicadas
+pub(crate) fn status(conn: &Connection, migrations: &[Migration]) -> Result<SchemaStatus> {
+    let migrations_applied = applied(conn)?;
+    Ok(SchemaStatus {
+        version: migrations_applied.last().map(|m| m.version).unwrap_or(0),
+        latest: migrations.iter().map(|m| m.version).max().unwrap_or(0),
+        migrations: migrations_applied,
+    })
+}
+
+fn applied(conn: &Connection) -> Result<Vec<AppliedMigration>> {
+    let mut stmt = conn.prepare(
+        "SELECT version, name, checksum, applied_at FROM schema_migrations ORDER BY version",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok(AppliedMigration {
+            version: row.get(0)?,
+            name: row.get(1)?,
+            checksum: row.get(2)?,
+            applied_at: row.get(3)?,
+        })
+    })?;
+    Ok(rows.collect::<rusqlite::Result<_>>()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn memory() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.pragma_update(None, "foreign_keys", "ON").unwrap();
+        conn
+    }
+
+    #[test]
+    fn applies_migrations_once_in_order() {
+        let mut conn = memory();
+        let status = migrate(&mut conn, MIGRATIONS).unwrap();
+        let latest = MIGRATIONS.len() as u32;
+        assert_eq!(status.version, latest);
+        assert_eq!(status.latest, latest);
+        let versions: Vec<u32> = status.migrations.iter().map(|m| m.version).collect();
+        assert_eq!(versions, (1..=latest).collect::<Vec<_>>());
+
+        // La segunda apertura no vuelve a ejecutar los CREATE TABLE
+        let again = migrate(&mut conn, MIGRATIONS).unwrap();
+        assert_eq!(again.migrations, status.migrations);
+
+        // Solo las dos primeras; las demás se aplican después
+        let mut partial = memory();
+        assert_eq!(migrate(&mut partial, &MIGRATIONS[..2]).unwrap().version, 2);
+        assert_eq!(migrate(&mut partial, MIGRATIONS).unwrap().version, latest);
+    }
+
+    #[test]
+    fn rejects_edited_or_unknown_migrations() {
+        let mut conn = memory();
+        migrate(&mut conn, MIGRATIONS).unwrap();
+
+        let mut edited = MIGRATIONS.to_vec();
+        edited[1].sql = "CREATE TABLE batches (id TEXT);";
+        let err = migrate(&mut conn, &edited).unwrap_err();
+        assert!(
+            matches!(err, Error::Storage(ref m) if m.contains("inventario")),
+            "{}",
+            err
+        );
+
+        // Una base creada por una versión más nueva de la app
+        let err = migrate(&mut conn, &MIGRATIONS[..2]).unwrap_err();
+        assert!(
+            matches!(err, Error::Storage(ref m) if m.contains("ventas")),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn schema_enforces_local_invariants() {
+        let mut conn = memory();
+        migrate(&mut conn, MIGRATIONS).unwrap();
+        conn.execute(
+            "INSERT INTO products (id, sku, name, created_at, updated_at)
+             VALUES ('p1', 'SKU-1', 'Acetaminofén 500 mg', 0, 0)",
+            [],
+        )
+        .unwrap();
+        let batch = |id: &str, expiry: &str, quantity: i64| {
+            conn.execute(
+                "INSERT INTO batches (id, product_id, lot_number, expiry_date, warehouse_id,
+                                      quantity, created_at, updated_at)
+                 VALUES (?1, 'p1', ?1, ?2, 'w1', ?3, 0, 0)",
+                params![id, expiry, quantity],
+            )
+        };
+        batch("L1", "2027-03-31", 10).unwrap();
+        assert!(batch("L2", "31/03/2027", 10).is_err());
+        assert!(batch("L3", "2027-03-31", -1).is_err());
+
+        // Los movimientos exigen un producto existente
+        let orphan = conn.execute(
+            "INSERT INTO inventory_movements (id, pharmacy_id, product_id, movement_type,
+                                              quantity, created_at)
+             VALUES ('m1', 'f1', 'p2', 'entrada', 100, 0)",
+            [],
+        );
+        assert!(orphan.is_err());
+    }
+}
@@ -0,0 +1,471 @@
+// Perfiles locales por usuario
+// Cada usuario que inicia sesión en el equipo tiene su propio directorio en
+// `app_data_dir/profiles/<user_id>/` con su base offline y sus archivos. Solo
+// el perfil del usuario con sesión está abierto; los demás no se pueden leer.
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Runtime, State};
+
+use crate::error::{Error, Result};
+use crate::offline::app_data_dir;
+use crate::paths::validate_file_name;
+use crate::session::SessionManager;
+use crate::store::{now_millis, OfflineStore};
+use crate::vault;
+
+/// Carpeta de `app_data_dir` con un directorio por usuario
+pub(crate) const PROFILES_DIR: &str = "profiles";
+/// Metadatos del perfil, legibles sin desbloquearlo
+pub(crate) const PROFILE_FILE: &str = "profile.json";
+const DB_FILE: &str = "offline.db";
+/// Datos de versiones sin perfiles que pasan al primer perfil que se abre
+const LEGACY_ENTRIES: &[&str] = &[
+    "offline.db",
+    "offline.db-wal",
+    "offline.db-shm",
+    "offline_data",
+    "offline_data.migrated",
+    "files",
+];
+
+/// Perfil abierto en el almacén
+#[derive(Debug, Clone)]
+pub struct ActiveProfile {
+    pub user_id: String,
+    pub dir: PathBuf,
+}
+
+impl ActiveProfile {
+    pub fn db_path(&self) -> PathBuf {
+        self.dir.join(DB_FILE)
+    }
+}
+
+/// Contenido de `profile.json`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProfileMeta {
+    pub user_id: String,
+    /// Nombre para mostrar en el selector de perfiles
+    #[serde(default)]
+    pub label: Option<String>,
+    /// Milisegundos desde epoch
+    pub created_at: i64,
+    /// Milisegundos desde epoch
+    pub last_used_at: i64,
+}
+
+/// Perfil local tal como lo ve el webview
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProfileInfo {
+    #[serde(flatten)]
+    pub meta: ProfileMeta,
+    pub active: bool,
+    /// Espacio ocupado en disco
+    pub size_bytes: u64,
+}
+
+fn profiles_root(app_dir: &Path) -> PathBuf {
+    app_dir.join(PROFILES_DIR)
+}
+
+fn profile_dir(app_dir: &Path, user_id: &str) -> Result<PathBuf> {
+    Ok(profiles_root(app_dir).join(validate_file_name(user_id)?))
+}
+
+fn read_meta(dir: &Path) -> Option<ProfileMeta> {
+    let text = fs::read_to_string(dir.join(PROFILE_FILE)).ok()?;
+    serde_json::from_str(&text).ok()
+}
+
+fn write_meta(dir: &Path, meta: &ProfileMeta) -> Result<()> {
+    fs::create_dir_all(dir)?;
+    let json = serde_json::to_vec_pretty(meta).map_err(|e| Error::Storage(e.to_string()))?;
+    fs::write(dir.join(PROFILE_FILE), json)?;
+    Ok(())
+}
+
+fn dir_size(path: &Path) -> u64 {
+    let Ok(metadata) = fs::symlink_metadata(path) else {
+        return 0;
+    };
+    if !metadata.is_dir() {
+        return metadata.len();
+    }
+    fs::read_dir(path)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| dir_size(&entry.path()))
+                .sum()
+        })
+        .unwrap_or(0)
+}
+
+/// Mueve al perfil los datos que las versiones anteriores guardaban
+/// directamente en `app_data_dir`
+fn adopt_legacy(app_dir: &Path, dir: &Path) -> Result<()> {
+    fs::create_dir_all(dir)?;
+    for name in LEGACY_ENTRIES {
+        let legacy = app_dir.join(name);
+        if legacy.exists() {
+            fs::rename(&legacy, dir.join(name))?;
+        }
+    }
+    Ok(())
+}
+
+/// Perfil abierto o error si no hay ninguno
+pub(crate) fn require_active(store: &OfflineStore) -> Result<ActiveProfile> {
+    store
+        .active_profile()
+        .ok_or_else(|| Error::auth("no hay un perfil abierto: inicie sesión"))
+}
+
+/// Abre el perfil de `user_id`, creándolo si no existe. Cambiar de perfil
+/// cierra el anterior y deja el almacén bloqueado.
+pub(crate) fn activate(
+    store: &OfflineStore,
+    app_dir: &Path,
+    user_id: &str,
+    label: Option<String>,
+) -> Result<ActiveProfile> {
+    let dir = profile_dir(app_dir, user_id)?;
+    let now = now_millis();
+    let meta = match read_meta(&dir) {
+        Some(meta) => ProfileMeta {
+            label: label.or(meta.label),
+            last_used_at: now,
+            ..meta
+        },
+        None => {
+            if list(store, app_dir)?.is_empty() {
+                adopt_legacy(app_dir, &dir)?;
+            }
+            ProfileMeta {
+                user_id: user_id.to_string(),
+                label,
+                created_at: now,
+                last_used_at: now,
+            }
+        }
+    };
+    write_meta(&dir, &meta)?;
+
+    if let Some(active) = store.active_profile().filter(|p| p.user_id == user_id) {
+        return Ok(active);
+    }
+    let profile = ActiveProfile {
+        user_id: user_id.to_string(),
+        dir,
+    };
+    store.attach(Some(profile.clone()))?;
+    store.migrate_json_dir(&profile.dir.join("offline_data"))?;
+    Ok(profile)
+}
+
+/// Cierra el perfil abierto; el almacén vuelve a una base vacía en memoria
+pub(crate) fn close(store: &OfflineStore) -> Result<()> {
+    if store.active_profile().is_some() {
+        store.attach(None)?;
+    }
+    Ok(())
+}
+
+/// Cierra el perfil abierto si no pertenece al usuario de la sesión
+pub(crate) fn close_unless_owner(store: &OfflineStore, user_id: Option<&str>) -> Result<()> {
+    match store.active_profile() {
+        Some(active) if Some(active.user_id.as_str()) != user_id => store.attach(None),
+        _ => Ok(()),
+    }
+}
+
+/// Perfiles presentes en el equipo, del más reciente al más antiguo
+pub(crate) fn list(store: &OfflineStore, app_dir: &Path) -> Result<Vec<ProfileInfo>> {
+    let root = profiles_root(app_dir);
+    if !root.is_dir() {
+        return Ok(Vec::new());
+    }
+    let active = store.active_profile().map(|p| p.user_id);
+    let mut profiles = Vec::new();
+    for entry in fs::read_dir(root)? {
+        let dir = entry?.path();
+        let Some(meta) = read_meta(&dir) else {
+            continue;
+        };
+        profiles.push(ProfileInfo {
+            active: active.as_deref() == Some(meta.user_id.as_str()),
+            size_bytes: dir_size(&dir),
+            meta,
+        });
+    }
+    profiles.sort_by_key(|profile| std::cmp::Reverse(profile.meta.last_used_at));
+    Ok(profiles)
+}
+
+/// Borra del equipo todos los datos locales de un perfil
+pub(crate) fn wipe(store: &OfflineStore, app_dir: &Path, user_id: &str) -> Result<()> {
+    let dir = profile_dir(app_dir, user_id)?;
+    if !dir.is_dir() {
+        return Err(Error::NotFound(format!("no existe el perfil {}", user_id)));
+    }
+    if store
+        .active_profile()
+        .is_some_and(|active| active.user_id == user_id)
+    {
+        store.attach(None)?;
+    }
+    fs::remove_dir_all(dir)?;
+    Ok(())
+}
+
+/// Quién puede borrar un perfil: el usuario con sesión el suyo; el de otro
+/// usuario solo si no está abierto y se confirmó explícitamente
+pub(crate) fn authorize_wipe(
+    store: &OfflineStore,
+    owner: Option<&str>,
+    user_id: &str,
+    confirm: bool,
+) -> Result<()> {
+    if owner == Some(user_id) {
+        return Ok(());
+    }
+    if store
+        .active_profile()
+        .is_some_and(|active| active.user_id == user_id)
+    {
+        return Err(Error::auth(
+            "no se puede borrar el perfil abierto de otro usuario",
+        ));
+    }
+    if !confirm {
+        return Err(Error::Validation(
+            "borrar el perfil de otro usuario requiere confirmación".into(),
+        ));
+    }
+    Ok(())
+}
+
+/// Rechaza rutas (ya canónicas) dentro de perfiles que no son el abierto
+pub(crate) fn ensure_visible(store: &OfflineStore, app_dir: &Path, path: &Path) -> Result<()> {
+    let Ok(root) = profiles_root(app_dir).canonicalize() else {
+        return Ok(());
+    };
+    if !path.starts_with(&root) {
+        return Ok(());
+    }
+    let own = store
+        .active_profile()
+        .and_then(|active| active.dir.canonicalize().ok())
+        .is_some_and(|dir| path.starts_with(dir));
+    if own {
+        Ok(())
+    } else {
+        Err(Error::Validation(
+            "el archivo pertenece a otro perfil".into(),
+        ))
+    }
+}
+
+// ============================================
+// COMMANDS
+// ============================================
+
+/// Lista los perfiles locales del equipo
+#[tauri::command]
+pub async fn list_profiles<R: Runtime>(
+    app_handle: AppHandle<R>,
+    store: State<'_, OfflineStore>,
+) -> Result<Vec<ProfileInfo>> {
+    list(&store, &app_data_dir(&app_handle)?)
+}
+
+/// Abre el perfil del usuario con sesión (la de `set_session`). Queda
+/// bloqueado hasta llamar a `unlock_storage` con su secreto.
+#[tauri::command]
+pub async fn switch_profile<R: Runtime>(
+    app_handle: AppHandle<R>,
+    store: State<'_, OfflineStore>,
+    session: State<'_, SessionManager>,
+    user_id: String,
+    label: Option<String>,
+) -> Result<ProfileInfo> {
+    if session.status().await.user_id.as_deref() != Some(user_id.as_str()) {
+        return Err(Error::auth(
+            "solo se puede abrir el perfil del usuario con sesión",
+        ));
+    }
+    let app_dir = app_data_dir(&app_handle)?;
+    vault::clear_opened(&app_handle);
+    activate(&store, &app_dir, &user_id, label)?;
+    list(&store, &app_dir)?
+        .into_iter()
+        .find(|profile| profile.active)
+        .ok_or_else(|| Error::Storage("el perfil no quedó abierto".into()))
+}
+
+/// Borra un perfil local y todos sus datos. El de otro usuario solo si no
+/// está abierto y con `confirm`.
+#[tauri::command]
+pub async fn wipe_profile<R: Runtime>(
+    app_handle: AppHandle<R>,
+    store: State<'_, OfflineStore>,
+    session: State<'_, SessionManager>,
+    user_id: String,
+    confirm: Option<bool>,
+) -> Result<()> {
+    let owner = session.status().await.user_id;
+    authorize_wipe(&store, owner.as_deref(), &user_id, confirm.unwrap_or(false))?;
+    wipe(&store, &app_data_dir(&app_handle)?, &user_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_ITERATIONS: u32 = 1_000;
+
+    fn open(store: &OfflineStore, app_dir: &Path, user_id: &str) {
+        let profile = activate(store, app_dir, user_id, None).unwrap();
+        vault::unlock(store, &profile.dir, user_id, TEST_ITERATIONS).unwrap();
+    }
+
+    #[test]
+    fn profiles_do_not_see_each_other() {
+        let app_dir = tempfile::tempdir().unwrap();
+        let store = OfflineStore::open_in_memory().unwrap();
+
+        open(&store, app_dir.path(), "medico-a");
+        store.set("pacientes", "[a]", None).unwrap();
+
+        open(&store, app_dir.path(), "medico-b");
+        assert!(store.keys().unwrap().is_empty());
+        assert_eq!(store.get("pacientes").unwrap(), None);
+
+        // Volver a A exige desbloquear otra vez
+        activate(&store, app_dir.path(), "medico-a", None).unwrap();
+        assert_eq!(store.get("pacientes").unwrap_err().code(), "AUTH");
+        vault::unlock(
+            &store,
+            &require_active(&store).unwrap().dir,
+            "medico-a",
+            TEST_ITERATIONS,
+        )
+        .unwrap();
+        assert_eq!(store.get("pacientes").unwrap().unwrap(), "[a]");
+
+        close(&store).unwrap();
+        assert_eq!(store.keys().unwrap_err().code(), "AUTH");
+        assert_eq!(require_active(&store).unwrap_err().code(), "AUTH");
+    }
+
+    #[test]
+    fn list_and_wipe_profiles() {
+        let app_dir = tempfile::tempdir().unwrap();
+        let store = OfflineStore::open_in_memory().unwrap();
+        open(&store, app_dir.path(), "medico-a");
+        activate(&store, app_dir.path(), "medico-b", Some("Dra. B".into())).unwrap();
+
+        let profiles = list(&store, app_dir.path()).unwrap();
+        assert_eq!(profiles.len(), 2);
+        assert_eq!(profiles[0].meta.user_id, "medico-b");
+        assert!(profiles[0].active && !profiles[1].active);
+        assert_eq!(profiles[0].meta.label.as_deref(), Some("Dra. B"));
+
+        wipe(&store, app_dir.path(), "medico-b").unwrap();
+        assert!(store.active_profile().is_none());
+        assert_eq!(list(&store, app_dir.path()).unwrap().len(), 1);
+        assert_eq!(
+            wipe(&store, app_dir.path(), "medico-b").unwrap_err().code(),
+            "NOT_FOUND"
+        );
+        assert_eq!(
+            activate(&store, app_dir.path(), "../medico-a", None)
+                .unwrap_err()
+                .code(),
+            "VALIDATION"
+        );
+    }
+
+    #[test]
+    fn only_own_or_confirmed_inactive_profiles_are_wiped() {
+        let app_dir = tempfile::tempdir().unwrap();
+        let store = OfflineStore::open_in_memory().unwrap();
+        activate(&store, app_dir.path(), "medico-a", None).unwrap();
+        activate(&store, app_dir.path(), "medico-b", None).unwrap();
+
+        // El perfil propio, abierto o no
+        authorize_wipe(&store, Some("medico-b"), "medico-b", false).unwrap();
+        // El abierto de otro usuario nunca
+        assert_eq!(
+            authorize_wipe(&store, Some("medico-a"), "medico-b", true)
+                .unwrap_err()
+                .code(),
+            "AUTH"
+        );
+        assert_eq!(
+            authorize_wipe(&store, None, "medico-b", true)
+                .unwrap_err()
+                .code(),
+            "AUTH"
+        );
+        // Uno inactivo ajeno solo con confirmación
+        assert_eq!(
+            authorize_wipe(&store, Some("medico-b"), "medico-a", false)
+                .unwrap_err()
+                .code(),
+            "VALIDATION"
+        );
+        authorize_wipe(&store, Some("medico-b"), "medico-a", true).unwrap();
+    }
+
+    #[test]
+    fn first_profile_adopts_shared_data() {
+        let app_dir = tempfile::tempdir().unwrap();
+        let legacy = app_dir.path().join("offline_data");
+        fs::create_dir_all(&legacy).unwrap();
+        fs::write(legacy.join("citas.json"), "[1]").unwrap();
+        fs::create_dir_all(app_dir.path().join("files")).unwrap();
+        fs::write(app_dir.path().join("files/r.pdf"), b"%PDF").unwrap();
+
+        let store = OfflineStore::open_in_memory().unwrap();
+        open(&store, app_dir.path(), "medico-a");
+        assert_eq!(store.get("citas").unwrap().unwrap(), "[1]");
+        let dir = require_active(&store).unwrap().dir;
+        assert!(dir.join("files/r.pdf").is_file());
+        assert!(!app_dir.path().join("files").exists());
+
+        // El segundo perfil empieza vacío
+        open(&store, app_dir.path(), "medico-b");
+        assert!(store.keys().unwrap().is_empty());
+    }
+
+    #[test]
+    fn files_of_other_profiles_are_hidden() {
+        let app_dir = tempfile::tempdir().unwrap();
+        let store = OfflineStore::open_in_memory().unwrap();
+        open(&store, app_dir.path(), "medico-a");
+        let own = require_active(&store).unwrap().dir.join("files");
+        fs::create_dir_all(&own).unwrap();
+        let own = own.canonicalize().unwrap();
+        activate(&store, app_dir.path(), "medico-b", None).unwrap();
+
+        assert_eq!(
+            ensure_visible(&store, app_dir.path(), &own)
+                .unwrap_err()
+                .code(),
+            "VALIDATION"
+        );
+        let other = require_active(&store).unwrap().dir.canonicalize().unwrap();
+        ensure_visible(&store, app_dir.path(), &other).unwrap();
+        ensure_visible(
+            &store,
+            app_dir.path(),
+            &app_dir.path().canonicalize().unwrap(),
+        )
+        .unwrap();
+    }
+}
@@ -0,0 +1,382 @@
+// Constructor de consultas PostgREST
+// Traduce la descripción estructurada que envía el webview (tabla, columnas,
+// filtros, orden, paginación y preferencias) en la ruta `/rest/v1/...` y la
+// cabecera `Prefer`, con la codificación y el entrecomillado que espera
+// PostgREST, en lugar de concatenar cadenas a mano.
+
+use serde::Deserialize;
+use serde_json::Value;
+use url::form_urlencoded;
+
+use crate::error::{Error, Result};
+
+const REST_PREFIX: &str = "/rest/v1/";
+/// Caracteres que obligan a entrecomillar un valor dentro de `in.(...)`
+const RESERVED: &[char] = &[',', '.', ':', '(', ')', '"', '\\', ' '];
+
+/// Consulta de lectura sobre una tabla o vista
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Query {
+    pub table: String,
+    /// Columnas y recursos embebidos (`id,paciente:profiles(nombre)`); por defecto `*`
+    #[serde(default)]
+    pub select: Option<String>,
+    #[serde(default)]
+    pub filters: Vec<Filter>,
+    #[serde(default)]
+    pub order: Vec<Order>,
+    #[serde(default)]
+    pub limit: Option<u64>,
+    #[serde(default)]
+    pub offset: Option<u64>,
+    /// Filas `from..=to`, como `range()` de supabase-js. Excluye `limit`/`offset`.
+    #[serde(default)]
+    pub range: Option<RowRange>,
+    /// Total de filas en `Content-Range` (`Prefer: count=...`)
+    #[serde(default)]
+    pub count: Option<CountMode>,
+}
+
+/// Filtro sobre una columna (`columna=op.valor`)
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Filter {
+    /// Columna, o `recurso.columna` para filtrar un recurso embebido
+    pub column: String,
+    #[serde(flatten)]
+    pub op: FilterOp,
+    /// Niega el filtro (`not.eq.5`)
+    #[serde(default)]
+    pub not: bool,
+}
+
+/// Operador de un filtro con su valor
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "op", content = "value", rename_all = "lowercase")]
+pub enum FilterOp {
+    Eq(Value),
+    Neq(Value),
+    Gt(Value),
+    Gte(Value),
+    Lt(Value),
+    Lte(Value),
+    /// Patrón con `%` o `*` como comodín
+    Like(String),
+    Ilike(String),
+    In(Vec<Value>),
+    /// `null`, `true` o `false`
+    Is(Option<bool>),
+}
+
+/// Orden por una columna
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Order {
+    pub column: String,
+    #[serde(default = "ascending")]
+    pub ascending: bool,
+    #[serde(default)]
+    pub nulls_first: Option<bool>,
+}
+
+fn ascending() -> bool {
+    true
+}
+
+/// Rango de filas, ambos extremos incluidos
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct RowRange {
+    pub from: u64,
+    pub to: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CountMode {
+    Exact,
+    Planned,
+    Estimated,
+}
+
+impl CountMode {
+    fn as_str(self) -> &'static str {
+        match self {
+            CountMode::Exact => "exact",
+            CountMode::Planned => "planned",
+            CountMode::Estimated => "estimated",
+        }
+    }
+}
+
+impl Query {
+    /// Ruta relativa al proyecto: `/rest/v1/tabla?select=...&columna=op.valor`
+    pub fn endpoint(&self) -> Result<String> {
+        if !is_identifier(&self.table) {
+            return Err(Error::Validation(format!(
+                "nombre de tabla no válido: {:?}",
+                self.table
+            )));
+        }
+
+        let mut params = form_urlencoded::Serializer::new(String::new());
+        let select = match self.select.as_deref() {
+            Some(select) => clean_select(select)?,
+            None => "*".to_string(),
+        };
+        params.append_pair("select", &select);
+
+        for filter in &self.filters {
+            validate_column(&filter.column)?;
+            let mut value = filter.op.to_param()?;
+            if filter.not {
+                value.insert_str(0, "not.");
+            }
+            params.append_pair(&filter.column, &value);
+        }
+
+        if !self.order.is_empty() {
+            let order = self
+                .order
+                .iter()
+                .map(Order::to_param)
+                .collect::<Result<Vec<_>>>()?;
+            params.append_pair("order", &order.join(","));
+        }
+
+        let (limit, offset) = match self.range {
+            Some(_) if self.limit.is_some() || self.offset.is_some() => {
+                return Err(Error::Validation(
+                    "range no se puede combinar con limit u offset".into(),
+                ))
+            }
+            Some(RowRange { from, to }) if to < from => {
+                return Err(Error::Validation(format!(
+                    "rango de filas vacío: {}-{}",
+                    from, to
+                )))
+            }
+            Some(RowRange { from, to }) => (Some(to - from + 1), Some(from)),
+            None => (self.limit, self.offset),
+        };
+        if let Some(limit) = limit {
+            params.append_pair("limit", &limit.to_string());
+        }
+        if let Some(offset) = offset.filter(|offset| *offset > 0) {
+            params.append_pair("offset", &offset.to_string());
+        }
+
+        Ok(format!("{}{}?{}", REST_PREFIX, self.table, params.finish()))
+    }
+
+    /// Valor de la cabecera `Prefer`, si la consulta lo necesita
+    pub fn prefer(&self) -> Option<String> {
+        self.count.map(|count| format!("count={}", count.as_str()))
+    }
+}
+
+impl FilterOp {
+    fn to_param(&self) -> Result<String> {
+        Ok(match self {
+            FilterOp::Eq(value) => format!("eq.{}", scalar(value)?),
+            FilterOp::Neq(value) => format!("neq.{}", scalar(value)?),
+            FilterOp::Gt(value) => format!("gt.{}", scalar(value)?),
+            FilterOp::Gte(value) => format!("gte.{}", scalar(value)?),
+            FilterOp::Lt(value) => format!("lt.{}", scalar(value)?),
+            FilterOp::Lte(value) => format!("lte.{}", scalar(value)?),
+            FilterOp::Like(pattern) => format!("like.{}", pattern),
+            FilterOp::Ilike(pattern) => format!("ilike.{}", pattern),
+            FilterOp::In(values) => {
+                let items = values
+                    .iter()
+                    .map(|value| scalar(value).map(|item| quote_list_item(&item)))
+                    .collect::<Result<Vec<_>>>()?;
+                format!("in.({})", items.join(","))
+            }
+            FilterOp::Is(None) => "is.null".into(),
+            FilterOp::Is(Some(value)) => format!("is.{}", value),
+        })
+    }
+}
+
+impl Order {
+    fn to_param(&self) -> Result<String> {
+        validate_column(&self.column)?;
+        let mut param = format!(
+            "{}.{}",
+            self.column,
+            if self.ascending { "asc" } else { "desc" }
+        );
+        match self.nulls_first {
+            Some(true) => param.push_str(".nullsfirst"),
+            Some(false) => param.push_str(".nullslast"),
+            None => {}
+        }
+        Ok(param)
+    }
+}
+
+/// Valor de un filtro como texto. Los nulos se filtran con `is`.
+fn scalar(value: &Value) -> Result<String> {
+    match value {
+        Value::String(text) => Ok(text.clone()),
+        Value::Number(number) => Ok(number.to_string()),
+        Value::Bool(flag) => Ok(flag.to_string()),
+        Value::Null => Err(Error::Validation(
+            "para comparar con null usa el operador is".into(),
+        )),
+        Value::Array(_) | Value::Object(_) => Err(Error::Validation(format!(
+            "valor de filtro no válido: {}",
+            value
+        ))),
+    }
+}
+
+/// Entrecomilla un elemento de `in.(...)` si contiene caracteres reservados
+fn quote_list_item(item: &str) -> String {
+    if item.is_empty() || item.contains(RESERVED) {
+        format!("\"{}\"", item.replace('\\', "\\\\").replace('"', "\\\""))
+    } else {
+        item.to_string()
+    }
+}
+
+pub(crate) fn is_identifier(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= 63
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Columna simple, de un recurso embebido (`citas.estado`) o ruta JSON (`datos->>campo`)
+fn validate_column(column: &str) -> Result<()> {
+    let valid = column
+        .split(['.', '-', '>'])
+        .filter(|part| !part.is_empty())
+        .all(is_identifier)
+        && column.starts_with(|c: char| c.is_ascii_alphanumeric() || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(Error::Validation(format!(
+            "nombre de columna no válido: {:?}",
+            column
+        )))
+    }
+}
+
+/// Quita los espacios fuera de comillas, como hace supabase-js
+fn clean_select(select: &str) -> Result<String> {
+    if select.chars().any(char::is_control) {
+        return Err(Error::Validation(
+            "select contiene caracteres de control".into(),
+        ));
+    }
+    let mut quoted = false;
+    let cleaned: String = select
+        .chars()
+        .filter(|c| {
+            if *c == '"' {
+                quoted = !quoted;
+            }
+            quoted || !c.is_whitespace()
+        })
+        .collect();
+    if cleaned.is_empty() {
+        return Err(Error::Validation("select no puede estar vacío".into()));
+    }
+    Ok(cleaned)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn query(value: Value) -> Query {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn builds_an_encoded_postgrest_path() {
+        let query = query(json!({
+            "table": "appointments",
+            "select": "id, fecha_hora, paciente:profiles!appointments_paciente_id_fkey(nombre_completo)",
+            "filters": [
+                { "column": "medico_id", "op": "eq", "value": "d1" },
+                { "column": "fecha_hora", "op": "gte", "value": "2026-01-01T00:00:00+00:00" },
+                { "column": "status", "op": "in", "value": ["pendiente", "en curso", 3] },
+                { "column": "motivo", "op": "ilike", "value": "%dolor & fiebre%" },
+                { "column": "cancelada", "op": "is", "value": null, "not": true }
+            ],
+            "order": [{ "column": "fecha_hora", "ascending": false, "nullsFirst": false }],
+            "limit": 25,
+            "count": "exact"
+        }));
+        assert_eq!(
+            query.endpoint().unwrap(),
+            "/rest/v1/appointments?\
+             select=id%2Cfecha_hora%2Cpaciente%3Aprofiles%21appointments_paciente_id_fkey%28nombre_completo%29\
+             &medico_id=eq.d1\
+             &fecha_hora=gte.2026-01-01T00%3A00%3A00%2B00%3A00\
+             &status=in.%28pendiente%2C%22en+curso%22%2C3%29\
+             &motivo=ilike.%25dolor+%26+fiebre%25\
+             &cancelada=not.is.null\
+             &order=fecha_hora.desc.nullslast\
+             &limit=25"
+        );
+        assert_eq!(query.prefer().as_deref(), Some("count=exact"));
+    }
+
+    #[test]
+    fn range_maps_to_limit_and_offset() {
+        let page = query(json!({ "table": "patients", "range": { "from": 50, "to": 74 } }));
+        assert_eq!(
+            page.endpoint().unwrap(),
+            "/rest/v1/patients?select=*&limit=25&offset=50"
+        );
+        assert_eq!(page.prefer(), None);
+
+        let both =
+            query(json!({ "table": "patients", "limit": 5, "range": { "from": 0, "to": 9 } }));
+        assert_eq!(both.endpoint().unwrap_err().code(), "VALIDATION");
+        let empty = query(json!({ "table": "patients", "range": { "from": 9, "to": 0 } }));
+        assert_eq!(empty.endpoint().unwrap_err().code(), "VALIDATION");
+    }
+
+    #[test]
+    fn list_items_with_reserved_characters_are_quoted() {
+        assert_eq!(quote_list_item("abc"), "abc");
+        assert_eq!(quote_list_item("a,b"), "\"a,b\"");
+        assert_eq!(quote_list_item("di \"jo\""), "\"di \\\"jo\\\"\"");
+        assert_eq!(quote_list_item(""), "\"\"");
+    }
+
+    #[test]
+    fn rejects_invalid_names_and_values() {
+        let invalid = [
+            json!({ "table": "patients?select=*" }),
+            json!({ "table": "" }),
+            json!({ "table": "patients", "select": "  " }),
+            json!({ "table": "patients", "filters": [{ "column": "id=eq.1&x", "op": "eq", "value": 1 }] }),
+            json!({ "table": "patients", "filters": [{ "column": "id", "op": "eq", "value": null }] }),
+            json!({ "table": "patients", "filters": [{ "column": "id", "op": "in", "value": [[1]] }] }),
+            json!({ "table": "patients", "order": [{ "column": "id.desc,x" }] }),
+        ];
+        for value in invalid {
+            let error = query(value.clone()).endpoint().unwrap_err();
+            assert_eq!(error.code(), "VALIDATION", "{value}");
+        }
+
+        let nested = query(json!({
+            "table": "patients",
+            "filters": [
+                { "column": "citas.estado", "op": "eq", "value": "ok" },
+                { "column": "datos->>grupo", "op": "eq", "value": "O+" }
+            ]
+        }));
+        assert_eq!(
+            nested.endpoint().unwrap(),
+            "/rest/v1/patients?select=*&citas.estado=eq.ok&datos-%3E%3Egrupo=eq.O%2B"
+        );
+    }
+}
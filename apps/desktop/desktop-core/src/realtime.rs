@@ -0,0 +1,1003 @@
+// Puente de Supabase Realtime
+// Mantiene desde Rust la conexión WebSocket con Realtime (canales de Phoenix)
+// y se suscribe a `postgres_changes` de las tablas que pide el webview. Los
+// cambios actualizan la caché offline y se reenvían como eventos de Tauri,
+// aunque la ventana esté oculta. Ante un corte reconecta con backoff.
+
+use futures_util::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager, Runtime, State};
+use tokio::sync::Notify;
+use tokio_tungstenite::tungstenite::Message;
+use url::Url;
+
+use crate::cache::{CacheMeta, CacheUpdated, CACHE_UPDATED_EVENT};
+use crate::config::SupabaseConfig;
+use crate::error::{Error, Result};
+use crate::http::HttpClient;
+use crate::notifications::{self, fill_template, Notification, NotificationCategory};
+use crate::session::{self, is_transient};
+use crate::store::{now_millis, OfflineStore};
+
+/// Evento con cada cambio recibido
+pub const REALTIME_CHANGE_EVENT: &str = "realtime-change";
+/// Evento emitido cuando cambia el estado de la conexión
+pub const REALTIME_STATUS_EVENT: &str = "realtime-status";
+
+/// Versión del protocolo de Phoenix (mensajes como objetos JSON)
+const PROTOCOL_VERSION: &str = "1.0.0";
+/// Intervalo de heartbeat; Realtime cierra el socket tras 60 s sin ninguno
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(25);
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const BASE_RECONNECT_DELAY: Duration = Duration::from_secs(1);
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(60);
+
+/// Suscripción a los cambios de una tabla
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RealtimeSubscription {
+    /// Identificador elegido por el webview; da nombre al canal
+    pub id: String,
+    #[serde(default = "default_schema")]
+    pub schema: String,
+    pub table: String,
+    /// `INSERT`, `UPDATE`, `DELETE` o `*`
+    #[serde(default = "default_event")]
+    pub event: String,
+    /// Filtro de Realtime, por ejemplo `medico_id=eq.123`
+    #[serde(default)]
+    pub filter: Option<String>,
+    /// Clave de la caché (un arreglo de filas) que se actualiza con los cambios
+    #[serde(default)]
+    pub cache_key: Option<String>,
+    /// Columna que identifica las filas en la caché
+    #[serde(default = "default_primary_key")]
+    pub primary_key: String,
+    /// Aviso nativo para las filas nuevas
+    #[serde(default)]
+    pub notify: Option<RealtimeNotify>,
+}
+
+/// Aviso que genera cada `INSERT` de una suscripción
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RealtimeNotify {
+    pub category: NotificationCategory,
+    /// Por defecto el de la categoría
+    #[serde(default)]
+    pub title: Option<String>,
+    /// Plantilla con columnas de la fila, por ejemplo `Receta de {paciente_nombre}`
+    pub body: String,
+}
+
+fn default_schema() -> String {
+    "public".into()
+}
+
+fn default_event() -> String {
+    "*".into()
+}
+
+fn default_primary_key() -> String {
+    "id".into()
+}
+
+impl RealtimeSubscription {
+    fn validate(&self) -> Result<()> {
+        let is_name = |value: &str| {
+            !value.is_empty()
+                && value
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        };
+        if !is_name(&self.id) {
+            return Err(Error::Validation(format!(
+                "id de suscripción no válido: {:?}",
+                self.id
+            )));
+        }
+        if !is_name(&self.schema) || !is_name(&self.table) || !is_name(&self.primary_key) {
+            return Err(Error::Validation(format!(
+                "tabla no válida: {}.{}",
+                self.schema, self.table
+            )));
+        }
+        if !matches!(self.event.as_str(), "*" | "INSERT" | "UPDATE" | "DELETE") {
+            return Err(Error::Validation(format!(
+                "evento no válido: {:?}",
+                self.event
+            )));
+        }
+        if let Some(filter) = &self.filter {
+            if !filter.contains('=') {
+                return Err(Error::Validation(format!("filtro no válido: {:?}", filter)));
+            }
+        }
+        Ok(())
+    }
+
+    fn topic(&self) -> String {
+        format!("realtime:{}", self.id)
+    }
+
+    fn join_payload(&self, access_token: &str) -> Value {
+        let mut change = json!({
+            "event": self.event,
+            "schema": self.schema,
+            "table": self.table,
+        });
+        if let Some(filter) = &self.filter {
+            change["filter"] = json!(filter);
+        }
+        json!({
+            "config": {
+                "broadcast": { "ack": false, "self": false },
+                "presence": { "key": "" },
+                "postgres_changes": [change],
+                "private": false,
+            },
+            "access_token": access_token,
+        })
+    }
+}
+
+/// Cambio de una fila recibido por Realtime
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RealtimeChange {
+    /// Id de la suscripción que lo recibió
+    pub subscription: String,
+    pub schema: String,
+    pub table: String,
+    /// `INSERT`, `UPDATE` o `DELETE`
+    pub event_type: String,
+    pub record: Value,
+    /// En `DELETE` y `UPDATE` trae al menos la clave primaria
+    pub old_record: Value,
+    pub commit_timestamp: Option<String>,
+}
+
+/// Estado de la conexión con Realtime
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RealtimeState {
+    /// Sin suscripciones: no hay conexión abierta
+    #[default]
+    Idle,
+    Connecting,
+    Connected,
+    /// Conexión perdida; se reintenta con backoff
+    Disconnected,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RealtimeStatus {
+    pub state: RealtimeState,
+    /// Suscripciones aceptadas por el servidor en la conexión actual
+    pub joined: Vec<String>,
+    /// Reintentos fallidos desde la última conexión establecida
+    pub attempt: u32,
+    pub last_error: Option<String>,
+    /// Milisegundos desde epoch de la última conexión establecida
+    pub connected_at: Option<i64>,
+    pub subscriptions: Vec<RealtimeSubscription>,
+}
+
+/// Estado compartido del puente
+#[derive(Default)]
+pub struct RealtimeBridge {
+    subscriptions: Mutex<Vec<RealtimeSubscription>>,
+    status: Mutex<RealtimeStatus>,
+    notify: Notify,
+}
+
+impl RealtimeBridge {
+    pub fn subscriptions(&self) -> Vec<RealtimeSubscription> {
+        self.subscriptions.lock().unwrap().clone()
+    }
+
+    pub fn status(&self) -> RealtimeStatus {
+        RealtimeStatus {
+            subscriptions: self.subscriptions(),
+            ..self.status.lock().unwrap().clone()
+        }
+    }
+
+    /// Añade o reemplaza una suscripción
+    pub(crate) fn subscribe(&self, subscription: RealtimeSubscription) {
+        let mut subscriptions = self.subscriptions.lock().unwrap();
+        subscriptions.retain(|s| s.id != subscription.id);
+        subscriptions.push(subscription);
+        drop(subscriptions);
+        self.wake();
+    }
+
+    /// Quita una suscripción; `false` si no existía
+    pub(crate) fn unsubscribe(&self, id: &str) -> bool {
+        let mut subscriptions = self.subscriptions.lock().unwrap();
+        let before = subscriptions.len();
+        subscriptions.retain(|s| s.id != id);
+        let removed = subscriptions.len() != before;
+        drop(subscriptions);
+        self.wake();
+        removed
+    }
+
+    /// Quita todas las suscripciones (al cerrar la sesión)
+    pub(crate) fn clear(&self) {
+        self.subscriptions.lock().unwrap().clear();
+        self.wake();
+    }
+
+    /// Avisa de un cambio de suscripciones o de sesión
+    pub fn wake(&self) {
+        self.notify.notify_one();
+    }
+
+    fn update_status(&self, f: impl FnOnce(&mut RealtimeStatus)) -> RealtimeStatus {
+        f(&mut self.status.lock().unwrap());
+        self.status()
+    }
+}
+
+/// Mensaje del protocolo de Phoenix
+#[derive(Debug, Serialize, Deserialize)]
+struct PhoenixMessage {
+    topic: String,
+    event: String,
+    #[serde(default)]
+    payload: Value,
+    #[serde(default, rename = "ref")]
+    reference: Option<String>,
+    #[serde(default)]
+    join_ref: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ChangePayload {
+    data: ChangeData,
+}
+
+#[derive(Deserialize)]
+struct ChangeData {
+    schema: String,
+    table: String,
+    #[serde(rename = "type")]
+    event_type: String,
+    #[serde(default)]
+    record: Value,
+    #[serde(default)]
+    old_record: Value,
+    #[serde(default)]
+    commit_timestamp: Option<String>,
+}
+
+/// Lo que ocurre en una conexión
+#[derive(Debug)]
+pub(crate) enum BridgeEvent {
+    Connected,
+    Joined(String),
+    JoinFailed { id: String, message: String },
+    Left(String),
+    Change(RealtimeChange),
+}
+
+/// URL del WebSocket de Realtime del proyecto
+fn websocket_url(config: &SupabaseConfig) -> Result<Url> {
+    let mut url = Url::parse(&config.url)
+        .map_err(|e| Error::Validation(format!("URL de Supabase no válida: {}", e)))?;
+    let scheme = match url.scheme() {
+        "https" => "wss",
+        "http" => "ws",
+        other => {
+            return Err(Error::Validation(format!(
+                "esquema no soportado por Realtime: {}",
+                other
+            )))
+        }
+    };
+    url.set_scheme(scheme)
+        .map_err(|_| Error::Validation("URL de Supabase no válida".into()))?;
+    url.path_segments_mut()
+        .map_err(|_| Error::Validation("URL de Supabase no válida".into()))?
+        .pop_if_empty()
+        .extend(["realtime", "v1", "websocket"]);
+    url.query_pairs_mut()
+        .append_pair("apikey", &config.anon_key)
+        .append_pair("vsn", PROTOCOL_VERSION);
+    Ok(url)
+}
+
+fn reconnect_delay(attempt: u32) -> Duration {
+    BASE_RECONNECT_DELAY
+        .saturating_mul(1 << attempt.min(16))
+        .min(MAX_RECONNECT_DELAY)
+}
+
+fn network_error(e: impl std::fmt::Display) -> Error {
+    Error::Network(format!("Realtime: {}", e))
+}
+
+/// Conexión abierta y los canales unidos en ella
+struct Connection<S> {
+    sink: S,
+    next_ref: u64,
+    /// Suscripciones unidas (o en curso), por topic
+    joined: HashMap<String, RealtimeSubscription>,
+    /// `ref` de los `phx_join` sin respuesta, con su topic
+    pending: HashMap<String, String>,
+    /// `ref` del último `phx_join` de cada topic
+    join_refs: HashMap<String, String>,
+}
+
+impl<S> Connection<S>
+where
+    S: futures_util::Sink<Message, Error = tokio_tungstenite::tungstenite::Error> + Unpin,
+{
+    async fn send(&mut self, topic: &str, event: &str, payload: Value) -> Result<String> {
+        self.next_ref += 1;
+        let reference = self.next_ref.to_string();
+        // Los mensajes de un canal llevan el `ref` de su `phx_join`
+        if event == "phx_join" {
+            self.join_refs.insert(topic.to_string(), reference.clone());
+        }
+        let join_ref = self.join_refs.get(topic).cloned();
+        let message = PhoenixMessage {
+            topic: topic.to_string(),
+            event: event.to_string(),
+            payload,
+            reference: Some(reference.clone()),
+            join_ref,
+        };
+        let text = serde_json::to_string(&message).map_err(network_error)?;
+        self.sink
+            .send(Message::Text(text))
+            .await
+            .map_err(network_error)?;
+        Ok(reference)
+    }
+
+    /// Une los canales nuevos y deja los que ya no se piden
+    async fn sync(
+        &mut self,
+        wanted: &[RealtimeSubscription],
+        access_token: &str,
+        on_event: &mut impl FnMut(BridgeEvent),
+    ) -> Result<()> {
+        let stale: Vec<_> = self
+            .joined
+            .iter()
+            .filter(|(_, joined)| !wanted.contains(joined))
+            .map(|(topic, joined)| (topic.clone(), joined.id.clone()))
+            .collect();
+        for (topic, id) in stale {
+            self.joined.remove(&topic);
+            self.pending.retain(|_, pending| *pending != topic);
+            self.send(&topic, "phx_leave", json!({})).await?;
+            self.join_refs.remove(&topic);
+            on_event(BridgeEvent::Left(id));
+        }
+        for subscription in wanted {
+            let topic = subscription.topic();
+            if self.joined.contains_key(&topic) {
+                continue;
+            }
+            self.joined.insert(topic.clone(), subscription.clone());
+            let reference = self
+                .send(&topic, "phx_join", subscription.join_payload(access_token))
+                .await?;
+            self.pending.insert(reference, topic);
+        }
+        Ok(())
+    }
+}
+
+/// Abre una conexión con Realtime, une las suscripciones del puente y
+/// procesa los mensajes. Termina con `Ok` cuando ya no quedan suscripciones
+/// y con error si la conexión se pierde. `access_token` se consulta en cada
+/// heartbeat para renovar el token de los canales.
+pub(crate) async fn run_connection<T, F>(
+    config: &SupabaseConfig,
+    bridge: &RealtimeBridge,
+    heartbeat: Duration,
+    mut access_token: T,
+    mut on_event: impl FnMut(BridgeEvent),
+) -> Result<()>
+where
+    T: FnMut() -> F,
+    F: std::future::Future<Output = Result<String>>,
+{
+    let url = websocket_url(config)?;
+    let mut token = access_token().await?;
+    let (socket, _) = tokio::time::timeout(
+        CONNECT_TIMEOUT,
+        tokio_tungstenite::connect_async(url.as_str()),
+    )
+    .await
+    .map_err(|_| network_error("tiempo de conexión agotado"))?
+    .map_err(network_error)?;
+    let (sink, mut stream) = socket.split();
+    let mut connection = Connection {
+        sink,
+        next_ref: 0,
+        joined: HashMap::new(),
+        pending: HashMap::new(),
+        join_refs: HashMap::new(),
+    };
+    on_event(BridgeEvent::Connected);
+    connection
+        .sync(&bridge.subscriptions(), &token, &mut on_event)
+        .await?;
+
+    let mut ticker = tokio::time::interval_at(tokio::time::Instant::now() + heartbeat, heartbeat);
+    let mut heartbeat_ref: Option<String> = None;
+    loop {
+        tokio::select! {
+            message = stream.next() => {
+                let text = match message {
+                    Some(Ok(Message::Text(text))) => text,
+                    Some(Ok(Message::Close(_))) | None => {
+                        return Err(network_error("el servidor cerró la conexión"))
+                    }
+                    Some(Err(e)) => return Err(network_error(e)),
+                    Some(Ok(_)) => continue,
+                };
+                let Ok(message) = serde_json::from_str::<PhoenixMessage>(&text) else {
+                    continue;
+                };
+                handle_message(&mut connection, &mut heartbeat_ref, message, &mut on_event)?;
+            }
+            _ = ticker.tick() => {
+                // Sin respuesta al heartbeat anterior la conexión está muerta
+                if heartbeat_ref.is_some() {
+                    return Err(network_error("sin respuesta al heartbeat"));
+                }
+                heartbeat_ref = Some(connection.send("phoenix", "heartbeat", json!({})).await?);
+                match access_token().await {
+                    Ok(fresh) if fresh != token => {
+                        token = fresh;
+                        let topics: Vec<_> = connection.joined.keys().cloned().collect();
+                        for topic in topics {
+                            connection
+                                .send(&topic, "access_token", json!({ "access_token": token }))
+                                .await?;
+                        }
+                    }
+                    Ok(_) => {}
+                    // Sin red el token actual sigue sirviendo mientras no venza
+                    Err(e) if is_transient(&e) => {}
+                    Err(e) => return Err(e),
+                }
+            }
+            _ = bridge.notify.notified() => {
+                let wanted = bridge.subscriptions();
+                if wanted.is_empty() {
+                    let _ = connection.sink.send(Message::Close(None)).await;
+                    return Ok(());
+                }
+                connection.sync(&wanted, &token, &mut on_event).await?;
+            }
+        }
+    }
+}
+
+fn handle_message<S>(
+    connection: &mut Connection<S>,
+    heartbeat_ref: &mut Option<String>,
+    message: PhoenixMessage,
+    on_event: &mut impl FnMut(BridgeEvent),
+) -> Result<()> {
+    match message.event.as_str() {
+        "phx_reply" => {
+            let Some(reference) = message.reference else {
+                return Ok(());
+            };
+            if heartbeat_ref.as_ref() == Some(&reference) {
+                *heartbeat_ref = None;
+                return Ok(());
+            }
+            let Some(topic) = connection.pending.remove(&reference) else {
+                return Ok(());
+            };
+            let Some(id) = connection.joined.get(&topic).map(|s| s.id.clone()) else {
+                return Ok(());
+            };
+            if message.payload["status"] == "ok" {
+                on_event(BridgeEvent::Joined(id));
+            } else {
+                connection.joined.remove(&topic);
+                let response = &message.payload["response"];
+                let message = response["reason"]
+                    .as_str()
+                    .or_else(|| response["message"].as_str())
+                    .unwrap_or("el servidor rechazó la suscripción")
+                    .to_string();
+                on_event(BridgeEvent::JoinFailed { id, message });
+            }
+        }
+        "postgres_changes" => {
+            let Some(subscription) = connection.joined.get(&message.topic) else {
+                return Ok(());
+            };
+            let Ok(ChangePayload { data }) = serde_json::from_value(message.payload) else {
+                return Ok(());
+            };
+            on_event(BridgeEvent::Change(RealtimeChange {
+                subscription: subscription.id.clone(),
+                schema: data.schema,
+                table: data.table,
+                event_type: data.event_type,
+                record: data.record,
+                old_record: data.old_record,
+                commit_timestamp: data.commit_timestamp,
+            }));
+        }
+        // Un canal caído se vuelve a unir reconectando
+        "phx_error" | "phx_close" if connection.joined.contains_key(&message.topic) => {
+            return Err(network_error(format!(
+                "el canal {} se cerró",
+                message.topic
+            )));
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Aplica un cambio a la caché de la suscripción, si es un arreglo de filas
+/// ya descargado. Las filas existentes se fusionan para conservar las
+/// columnas embebidas que Realtime no envía.
+pub(crate) fn apply_to_cache(
+    store: &OfflineStore,
+    subscription: &RealtimeSubscription,
+    change: &RealtimeChange,
+) -> Result<Option<CacheUpdated>> {
+    let Some(cache_key) = &subscription.cache_key else {
+        return Ok(None);
+    };
+    let Some(entry) = store.get_entry(cache_key)? else {
+        return Ok(None);
+    };
+    let Ok(Value::Array(mut rows)) = serde_json::from_str::<Value>(&entry.value) else {
+        return Ok(None);
+    };
+    let key = subscription.primary_key.as_str();
+    match change.event_type.as_str() {
+        "INSERT" | "UPDATE" => {
+            let Some(id) = change.record.get(key) else {
+                return Ok(None);
+            };
+            match rows.iter_mut().find(|row| row.get(key) == Some(id)) {
+                Some(Value::Object(row)) => {
+                    if let Value::Object(record) = &change.record {
+                        row.extend(record.clone());
+                    }
+                }
+                Some(row) => *row = change.record.clone(),
+                None => rows.push(change.record.clone()),
+            }
+        }
+        "DELETE" => {
+            let Some(id) = change.old_record.get(key) else {
+                return Ok(None);
+            };
+            rows.retain(|row| row.get(key) != Some(id));
+        }
+        _ => return Ok(None),
+    }
+
+    // El ETag ya no describe el contenido: el próximo refresco trae todo
+    let mut meta = CacheMeta::from_entry(&entry);
+    meta.etag = None;
+    store.set(
+        cache_key,
+        &Value::Array(rows).to_string(),
+        Some(&meta.to_value()),
+    )?;
+    Ok(Some(CacheUpdated {
+        cache_key: cache_key.clone(),
+        endpoint: meta.endpoint.unwrap_or_default(),
+        fetched_at: meta.fetched_at,
+    }))
+}
+
+/// Aviso de una fila nueva, si la suscripción lo pide
+fn notification_for(
+    subscription: &RealtimeSubscription,
+    change: &RealtimeChange,
+) -> Option<Notification> {
+    let notify = subscription.notify.as_ref()?;
+    if change.event_type != "INSERT" {
+        return None;
+    }
+    let id = change.record.get(&subscription.primary_key)?;
+    let id = id
+        .as_str()
+        .map(str::to_string)
+        .unwrap_or_else(|| id.to_string());
+    let mut notification =
+        Notification::new(notify.category, fill_template(&notify.body, &change.record))
+            .dedup_key(format!("{}:{}", change.table, id));
+    if let Some(title) = &notify.title {
+        notification.title = fill_template(title, &change.record);
+    }
+    notification.data = json!({
+        "subscription": subscription.id,
+        "table": change.table,
+        "id": id,
+    });
+    Some(notification)
+}
+
+fn emit_status<R: Runtime>(app_handle: &AppHandle<R>, status: RealtimeStatus) {
+    let _ = app_handle.emit(REALTIME_STATUS_EVENT, status);
+}
+
+fn handle_event<R: Runtime>(app_handle: &AppHandle<R>, event: BridgeEvent) {
+    let bridge = app_handle.state::<RealtimeBridge>();
+    match event {
+        BridgeEvent::Connected => emit_status(
+            app_handle,
+            bridge.update_status(|status| {
+                status.state = RealtimeState::Connected;
+                status.joined.clear();
+                status.attempt = 0;
+                status.last_error = None;
+                status.connected_at = Some(now_millis());
+            }),
+        ),
+        BridgeEvent::Joined(id) => emit_status(
+            app_handle,
+            bridge.update_status(|status| status.joined.push(id)),
+        ),
+        BridgeEvent::JoinFailed { id, message } => emit_status(
+            app_handle,
+            bridge.update_status(|status| {
+                status.joined.retain(|joined| *joined != id);
+                status.last_error = Some(format!("{}: {}", id, message));
+            }),
+        ),
+        BridgeEvent::Left(id) => emit_status(
+            app_handle,
+            bridge.update_status(|status| status.joined.retain(|joined| *joined != id)),
+        ),
+        BridgeEvent::Change(change) => {
+            let subscription = bridge
+                .subscriptions()
+                .into_iter()
+                .find(|s| s.id == change.subscription);
+            if let Some(subscription) = subscription {
+                let store = app_handle.state::<OfflineStore>();
+                if let Ok(Some(updated)) = apply_to_cache(&store, &subscription, &change) {
+                    let _ = app_handle.emit(CACHE_UPDATED_EVENT, updated);
+                }
+                if let Some(notification) = notification_for(&subscription, &change) {
+                    let _ = notifications::notify(app_handle, notification);
+                }
+            }
+            let _ = app_handle.emit(REALTIME_CHANGE_EVENT, change);
+        }
+    }
+}
+
+/// Arranca el puente en segundo plano. Solo conecta mientras haya
+/// suscripciones; tras un corte reintenta con backoff exponencial.
+pub(crate) fn spawn_bridge<R: Runtime>(app_handle: AppHandle<R>) {
+    tauri::async_runtime::spawn(async move {
+        let mut attempt = 0;
+        loop {
+            let bridge = app_handle.state::<RealtimeBridge>();
+            if bridge.subscriptions().is_empty() {
+                emit_status(
+                    &app_handle,
+                    bridge.update_status(|status| {
+                        *status = RealtimeStatus::default();
+                    }),
+                );
+                attempt = 0;
+                bridge.notify.notified().await;
+                continue;
+            }
+
+            emit_status(
+                &app_handle,
+                bridge.update_status(|status| status.state = RealtimeState::Connecting),
+            );
+            let mut connected = false;
+            let result = match app_handle.state::<HttpClient>().supabase() {
+                Ok(config) => {
+                    run_connection(
+                        &config,
+                        &bridge,
+                        HEARTBEAT_INTERVAL,
+                        || session::access_token(&app_handle),
+                        |event| {
+                            connected |= matches!(event, BridgeEvent::Connected);
+                            handle_event(&app_handle, event);
+                        },
+                    )
+                    .await
+                }
+                Err(e) => Err(e),
+            };
+            let Err(e) = result else {
+                continue;
+            };
+
+            if connected {
+                attempt = 0;
+            }
+            let retry = is_transient(&e);
+            emit_status(
+                &app_handle,
+                bridge.update_status(|status| {
+                    status.state = RealtimeState::Disconnected;
+                    status.joined.clear();
+                    status.attempt = attempt + 1;
+                    status.last_error = Some(e.to_string());
+                }),
+            );
+            // Sin sesión o sin configuración se espera a que cambien
+            if retry {
+                tokio::select! {
+                    _ = bridge.notify.notified() => {}
+                    _ = tokio::time::sleep(reconnect_delay(attempt)) => {}
+                }
+                attempt += 1;
+            } else {
+                bridge.notify.notified().await;
+            }
+        }
+    });
+}
+
+// ============================================
+// COMMANDS
+// ============================================
+
+/// Se suscribe a los cambios de una tabla. Reemplaza la suscripción con el
+/// mismo `id`. Los cambios llegan en `realtime-change` y, si hay
+/// `cache_key`, actualizan esa entrada de la caché.
+#[tauri::command]
+pub async fn realtime_subscribe(
+    bridge: State<'_, RealtimeBridge>,
+    subscription: RealtimeSubscription,
+) -> Result<()> {
+    subscription.validate()?;
+    bridge.subscribe(subscription);
+    Ok(())
+}
+
+/// Cancela una suscripción; sin suscripciones se cierra la conexión
+#[tauri::command]
+pub async fn realtime_unsubscribe(bridge: State<'_, RealtimeBridge>, id: String) -> Result<()> {
+    if bridge.unsubscribe(&id) {
+        Ok(())
+    } else {
+        Err(Error::NotFound(format!("no existe la suscripción {}", id)))
+    }
+}
+
+/// Estado de la conexión con Realtime y suscripciones activas
+#[tauri::command]
+pub async fn get_realtime_status(bridge: State<'_, RealtimeBridge>) -> Result<RealtimeStatus> {
+    Ok(bridge.status())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::unlocked_store;
+    use red_salud_supabase_mock::{MockRealtime, ANON_KEY};
+    use std::cell::RefCell;
+
+    const WAIT: Duration = Duration::from_secs(3);
+
+    fn config(mock: &MockRealtime) -> SupabaseConfig {
+        SupabaseConfig {
+            url: mock.url(),
+            anon_key: ANON_KEY.into(),
+        }
+    }
+
+    fn appointments() -> RealtimeSubscription {
+        RealtimeSubscription {
+            id: "citas".into(),
+            schema: default_schema(),
+            table: "appointments".into(),
+            event: default_event(),
+            filter: Some("medico_id=eq.m1".into()),
+            cache_key: Some("citas".into()),
+            primary_key: default_primary_key(),
+            notify: None,
+        }
+    }
+
+    #[test]
+    fn builds_the_websocket_url_and_backs_off() {
+        let config = SupabaseConfig {
+            url: "https://x.supabase.co".into(),
+            anon_key: "k".into(),
+        };
+        assert_eq!(
+            websocket_url(&config).unwrap().as_str(),
+            "wss://x.supabase.co/realtime/v1/websocket?apikey=k&vsn=1.0.0"
+        );
+        assert_eq!(reconnect_delay(0), Duration::from_secs(1));
+        assert_eq!(reconnect_delay(3), Duration::from_secs(8));
+        assert_eq!(reconnect_delay(40), MAX_RECONNECT_DELAY);
+
+        let mut invalid = appointments();
+        invalid.event = "TRUNCATE".into();
+        assert!(invalid.validate().is_err());
+        invalid = appointments();
+        invalid.id = "citas:medico".into();
+        assert!(invalid.validate().is_err());
+    }
+
+    #[tokio::test]
+    async fn joins_forwards_changes_and_updates_the_cache() {
+        let mock = MockRealtime::start();
+        let config = config(&mock);
+        let (store, _root) = unlocked_store();
+        let cached = json!([
+            { "id": 1, "status": "pendiente", "patient": { "name": "Ana" } },
+            { "id": 2, "status": "pendiente" },
+        ]);
+        let meta = CacheMeta {
+            fetched_at: 10,
+            etag: Some("\"v1\"".into()),
+            endpoint: Some("/rest/v1/appointments".into()),
+            ..Default::default()
+        };
+        store
+            .set("citas", &cached.to_string(), Some(&meta.to_value()))
+            .unwrap();
+
+        let bridge = RealtimeBridge::default();
+        bridge.subscribe(appointments());
+        let changes = RefCell::new(Vec::new());
+        let connection = run_connection(
+            &config,
+            &bridge,
+            HEARTBEAT_INTERVAL,
+            || async { Ok("jwt".to_string()) },
+            |event| {
+                if let BridgeEvent::Change(change) = event {
+                    let subscription = appointments();
+                    apply_to_cache(&store, &subscription, &change).unwrap();
+                    changes.borrow_mut().push(change);
+                }
+            },
+        );
+        let driver = async {
+            assert!(
+                mock.wait_until(WAIT, |m| !m.received("phx_join").is_empty())
+                    .await
+            );
+            let join = &mock.received("phx_join")[0];
+            assert_eq!(join["topic"], "realtime:citas");
+            assert_eq!(join["payload"]["access_token"], "jwt");
+            assert_eq!(
+                join["payload"]["config"]["postgres_changes"][0],
+                json!({ "event": "*", "schema": "public", "table": "appointments", "filter": "medico_id=eq.m1" })
+            );
+            assert!(mock.connections()[0].contains("vsn=1.0.0"));
+
+            mock.postgres_change(
+                "realtime:citas",
+                "UPDATE",
+                "appointments",
+                json!({ "id": 1, "status": "confirmada" }),
+                json!({ "id": 1 }),
+            );
+            mock.postgres_change(
+                "realtime:citas",
+                "DELETE",
+                "appointments",
+                json!({}),
+                json!({ "id": 2 }),
+            );
+            mock.postgres_change(
+                "realtime:citas",
+                "INSERT",
+                "appointments",
+                json!({ "id": 3, "status": "pendiente" }),
+                json!({}),
+            );
+            // De otro canal: se ignora
+            mock.postgres_change(
+                "realtime:otro",
+                "INSERT",
+                "appointments",
+                json!({ "id": 9 }),
+                json!({}),
+            );
+            assert!(mock.wait_until(WAIT, |_| changes.borrow().len() == 3).await);
+
+            bridge.unsubscribe("citas");
+        };
+        let (result, _) = tokio::join!(connection, driver);
+        result.unwrap();
+
+        let changes = changes.into_inner();
+        assert_eq!(changes[0].event_type, "UPDATE");
+        assert_eq!(changes[0].subscription, "citas");
+        let entry = store.get_entry("citas").unwrap().unwrap();
+        let rows: Value = serde_json::from_str(&entry.value).unwrap();
+        assert_eq!(
+            rows,
+            json!([
+                { "id": 1, "status": "confirmada", "patient": { "name": "Ana" } },
+                { "id": 3, "status": "pendiente" },
+            ])
+        );
+        let meta = CacheMeta::from_entry(&entry);
+        assert_eq!(meta.etag, None);
+        assert_eq!(meta.fetched_at, 10);
+    }
+
+    #[tokio::test]
+    async fn drops_are_errors_and_reconnecting_rejoins_with_fresh_tokens() {
+        let mock = MockRealtime::start();
+        let config = config(&mock);
+        let bridge = RealtimeBridge::default();
+        bridge.subscribe(appointments());
+        let tokens = RefCell::new(vec!["jwt-2", "jwt-1"]);
+        let token = || {
+            let token = tokens.borrow_mut().pop().unwrap_or("jwt-3").to_string();
+            async move { Ok(token) }
+        };
+
+        let events = RefCell::new(Vec::new());
+        let first = run_connection(
+            &config,
+            &bridge,
+            Duration::from_millis(50),
+            token,
+            |event| events.borrow_mut().push(format!("{:?}", event)),
+        );
+        let driver = async {
+            // El heartbeat renueva el token de los canales unidos
+            assert!(
+                mock.wait_until(WAIT, |m| !m.received("access_token").is_empty())
+                    .await
+            );
+            assert!(!mock.received("heartbeat").is_empty());
+            mock.drop_connections();
+        };
+        let (result, _) = tokio::join!(first, driver);
+        assert_eq!(result.unwrap_err().code(), "NETWORK");
+        let update = &mock.received("access_token")[0];
+        assert_eq!(update["topic"], "realtime:citas");
+        assert_eq!(update["payload"]["access_token"], "jwt-2");
+        assert!(events.borrow().iter().any(|e| e == "Joined(\"citas\")"));
+
+        // La reconexión vuelve a unir la suscripción con el token vigente
+        mock.reject_join("realtime:citas");
+        let second = run_connection(&config, &bridge, HEARTBEAT_INTERVAL, token, |event| {
+            events.borrow_mut().push(format!("{:?}", event))
+        });
+        let driver = async {
+            assert!(
+                mock.wait_until(WAIT, |_| events
+                    .borrow()
+                    .iter()
+                    .any(|e| e.starts_with("JoinFailed")))
+                    .await
+            );
+            bridge.clear();
+        };
+        let (result, _) = tokio::join!(second, driver);
+        result.unwrap();
+        assert_eq!(mock.connections().len(), 2);
+        let joins = mock.received("phx_join");
+        assert_eq!(joins.len(), 2);
+        assert_eq!(joins[1]["payload"]["access_token"], "jwt-3");
+    }
+}
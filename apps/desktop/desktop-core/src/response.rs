@@ -0,0 +1,115 @@
+// Respuesta estructurada de los comandos proxy de Supabase
+// Conserva el estado HTTP y las cabeceras que necesita el frontend (paginación,
+// ETag, preferencias aplicadas) y convierte los estados de error en `Error`.
+
+use reqwest::header::{HeaderMap, CONTENT_RANGE, ETAG};
+use reqwest::StatusCode;
+use serde::Serialize;
+
+use crate::error::{Error, Result};
+
+const PREFERENCE_APPLIED: &str = "preference-applied";
+
+/// Respuesta de Supabase tal como la recibe el webview
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SupabaseResponse {
+    pub status: u16,
+    /// `Content-Range` de PostgREST (`0-24/120` con `Prefer: count=exact`)
+    pub content_range: Option<String>,
+    pub etag: Option<String>,
+    pub preference_applied: Option<String>,
+    pub body: String,
+}
+
+impl SupabaseResponse {
+    /// Lee estado, cabeceras y cuerpo de una respuesta
+    pub(crate) async fn read(response: reqwest::Response) -> Result<Self> {
+        let status = response.status().as_u16();
+        let headers = response.headers();
+        let content_range = header(headers, CONTENT_RANGE.as_str());
+        let etag = header(headers, ETAG.as_str());
+        let preference_applied = header(headers, PREFERENCE_APPLIED);
+        Ok(Self {
+            status,
+            content_range,
+            etag,
+            preference_applied,
+            body: response.text().await?,
+        })
+    }
+
+    /// Respuesta 200 generada localmente (caché, resolución de conflictos)
+    pub(crate) fn ok(body: String) -> Self {
+        Self {
+            status: StatusCode::OK.as_u16(),
+            content_range: None,
+            etag: None,
+            preference_applied: None,
+            body,
+        }
+    }
+
+    pub fn status_code(&self) -> StatusCode {
+        StatusCode::from_u16(self.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.status_code().is_success()
+    }
+
+    /// Devuelve la respuesta si es 2xx; si no, el error tipado correspondiente
+    pub fn into_result(self) -> Result<Self> {
+        if self.is_success() {
+            Ok(self)
+        } else {
+            Err(Error::from_response(self.status_code(), &self.body))
+        }
+    }
+}
+
+fn header(headers: &HeaderMap, name: &str) -> Option<String> {
+    headers
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(status: u16, body: &str) -> SupabaseResponse {
+        SupabaseResponse {
+            status,
+            ..SupabaseResponse::ok(body.into())
+        }
+    }
+
+    #[test]
+    fn success_passes_through() {
+        let ok = response(206, "[]").into_result().unwrap();
+        assert_eq!(ok.status, 206);
+    }
+
+    #[test]
+    fn error_statuses_become_typed_errors() {
+        let postgrest = r#"{"code":"42501","message":"permission denied for table patients"}"#;
+        let err = response(403, postgrest).into_result().unwrap_err();
+        assert_eq!((err.code(), err.status()), ("HTTP", Some(403)));
+        assert!(err.to_string().contains("permission denied"));
+
+        let gotrue = r#"{"error":"invalid_grant","error_description":"Invalid Refresh Token"}"#;
+        let err = response(401, gotrue).into_result().unwrap_err();
+        assert_eq!((err.code(), err.status()), ("AUTH", Some(401)));
+        assert_eq!(err.to_string(), "Invalid Refresh Token");
+
+        let err = response(409, "duplicate").into_result().unwrap_err();
+        assert_eq!((err.code(), err.status()), ("HTTP", Some(409)));
+        assert_eq!(err.to_string(), "HTTP 409: duplicate");
+
+        let err = response(404, "").into_result().unwrap_err();
+        assert_eq!(err.code(), "NOT_FOUND");
+        assert_eq!(err.to_string(), "Not Found");
+    }
+}
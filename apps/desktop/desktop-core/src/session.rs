@@ -0,0 +1,396 @@
+// Sesión de Supabase en Rust
+// El webview entrega el par de tokens tras el login; a partir de ahí el plugin
+// los guarda en memoria, refresca el token de acceso contra GoTrue antes de
+// que venza y lo inyecta en las llamadas al proxy.
+
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager, Runtime, State};
+use tokio::sync::{Mutex, MutexGuard, Notify};
+
+use crate::error::{Error, Result};
+use crate::http::HttpClient;
+use crate::outbox::Outbox;
+use crate::profiles;
+use crate::realtime::RealtimeBridge;
+use crate::response::SupabaseResponse;
+use crate::store::{now_millis, OfflineStore};
+
+/// Evento emitido cuando la sesión no se pudo refrescar y hay que volver a iniciarla
+pub const SESSION_EXPIRED_EVENT: &str = "session-expired";
+/// Evento con los tokens nuevos tras un refresco, para que el cliente JS no
+/// reutilice un refresh token ya rotado
+pub const SESSION_REFRESHED_EVENT: &str = "session-refreshed";
+
+/// Segundos antes del vencimiento en que se refresca el token
+const REFRESH_MARGIN_SECS: i64 = 60;
+/// Espera antes de reintentar un refresco que falló por red
+const RETRY_WAIT: Duration = Duration::from_secs(30);
+/// Espera máxima del refresco en segundo plano sin sesión
+const IDLE_WAIT: Duration = Duration::from_secs(300);
+
+/// Par de tokens de la sesión
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Session {
+    pub access_token: String,
+    pub refresh_token: String,
+    /// Segundos desde epoch en que vence `access_token`
+    pub expires_at: i64,
+    #[serde(default)]
+    pub user_id: Option<String>,
+}
+
+impl Session {
+    fn needs_refresh(&self, now_secs: i64) -> bool {
+        self.expires_at - REFRESH_MARGIN_SECS <= now_secs
+    }
+
+    fn is_expired(&self, now_secs: i64) -> bool {
+        self.expires_at <= now_secs
+    }
+
+    /// Construye la sesión a partir de la respuesta de `/token` de GoTrue
+    fn from_token_response(json: &serde_json::Value, now_secs: i64) -> Result<Self> {
+        let text = |field: &str| json.get(field).and_then(|v| v.as_str()).map(str::to_string);
+        let (Some(access_token), Some(refresh_token)) =
+            (text("access_token"), text("refresh_token"))
+        else {
+            return Err(Error::auth("respuesta de refresco sin tokens"));
+        };
+        let expires_at = json
+            .get("expires_at")
+            .and_then(|v| v.as_i64())
+            .or_else(|| {
+                json.get("expires_in")
+                    .and_then(|v| v.as_i64())
+                    .map(|secs| now_secs + secs)
+            })
+            .unwrap_or(now_secs);
+        let user_id = json
+            .get("user")
+            .and_then(|user| user.get("id"))
+            .and_then(|id| id.as_str())
+            .map(str::to_string);
+        Ok(Self {
+            access_token,
+            refresh_token,
+            expires_at,
+            user_id,
+        })
+    }
+}
+
+/// Estado de la sesión visible para el webview (sin tokens)
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionStatus {
+    pub authenticated: bool,
+    pub user_id: Option<String>,
+    pub expires_at: Option<i64>,
+}
+
+/// Sesión actual. El mutex asíncrono garantiza un único refresco a la vez.
+#[derive(Default)]
+pub struct SessionManager {
+    session: Mutex<Option<Session>>,
+    notify: Notify,
+}
+
+impl SessionManager {
+    pub async fn set(&self, session: Session) {
+        *self.session.lock().await = Some(session);
+        self.notify.notify_one();
+    }
+
+    pub async fn clear(&self) {
+        *self.session.lock().await = None;
+        self.notify.notify_one();
+    }
+
+    pub async fn status(&self) -> SessionStatus {
+        let session = self.session.lock().await;
+        SessionStatus {
+            authenticated: session.is_some(),
+            user_id: session.as_ref().and_then(|s| s.user_id.clone()),
+            expires_at: session.as_ref().map(|s| s.expires_at),
+        }
+    }
+}
+
+fn now_secs() -> i64 {
+    now_millis() / 1000
+}
+
+/// Pide a GoTrue un nuevo par de tokens
+async fn request_refresh(http: &HttpClient, refresh_token: &str) -> Result<Session> {
+    let config = http.supabase()?;
+    let request = http
+        .client()
+        .post(format!(
+            "{}/auth/v1/token?grant_type=refresh_token",
+            config.url
+        ))
+        .header("apikey", &config.anon_key)
+        .json(&serde_json::json!({ "refresh_token": refresh_token }));
+    let response = SupabaseResponse::read(http.send(request).await?)
+        .await?
+        .into_result()?;
+    let json: serde_json::Value = serde_json::from_str(&response.body)
+        .map_err(|e| Error::auth(format!("respuesta de refresco no válida: {}", e)))?;
+    Session::from_token_response(&json, now_secs())
+}
+
+/// Errores que no invalidan la sesión: se puede volver a intentar
+pub(crate) fn is_transient(error: &Error) -> bool {
+    match error {
+        Error::Network(_) => true,
+        Error::Http { status, .. } => *status == 429 || *status >= 500,
+        _ => false,
+    }
+}
+
+/// Refresca la sesión con el lock ya tomado. Si GoTrue rechaza el refresh
+/// token, la sesión se descarta y se emite `session-expired`.
+async fn refresh_locked<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    guard: &mut MutexGuard<'_, Option<Session>>,
+) -> Result<Session> {
+    let Some(current) = guard.as_ref() else {
+        return Err(Error::auth("no hay una sesión iniciada"));
+    };
+    let http = app_handle.state::<HttpClient>();
+    match request_refresh(&http, &current.refresh_token).await {
+        Ok(mut session) => {
+            if session.user_id.is_none() {
+                session.user_id = current.user_id.clone();
+            }
+            **guard = Some(session.clone());
+            let _ = app_handle.emit(SESSION_REFRESHED_EVENT, &session);
+            Ok(session)
+        }
+        Err(e) if is_transient(&e) => Err(e),
+        Err(e) => {
+            **guard = None;
+            let _ = profiles::close(&app_handle.state::<OfflineStore>());
+            let _ = app_handle.emit(SESSION_EXPIRED_EVENT, e.to_string());
+            Err(Error::Auth {
+                message: format!("la sesión expiró: {}", e),
+                status: e.status(),
+            })
+        }
+    }
+}
+
+/// Token de acceso vigente, refrescándolo si está por vencer
+pub(crate) async fn access_token<R: Runtime>(app_handle: &AppHandle<R>) -> Result<String> {
+    let manager = app_handle.state::<SessionManager>();
+    let mut guard = manager.session.lock().await;
+    let Some(session) = guard.as_ref() else {
+        return Err(Error::auth("no hay una sesión iniciada"));
+    };
+    let now = now_secs();
+    if !session.needs_refresh(now) {
+        return Ok(session.access_token.clone());
+    }
+    let fallback = (!session.is_expired(now)).then(|| session.access_token.clone());
+    match refresh_locked(app_handle, &mut guard).await {
+        Ok(session) => Ok(session.access_token),
+        // Sin red, el token actual sirve mientras no haya vencido
+        Err(e) if is_transient(&e) => fallback.ok_or(e),
+        Err(e) => Err(e),
+    }
+}
+
+/// Token explícito enviado por el webview o, si no lo hay, el de la sesión
+pub(crate) async fn resolve_token<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    explicit: Option<String>,
+) -> Result<String> {
+    match explicit.filter(|token| !token.is_empty()) {
+        Some(token) => Ok(token),
+        None => access_token(app_handle).await,
+    }
+}
+
+/// Refresca la sesión en segundo plano antes de que venza
+pub(crate) fn spawn_refresher<R: Runtime>(app_handle: AppHandle<R>) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let manager = app_handle.state::<SessionManager>();
+            let expires_at = manager.session.lock().await.as_ref().map(|s| s.expires_at);
+            let wait = match expires_at {
+                None => IDLE_WAIT,
+                Some(expires_at) => {
+                    let due = expires_at - REFRESH_MARGIN_SECS - now_secs();
+                    if due > 0 {
+                        Duration::from_secs(due as u64)
+                    } else {
+                        let mut guard = manager.session.lock().await;
+                        match refresh_locked(&app_handle, &mut guard).await {
+                            Err(e) if is_transient(&e) => RETRY_WAIT,
+                            _ => continue,
+                        }
+                    }
+                }
+            };
+            tokio::select! {
+                _ = manager.notify.notified() => {}
+                _ = tokio::time::sleep(wait) => {}
+            }
+        }
+    });
+}
+
+// ============================================
+// COMMANDS
+// ============================================
+
+/// Entrega al plugin la sesión iniciada en el webview.
+/// `expires_at` (segundos desde epoch) tiene prioridad sobre `expires_in`.
+/// Si el perfil abierto es de otro usuario, se cierra.
+#[tauri::command]
+pub async fn set_session<R: Runtime>(
+    app_handle: AppHandle<R>,
+    access_token: String,
+    refresh_token: String,
+    expires_at: Option<i64>,
+    expires_in: Option<i64>,
+    user_id: Option<String>,
+) -> Result<SessionStatus> {
+    if access_token.is_empty() || refresh_token.is_empty() {
+        return Err(Error::Validation(
+            "los tokens no pueden estar vacíos".into(),
+        ));
+    }
+    let expires_at = expires_at
+        .or_else(|| expires_in.map(|secs| now_secs() + secs))
+        .ok_or_else(|| Error::Validation("falta expires_at o expires_in".into()))?;
+    profiles::close_unless_owner(&app_handle.state::<OfflineStore>(), user_id.as_deref())?;
+    let manager = app_handle.state::<SessionManager>();
+    manager
+        .set(Session {
+            access_token,
+            refresh_token,
+            expires_at,
+            user_id,
+        })
+        .await;
+    app_handle.state::<RealtimeBridge>().wake();
+    Ok(manager.status().await)
+}
+
+/// Cierra la sesión en el plugin y el perfil abierto. Las suscripciones de
+/// Realtime y el token de la cola offline son del usuario y también se
+/// descartan.
+#[tauri::command]
+pub async fn clear_session(
+    manager: State<'_, SessionManager>,
+    store: State<'_, OfflineStore>,
+    realtime: State<'_, RealtimeBridge>,
+    outbox: State<'_, Outbox>,
+) -> Result<()> {
+    manager.clear().await;
+    realtime.clear();
+    outbox.clear_access_token();
+    profiles::close(&store)
+}
+
+/// Indica si hay sesión, de qué usuario y cuándo vence
+#[tauri::command]
+pub async fn get_session_status(manager: State<'_, SessionManager>) -> Result<SessionStatus> {
+    Ok(manager.status().await)
+}
+
+/// Fuerza el refresco de la sesión
+#[tauri::command]
+pub async fn refresh_session<R: Runtime>(app_handle: AppHandle<R>) -> Result<SessionStatus> {
+    let manager = app_handle.state::<SessionManager>();
+    {
+        let mut guard = manager.session.lock().await;
+        refresh_locked(&app_handle, &mut guard).await?;
+    }
+    Ok(manager.status().await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::client;
+    use red_salud_supabase_mock::{MockResponse, MockSupabase};
+    use serde_json::json;
+
+    #[test]
+    fn token_response_uses_expires_at_or_expires_in() {
+        let body = json!({
+            "access_token": "a2",
+            "refresh_token": "r2",
+            "expires_in": 3600,
+            "user": { "id": "u1" }
+        });
+        let session = Session::from_token_response(&body, 1_000).unwrap();
+        assert_eq!(session.expires_at, 4_600);
+        assert_eq!(session.user_id.as_deref(), Some("u1"));
+
+        let body = json!({ "access_token": "a", "refresh_token": "r", "expires_at": 99 });
+        assert_eq!(
+            Session::from_token_response(&body, 0).unwrap().expires_at,
+            99
+        );
+
+        let err = Session::from_token_response(&json!({ "access_token": "a" }), 0).unwrap_err();
+        assert_eq!(err.code(), "AUTH");
+    }
+
+    #[test]
+    fn refresh_happens_inside_the_margin() {
+        let session = Session {
+            access_token: "a".into(),
+            refresh_token: "r".into(),
+            expires_at: 1_000,
+            user_id: None,
+        };
+        assert!(!session.needs_refresh(1_000 - REFRESH_MARGIN_SECS - 1));
+        assert!(session.needs_refresh(1_000 - REFRESH_MARGIN_SECS));
+        assert!(!session.is_expired(999));
+        assert!(session.is_expired(1_000));
+    }
+
+    #[test]
+    fn only_rejections_expire_the_session() {
+        assert!(is_transient(&Error::Network("sin red".into())));
+        assert!(is_transient(&Error::Http {
+            status: 503,
+            message: String::new()
+        }));
+        assert!(!is_transient(&Error::Http {
+            status: 400,
+            message: "invalid_grant".into()
+        }));
+        assert!(!is_transient(&Error::auth("Invalid Refresh Token")));
+    }
+
+    #[tokio::test]
+    async fn refresh_exchanges_the_refresh_token_with_gotrue() {
+        let mock = MockSupabase::start();
+        mock.on("POST", "/auth/v1/token")
+            .once(MockResponse::token("a2", "r2", 3_600, "u1"))
+            .once(MockResponse::json(
+                400,
+                r#"{"error":"invalid_grant","error_description":"Invalid Refresh Token"}"#,
+            ));
+        let http = client(&mock);
+
+        let session = request_refresh(&http, "r1").await.unwrap();
+        assert_eq!(session.access_token, "a2");
+        assert_eq!(session.refresh_token, "r2");
+        assert_eq!(session.user_id.as_deref(), Some("u1"));
+        let request = &mock.requests_to("POST", "/auth/v1/token")[0];
+        assert_eq!(request.query.as_deref(), Some("grant_type=refresh_token"));
+        let body: serde_json::Value = serde_json::from_str(&request.body).unwrap();
+        assert_eq!(body["refresh_token"], "r1");
+
+        let error = request_refresh(&http, "r2").await.unwrap_err();
+        assert!(!is_transient(&error), "{error:?}");
+    }
+}
@@ -0,0 +1,1303 @@
+// Supabase Storage
+// Sube y descarga objetos de los buckets de Storage desde Rust. Las subidas
+// grandes usan el protocolo TUS de Storage (bloques de 6 MB que se pueden
+// reanudar); las descargas se escriben a disco según llegan, cifradas por
+// bloques, y se reanudan con `Range`. Ambas calculan el SHA-256, emiten
+// progreso y dejan la copia local en los archivos del perfil.
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use reqwest::header::{CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE, LOCATION, RANGE};
+use reqwest::{Response, StatusCode};
+use ring::digest::{Context, SHA256};
+use rusqlite::{params, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::future::Future;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tauri::{AppHandle, Emitter, Manager, Runtime, State};
+use url::Url;
+
+use crate::config::SupabaseConfig;
+use crate::error::{Error, Result};
+use crate::http::HttpClient;
+use crate::jobs::{JobStatus, Jobs, ProfileGuard};
+use crate::paths::resolve_in;
+use crate::profiles::require_active;
+use crate::response::SupabaseResponse;
+use crate::session::resolve_token;
+use crate::store::{now_millis, OfflineStore};
+use crate::vault::{FileOpener, FileSealer, Vault, FILE_CHUNK};
+
+pub(crate) const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS storage_transfers (
+        id              TEXT PRIMARY KEY,
+        direction       TEXT NOT NULL,
+        bucket          TEXT NOT NULL,
+        object_path     TEXT NOT NULL,
+        subfolder       TEXT,
+        filename        TEXT NOT NULL,
+        content_type    TEXT,
+        upsert          INTEGER NOT NULL DEFAULT 0,
+        upload_url      TEXT,
+        total_bytes     INTEGER,
+        bytes_done      INTEGER NOT NULL DEFAULT 0,
+        sha256          TEXT,
+        expected_sha256 TEXT,
+        local_path      TEXT,
+        status          TEXT NOT NULL,
+        error           TEXT,
+        updated_at      INTEGER NOT NULL
+    );
+";
+
+/// Evento con el avance de una transferencia
+pub const STORAGE_PROGRESS_EVENT: &str = "storage-transfer-progress";
+
+/// Carpeta del perfil con las descargas a medias
+pub(crate) const TRANSFERS_DIR: &str = "transfers";
+
+/// Storage exige bloques TUS de exactamente 6 MB (salvo el último). Los
+/// archivos que caben en uno se suben de una vez.
+const TUS_CHUNK_SIZE: usize = 6 * 1024 * 1024;
+const TUS_VERSION: &str = "1.0.0";
+/// Bytes recibidos que se cifran juntos en la descarga a medias
+const RECORD_SIZE: usize = 1024 * 1024;
+const MAX_OBJECT_PATH_LEN: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    Upload,
+    Download,
+}
+
+impl Direction {
+    fn as_str(self) -> &'static str {
+        match self {
+            Direction::Upload => "upload",
+            Direction::Download => "download",
+        }
+    }
+}
+
+/// Subida o descarga de un objeto de Storage
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StorageTransfer {
+    pub id: String,
+    pub direction: Direction,
+    pub bucket: String,
+    pub object_path: String,
+    /// Archivo local dentro del perfil (`subfolder/filename`, `files/` por defecto)
+    pub subfolder: Option<String>,
+    pub filename: String,
+    pub content_type: Option<String>,
+    pub upsert: bool,
+    /// URL de la subida TUS en curso
+    #[serde(skip)]
+    pub upload_url: Option<String>,
+    pub total_bytes: Option<u64>,
+    pub bytes_done: u64,
+    /// SHA-256 en hexadecimal del contenido transferido
+    pub sha256: Option<String>,
+    pub expected_sha256: Option<String>,
+    /// Ruta de la copia local al terminar
+    pub local_path: Option<String>,
+    pub status: JobStatus,
+    pub error: Option<String>,
+    pub updated_at: i64,
+}
+
+impl StorageTransfer {
+    fn new(direction: Direction, bucket: String, object_path: String, filename: String) -> Self {
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            direction,
+            bucket,
+            object_path,
+            subfolder: None,
+            filename,
+            content_type: None,
+            upsert: false,
+            upload_url: None,
+            total_bytes: None,
+            bytes_done: 0,
+            sha256: None,
+            expected_sha256: None,
+            local_path: None,
+            status: JobStatus::Running,
+            error: None,
+            updated_at: now_millis(),
+        }
+    }
+
+    fn from_row(row: &Row<'_>) -> rusqlite::Result<Self> {
+        let direction: String = row.get("direction")?;
+        let status: String = row.get("status")?;
+        Ok(Self {
+            id: row.get("id")?,
+            direction: if direction == "upload" {
+                Direction::Upload
+            } else {
+                Direction::Download
+            },
+            bucket: row.get("bucket")?,
+            object_path: row.get("object_path")?,
+            subfolder: row.get("subfolder")?,
+            filename: row.get("filename")?,
+            content_type: row.get("content_type")?,
+            upsert: row.get("upsert")?,
+            upload_url: row.get("upload_url")?,
+            total_bytes: row.get("total_bytes")?,
+            bytes_done: row.get("bytes_done")?,
+            sha256: row.get("sha256")?,
+            expected_sha256: row.get("expected_sha256")?,
+            local_path: row.get("local_path")?,
+            status: JobStatus::parse(&status),
+            error: row.get("error")?,
+            updated_at: row.get("updated_at")?,
+        })
+    }
+
+    /// Archivo del perfil que se sube o donde queda la descarga
+    fn local_file(&self, store: &OfflineStore) -> Result<PathBuf> {
+        resolve_in(
+            &require_active(store)?.dir,
+            self.subfolder.as_deref(),
+            &self.filename,
+        )
+    }
+
+    fn content_type(&self) -> &str {
+        self.content_type
+            .as_deref()
+            .unwrap_or_else(|| guess_content_type(&self.filename))
+    }
+}
+
+/// Subida de un archivo del perfil a Storage
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UploadRequest {
+    pub bucket: String,
+    pub object_path: String,
+    pub filename: String,
+    #[serde(default)]
+    pub subfolder: Option<String>,
+    /// Por defecto se deduce de la extensión
+    #[serde(default)]
+    pub content_type: Option<String>,
+    /// Reemplaza el objeto si ya existe
+    #[serde(default)]
+    pub upsert: bool,
+    /// SHA-256 esperado; si el archivo local no coincide no se sube
+    #[serde(default)]
+    pub expected_sha256: Option<String>,
+}
+
+/// Descarga de un objeto de Storage a los archivos del perfil
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DownloadRequest {
+    pub bucket: String,
+    pub object_path: String,
+    pub filename: String,
+    #[serde(default)]
+    pub subfolder: Option<String>,
+    /// SHA-256 esperado (por ejemplo el guardado en `public.documents`)
+    #[serde(default)]
+    pub expected_sha256: Option<String>,
+}
+
+// ============================================
+// VALIDACIÓN Y URLS
+// ============================================
+
+fn validate_bucket(bucket: &str) -> Result<()> {
+    let valid = !bucket.is_empty()
+        && bucket.len() <= 100
+        && bucket != "."
+        && bucket != ".."
+        && bucket
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if valid {
+        Ok(())
+    } else {
+        Err(Error::Validation(format!("bucket no válido: {:?}", bucket)))
+    }
+}
+
+/// Ruta del objeto dentro del bucket (`carpeta/archivo.pdf`)
+fn validate_object_path(object_path: &str) -> Result<()> {
+    let valid = object_path.len() <= MAX_OBJECT_PATH_LEN
+        && object_path.split('/').all(|part| {
+            !part.is_empty() && part != "." && part != ".." && !part.chars().any(char::is_control)
+        });
+    if valid {
+        Ok(())
+    } else {
+        Err(Error::Validation(format!(
+            "ruta de objeto no válida: {:?}",
+            object_path
+        )))
+    }
+}
+
+/// URL de la API de Storage con cada segmento codificado
+fn storage_url(config: &SupabaseConfig, segments: &[&str], object_path: &str) -> Result<Url> {
+    let mut url = Url::parse(&config.url)
+        .map_err(|e| Error::Validation(format!("URL de Supabase no válida: {}", e)))?;
+    url.path_segments_mut()
+        .map_err(|_| Error::Validation("URL de Supabase no válida".into()))?
+        .pop_if_empty()
+        .extend(["storage", "v1"])
+        .extend(segments)
+        .extend(object_path.split('/').filter(|part| !part.is_empty()));
+    Ok(url)
+}
+
+/// `Location` de una subida TUS. Debe quedar en el mismo origen que el
+/// proyecto: allí se envía el token de la sesión.
+fn upload_location(config: &SupabaseConfig, location: &str) -> Result<String> {
+    let base = Url::parse(&config.url)
+        .map_err(|e| Error::Validation(format!("URL de Supabase no válida: {}", e)))?;
+    let url = base
+        .join(location)
+        .map_err(|e| Error::Validation(format!("Location no válida: {}", e)))?;
+    if url.origin() != base.origin() {
+        return Err(Error::Validation(format!(
+            "la subida apunta a otro origen: {}",
+            url
+        )));
+    }
+    Ok(url.to_string())
+}
+
+fn guess_content_type(filename: &str) -> &'static str {
+    let extension = filename
+        .rsplit_once('.')
+        .map(|(_, ext)| ext.to_ascii_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        "pdf" => "application/pdf",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "webp" => "image/webp",
+        "json" => "application/json",
+        "txt" => "text/plain",
+        "xml" => "application/xml",
+        _ => "application/octet-stream",
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Total de `Content-Range: bytes 0-99/1234`
+fn content_range_total(value: &str) -> Option<u64> {
+    value.rsplit_once('/')?.1.trim().parse().ok()
+}
+
+fn header_u64(response: &Response, name: &str) -> Option<u64> {
+    response
+        .headers()
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse().ok())
+}
+
+/// Error tipado de una respuesta no exitosa
+async fn error_from(response: Response) -> Error {
+    match SupabaseResponse::read(response).await {
+        Ok(response) => Error::from_response(response.status_code(), &response.body),
+        Err(e) => e,
+    }
+}
+
+// ============================================
+// DESCARGA A MEDIAS
+// ============================================
+
+/// Bytes recibidos de una descarga, guardados como registros
+/// `[longitud u32][bloque cifrado]` para poder reanudarla sin dejar nada en
+/// claro en disco. Cada bloque se autentica con el id de la transferencia y
+/// su posición, de modo que no se pueden reordenar ni mezclar.
+struct PartFile<'a> {
+    vault: &'a Vault,
+    id: String,
+    path: PathBuf,
+    file: File,
+    records: u64,
+    /// Bytes en claro recibidos, incluidos los aún no escritos
+    len: u64,
+    hasher: Context,
+    buffer: Vec<u8>,
+}
+
+impl<'a> PartFile<'a> {
+    /// Abre (o crea) la descarga a medias y recorre lo ya recibido. Un
+    /// registro incompleto o dañado al final se descarta.
+    fn open(vault: &'a Vault, path: &Path, id: &str) -> Result<Self> {
+        vault.ensure_unlocked()?;
+        let file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(path)?;
+        let mut part = Self {
+            vault,
+            id: id.to_string(),
+            path: path.to_path_buf(),
+            file,
+            records: 0,
+            len: 0,
+            hasher: Context::new(&SHA256),
+            buffer: Vec::new(),
+        };
+
+        let mut valid_len = 0u64;
+        for record in part.records()? {
+            let Ok((sealed_len, plaintext)) = record else {
+                break;
+            };
+            part.hasher.update(&plaintext);
+            part.len += plaintext.len() as u64;
+            part.records += 1;
+            valid_len += 4 + sealed_len as u64;
+        }
+        part.file.set_len(valid_len)?;
+        Ok(part)
+    }
+
+    fn aad(id: &str, index: u64) -> Vec<u8> {
+        format!("red-salud:part:{}:{}", id, index).into_bytes()
+    }
+
+    /// Registros descifrados en orden, con el tamaño del bloque cifrado
+    fn records(&self) -> Result<impl Iterator<Item = Result<(usize, Vec<u8>)>> + 'a> {
+        let vault = self.vault;
+        let id = self.id.clone();
+        let mut reader = BufReader::new(File::open(&self.path)?);
+        let mut index = 0;
+        Ok(std::iter::from_fn(move || {
+            let mut len = [0u8; 4];
+            reader.read_exact(&mut len).ok()?;
+            let len = u32::from_be_bytes(len) as usize;
+            let mut sealed = vec![0u8; len];
+            if reader.read_exact(&mut sealed).is_err() {
+                return Some(Err(Error::Storage("registro incompleto".into())));
+            }
+            let record = vault
+                .open(&sealed, &Self::aad(&id, index))
+                .map(|plaintext| (len, plaintext));
+            index += 1;
+            Some(record)
+        }))
+    }
+
+    /// Añade bytes recibidos. Devuelve `true` si se escribió un registro.
+    fn write(&mut self, chunk: &[u8]) -> Result<bool> {
+        self.hasher.update(chunk);
+        self.len += chunk.len() as u64;
+        self.buffer.extend_from_slice(chunk);
+        if self.buffer.len() >= RECORD_SIZE {
+            self.flush()?;
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        let sealed = self
+            .vault
+            .seal(&self.buffer, &Self::aad(&self.id, self.records))?;
+        self.file.write_all(&(sealed.len() as u32).to_be_bytes())?;
+        self.file.write_all(&sealed)?;
+        self.file.sync_data()?;
+        self.records += 1;
+        self.buffer.clear();
+        Ok(())
+    }
+
+    /// Vuelve a empezar (el servidor ignoró el `Range`)
+    fn reset(&mut self) -> Result<()> {
+        self.file.set_len(0)?;
+        self.records = 0;
+        self.len = 0;
+        self.hasher = Context::new(&SHA256);
+        self.buffer.clear();
+        Ok(())
+    }
+
+    fn sha256(&self) -> String {
+        to_hex(self.hasher.clone().finish().as_ref())
+    }
+
+    /// Pasa lo recibido, registro a registro, al archivo cifrado final
+    fn copy_to<W: Write>(&self, sealer: &mut FileSealer<'_, W>) -> Result<()> {
+        for record in self.records()? {
+            sealer.write(&record?.1)?;
+        }
+        sealer.write(&self.buffer)
+    }
+}
+
+// ============================================
+// PERSISTENCIA
+// ============================================
+
+fn save(store: &OfflineStore, transfer: &StorageTransfer) -> Result<()> {
+    store.with_conn(|conn| {
+        conn.execute(
+            "INSERT INTO storage_transfers
+                (id, direction, bucket, object_path, subfolder, filename, content_type, upsert,
+                 upload_url, total_bytes, bytes_done, sha256, expected_sha256, local_path,
+                 status, error, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17)
+             ON CONFLICT(id) DO UPDATE SET
+                upload_url = excluded.upload_url,
+                total_bytes = excluded.total_bytes,
+                bytes_done = excluded.bytes_done,
+                sha256 = excluded.sha256,
+                local_path = excluded.local_path,
+                status = excluded.status,
+                error = excluded.error,
+                updated_at = excluded.updated_at",
+            params![
+                transfer.id,
+                transfer.direction.as_str(),
+                transfer.bucket,
+                transfer.object_path,
+                transfer.subfolder,
+                transfer.filename,
+                transfer.content_type,
+                transfer.upsert,
+                transfer.upload_url,
+                transfer.total_bytes,
+                transfer.bytes_done,
+                transfer.sha256,
+                transfer.expected_sha256,
+                transfer.local_path,
+                transfer.status.as_str(),
+                transfer.error,
+                transfer.updated_at
+            ],
+        )?;
+        Ok(())
+    })
+}
+
+fn get(store: &OfflineStore, id: &str) -> Result<StorageTransfer> {
+    store
+        .with_conn(|conn| {
+            Ok(conn
+                .query_row(
+                    "SELECT * FROM storage_transfers WHERE id = ?1",
+                    params![id],
+                    StorageTransfer::from_row,
+                )
+                .optional()?)
+        })?
+        .ok_or_else(|| Error::NotFound(format!("no existe la transferencia {}", id)))
+}
+
+fn list(store: &OfflineStore) -> Result<Vec<StorageTransfer>> {
+    store.with_conn(|conn| {
+        let mut stmt = conn.prepare("SELECT * FROM storage_transfers ORDER BY updated_at DESC")?;
+        let transfers = stmt
+            .query_map([], StorageTransfer::from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(transfers)
+    })
+}
+
+fn part_path(store: &OfflineStore, id: &str) -> Result<PathBuf> {
+    Ok(require_active(store)?
+        .dir
+        .join(TRANSFERS_DIR)
+        .join(format!("{}.part", id)))
+}
+
+/// Borra la transferencia y su descarga a medias; la copia local se conserva
+fn remove(store: &OfflineStore, id: &str) -> Result<()> {
+    match fs::remove_file(part_path(store, id)?) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+        _ => {}
+    }
+    store.with_conn(|conn| {
+        conn.execute("DELETE FROM storage_transfers WHERE id = ?1", params![id])?;
+        Ok(())
+    })
+}
+
+// ============================================
+// TRANSFERENCIA
+// ============================================
+
+enum Finish {
+    Completed,
+    Cancelled,
+}
+
+/// Ejecuta una transferencia hasta terminar, fallar o ser cancelada. El
+/// avance se guarda y se notifica tras cada bloque; `access_token` se
+/// consulta antes de cada petición.
+pub(crate) async fn run<T, F>(
+    http: &HttpClient,
+    store: &OfflineStore,
+    transfer: &mut StorageTransfer,
+    cancel: &AtomicBool,
+    access_token: T,
+    mut on_progress: impl FnMut(&StorageTransfer),
+) -> Result<()>
+where
+    T: FnMut() -> F,
+    F: Future<Output = Result<String>>,
+{
+    let profile = ProfileGuard::new(store);
+    let mut checkpoint = |transfer: &StorageTransfer| -> Result<()> {
+        profile.check(store)?;
+        save(store, transfer)?;
+        on_progress(transfer);
+        Ok(())
+    };
+    let result = match transfer.direction {
+        Direction::Upload => {
+            upload(http, store, transfer, cancel, access_token, &mut checkpoint).await
+        }
+        Direction::Download => {
+            download(http, store, transfer, cancel, access_token, &mut checkpoint).await
+        }
+    };
+
+    transfer.updated_at = now_millis();
+    let result = match result {
+        Ok(Finish::Completed) => {
+            transfer.status = JobStatus::Completed;
+            transfer.error = None;
+            Ok(())
+        }
+        Ok(Finish::Cancelled) => {
+            transfer.status = JobStatus::Cancelled;
+            Ok(())
+        }
+        Err(e) => {
+            transfer.status = JobStatus::Failed;
+            transfer.error = Some(e.to_string());
+            Err(e)
+        }
+    };
+    profile.check(store)?;
+    save(store, transfer)?;
+    on_progress(transfer);
+    result
+}
+
+async fn upload<T, F>(
+    http: &HttpClient,
+    store: &OfflineStore,
+    transfer: &mut StorageTransfer,
+    cancel: &AtomicBool,
+    mut access_token: T,
+    checkpoint: &mut impl FnMut(&StorageTransfer) -> Result<()>,
+) -> Result<Finish>
+where
+    T: FnMut() -> F,
+    F: Future<Output = Result<String>>,
+{
+    let config = http.supabase()?;
+    let path = transfer.local_file(store)?;
+    let open = || FileOpener::new(store.vault(), BufReader::new(File::open(&path)?));
+
+    // Primera pasada, sin cargar el archivo: tamaño y SHA-256 antes de crear
+    // nada en el servidor
+    let mut reader = open()?;
+    let mut hasher = Context::new(&SHA256);
+    let mut total = 0u64;
+    loop {
+        let block = reader.read_up_to(FILE_CHUNK)?;
+        if block.is_empty() {
+            break;
+        }
+        hasher.update(&block);
+        total += block.len() as u64;
+    }
+    let sha256 = to_hex(hasher.finish().as_ref());
+    if let Some(expected) = &transfer.expected_sha256 {
+        if !expected.eq_ignore_ascii_case(&sha256) {
+            return Err(Error::Validation(format!(
+                "el SHA-256 no coincide: se esperaba {} y el archivo tiene {}",
+                expected, sha256
+            )));
+        }
+    }
+    // Si el archivo cambió desde el intento anterior, la subida empieza de cero
+    if transfer
+        .sha256
+        .as_ref()
+        .is_some_and(|previous| *previous != sha256)
+    {
+        transfer.upload_url = None;
+    }
+    transfer.sha256 = Some(sha256);
+    transfer.total_bytes = Some(total);
+    transfer.local_path = Some(path.to_string_lossy().to_string());
+
+    let mut reader = open()?;
+    if total <= TUS_CHUNK_SIZE as u64 && transfer.upload_url.is_none() {
+        let data = reader.read_up_to(TUS_CHUNK_SIZE)?;
+        let url = storage_url(
+            &config,
+            &["object", &transfer.bucket],
+            &transfer.object_path,
+        )?;
+        let request = http
+            .client()
+            .post(url)
+            .header("apikey", &config.anon_key)
+            .header("Authorization", format!("Bearer {}", access_token().await?))
+            .header(CONTENT_TYPE, transfer.content_type())
+            .header("x-upsert", transfer.upsert.to_string())
+            .body(data);
+        SupabaseResponse::read(http.send(request).await?)
+            .await?
+            .into_result()?;
+        transfer.bytes_done = transfer.total_bytes.unwrap_or_default();
+        return Ok(Finish::Completed);
+    }
+
+    // Se retoma la subida TUS si el servidor aún la conserva
+    let token = access_token().await?;
+    let offset = match &transfer.upload_url {
+        Some(url) => tus_offset(http, &config, url, &token).await?,
+        None => None,
+    };
+    match offset {
+        Some(offset) => transfer.bytes_done = offset,
+        None => {
+            transfer.upload_url = Some(tus_create(http, &config, transfer, &token).await?);
+            transfer.bytes_done = 0;
+        }
+    }
+    checkpoint(transfer)?;
+
+    let upload_url = transfer.upload_url.clone().unwrap_or_default();
+    let mut position = 0u64;
+    while transfer.bytes_done < total {
+        if cancel.load(Ordering::Relaxed) {
+            return Ok(Finish::Cancelled);
+        }
+        // Lo que el servidor ya tiene se descifra y se salta
+        while position < transfer.bytes_done {
+            let skip = (transfer.bytes_done - position).min(FILE_CHUNK as u64) as usize;
+            position += reader.read_up_to(skip)?.len() as u64;
+        }
+        let start = transfer.bytes_done;
+        let data = reader.read_up_to(TUS_CHUNK_SIZE)?;
+        position += data.len() as u64;
+        let end = start + data.len() as u64;
+        let request = http
+            .client()
+            .patch(&upload_url)
+            .header("apikey", &config.anon_key)
+            .header("Authorization", format!("Bearer {}", access_token().await?))
+            .header("Tus-Resumable", TUS_VERSION)
+            .header("Upload-Offset", start.to_string())
+            .header(CONTENT_TYPE, "application/offset+octet-stream")
+            .body(data);
+        let response = http.send(request).await?;
+        if !response.status().is_success() {
+            return Err(error_from(response).await);
+        }
+        transfer.bytes_done = header_u64(&response, "upload-offset").unwrap_or(end);
+        checkpoint(transfer)?;
+    }
+    Ok(Finish::Completed)
+}
+
+/// Crea la subida TUS y devuelve su URL
+async fn tus_create(
+    http: &HttpClient,
+    config: &SupabaseConfig,
+    transfer: &StorageTransfer,
+    access_token: &str,
+) -> Result<String> {
+    let metadata = [
+        ("bucketName", transfer.bucket.as_str()),
+        ("objectName", transfer.object_path.as_str()),
+        ("contentType", transfer.content_type()),
+        ("cacheControl", "3600"),
+    ]
+    .iter()
+    .map(|(name, value)| format!("{} {}", name, BASE64.encode(value)))
+    .collect::<Vec<_>>()
+    .join(",");
+    let request = http
+        .client()
+        .post(storage_url(config, &["upload", "resumable"], "")?)
+        .header("apikey", &config.anon_key)
+        .header("Authorization", format!("Bearer {}", access_token))
+        .header("Tus-Resumable", TUS_VERSION)
+        .header(
+            "Upload-Length",
+            transfer.total_bytes.unwrap_or_default().to_string(),
+        )
+        .header("Upload-Metadata", metadata)
+        .header("x-upsert", transfer.upsert.to_string());
+    let response = http.send(request).await?;
+    if !response.status().is_success() {
+        return Err(error_from(response).await);
+    }
+    let location = response
+        .headers()
+        .get(LOCATION)
+        .and_then(|value| value.to_str().ok())
+        .ok_or_else(|| Error::Storage("la subida TUS no devolvió Location".into()))?;
+    upload_location(config, location)
+}
+
+/// Bytes ya recibidos por el servidor, o `None` si la subida caducó
+async fn tus_offset(
+    http: &HttpClient,
+    config: &SupabaseConfig,
+    upload_url: &str,
+    access_token: &str,
+) -> Result<Option<u64>> {
+    let request = http
+        .client()
+        .head(upload_url)
+        .header("apikey", &config.anon_key)
+        .header("Authorization", format!("Bearer {}", access_token))
+        .header("Tus-Resumable", TUS_VERSION);
+    let response = http.send(request).await?;
+    match response.status() {
+        StatusCode::NOT_FOUND | StatusCode::GONE => Ok(None),
+        status if status.is_success() => Ok(header_u64(&response, "upload-offset")),
+        _ => Err(error_from(response).await),
+    }
+}
+
+async fn download<T, F>(
+    http: &HttpClient,
+    store: &OfflineStore,
+    transfer: &mut StorageTransfer,
+    cancel: &AtomicBool,
+    mut access_token: T,
+    checkpoint: &mut impl FnMut(&StorageTransfer) -> Result<()>,
+) -> Result<Finish>
+where
+    T: FnMut() -> F,
+    F: Future<Output = Result<String>>,
+{
+    let config = http.supabase()?;
+    let destination = transfer.local_file(store)?;
+    let part_path = part_path(store, &transfer.id)?;
+    if let Some(dir) = part_path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut part = PartFile::open(store.vault(), &part_path, &transfer.id)?;
+
+    let url = storage_url(
+        &config,
+        &["object", "authenticated", &transfer.bucket],
+        &transfer.object_path,
+    )?;
+    let mut request = http
+        .client()
+        .get(url)
+        .header("apikey", &config.anon_key)
+        .header("Authorization", format!("Bearer {}", access_token().await?));
+    if part.len > 0 {
+        request = request.header(RANGE, format!("bytes={}-", part.len));
+    }
+    let mut response = http.send(request).await?;
+
+    let status = response.status();
+    let resumed = status == StatusCode::PARTIAL_CONTENT;
+    let total = match status {
+        StatusCode::PARTIAL_CONTENT => response
+            .headers()
+            .get(CONTENT_RANGE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(content_range_total),
+        // Lo pedido ya estaba completo
+        StatusCode::RANGE_NOT_SATISFIABLE if part.len > 0 => Some(part.len),
+        status if status.is_success() => {
+            if part.len > 0 {
+                part.reset()?;
+            }
+            header_u64(&response, CONTENT_LENGTH.as_str())
+        }
+        _ => return Err(error_from(response).await),
+    };
+    transfer.total_bytes = total;
+    transfer.bytes_done = part.len;
+    checkpoint(transfer)?;
+
+    if resumed || status == StatusCode::OK {
+        while let Some(chunk) = response.chunk().await? {
+            if part.write(&chunk)? {
+                transfer.bytes_done = part.len;
+                checkpoint(transfer)?;
+                if cancel.load(Ordering::Relaxed) {
+                    return Ok(Finish::Cancelled);
+                }
+            }
+        }
+        part.flush()?;
+    }
+
+    transfer.bytes_done = part.len;
+    if let Some(total) = total.filter(|total| *total != part.len) {
+        return Err(Error::Network(format!(
+            "descarga incompleta: {} de {} bytes",
+            part.len, total
+        )));
+    }
+    let sha256 = part.sha256();
+    if let Some(expected) = &transfer.expected_sha256 {
+        if !expected.eq_ignore_ascii_case(&sha256) {
+            drop(part);
+            fs::remove_file(&part_path)?;
+            return Err(Error::Validation(format!(
+                "el SHA-256 no coincide: se esperaba {} y se recibió {}",
+                expected, sha256
+            )));
+        }
+    }
+
+    if let Some(dir) = destination.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let tmp = part_path.with_extension("rse-tmp");
+    let mut sealer = FileSealer::new(store.vault(), BufWriter::new(File::create(&tmp)?))?;
+    part.copy_to(&mut sealer)?;
+    sealer.finish()?;
+    fs::rename(&tmp, &destination)?;
+    drop(part);
+    fs::remove_file(&part_path)?;
+
+    transfer.sha256 = Some(sha256);
+    transfer.local_path = Some(destination.to_string_lossy().to_string());
+    Ok(Finish::Completed)
+}
+
+fn spawn<R: Runtime>(
+    app_handle: AppHandle<R>,
+    mut transfer: StorageTransfer,
+    cancel: Arc<AtomicBool>,
+    access_token: Option<String>,
+) {
+    tauri::async_runtime::spawn(async move {
+        let http = app_handle.state::<HttpClient>();
+        let store = app_handle.state::<OfflineStore>();
+        let _ = run(
+            &http,
+            &store,
+            &mut transfer,
+            &cancel,
+            || resolve_token(&app_handle, access_token.clone()),
+            |transfer| {
+                let _ = app_handle.emit(STORAGE_PROGRESS_EVENT, transfer);
+            },
+        )
+        .await;
+        app_handle.state::<Jobs>().finish(&transfer.id);
+    });
+}
+
+fn start<R: Runtime>(
+    app_handle: AppHandle<R>,
+    store: &OfflineStore,
+    jobs: &Jobs,
+    transfer: StorageTransfer,
+    access_token: Option<String>,
+) -> Result<StorageTransfer> {
+    save(store, &transfer)?;
+    let cancel = jobs.register(&transfer.id)?;
+    spawn(app_handle, transfer.clone(), cancel, access_token);
+    Ok(transfer)
+}
+
+// ============================================
+// COMMANDS
+// ============================================
+
+/// Sube un archivo del perfil a un bucket de Storage. Los archivos de más
+/// de 6 MB se suben por TUS y se pueden reanudar. El avance se emite en
+/// `storage-transfer-progress`.
+#[tauri::command]
+pub async fn storage_upload<R: Runtime>(
+    app_handle: AppHandle<R>,
+    store: State<'_, OfflineStore>,
+    jobs: State<'_, Jobs>,
+    request: UploadRequest,
+    access_token: Option<String>,
+) -> Result<StorageTransfer> {
+    validate_bucket(&request.bucket)?;
+    validate_object_path(&request.object_path)?;
+    let mut transfer = StorageTransfer::new(
+        Direction::Upload,
+        request.bucket,
+        request.object_path,
+        request.filename,
+    );
+    transfer.subfolder = request.subfolder;
+    transfer.content_type = request.content_type;
+    transfer.upsert = request.upsert;
+    transfer.expected_sha256 = request.expected_sha256;
+    let path = transfer.local_file(&store)?;
+    if !path.is_file() {
+        return Err(Error::NotFound(format!("no existe: {}", path.display())));
+    }
+    start(app_handle, &store, &jobs, transfer, access_token)
+}
+
+/// Descarga un objeto de Storage a los archivos del perfil, cifrado como el
+/// resto. Verifica el SHA-256 si se indica `expected_sha256`.
+#[tauri::command]
+pub async fn storage_download<R: Runtime>(
+    app_handle: AppHandle<R>,
+    store: State<'_, OfflineStore>,
+    jobs: State<'_, Jobs>,
+    request: DownloadRequest,
+    access_token: Option<String>,
+) -> Result<StorageTransfer> {
+    validate_bucket(&request.bucket)?;
+    validate_object_path(&request.object_path)?;
+    let mut transfer = StorageTransfer::new(
+        Direction::Download,
+        request.bucket,
+        request.object_path,
+        request.filename,
+    );
+    transfer.subfolder = request.subfolder;
+    transfer.expected_sha256 = request.expected_sha256;
+    transfer.local_file(&store)?;
+    start(app_handle, &store, &jobs, transfer, access_token)
+}
+
+/// Pide cancelar una transferencia; se detiene al terminar el bloque en curso
+#[tauri::command]
+pub async fn cancel_storage_transfer(jobs: State<'_, Jobs>, id: String) -> Result<()> {
+    if jobs.cancel(&id) {
+        Ok(())
+    } else {
+        Err(Error::NotFound(format!(
+            "la transferencia {} no está en curso",
+            id
+        )))
+    }
+}
+
+/// Reanuda una transferencia cancelada o fallida
+#[tauri::command]
+pub async fn resume_storage_transfer<R: Runtime>(
+    app_handle: AppHandle<R>,
+    store: State<'_, OfflineStore>,
+    jobs: State<'_, Jobs>,
+    id: String,
+    access_token: Option<String>,
+) -> Result<StorageTransfer> {
+    let mut transfer = get(&store, &id)?;
+    if transfer.status == JobStatus::Completed {
+        return Ok(transfer);
+    }
+    transfer.status = JobStatus::Running;
+    transfer.error = None;
+    transfer.updated_at = now_millis();
+    start(app_handle, &store, &jobs, transfer, access_token)
+}
+
+/// Lista las transferencias del perfil. Las que figuran en curso sin tarea
+/// activa (la app se cerró a medias) se informan como canceladas.
+#[tauri::command]
+pub async fn get_storage_transfers(
+    store: State<'_, OfflineStore>,
+    jobs: State<'_, Jobs>,
+) -> Result<Vec<StorageTransfer>> {
+    let mut list = list(&store)?;
+    for transfer in &mut list {
+        if transfer.status == JobStatus::Running && !jobs.is_running(&transfer.id) {
+            transfer.status = JobStatus::Cancelled;
+        }
+    }
+    Ok(list)
+}
+
+/// Borra una transferencia terminada o detenida y su descarga a medias
+#[tauri::command]
+pub async fn delete_storage_transfer(
+    store: State<'_, OfflineStore>,
+    jobs: State<'_, Jobs>,
+    id: String,
+) -> Result<()> {
+    if jobs.is_running(&id) {
+        return Err(Error::Validation(format!(
+            "la transferencia {} sigue en curso; cancélala antes",
+            id
+        )));
+    }
+    get(&store, &id)?;
+    remove(&store, &id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{client, profile_store};
+    use red_salud_supabase_mock::{MockResponse, MockSupabase};
+
+    fn sha256_hex(data: &[u8]) -> String {
+        to_hex(ring::digest::digest(&SHA256, data).as_ref())
+    }
+
+    const TUS: &str = "/storage/v1/upload/resumable";
+
+    async fn run_ok(http: &HttpClient, store: &OfflineStore, transfer: &mut StorageTransfer) {
+        run(
+            http,
+            store,
+            transfer,
+            &AtomicBool::new(false),
+            || async { Ok("jwt".to_string()) },
+            |_| {},
+        )
+        .await
+        .unwrap();
+    }
+
+    fn save_local(store: &OfflineStore, filename: &str, data: &[u8]) {
+        let path = resolve_in(&require_active(store).unwrap().dir, None, filename).unwrap();
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, store.vault().seal_file(data).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn object_urls_encode_each_segment() {
+        let config = SupabaseConfig {
+            url: "https://x.supabase.co/".into(),
+            anon_key: "k".into(),
+        };
+        let url = storage_url(&config, &["object", "lab-results"], "2026/análisis #1.pdf").unwrap();
+        assert_eq!(
+            url.as_str(),
+            "https://x.supabase.co/storage/v1/object/lab-results/2026/an%C3%A1lisis%20%231.pdf"
+        );
+        assert!(validate_object_path("a/../b").is_err());
+        assert!(validate_object_path("/a").is_err());
+        assert!(validate_bucket("lab/results").is_err());
+        assert!(upload_location(&config, "https://evil.test/upload/1").is_err());
+        assert_eq!(
+            upload_location(&config, "/storage/v1/upload/resumable/abc").unwrap(),
+            "https://x.supabase.co/storage/v1/upload/resumable/abc"
+        );
+    }
+
+    #[tokio::test]
+    async fn small_files_upload_in_one_request() {
+        let mock = MockSupabase::start();
+        mock.on("POST", "/storage/v1/object/recetas/p1/receta.pdf")
+            .once(MockResponse::json(
+                200,
+                r#"{"Key":"recetas/p1/receta.pdf"}"#,
+            ));
+        let http = client(&mock);
+        let (store, _root) = profile_store();
+        save_local(&store, "receta.pdf", b"%PDF-1.7 receta");
+
+        let mut transfer = StorageTransfer::new(
+            Direction::Upload,
+            "recetas".into(),
+            "p1/receta.pdf".into(),
+            "receta.pdf".into(),
+        );
+        run_ok(&http, &store, &mut transfer).await;
+
+        assert_eq!(transfer.status, JobStatus::Completed);
+        assert_eq!(transfer.bytes_done, 15);
+        assert_eq!(transfer.sha256, Some(sha256_hex(b"%PDF-1.7 receta")));
+        let request = &mock.requests_to("POST", "/storage/v1/object/recetas/p1/receta.pdf")[0];
+        assert_eq!(request.body, "%PDF-1.7 receta");
+        assert_eq!(request.header("content-type"), Some("application/pdf"));
+        assert_eq!(request.header("x-upsert"), Some("false"));
+    }
+
+    #[tokio::test]
+    async fn uploads_with_another_checksum_never_reach_the_server() {
+        let mock = MockSupabase::start();
+        let http = client(&mock);
+        let (store, _root) = profile_store();
+        save_local(&store, "placa.png", &vec![b'a'; TUS_CHUNK_SIZE + 10]);
+
+        let mut transfer = StorageTransfer::new(
+            Direction::Upload,
+            "estudios".into(),
+            "p1/placa.png".into(),
+            "placa.png".into(),
+        );
+        transfer.expected_sha256 = Some(sha256_hex(b"otro archivo"));
+        let error = run(
+            &http,
+            &store,
+            &mut transfer,
+            &AtomicBool::new(false),
+            || async { Ok("jwt".to_string()) },
+            |_| {},
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(error.code(), "VALIDATION");
+        assert_eq!(transfer.upload_url, None);
+        assert!(mock.requests_to("POST", TUS).is_empty());
+    }
+
+    #[tokio::test]
+    async fn large_uploads_use_tus_and_resume_from_the_server_offset() {
+        let mock = MockSupabase::start();
+        let location = format!("{}{}/u1", mock.url(), TUS);
+        mock.on("POST", TUS)
+            .once(MockResponse::new(201).header("location", &location));
+        mock.on("PATCH", &format!("{}/u1", TUS))
+            .once(MockResponse::new(204).header("upload-offset", &TUS_CHUNK_SIZE.to_string()))
+            .once(MockResponse::new(503))
+            .once(
+                MockResponse::new(204).header("upload-offset", &(TUS_CHUNK_SIZE + 10).to_string()),
+            );
+        mock.on("HEAD", &format!("{}/u1", TUS))
+            .once(MockResponse::new(200).header("upload-offset", &TUS_CHUNK_SIZE.to_string()));
+        let http = client(&mock);
+        let (store, _root) = profile_store();
+        let data = vec![b'a'; TUS_CHUNK_SIZE + 10];
+        save_local(&store, "placa.png", &data);
+
+        let mut transfer = StorageTransfer::new(
+            Direction::Upload,
+            "estudios".into(),
+            "p1/placa.png".into(),
+            "placa.png".into(),
+        );
+        let error = run(
+            &http,
+            &store,
+            &mut transfer,
+            &AtomicBool::new(false),
+            || async { Ok("jwt".to_string()) },
+            |_| {},
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(error.status(), Some(503));
+        assert_eq!(transfer.status, JobStatus::Failed);
+        assert_eq!(transfer.bytes_done, TUS_CHUNK_SIZE as u64);
+
+        let mut resumed = get(&store, &transfer.id).unwrap();
+        run_ok(&http, &store, &mut resumed).await;
+        assert_eq!(resumed.status, JobStatus::Completed);
+        assert_eq!(resumed.bytes_done, data.len() as u64);
+        assert_eq!(resumed.sha256, Some(sha256_hex(&data)));
+
+        let create = &mock.requests_to("POST", TUS)[0];
+        assert_eq!(create.header("upload-length"), Some("6291466"));
+        assert!(create
+            .header("upload-metadata")
+            .unwrap()
+            .contains(&format!("objectName {}", BASE64.encode("p1/placa.png"))));
+        let offsets: Vec<_> = mock
+            .requests_to("PATCH", &format!("{}/u1", TUS))
+            .iter()
+            .map(|r| r.header("upload-offset").unwrap().to_string())
+            .collect();
+        assert_eq!(offsets, ["0", "6291456", "6291456"]);
+        assert_eq!(mock.requests_to("HEAD", &format!("{}/u1", TUS)).len(), 1);
+    }
+
+    #[tokio::test]
+    async fn downloads_resume_with_range_and_verify_the_checksum() {
+        let object = "/storage/v1/object/authenticated/laboratorio/p1/hemograma.txt";
+        let mock = MockSupabase::start();
+        mock.on("GET", object).once(
+            MockResponse::new(206)
+                .header("content-range", "bytes 6-10/11")
+                .header("content-type", "text/plain")
+                .body("world"),
+        );
+        let http = client(&mock);
+        let (store, _root) = profile_store();
+
+        let mut transfer = StorageTransfer::new(
+            Direction::Download,
+            "laboratorio".into(),
+            "p1/hemograma.txt".into(),
+            "hemograma.txt".into(),
+        );
+        transfer.expected_sha256 = Some(sha256_hex(b"hello world"));
+        // Una descarga anterior se quedó en los primeros 6 bytes
+        let part_path = part_path(&store, &transfer.id).unwrap();
+        fs::create_dir_all(part_path.parent().unwrap()).unwrap();
+        let mut part = PartFile::open(store.vault(), &part_path, &transfer.id).unwrap();
+        part.write(b"hello ").unwrap();
+        part.flush().unwrap();
+        drop(part);
+        // Restos de un registro a medio escribir
+        OpenOptions::new()
+            .append(true)
+            .open(&part_path)
+            .unwrap()
+            .write_all(&[0, 0, 0, 9, 1, 2])
+            .unwrap();
+
+        run_ok(&http, &store, &mut transfer).await;
+
+        assert_eq!(transfer.status, JobStatus::Completed);
+        assert_eq!(transfer.total_bytes, Some(11));
+        assert_eq!(
+            mock.requests_to("GET", object)[0].header("range"),
+            Some("bytes=6-")
+        );
+        let local = fs::read(transfer.local_path.as_ref().unwrap()).unwrap();
+        assert!(crate::vault::is_sealed_file(&local));
+        assert_eq!(store.vault().open_file(local).unwrap(), b"hello world");
+        assert!(!part_path.exists());
+    }
+
+    #[tokio::test]
+    async fn checksum_mismatches_fail_without_keeping_the_file() {
+        let object = "/storage/v1/object/authenticated/laboratorio/p1/orina.txt";
+        let mock = MockSupabase::start();
+        mock.on("GET", object)
+            .once(MockResponse::new(200).body("alterado"));
+        let http = client(&mock);
+        let (store, _root) = profile_store();
+
+        let mut transfer = StorageTransfer::new(
+            Direction::Download,
+            "laboratorio".into(),
+            "p1/orina.txt".into(),
+            "orina.txt".into(),
+        );
+        transfer.expected_sha256 = Some(sha256_hex(b"original"));
+        let error = run(
+            &http,
+            &store,
+            &mut transfer,
+            &AtomicBool::new(false),
+            || async { Ok("jwt".to_string()) },
+            |_| {},
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(error.code(), "VALIDATION");
+        assert_eq!(get(&store, &transfer.id).unwrap().status, JobStatus::Failed);
+        assert!(!transfer.local_file(&store).unwrap().exists());
+    }
+}
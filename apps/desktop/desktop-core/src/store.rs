@@ -0,0 +1,358 @@
+use rusqlite::types::Value;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+use std::sync::{Mutex, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::error::{Error, Result};
+use crate::profiles::ActiveProfile;
+use crate::vault::Vault;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS offline_entries (
+        key        TEXT PRIMARY KEY NOT NULL,
+        value      TEXT NOT NULL,
+        metadata   TEXT NOT NULL DEFAULT '{}',
+        created_at INTEGER NOT NULL,
+        updated_at INTEGER NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS idx_offline_entries_updated_at
+        ON offline_entries (updated_at);
+";
+
+/// Migraciones del esquema en orden. La versión de la base
+/// (`PRAGMA user_version`) es el número de migraciones aplicadas.
+const MIGRATIONS: &[&str] = &[
+    SCHEMA,
+    crate::outbox::SCHEMA,
+    crate::conflicts::SCHEMA,
+    crate::vault::SCHEMA,
+    crate::paged::SCHEMA,
+    crate::storage::SCHEMA,
+    crate::notifications::SCHEMA,
+];
+
+/// Entrada del almacén offline con sus metadatos
+#[derive(Debug, Clone, Serialize)]
+pub struct OfflineEntry {
+    pub key: String,
+    pub value: String,
+    pub metadata: serde_json::Value,
+    /// Milisegundos desde epoch
+    pub created_at: i64,
+    /// Milisegundos desde epoch
+    pub updated_at: i64,
+}
+
+/// Almacén clave/valor offline sobre SQLite.
+///
+/// Reemplaza al antiguo directorio `offline_data/` con un archivo JSON por
+/// clave. Todas las escrituras son transaccionales. Los valores se guardan
+/// cifrados con la clave del `Vault`; mientras está bloqueado no se pueden
+/// leer ni escribir.
+///
+/// Cada usuario tiene su propia base en su perfil. Sin perfil activo el
+/// almacén trabaja sobre una base vacía en memoria.
+pub struct OfflineStore {
+    conn: Mutex<Connection>,
+    vault: Vault,
+    profile: RwLock<Option<ActiveProfile>>,
+}
+
+impl OfflineStore {
+    /// Abre (o crea) la base de datos en `db_path` y aplica el esquema
+    pub fn open(db_path: &Path) -> Result<Self> {
+        if let Some(parent) = db_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        Self::init(Connection::open(db_path)?)
+    }
+
+    /// Almacén en memoria: el de arranque, sin perfil abierto, y el de las pruebas
+    pub fn open_in_memory() -> Result<Self> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    fn init(conn: Connection) -> Result<Self> {
+        Ok(Self {
+            conn: Mutex::new(migrate(conn)?),
+            vault: Vault::default(),
+            profile: RwLock::new(None),
+        })
+    }
+
+    /// Cambia la base por la del perfil indicado (o por una vacía en memoria).
+    /// El almacén queda bloqueado hasta volver a desbloquearlo.
+    pub(crate) fn attach(&self, profile: Option<ActiveProfile>) -> Result<()> {
+        let conn = match &profile {
+            Some(profile) => {
+                fs::create_dir_all(&profile.dir)?;
+                migrate(Connection::open(profile.db_path())?)?
+            }
+            None => migrate(Connection::open_in_memory()?)?,
+        };
+        let mut current = self
+            .profile
+            .write()
+            .map_err(|_| Error::Storage("el perfil activo quedó bloqueado".into()))?;
+        self.vault.lock();
+        *self.conn()? = conn;
+        *current = profile;
+        Ok(())
+    }
+
+    /// Perfil cuyos datos están abiertos
+    pub fn active_profile(&self) -> Option<ActiveProfile> {
+        self.profile.read().ok().and_then(|profile| profile.clone())
+    }
+
+    /// Claves de cifrado del almacén
+    pub fn vault(&self) -> &Vault {
+        &self.vault
+    }
+
+    /// Descifra un valor; los valores en texto son anteriores al cifrado
+    fn decode(&self, key: &str, value: Value) -> Result<String> {
+        match value {
+            Value::Blob(sealed) => String::from_utf8(self.vault.open(&sealed, key.as_bytes())?)
+                .map_err(|e| Error::Storage(e.to_string())),
+            Value::Text(text) => Ok(text),
+            _ => Err(Error::Storage(format!("valor no válido para {}", key))),
+        }
+    }
+
+    fn conn(&self) -> Result<std::sync::MutexGuard<'_, Connection>> {
+        self.conn
+            .lock()
+            .map_err(|_| Error::Storage("el almacén offline quedó bloqueado".into()))
+    }
+
+    /// Ejecuta `f` con la conexión bloqueada, para módulos que guardan sus
+    /// propias tablas en la misma base
+    pub(crate) fn with_conn<T>(&self, f: impl FnOnce(&mut Connection) -> Result<T>) -> Result<T> {
+        f(&mut *self.conn()?)
+    }
+
+    /// Obtiene una entrada completa
+    pub fn get_entry(&self, key: &str) -> Result<Option<OfflineEntry>> {
+        let row = self
+            .conn()?
+            .query_row(
+                "SELECT key, value, metadata, created_at, updated_at
+                 FROM offline_entries WHERE key = ?1",
+                params![key],
+                |row| {
+                    let metadata: String = row.get(2)?;
+                    let value: Value = row.get(1)?;
+                    Ok((
+                        OfflineEntry {
+                            key: row.get(0)?,
+                            value: String::new(),
+                            metadata: serde_json::from_str(&metadata)
+                                .unwrap_or(serde_json::Value::Null),
+                            created_at: row.get(3)?,
+                            updated_at: row.get(4)?,
+                        },
+                        value,
+                    ))
+                },
+            )
+            .optional()?;
+        let Some((mut entry, value)) = row else {
+            return Ok(None);
+        };
+        entry.value = self.decode(key, value)?;
+        Ok(Some(entry))
+    }
+
+    /// Obtiene solo el valor de una clave
+    pub fn get(&self, key: &str) -> Result<Option<String>> {
+        Ok(self.get_entry(key)?.map(|entry| entry.value))
+    }
+
+    /// Inserta o reemplaza una clave. Si `metadata` es `None` se conservan
+    /// los metadatos existentes.
+    pub fn set(&self, key: &str, value: &str, metadata: Option<&serde_json::Value>) -> Result<()> {
+        let now = now_millis();
+        let metadata = metadata.map(|m| m.to_string());
+        let sealed = self.vault.seal(value.as_bytes(), key.as_bytes())?;
+        let mut conn = self.conn()?;
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT INTO offline_entries (key, value, metadata, created_at, updated_at)
+             VALUES (?1, ?2, COALESCE(?3, '{}'), ?4, ?4)
+             ON CONFLICT(key) DO UPDATE SET
+                value = excluded.value,
+                metadata = COALESCE(?3, offline_entries.metadata),
+                updated_at = excluded.updated_at",
+            params![key, sealed, metadata, now],
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Cifra un valor de otra tabla de la base; `aad` lo ata a su fila y
+    /// columna para que no se pueda mover a otra
+    pub(crate) fn seal_value(&self, value: &str, aad: &str) -> Result<Vec<u8>> {
+        self.vault.seal(value.as_bytes(), aad.as_bytes())
+    }
+
+    /// Descifra un valor guardado con `seal_value`; `NULL` es `None`
+    pub(crate) fn open_value(&self, value: Value, aad: &str) -> Result<Option<String>> {
+        match value {
+            Value::Null => Ok(None),
+            value => self.decode(aad, value).map(Some),
+        }
+    }
+
+    /// Reescribe en una transacción las celdas de `table.column` para las que
+    /// `f` devuelva un nuevo contenido cifrado. `f` recibe el valor de
+    /// `key_column` de la fila. Devuelve cuántas cambiaron.
+    pub(crate) fn reseal_column(
+        &self,
+        table: &str,
+        key_column: &str,
+        column: &str,
+        mut f: impl FnMut(&str, &[u8]) -> Result<Option<Vec<u8>>>,
+    ) -> Result<usize> {
+        let mut conn = self.conn()?;
+        let tx = conn.transaction()?;
+        let rows = {
+            let mut stmt = tx.prepare(&format!(
+                "SELECT rowid, {}, {} FROM {} WHERE {} IS NOT NULL",
+                key_column, column, table, column
+            ))?;
+            let rows = stmt
+                .query_map([], |row| {
+                    Ok((
+                        row.get::<_, i64>(0)?,
+                        row.get::<_, String>(1)?,
+                        row.get::<_, Value>(2)?,
+                    ))
+                })?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            rows
+        };
+
+        let mut changed = 0;
+        for (rowid, key, value) in rows {
+            let bytes = match &value {
+                Value::Blob(bytes) => bytes.as_slice(),
+                Value::Text(text) => text.as_bytes(),
+                _ => continue,
+            };
+            if let Some(sealed) = f(&key, bytes)? {
+                tx.execute(
+                    &format!("UPDATE {} SET {} = ?2 WHERE rowid = ?1", table, column),
+                    params![rowid, sealed],
+                )?;
+                changed += 1;
+            }
+        }
+        tx.commit()?;
+        Ok(changed)
+    }
+
+    /// Elimina una clave; no falla si no existe
+    pub fn delete(&self, key: &str) -> Result<()> {
+        let conn = self.conn()?;
+        conn.execute("DELETE FROM offline_entries WHERE key = ?1", params![key])?;
+        Ok(())
+    }
+
+    /// Elimina todas las claves
+    pub fn clear(&self) -> Result<()> {
+        let conn = self.conn()?;
+        conn.execute("DELETE FROM offline_entries", [])?;
+        Ok(())
+    }
+
+    /// Lista todas las claves almacenadas. Los nombres también revelan datos,
+    /// así que requiere el almacén desbloqueado.
+    pub fn keys(&self) -> Result<Vec<String>> {
+        self.vault.ensure_unlocked()?;
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare("SELECT key FROM offline_entries ORDER BY key")?;
+        let keys = stmt
+            .query_map([], |row| row.get(0))?
+            .collect::<rusqlite::Result<Vec<String>>>()?;
+        Ok(keys)
+    }
+
+    /// Importa los archivos `{key}.json` del antiguo directorio offline.
+    ///
+    /// Se ejecuta en una sola transacción; si termina bien, el directorio se
+    /// renombra a `offline_data.migrated` para que la migración no se repita.
+    /// Devuelve el número de claves importadas.
+    pub fn migrate_json_dir(&self, legacy_dir: &Path) -> Result<usize> {
+        if !legacy_dir.is_dir() {
+            return Ok(0);
+        }
+
+        let mut imported = 0;
+        {
+            let mut conn = self.conn()?;
+            let tx = conn.transaction()?;
+            for entry in fs::read_dir(legacy_dir)? {
+                let path = entry?.path();
+                if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                    continue;
+                }
+                let Some(key) = path.file_stem().and_then(|s| s.to_str()) else {
+                    continue;
+                };
+                let value = fs::read_to_string(&path)?;
+                let modified = fs::metadata(&path)
+                    .and_then(|m| m.modified())
+                    .map(system_time_millis)
+                    .unwrap_or_else(|_| now_millis());
+
+                // Si la clave ya existe en SQLite se conserva: es más reciente que el archivo
+                tx.execute(
+                    "INSERT INTO offline_entries (key, value, metadata, created_at, updated_at)
+                     VALUES (?1, ?2, '{}', ?3, ?3)
+                     ON CONFLICT(key) DO NOTHING",
+                    params![key, value, modified],
+                )?;
+                imported += 1;
+            }
+            tx.commit()?;
+        }
+
+        let backup = legacy_dir.with_file_name("offline_data.migrated");
+        if backup.exists() {
+            fs::remove_dir_all(&backup)?;
+        }
+        fs::rename(legacy_dir, backup)?;
+
+        Ok(imported)
+    }
+}
+
+/// Configura la conexión y aplica las migraciones pendientes
+fn migrate(mut conn: Connection) -> Result<Connection> {
+    conn.pragma_update(None, "journal_mode", "WAL")?;
+    conn.pragma_update(None, "foreign_keys", "ON")?;
+
+    let version: usize = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+        let tx = conn.transaction()?;
+        tx.execute_batch(migration)?;
+        tx.pragma_update(None, "user_version", index + 1)?;
+        tx.commit()?;
+    }
+    Ok(conn)
+}
+
+fn system_time_millis(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or(0)
+}
+
+/// Milisegundos desde epoch
+pub(crate) fn now_millis() -> i64 {
+    system_time_millis(SystemTime::now())
+}
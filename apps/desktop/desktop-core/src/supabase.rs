@@ -0,0 +1,822 @@
+use reqwest::header::IF_NONE_MATCH;
+use reqwest::StatusCode;
+use serde::Serialize;
+use std::future::Future;
+use tauri::{AppHandle, Emitter, Manager, Runtime, State};
+
+use crate::cache::{
+    CacheMeta, CacheMode, CachePolicy, CacheUpdated, Freshness, CACHE_UPDATED_EVENT,
+};
+use crate::config::{AppConfig, SupabaseConfig};
+use crate::error::{Error, Result};
+use crate::http::HttpClient;
+use crate::outbox::{send_or_enqueue, Mutation};
+use crate::paths::validate_key;
+use crate::query::Query;
+use crate::response::SupabaseResponse;
+use crate::session::{is_transient, resolve_token};
+use crate::store::{now_millis, OfflineEntry, OfflineStore};
+
+// ============================================
+// SUPABASE INTEGRATION
+// ============================================
+
+/// Proyecto de Supabase de la configuración efectiva (URL y clave anónima,
+/// que el webview necesita para su propio cliente)
+#[tauri::command]
+pub async fn get_supabase_config(config: State<'_, AppConfig>) -> Result<SupabaseConfig> {
+    config.supabase()
+}
+
+// ============================================
+// API PROXY COMMANDS
+// ============================================
+
+/// Hace una petición GET a Supabase.
+///
+/// Con `cache_key` la respuesta se guarda en el almacén offline junto a su
+/// fecha de obtención y ETag, y se sirve según `cache` (por defecto
+/// `cache_first` con 5 minutos de TTL). Si se sirve una entrada vencida se
+/// refresca en segundo plano y se emite `supabase-cache-updated` cuando
+/// llegan datos nuevos. Los estados no 2xx se devuelven como error.
+///
+/// Sin `access_token` se usa el de la sesión del plugin (`set_session`).
+#[tauri::command]
+pub async fn supabase_get<R: Runtime>(
+    app_handle: AppHandle<R>,
+    http: State<'_, HttpClient>,
+    store: State<'_, OfflineStore>,
+    endpoint: String,
+    access_token: Option<String>,
+    cache_key: Option<String>,
+    cache: Option<CachePolicy>,
+) -> Result<SupabaseResponse> {
+    let request = GetRequest {
+        endpoint,
+        prefer: None,
+    };
+    proxied_get(
+        app_handle,
+        &http,
+        &store,
+        request,
+        access_token,
+        cache_key,
+        cache,
+    )
+    .await
+}
+
+/// Consulta una tabla de Supabase a partir de una descripción estructurada
+/// (tabla, columnas, filtros, orden, paginación y `count`) en lugar de una
+/// ruta escrita a mano. La caché funciona igual que en `supabase_get`.
+#[tauri::command]
+pub async fn supabase_query<R: Runtime>(
+    app_handle: AppHandle<R>,
+    http: State<'_, HttpClient>,
+    store: State<'_, OfflineStore>,
+    query: Query,
+    access_token: Option<String>,
+    cache_key: Option<String>,
+    cache: Option<CachePolicy>,
+) -> Result<SupabaseResponse> {
+    let request = GetRequest {
+        endpoint: query.endpoint()?,
+        prefer: query.prefer(),
+    };
+    proxied_get(
+        app_handle,
+        &http,
+        &store,
+        request,
+        access_token,
+        cache_key,
+        cache,
+    )
+    .await
+}
+
+/// GET a Supabase: ruta relativa al proyecto y cabecera `Prefer` opcional
+#[derive(Debug, Clone)]
+struct GetRequest {
+    endpoint: String,
+    prefer: Option<String>,
+}
+
+async fn proxied_get<R: Runtime>(
+    app_handle: AppHandle<R>,
+    http: &HttpClient,
+    store: &OfflineStore,
+    request: GetRequest,
+    access_token: Option<String>,
+    cache_key: Option<String>,
+    cache: Option<CachePolicy>,
+) -> Result<SupabaseResponse> {
+    // El token solo se resuelve si hay que ir a la red
+    let token = resolve_token(&app_handle, access_token.clone());
+    let (response, stale) = cached_get(
+        http,
+        store,
+        &request,
+        token,
+        cache_key.as_deref(),
+        &cache.unwrap_or_default(),
+    )
+    .await?;
+    if let (true, Some(key)) = (stale, cache_key) {
+        spawn_refresh(app_handle, key, request, access_token);
+    }
+    Ok(response)
+}
+
+/// Resuelve un GET según la política de caché. Devuelve la respuesta y si
+/// se sirvió una entrada vencida que hay que refrescar en segundo plano.
+///
+/// `access_token` solo se espera cuando hay que pedir a la red, así que una
+/// entrada en caché se sirve aunque no se pueda refrescar la sesión.
+async fn cached_get(
+    http: &HttpClient,
+    store: &OfflineStore,
+    request: &GetRequest,
+    access_token: impl Future<Output = Result<String>>,
+    cache_key: Option<&str>,
+    policy: &CachePolicy,
+) -> Result<(SupabaseResponse, bool)> {
+    let Some(key) = cache_key else {
+        let access_token = access_token.await?;
+        let (response, _) =
+            fetch_and_cache(http, store, None, request, &access_token, None).await?;
+        return Ok((response.into_result()?, false));
+    };
+    validate_key(key)?;
+
+    let cached = store.get_entry(key).ok().flatten();
+    let freshness = cached
+        .as_ref()
+        .map(|entry| policy.freshness(&CacheMeta::from_entry(entry), now_millis()));
+
+    let (response, stale) = match policy.mode {
+        CacheMode::CacheFirst => match (cached, freshness) {
+            (Some(entry), Some(Freshness::Fresh)) => (cached_response(entry), false),
+            (Some(entry), Some(Freshness::Stale)) => (cached_response(entry), true),
+            (cached, _) => {
+                let access_token = access_token.await?;
+                let fetched = fetch_and_cache(
+                    http,
+                    store,
+                    Some(key),
+                    request,
+                    &access_token,
+                    cached.as_ref(),
+                )
+                .await?;
+                (fetched.0, false)
+            }
+        },
+        CacheMode::NetworkFirst => {
+            let fetched = match access_token.await {
+                Ok(access_token) => {
+                    fetch_and_cache(
+                        http,
+                        store,
+                        Some(key),
+                        request,
+                        &access_token,
+                        cached.as_ref(),
+                    )
+                    .await
+                }
+                Err(e) => Err(e),
+            };
+            // Sin red (ni para refrescar el token) o con Supabase caído (5xx,
+            // 429) se recurre a la caché mientras no haya superado max_stale
+            let unavailable = match &fetched {
+                Ok((response, _)) => is_outage(response.status_code()),
+                Err(e) => is_transient(e),
+            };
+            match (unavailable, cached, freshness) {
+                (true, Some(entry), Some(Freshness::Fresh | Freshness::Stale)) => {
+                    (cached_response(entry), false)
+                }
+                _ => (fetched?.0, false),
+            }
+        }
+    };
+    Ok((response.into_result()?, stale))
+}
+
+/// Estados con los que Supabase no está atendiendo, no rechazando la petición
+fn is_outage(status: StatusCode) -> bool {
+    status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS
+}
+
+/// Respuesta servida desde la caché, con las cabeceras que se guardaron
+fn cached_response(entry: OfflineEntry) -> SupabaseResponse {
+    let meta = CacheMeta::from_entry(&entry);
+    SupabaseResponse {
+        content_range: meta.content_range,
+        etag: meta.etag,
+        ..SupabaseResponse::ok(entry.value)
+    }
+}
+
+/// Refresca una entrada en segundo plano y avisa al webview si cambió. Si
+/// no se consigue un token se deja la entrada como está.
+fn spawn_refresh<R: Runtime>(
+    app_handle: AppHandle<R>,
+    key: String,
+    request: GetRequest,
+    access_token: Option<String>,
+) {
+    tauri::async_runtime::spawn(async move {
+        let Ok(access_token) = resolve_token(&app_handle, access_token).await else {
+            return;
+        };
+        let http = app_handle.state::<HttpClient>();
+        let store = app_handle.state::<OfflineStore>();
+        let cached = store.get_entry(&key).ok().flatten();
+        if let Ok((_, true)) = fetch_and_cache(
+            &http,
+            &store,
+            Some(&key),
+            &request,
+            &access_token,
+            cached.as_ref(),
+        )
+        .await
+        {
+            let _ = app_handle.emit(
+                CACHE_UPDATED_EVENT,
+                CacheUpdated {
+                    cache_key: key,
+                    endpoint: request.endpoint,
+                    fetched_at: now_millis(),
+                },
+            );
+        }
+    });
+}
+
+/// Hace el GET (condicional si hay ETag en caché) y guarda la respuesta.
+/// Devuelve la respuesta y si difiere de lo que había en caché.
+async fn fetch_and_cache(
+    http: &HttpClient,
+    store: &OfflineStore,
+    key: Option<&str>,
+    get: &GetRequest,
+    access_token: &str,
+    cached: Option<&OfflineEntry>,
+) -> Result<(SupabaseResponse, bool)> {
+    let config = http.supabase()?;
+    let cached_meta = cached.map(CacheMeta::from_entry);
+
+    let mut request = http
+        .client()
+        .get(endpoint_url(&config, &get.endpoint)?)
+        .header("apikey", &config.anon_key)
+        .header("Authorization", format!("Bearer {}", access_token));
+    if let Some(prefer) = &get.prefer {
+        request = request.header("Prefer", prefer);
+    }
+    if let Some(etag) = cached_meta.as_ref().and_then(|meta| meta.etag.as_deref()) {
+        request = request.header(IF_NONE_MATCH, etag);
+    }
+    let response = SupabaseResponse::read(http.send(request).await?).await?;
+    let fetched_at = now_millis();
+
+    if response.status_code() == StatusCode::NOT_MODIFIED {
+        if let (Some(key), Some(entry), Some(mut meta)) = (key, cached, cached_meta) {
+            meta.fetched_at = fetched_at;
+            store.set(key, &entry.value, Some(&meta.to_value()))?;
+            return Ok((cached_response(entry.clone()), false));
+        }
+    }
+
+    // Solo se cachean respuestas exitosas, y solo con el almacén desbloqueado
+    let mut changed = false;
+    if let (Some(key), true) = (key, response.is_success() && store.vault().is_unlocked()) {
+        changed = cached.is_none_or(|entry| entry.value != response.body);
+        let meta = CacheMeta {
+            fetched_at,
+            etag: response.etag.clone(),
+            content_range: response.content_range.clone(),
+            endpoint: Some(get.endpoint.clone()),
+        };
+        store.set(key, &response.body, Some(&meta.to_value()))?;
+    }
+
+    Ok((response, changed))
+}
+
+/// URL completa de una ruta del proyecto. La ruta se concatena tal cual a
+/// la URL base, así que debe empezar por `/` y no traer espacios ni fragmentos.
+pub(crate) fn endpoint_url(config: &SupabaseConfig, endpoint: &str) -> Result<String> {
+    let valid = endpoint.starts_with('/')
+        && !endpoint.starts_with("//")
+        && !endpoint
+            .chars()
+            .any(|c| c.is_whitespace() || c.is_control() || c == '#');
+    if !valid {
+        return Err(Error::Validation(format!(
+            "ruta de Supabase no válida: {:?}",
+            endpoint
+        )));
+    }
+    Ok(format!("{}{}", config.url, endpoint))
+}
+
+/// Método HTTP de una mutación
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum MutationMethod {
+    Post,
+    Patch,
+    Delete,
+}
+
+impl MutationMethod {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            MutationMethod::Post => "POST",
+            MutationMethod::Patch => "PATCH",
+            MutationMethod::Delete => "DELETE",
+        }
+    }
+
+    pub fn parse(method: &str) -> Option<Self> {
+        match method {
+            "POST" => Some(MutationMethod::Post),
+            "PATCH" => Some(MutationMethod::Patch),
+            "DELETE" => Some(MutationMethod::Delete),
+            _ => None,
+        }
+    }
+}
+
+/// Envía una mutación a Supabase con su clave de idempotencia.
+/// Devuelve la respuesta sea cual sea su estado HTTP.
+pub(crate) async fn send_mutation(
+    http: &HttpClient,
+    method: MutationMethod,
+    endpoint: &str,
+    body: Option<&str>,
+    access_token: &str,
+    idempotency_key: &str,
+    prefer: Option<&str>,
+) -> Result<SupabaseResponse> {
+    let config = http.supabase()?;
+    let client = http.client();
+    let url = endpoint_url(&config, endpoint)?;
+
+    let mut request = match method {
+        MutationMethod::Post => client.post(url),
+        MutationMethod::Patch => client.patch(url),
+        MutationMethod::Delete => client.delete(url),
+    }
+    .header("apikey", &config.anon_key)
+    .header("Authorization", format!("Bearer {}", access_token))
+    .header("Idempotency-Key", idempotency_key);
+    if let Some(body) = body {
+        request = request
+            .header("Content-Type", "application/json")
+            .body(body.to_string());
+    }
+    if let Some(prefer) = prefer {
+        request = request.header("Prefer", prefer);
+    }
+
+    // Si el cuerpo no llega, la mutación ya se aplicó o no: tampoco se sabe
+    let response = http.send_unrepeatable(request).await?;
+    SupabaseResponse::read(response).await.map_err(|e| match e {
+        Error::Network(message) => Error::Unconfirmed(message),
+        e => e,
+    })
+}
+
+/// GET sin caché. Devuelve la respuesta sea cual sea su estado HTTP.
+pub(crate) async fn send_get(
+    http: &HttpClient,
+    endpoint: &str,
+    access_token: &str,
+) -> Result<SupabaseResponse> {
+    let config = http.supabase()?;
+    let request = http
+        .client()
+        .get(endpoint_url(&config, endpoint)?)
+        .header("apikey", &config.anon_key)
+        .header("Authorization", format!("Bearer {}", access_token));
+    SupabaseResponse::read(http.send(request).await?).await
+}
+
+/// Hace una petición POST a Supabase.
+/// Si no hay conexión se guarda en la cola offline y devuelve `QUEUED`.
+#[tauri::command]
+pub async fn supabase_post<R: Runtime>(
+    app_handle: AppHandle<R>,
+    endpoint: String,
+    body: String,
+    access_token: Option<String>,
+    idempotency_key: Option<String>,
+) -> Result<SupabaseResponse> {
+    let mutation = Mutation::new(MutationMethod::Post, endpoint, Some(body), idempotency_key);
+    send_or_enqueue(&app_handle, mutation, access_token).await
+}
+
+/// Hace una petición PATCH a Supabase.
+/// Si no hay conexión se guarda en la cola offline y devuelve `QUEUED`.
+///
+/// `base` es la fila (o al menos su `updated_at`) sobre la que se hizo la
+/// edición; con ella se detectan conflictos y se aplica la política de la
+/// tabla. Un conflicto sin resolver devuelve `CONFLICT`.
+#[tauri::command]
+pub async fn supabase_patch<R: Runtime>(
+    app_handle: AppHandle<R>,
+    endpoint: String,
+    body: String,
+    access_token: Option<String>,
+    idempotency_key: Option<String>,
+    base: Option<serde_json::Value>,
+) -> Result<SupabaseResponse> {
+    let mut mutation = Mutation::new(MutationMethod::Patch, endpoint, Some(body), idempotency_key);
+    mutation.base = base;
+    send_or_enqueue(&app_handle, mutation, access_token).await
+}
+
+/// Hace una petición DELETE a Supabase.
+/// Si no hay conexión se guarda en la cola offline y devuelve `QUEUED`.
+#[tauri::command]
+pub async fn supabase_delete<R: Runtime>(
+    app_handle: AppHandle<R>,
+    endpoint: String,
+    access_token: Option<String>,
+    idempotency_key: Option<String>,
+) -> Result<SupabaseResponse> {
+    let mutation = Mutation::new(MutationMethod::Delete, endpoint, None, idempotency_key);
+    send_or_enqueue(&app_handle, mutation, access_token).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{client, unlocked_store};
+    use red_salud_supabase_mock::{MockResponse, MockSupabase, ANON_KEY};
+
+    const CITAS: &str = "/rest/v1/citas";
+
+    fn jwt() -> std::future::Ready<Result<String>> {
+        std::future::ready(Ok("jwt".into()))
+    }
+
+    fn get(endpoint: &str) -> GetRequest {
+        GetRequest {
+            endpoint: endpoint.into(),
+            prefer: None,
+        }
+    }
+
+    fn policy(mode: CacheMode, ttl_secs: u64) -> CachePolicy {
+        CachePolicy {
+            mode,
+            ttl_secs,
+            max_stale_secs: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn cache_first_serves_fresh_entries_without_the_network() {
+        let mock = MockSupabase::start();
+        mock.on("GET", CITAS).always(
+            MockResponse::json(200, r#"[{"id":1}]"#)
+                .etag("\"v1\"")
+                .header("content-range", "0-0/1"),
+        );
+        let http = client(&mock);
+        let (store, _root) = unlocked_store();
+        let policy = policy(CacheMode::CacheFirst, 300);
+
+        for _ in 0..2 {
+            let (response, stale) =
+                cached_get(&http, &store, &get(CITAS), jwt(), Some("citas"), &policy)
+                    .await
+                    .unwrap();
+            assert!(!stale);
+            assert_eq!(response.body, r#"[{"id":1}]"#);
+            assert_eq!(response.etag.as_deref(), Some("\"v1\""));
+            assert_eq!(response.content_range.as_deref(), Some("0-0/1"));
+        }
+        let requests = mock.requests_to("GET", CITAS);
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].header("apikey"), Some(ANON_KEY));
+        assert_eq!(requests[0].header("authorization"), Some("Bearer jwt"));
+    }
+
+    #[tokio::test]
+    async fn stale_entries_are_served_and_revalidated_with_the_etag() {
+        let mock = MockSupabase::start();
+        mock.on("GET", CITAS)
+            .always(MockResponse::json(200, "[]").etag("\"v1\""));
+        let http = client(&mock);
+        let (store, _root) = unlocked_store();
+        let policy = policy(CacheMode::CacheFirst, 0);
+
+        cached_get(&http, &store, &get(CITAS), jwt(), Some("citas"), &policy)
+            .await
+            .unwrap();
+        let (response, stale) =
+            cached_get(&http, &store, &get(CITAS), jwt(), Some("citas"), &policy)
+                .await
+                .unwrap();
+        assert!(stale);
+        assert_eq!(response.body, "[]");
+        assert_eq!(mock.requests_to("GET", CITAS).len(), 1);
+
+        // El refresco en segundo plano manda el ETag y recibe 304
+        let cached = store.get_entry("citas").unwrap();
+        let (response, changed) = fetch_and_cache(
+            &http,
+            &store,
+            Some("citas"),
+            &get(CITAS),
+            "jwt",
+            cached.as_ref(),
+        )
+        .await
+        .unwrap();
+        assert!(!changed);
+        assert_eq!(response.body, "[]");
+        let requests = mock.requests_to("GET", CITAS);
+        assert_eq!(requests[1].header("if-none-match"), Some("\"v1\""));
+    }
+
+    #[tokio::test]
+    async fn network_first_falls_back_to_the_cache_without_network() {
+        let mut mock = MockSupabase::start();
+        mock.on("GET", CITAS)
+            .always(MockResponse::json(200, r#"[{"id":2}]"#));
+        let http = client(&mock);
+        let (store, _root) = unlocked_store();
+        let policy = policy(CacheMode::NetworkFirst, 300);
+
+        cached_get(&http, &store, &get(CITAS), jwt(), Some("citas"), &policy)
+            .await
+            .unwrap();
+        mock.stop();
+
+        let (response, stale) =
+            cached_get(&http, &store, &get(CITAS), jwt(), Some("citas"), &policy)
+                .await
+                .unwrap();
+        assert!(!stale);
+        assert_eq!(response.body, r#"[{"id":2}]"#);
+
+        let error = cached_get(&http, &store, &get(CITAS), jwt(), Some("otra"), &policy)
+            .await
+            .unwrap_err();
+        assert!(matches!(error, Error::Network(_)), "{error:?}");
+    }
+
+    #[tokio::test]
+    async fn network_first_falls_back_to_the_cache_during_outages() {
+        let mock = MockSupabase::start();
+        mock.on("GET", CITAS)
+            .once(MockResponse::json(200, r#"[{"id":4}]"#))
+            .once(MockResponse::json(429, r#"{"message":"rate limited"}"#))
+            .always(MockResponse::json(503, r#"{"message":"unavailable"}"#));
+        let http = client(&mock);
+        let (store, _root) = unlocked_store();
+        let policy = policy(CacheMode::NetworkFirst, 300);
+
+        for _ in 0..3 {
+            let (response, stale) =
+                cached_get(&http, &store, &get(CITAS), jwt(), Some("citas"), &policy)
+                    .await
+                    .unwrap();
+            assert!(!stale);
+            assert_eq!(response.body, r#"[{"id":4}]"#);
+        }
+
+        // Sin entrada en caché el error llega tal cual
+        let error = cached_get(&http, &store, &get(CITAS), jwt(), Some("otra"), &policy)
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(error, Error::Http { status: 503, .. }),
+            "{error:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn cached_entries_are_served_when_the_token_cannot_be_refreshed() {
+        let mock = MockSupabase::start();
+        mock.on("GET", CITAS)
+            .always(MockResponse::json(200, r#"[{"id":5}]"#));
+        let http = client(&mock);
+        let (store, _root) = unlocked_store();
+        let offline = || std::future::ready(Err(Error::Network("sin conexión".into())));
+
+        let cache_first = policy(CacheMode::CacheFirst, 300);
+        cached_get(
+            &http,
+            &store,
+            &get(CITAS),
+            jwt(),
+            Some("citas"),
+            &cache_first,
+        )
+        .await
+        .unwrap();
+        let (response, _) = cached_get(
+            &http,
+            &store,
+            &get(CITAS),
+            offline(),
+            Some("citas"),
+            &cache_first,
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.body, r#"[{"id":5}]"#);
+
+        let network_first = policy(CacheMode::NetworkFirst, 300);
+        let (response, _) = cached_get(
+            &http,
+            &store,
+            &get(CITAS),
+            offline(),
+            Some("citas"),
+            &network_first,
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.body, r#"[{"id":5}]"#);
+        assert_eq!(mock.requests_to("GET", CITAS).len(), 1);
+
+        // Una sesión rechazada no es una caída: el error llega al webview
+        let rejected = std::future::ready(Err(Error::auth("la sesión expiró")));
+        let error = cached_get(
+            &http,
+            &store,
+            &get(CITAS),
+            rejected,
+            Some("citas"),
+            &network_first,
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(error, Error::Auth { .. }), "{error:?}");
+    }
+
+    #[tokio::test]
+    async fn error_statuses_become_typed_errors_and_are_not_cached() {
+        let mock = MockSupabase::start();
+        mock.on("GET", CITAS)
+            .once(MockResponse::postgrest_error(
+                403,
+                "42501",
+                "permiso denegado",
+            ))
+            .once(MockResponse::json(401, r#"{"message":"JWT expired"}"#));
+        let http = client(&mock);
+        let (store, _root) = unlocked_store();
+        let policy = CachePolicy::default();
+
+        let error = cached_get(&http, &store, &get(CITAS), jwt(), Some("citas"), &policy)
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(&error, Error::Http { status: 403, message } if message == "permiso denegado"),
+            "{error:?}"
+        );
+        let error = cached_get(&http, &store, &get(CITAS), jwt(), None, &policy)
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(
+                error,
+                Error::Auth {
+                    status: Some(401),
+                    ..
+                }
+            ),
+            "{error:?}"
+        );
+        assert!(store.get_entry("citas").unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn mutations_without_a_reply_are_unconfirmed() {
+        let mock = MockSupabase::start();
+        mock.on("POST", CITAS)
+            .once(MockResponse::json(201, "[]").close_connection());
+        let http = client(&mock);
+
+        let error = send_mutation(
+            &http,
+            MutationMethod::Post,
+            CITAS,
+            Some("{}"),
+            "jwt",
+            "clave-1",
+            None,
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(error.code(), "UNCONFIRMED");
+        assert_eq!(mock.requests_to("POST", CITAS).len(), 1);
+    }
+
+    #[tokio::test]
+    async fn mutations_carry_the_idempotency_key_and_preferences() {
+        let mock = MockSupabase::start();
+        mock.on("POST", CITAS).once(
+            MockResponse::json(201, r#"[{"id":3}]"#)
+                .header("preference-applied", "return=representation"),
+        );
+        let http = client(&mock);
+
+        let response = send_mutation(
+            &http,
+            MutationMethod::Post,
+            CITAS,
+            Some(r#"{"motivo":"control"}"#),
+            "jwt",
+            "clave-1",
+            Some("return=representation"),
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.status, 201);
+        assert_eq!(
+            response.preference_applied.as_deref(),
+            Some("return=representation")
+        );
+
+        let request = &mock.requests_to("POST", CITAS)[0];
+        assert_eq!(request.header("idempotency-key"), Some("clave-1"));
+        assert_eq!(request.header("prefer"), Some("return=representation"));
+        assert_eq!(request.header("content-type"), Some("application/json"));
+        assert_eq!(request.body, r#"{"motivo":"control"}"#);
+    }
+
+    #[tokio::test]
+    async fn structured_queries_send_the_encoded_path_and_prefer_header() {
+        let mock = MockSupabase::start();
+        mock.on("GET", "/rest/v1/patients")
+            .always(MockResponse::json(200, "[]").header("content-range", "*/0"));
+        let http = client(&mock);
+        let (store, _root) = unlocked_store();
+        let query: Query = serde_json::from_value(serde_json::json!({
+            "table": "patients",
+            "filters": [{ "column": "nombre", "op": "ilike", "value": "%pérez%" }],
+            "count": "exact"
+        }))
+        .unwrap();
+        let request = GetRequest {
+            endpoint: query.endpoint().unwrap(),
+            prefer: query.prefer(),
+        };
+
+        let (response, _) = cached_get(
+            &http,
+            &store,
+            &request,
+            jwt(),
+            None,
+            &CachePolicy::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.content_range.as_deref(), Some("*/0"));
+        let recorded = &mock.requests_to("GET", "/rest/v1/patients")[0];
+        assert_eq!(recorded.header("prefer"), Some("count=exact"));
+        assert_eq!(
+            recorded.query.as_deref(),
+            Some("select=*&nombre=ilike.%25p%C3%A9rez%25")
+        );
+    }
+
+    #[test]
+    fn endpoints_must_be_project_relative_paths() {
+        let config = SupabaseConfig {
+            url: "https://x.supabase.co".into(),
+            anon_key: ANON_KEY.into(),
+        };
+        assert_eq!(
+            endpoint_url(&config, "/rest/v1/citas?id=eq.1").unwrap(),
+            "https://x.supabase.co/rest/v1/citas?id=eq.1"
+        );
+        for endpoint in [
+            "rest/v1/citas",
+            "//evil.test/x",
+            "/rest/v1/citas?id=eq.1 2",
+            "/a#b",
+            "",
+        ] {
+            assert_eq!(
+                endpoint_url(&config, endpoint).unwrap_err().code(),
+                "VALIDATION",
+                "{endpoint}"
+            );
+        }
+    }
+}
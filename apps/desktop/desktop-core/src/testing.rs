@@ -0,0 +1,46 @@
+// Utilidades de prueba
+// Conectan el cliente HTTP y el almacén offline al doble de Supabase
+// (`red-salud-supabase-mock`) para probar el proxy sin red.
+
+use red_salud_supabase_mock::{MockSupabase, ANON_KEY};
+use std::time::Duration;
+use tempfile::TempDir;
+
+use crate::config::SupabaseConfig;
+use crate::http::{HttpClient, HttpSettings};
+use crate::store::OfflineStore;
+
+/// Timeout de lectura corto para probar la latencia sin alargar las pruebas
+pub(crate) const READ_TIMEOUT: Duration = Duration::from_millis(300);
+
+/// Cliente que apunta al doble, con timeouts cortos y dos reintentos
+pub(crate) fn client(mock: &MockSupabase) -> HttpClient {
+    let settings = HttpSettings {
+        connect_timeout: Duration::from_secs(1),
+        read_timeout: READ_TIMEOUT,
+        ..HttpSettings::default()
+    };
+    HttpClient::new(&settings, "red-salud-tests")
+        .unwrap()
+        .with_supabase(Ok(SupabaseConfig {
+            url: mock.url(),
+            anon_key: ANON_KEY.into(),
+        }))
+}
+
+/// Almacén con un perfil abierto y desbloqueado, para probar archivos
+pub(crate) fn profile_store() -> (OfflineStore, TempDir) {
+    let root = tempfile::tempdir().unwrap();
+    let store = OfflineStore::open_in_memory().unwrap();
+    let profile = crate::profiles::activate(&store, root.path(), "u1", None).unwrap();
+    crate::vault::unlock(&store, &profile.dir, "secreto", 1_000).unwrap();
+    (store, root)
+}
+
+/// Almacén en memoria desbloqueado; el directorio debe vivir tanto como él
+pub(crate) fn unlocked_store() -> (OfflineStore, TempDir) {
+    let root = tempfile::tempdir().unwrap();
+    let store = OfflineStore::open_in_memory().unwrap();
+    crate::vault::unlock(&store, root.path(), "secreto", 1_000).unwrap();
+    (store, root)
+}
@@ -0,0 +1,1042 @@
+// Cifrado en reposo
+// Los valores del almacén offline (entradas, cola y conflictos) y los archivos
+// guardados por el plugin se cifran con AES-256-GCM. La clave de datos es
+// aleatoria y se guarda envuelta con una clave derivada (PBKDF2-HMAC-SHA256)
+// del secreto de inicio de sesión, así que sin iniciar sesión no se puede
+// leer nada de lo guardado.
+//
+// Formato de un bloque cifrado: `RSE1` | id de clave (u32 BE) | nonce (12) |
+// texto cifrado + etiqueta (16).
+//
+// Los archivos se cifran por bloques para leerlos y escribirlos sin cargarlos
+// enteros: `RSF1` | id de clave (u32 BE) | prefijo de nonce (8) | bloques de
+// 64 KiB en claro + etiqueta. El nonce de cada bloque es el prefijo y su
+// número, y el AAD marca el último (que siempre tiene menos de 64 KiB, aunque
+// sea vacío), así que no se pueden reordenar, mezclar ni truncar.
+
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use ring::pbkdf2;
+use ring::rand::{SecureRandom, SystemRandom};
+use rusqlite::{params, OptionalExtension};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Read, Write};
+use std::num::NonZeroU32;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use tauri::{AppHandle, Manager, Runtime, State};
+
+use crate::conflicts;
+use crate::error::{Error, Result};
+use crate::outbox;
+use crate::paths::APP_RESERVED;
+use crate::profiles::{close, require_active};
+use crate::store::{now_millis, OfflineStore};
+
+pub(crate) const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS vault (
+        id         INTEGER PRIMARY KEY CHECK (id = 1),
+        salt       BLOB NOT NULL,
+        iterations INTEGER NOT NULL,
+        created_at INTEGER NOT NULL,
+        updated_at INTEGER NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS vault_keys (
+        id          INTEGER PRIMARY KEY AUTOINCREMENT,
+        wrapped_key BLOB NOT NULL,
+        created_at  INTEGER NOT NULL
+    );
+";
+
+const MAGIC: &[u8; 4] = b"RSE1";
+const HEADER_LEN: usize = MAGIC.len() + 4 + NONCE_LEN;
+const KEY_LEN: usize = 32;
+const SALT_LEN: usize = 16;
+/// Iteraciones de PBKDF2 para claves nuevas (recomendación OWASP para SHA-256)
+pub(crate) const KDF_ITERATIONS: u32 = 600_000;
+/// AAD con que se envuelven las claves de datos
+const WRAP_AAD: &[u8] = b"red-salud:vault-key";
+/// AAD de los archivos; los valores del almacén usan su propia clave
+const FILE_AAD: &[u8] = b"red-salud:file";
+const FILE_MAGIC: &[u8; 4] = b"RSF1";
+const FILE_PREFIX_LEN: usize = NONCE_LEN - 4;
+const FILE_HEADER_LEN: usize = FILE_MAGIC.len() + 4 + FILE_PREFIX_LEN;
+/// Bytes en claro de cada bloque de un archivo
+pub(crate) const FILE_CHUNK: usize = 64 * 1024;
+/// Carpeta de `app_cache_dir` donde se descifran los archivos para abrirlos
+pub(crate) const OPENED_DIR: &str = "opened";
+
+/// ¿Es `data` un bloque cifrado por el plugin?
+pub fn is_sealed(data: &[u8]) -> bool {
+    data.len() >= HEADER_LEN && data.starts_with(MAGIC)
+}
+
+/// ¿Es `data` (o su comienzo) un archivo cifrado por el plugin, por bloques o
+/// en el formato anterior de un solo bloque?
+pub fn is_sealed_file(data: &[u8]) -> bool {
+    data.starts_with(FILE_MAGIC) || is_sealed(data)
+}
+
+fn random<const N: usize>() -> Result<[u8; N]> {
+    let mut bytes = [0u8; N];
+    SystemRandom::new()
+        .fill(&mut bytes)
+        .map_err(|_| Error::Storage("no se pudieron generar bytes aleatorios".into()))?;
+    Ok(bytes)
+}
+
+fn aead_key(bytes: &[u8]) -> Result<LessSafeKey> {
+    let key = UnboundKey::new(&AES_256_GCM, bytes)
+        .map_err(|_| Error::Storage("clave de cifrado no válida".into()))?;
+    Ok(LessSafeKey::new(key))
+}
+
+fn seal_with(key: &LessSafeKey, key_id: u32, plaintext: &[u8], aad: &[u8]) -> Result<Vec<u8>> {
+    let nonce = random::<NONCE_LEN>()?;
+    let mut out = Vec::with_capacity(HEADER_LEN + plaintext.len() + AES_256_GCM.tag_len());
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&key_id.to_be_bytes());
+    out.extend_from_slice(&nonce);
+    let mut body = plaintext.to_vec();
+    key.seal_in_place_append_tag(
+        Nonce::assume_unique_for_key(nonce),
+        Aad::from(aad),
+        &mut body,
+    )
+    .map_err(|_| Error::Storage("no se pudo cifrar".into()))?;
+    out.extend_from_slice(&body);
+    Ok(out)
+}
+
+/// Id de la clave con que se cifró un bloque
+fn sealed_key_id(data: &[u8]) -> Result<u32> {
+    if !is_sealed(data) {
+        return Err(Error::Storage("el bloque no está cifrado".into()));
+    }
+    let mut id = [0u8; 4];
+    id.copy_from_slice(&data[MAGIC.len()..MAGIC.len() + 4]);
+    Ok(u32::from_be_bytes(id))
+}
+
+fn open_with(key: &LessSafeKey, data: &[u8], aad: &[u8]) -> Result<Vec<u8>> {
+    sealed_key_id(data)?;
+    let nonce = Nonce::try_assume_unique_for_key(&data[MAGIC.len() + 4..HEADER_LEN])
+        .map_err(|_| Error::Storage("nonce no válido".into()))?;
+    let mut body = data[HEADER_LEN..].to_vec();
+    let plaintext = key
+        .open_in_place(nonce, Aad::from(aad), &mut body)
+        .map_err(|_| Error::Storage("datos cifrados corruptos o manipulados".into()))?;
+    Ok(plaintext.to_vec())
+}
+
+/// Clave derivada del secreto de inicio de sesión
+fn derive_kek(secret: &str, salt: &[u8], iterations: u32) -> Result<LessSafeKey> {
+    let iterations = NonZeroU32::new(iterations)
+        .ok_or_else(|| Error::Storage("iteraciones de KDF no válidas".into()))?;
+    let mut bytes = [0u8; KEY_LEN];
+    pbkdf2::derive(
+        pbkdf2::PBKDF2_HMAC_SHA256,
+        iterations,
+        salt,
+        secret.as_bytes(),
+        &mut bytes,
+    );
+    let key = aead_key(&bytes);
+    bytes.fill(0);
+    key
+}
+
+fn unwrap_key(kek: &LessSafeKey, wrapped: &[u8]) -> Result<LessSafeKey> {
+    let mut bytes = open_with(kek, wrapped, WRAP_AAD)
+        .map_err(|_| Error::auth("el secreto no desbloquea el almacenamiento"))?;
+    let key = aead_key(&bytes);
+    bytes.fill(0);
+    key
+}
+
+/// Claves de datos desbloqueadas en memoria
+struct Keyring {
+    current: u32,
+    keys: HashMap<u32, LessSafeKey>,
+}
+
+/// Estado del cifrado. Mientras está bloqueado no se puede leer ni escribir
+/// nada cifrado.
+#[derive(Default)]
+pub struct Vault {
+    keyring: RwLock<Option<Keyring>>,
+}
+
+impl Vault {
+    pub fn is_unlocked(&self) -> bool {
+        self.keyring.read().map(|k| k.is_some()).unwrap_or(false)
+    }
+
+    pub(crate) fn lock(&self) {
+        if let Ok(mut keyring) = self.keyring.write() {
+            *keyring = None;
+        }
+    }
+
+    fn set_keyring(&self, keyring: Keyring) {
+        if let Ok(mut current) = self.keyring.write() {
+            *current = Some(keyring);
+        }
+    }
+
+    /// Error de autenticación si está bloqueado
+    pub(crate) fn ensure_unlocked(&self) -> Result<()> {
+        self.with_keyring(|_| Ok(()))
+    }
+
+    fn with_keyring<T>(&self, f: impl FnOnce(&Keyring) -> Result<T>) -> Result<T> {
+        let keyring = self
+            .keyring
+            .read()
+            .map_err(|_| Error::Storage("el almacén cifrado quedó bloqueado".into()))?;
+        match keyring.as_ref() {
+            Some(keyring) => f(keyring),
+            None => Err(Error::auth(
+                "el almacenamiento cifrado está bloqueado: inicie sesión",
+            )),
+        }
+    }
+
+    /// Cifra con la clave de datos vigente
+    pub fn seal(&self, plaintext: &[u8], aad: &[u8]) -> Result<Vec<u8>> {
+        self.with_keyring(|keyring| {
+            seal_with(
+                &keyring.keys[&keyring.current],
+                keyring.current,
+                plaintext,
+                aad,
+            )
+        })
+    }
+
+    /// Descifra con la clave indicada en la cabecera del bloque
+    pub fn open(&self, data: &[u8], aad: &[u8]) -> Result<Vec<u8>> {
+        let key_id = sealed_key_id(data)?;
+        self.with_keyring(|keyring| {
+            let key = keyring.keys.get(&key_id).ok_or_else(|| {
+                Error::Storage(format!("no existe la clave de datos #{}", key_id))
+            })?;
+            open_with(key, data, aad)
+        })
+    }
+
+    /// Cifra un archivo
+    pub fn seal_file(&self, plaintext: &[u8]) -> Result<Vec<u8>> {
+        let mut sealer = FileSealer::new(self, Vec::new())?;
+        sealer.write(plaintext)?;
+        sealer.finish()
+    }
+
+    /// Descifra un archivo; los archivos anteriores al cifrado se devuelven tal cual
+    pub fn open_file(&self, data: Vec<u8>) -> Result<Vec<u8>> {
+        FileOpener::new(self, std::io::Cursor::new(data))?.read_to_end()
+    }
+}
+
+fn chunk_nonce(prefix: &[u8; FILE_PREFIX_LEN], index: u32) -> Nonce {
+    let mut nonce = [0u8; NONCE_LEN];
+    nonce[..FILE_PREFIX_LEN].copy_from_slice(prefix);
+    nonce[FILE_PREFIX_LEN..].copy_from_slice(&index.to_be_bytes());
+    Nonce::assume_unique_for_key(nonce)
+}
+
+fn chunk_aad(last: bool) -> Vec<u8> {
+    let mut aad = FILE_AAD.to_vec();
+    aad.push(last as u8);
+    aad
+}
+
+/// Lee hasta llenar `buf` o llegar al final; devuelve cuánto leyó
+fn read_full(reader: &mut impl Read, buf: &mut [u8]) -> Result<usize> {
+    let mut read = 0;
+    while read < buf.len() {
+        match reader.read(&mut buf[read..]) {
+            Ok(0) => break,
+            Ok(n) => read += n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(read)
+}
+
+/// Cifra por bloques lo que se le escribe, con la clave vigente al crearlo
+pub struct FileSealer<'a, W: Write> {
+    vault: &'a Vault,
+    key_id: u32,
+    prefix: [u8; FILE_PREFIX_LEN],
+    index: u32,
+    buffer: Vec<u8>,
+    inner: W,
+}
+
+impl<'a, W: Write> FileSealer<'a, W> {
+    pub fn new(vault: &'a Vault, mut inner: W) -> Result<Self> {
+        let key_id = vault.with_keyring(|keyring| Ok(keyring.current))?;
+        let prefix = random::<FILE_PREFIX_LEN>()?;
+        inner.write_all(FILE_MAGIC)?;
+        inner.write_all(&key_id.to_be_bytes())?;
+        inner.write_all(&prefix)?;
+        Ok(Self {
+            vault,
+            key_id,
+            prefix,
+            index: 0,
+            buffer: Vec::with_capacity(FILE_CHUNK),
+            inner,
+        })
+    }
+
+    pub fn write(&mut self, mut data: &[u8]) -> Result<()> {
+        while !data.is_empty() {
+            let take = (FILE_CHUNK - self.buffer.len()).min(data.len());
+            self.buffer.extend_from_slice(&data[..take]);
+            data = &data[take..];
+            if self.buffer.len() == FILE_CHUNK {
+                self.seal_chunk(false)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn seal_chunk(&mut self, last: bool) -> Result<()> {
+        let nonce = chunk_nonce(&self.prefix, self.index);
+        self.index = self
+            .index
+            .checked_add(1)
+            .ok_or_else(|| Error::Storage("archivo demasiado grande para cifrar".into()))?;
+        let mut body = std::mem::replace(&mut self.buffer, Vec::with_capacity(FILE_CHUNK));
+        self.vault.with_keyring(|keyring| {
+            let key = keyring.keys.get(&self.key_id).ok_or_else(|| {
+                Error::Storage(format!("no existe la clave de datos #{}", self.key_id))
+            })?;
+            key.seal_in_place_append_tag(nonce, Aad::from(chunk_aad(last)), &mut body)
+                .map_err(|_| Error::Storage("no se pudo cifrar".into()))
+        })?;
+        self.inner.write_all(&body)?;
+        Ok(())
+    }
+
+    /// Cifra el último bloque y devuelve el destino
+    pub fn finish(mut self) -> Result<W> {
+        self.seal_chunk(true)?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+enum OpenerSource<R> {
+    Chunked {
+        key_id: u32,
+        prefix: [u8; FILE_PREFIX_LEN],
+        index: u32,
+        inner: R,
+    },
+    /// Archivo en claro (anterior al cifrado), leído tal cual
+    Plain(R),
+    Done,
+}
+
+/// Descifra un archivo por bloques. Los del formato anterior, de un solo
+/// bloque, se descifran enteros; los que están en claro se leen tal cual.
+pub struct FileOpener<'a, R: Read> {
+    vault: &'a Vault,
+    source: OpenerSource<R>,
+    /// Bytes en claro ya leídos y aún no entregados
+    pending: Vec<u8>,
+}
+
+impl<'a, R: Read> FileOpener<'a, R> {
+    pub fn new(vault: &'a Vault, mut inner: R) -> Result<Self> {
+        vault.ensure_unlocked()?;
+        let mut header = [0u8; FILE_HEADER_LEN];
+        let read = read_full(&mut inner, &mut header)?;
+        let header = &header[..read];
+        let (source, pending) = if let Some(key_id) = file_key_id(header) {
+            let mut prefix = [0u8; FILE_PREFIX_LEN];
+            prefix.copy_from_slice(&header[FILE_MAGIC.len() + 4..]);
+            let source = OpenerSource::Chunked {
+                key_id,
+                prefix,
+                index: 0,
+                inner,
+            };
+            (source, Vec::new())
+        } else if header.starts_with(MAGIC) {
+            let mut data = header.to_vec();
+            inner.read_to_end(&mut data)?;
+            (OpenerSource::Done, vault.open(&data, FILE_AAD)?)
+        } else {
+            (OpenerSource::Plain(inner), header.to_vec())
+        };
+        Ok(Self {
+            vault,
+            source,
+            pending,
+        })
+    }
+
+    /// Siguiente tramo en claro; `None` al terminar
+    fn next_block(&mut self) -> Result<Option<Vec<u8>>> {
+        let vault = self.vault;
+        match &mut self.source {
+            OpenerSource::Done => Ok(None),
+            OpenerSource::Plain(inner) => {
+                let mut block = vec![0u8; FILE_CHUNK];
+                let read = read_full(inner, &mut block)?;
+                if read == 0 {
+                    self.source = OpenerSource::Done;
+                    return Ok(None);
+                }
+                block.truncate(read);
+                Ok(Some(block))
+            }
+            OpenerSource::Chunked {
+                key_id,
+                prefix,
+                index,
+                inner,
+            } => {
+                let tag_len = AES_256_GCM.tag_len();
+                let mut body = vec![0u8; FILE_CHUNK + tag_len];
+                let read = read_full(inner, &mut body)?;
+                if read < tag_len {
+                    return Err(Error::Storage("archivo cifrado incompleto".into()));
+                }
+                let last = read < body.len();
+                body.truncate(read);
+                let nonce = chunk_nonce(prefix, *index);
+                let len = vault.with_keyring(|keyring| {
+                    let key = keyring.keys.get(key_id).ok_or_else(|| {
+                        Error::Storage(format!("no existe la clave de datos #{}", key_id))
+                    })?;
+                    key.open_in_place(nonce, Aad::from(chunk_aad(last)), &mut body)
+                        .map(|plaintext| plaintext.len())
+                        .map_err(|_| {
+                            Error::Storage("datos cifrados corruptos o manipulados".into())
+                        })
+                })?;
+                body.truncate(len);
+                *index += 1;
+                if last {
+                    self.source = OpenerSource::Done;
+                }
+                Ok(Some(body))
+            }
+        }
+    }
+
+    /// Hasta `len` bytes en claro; menos solo al final del archivo
+    pub fn read_up_to(&mut self, len: usize) -> Result<Vec<u8>> {
+        while self.pending.len() < len {
+            match self.next_block()? {
+                Some(block) => self.pending.extend_from_slice(&block),
+                None => break,
+            }
+        }
+        let rest = self.pending.split_off(len.min(self.pending.len()));
+        Ok(std::mem::replace(&mut self.pending, rest))
+    }
+
+    /// Descifra en `out` todo lo que queda
+    pub fn copy_to(&mut self, out: &mut impl Write) -> Result<u64> {
+        let mut copied = 0;
+        loop {
+            let block = self.read_up_to(FILE_CHUNK)?;
+            if block.is_empty() {
+                return Ok(copied);
+            }
+            out.write_all(&block)?;
+            copied += block.len() as u64;
+        }
+    }
+
+    pub fn read_to_end(mut self) -> Result<Vec<u8>> {
+        let mut data = Vec::new();
+        self.copy_to(&mut data)?;
+        Ok(data)
+    }
+}
+
+// ============================================
+// CLAVES
+// ============================================
+
+struct VaultParams {
+    salt: Vec<u8>,
+    iterations: u32,
+}
+
+fn params(store: &OfflineStore) -> Result<Option<VaultParams>> {
+    store.with_conn(|conn| {
+        Ok(conn
+            .query_row(
+                "SELECT salt, iterations FROM vault WHERE id = 1",
+                [],
+                |row| {
+                    Ok(VaultParams {
+                        salt: row.get(0)?,
+                        iterations: row.get(1)?,
+                    })
+                },
+            )
+            .optional()?)
+    })
+}
+
+fn wrapped_keys(store: &OfflineStore) -> Result<Vec<(u32, Vec<u8>)>> {
+    store.with_conn(|conn| {
+        let mut stmt = conn.prepare("SELECT id, wrapped_key FROM vault_keys ORDER BY id")?;
+        let keys = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(keys)
+    })
+}
+
+/// Genera una clave de datos nueva, la guarda envuelta y devuelve su id
+fn add_data_key(store: &OfflineStore, kek: &LessSafeKey) -> Result<(u32, LessSafeKey)> {
+    let mut bytes = random::<KEY_LEN>()?;
+    let wrapped = seal_with(kek, 0, &bytes, WRAP_AAD);
+    let key = aead_key(&bytes);
+    bytes.fill(0);
+    let wrapped = wrapped?;
+    let id = store.with_conn(|conn| {
+        conn.execute(
+            "INSERT INTO vault_keys (wrapped_key, created_at) VALUES (?1, ?2)",
+            params![wrapped, now_millis()],
+        )?;
+        Ok(conn.last_insert_rowid() as u32)
+    })?;
+    Ok((id, key?))
+}
+
+/// Desbloquea las claves con `secret`, creando el almacén cifrado la primera vez
+fn open_keyring(store: &OfflineStore, secret: &str, iterations: u32) -> Result<(Keyring, bool)> {
+    let (kek, created) = match params(store)? {
+        Some(params) => (derive_kek(secret, &params.salt, params.iterations)?, false),
+        None => {
+            let salt = random::<SALT_LEN>()?;
+            let kek = derive_kek(secret, &salt, iterations)?;
+            store.with_conn(|conn| {
+                let now = now_millis();
+                conn.execute(
+                    "INSERT INTO vault (id, salt, iterations, created_at, updated_at)
+                     VALUES (1, ?1, ?2, ?3, ?3)",
+                    params![salt.to_vec(), iterations, now],
+                )?;
+                Ok(())
+            })?;
+            (kek, true)
+        }
+    };
+
+    let mut keys = HashMap::new();
+    for (id, wrapped) in wrapped_keys(store)? {
+        keys.insert(id, unwrap_key(&kek, &wrapped)?);
+    }
+    let current = match keys.keys().max() {
+        Some(id) => *id,
+        None => {
+            let (id, key) = add_data_key(store, &kek)?;
+            keys.insert(id, key);
+            id
+        }
+    };
+    Ok((Keyring { current, keys }, created))
+}
+
+// ============================================
+// MIGRACIÓN Y RECIFRADO
+// ============================================
+
+/// Resultado de desbloquear o rotar
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VaultReport {
+    /// Se creó la clave en este desbloqueo
+    pub created: bool,
+    /// Valores cifrados o recifrados: entradas del almacén, cola offline y
+    /// conflictos
+    pub entries: usize,
+    /// Archivos cifrados o recifrados
+    pub files: usize,
+}
+
+/// Archivos guardados por el plugin bajo `root`, sin las entradas internas
+fn stored_files(root: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    if !root.is_dir() {
+        return Ok(files);
+    }
+    let mut pending = vec![root.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        for entry in fs::read_dir(&dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            if dir == root {
+                let name = entry.file_name();
+                let name = name.to_string_lossy();
+                if APP_RESERVED
+                    .iter()
+                    .any(|reserved| reserved.eq_ignore_ascii_case(&name))
+                {
+                    continue;
+                }
+            }
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                pending.push(path);
+            } else if file_type.is_file() {
+                files.push(path);
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// Si un archivo está en claro, en el formato de un solo bloque o (al rotar)
+/// cifrado con otra clave. Se recifra a un temporal que luego se renombra,
+/// para no dejar archivos a medio cifrar.
+fn needs_reseal_file(path: &Path, current: u32, rotate: bool) -> Result<bool> {
+    let mut header = [0u8; FILE_HEADER_LEN];
+    let read = read_full(&mut fs::File::open(path)?, &mut header)?;
+    Ok(match file_key_id(&header[..read]) {
+        Some(key_id) => rotate && key_id != current,
+        None => true,
+    })
+}
+
+/// Id de la clave de un archivo cifrado por bloques
+fn file_key_id(header: &[u8]) -> Option<u32> {
+    if header.len() < FILE_HEADER_LEN || !header.starts_with(FILE_MAGIC) {
+        return None;
+    }
+    let mut key_id = [0u8; 4];
+    key_id.copy_from_slice(&header[FILE_MAGIC.len()..FILE_MAGIC.len() + 4]);
+    Some(u32::from_be_bytes(key_id))
+}
+
+/// Cifra (o recifra con la clave vigente) todo lo que no lo esté ya.
+/// Con `rotate` también recifra lo cifrado con claves anteriores.
+fn reseal_all(store: &OfflineStore, files_root: &Path, rotate: bool) -> Result<VaultReport> {
+    let vault = store.vault();
+    let current = vault.with_keyring(|keyring| Ok(keyring.current))?;
+    let needs_reseal = |data: &[u8]| match sealed_key_id(data) {
+        Ok(key_id) => rotate && key_id != current,
+        Err(_) => true,
+    };
+
+    // `aad` es la clave de la entrada o el contexto de la columna cifrada
+    let mut reseal = |aad: &str, value: &[u8]| {
+        if !needs_reseal(value) {
+            return Ok(None);
+        }
+        let plaintext = if is_sealed(value) {
+            vault.open(value, aad.as_bytes())?
+        } else {
+            value.to_vec()
+        };
+        vault.seal(&plaintext, aad.as_bytes()).map(Some)
+    };
+    let entries = store.reseal_column("offline_entries", "key", "value", &mut reseal)?
+        + outbox::reseal(store, &mut reseal)?
+        + conflicts::reseal(store, &mut reseal)?;
+
+    let mut report = VaultReport {
+        entries,
+        ..VaultReport::default()
+    };
+    for path in stored_files(files_root)? {
+        if !needs_reseal_file(&path, current, rotate)? {
+            continue;
+        }
+        let tmp = path.with_extension("rse-tmp");
+        let mut opener = FileOpener::new(vault, std::io::BufReader::new(fs::File::open(&path)?))?;
+        let mut sealer = FileSealer::new(vault, std::io::BufWriter::new(fs::File::create(&tmp)?))?;
+        loop {
+            let block = opener.read_up_to(FILE_CHUNK)?;
+            if block.is_empty() {
+                break;
+            }
+            sealer.write(&block)?;
+        }
+        sealer.finish()?;
+        fs::rename(&tmp, &path)?;
+        report.files += 1;
+    }
+    Ok(report)
+}
+
+/// Desbloquea el almacenamiento y cifra los datos que aún estén en claro
+pub(crate) fn unlock(
+    store: &OfflineStore,
+    files_root: &Path,
+    secret: &str,
+    iterations: u32,
+) -> Result<VaultReport> {
+    if secret.is_empty() {
+        return Err(Error::Validation("el secreto no puede estar vacío".into()));
+    }
+    let (keyring, created) = open_keyring(store, secret, iterations)?;
+    store.vault().set_keyring(keyring);
+    let mut report = reseal_all(store, files_root, false)?;
+    report.created = created;
+
+    // La copia del antiguo directorio JSON queda en claro: ya no hace falta
+    let backup = files_root.join("offline_data.migrated");
+    if backup.is_dir() {
+        fs::remove_dir_all(backup)?;
+    }
+    Ok(report)
+}
+
+/// Vuelve a envolver las claves de datos con un secreto nuevo
+pub(crate) fn change_secret(
+    store: &OfflineStore,
+    current_secret: &str,
+    new_secret: &str,
+    iterations: u32,
+) -> Result<()> {
+    if new_secret.is_empty() {
+        return Err(Error::Validation("el secreto no puede estar vacío".into()));
+    }
+    let params = params(store)?
+        .ok_or_else(|| Error::NotFound("el almacenamiento aún no está cifrado".into()))?;
+    let kek = derive_kek(current_secret, &params.salt, params.iterations)?;
+    let salt = random::<SALT_LEN>()?;
+    let new_kek = derive_kek(new_secret, &salt, iterations)?;
+
+    let mut rewrapped = Vec::new();
+    for (id, wrapped) in wrapped_keys(store)? {
+        let mut bytes = open_with(&kek, &wrapped, WRAP_AAD)
+            .map_err(|_| Error::auth("el secreto actual no es correcto"))?;
+        let sealed = seal_with(&new_kek, 0, &bytes, WRAP_AAD);
+        bytes.fill(0);
+        rewrapped.push((id, sealed?));
+    }
+
+    store.with_conn(|conn| {
+        let tx = conn.transaction()?;
+        for (id, wrapped) in &rewrapped {
+            tx.execute(
+                "UPDATE vault_keys SET wrapped_key = ?2 WHERE id = ?1",
+                params![id, wrapped],
+            )?;
+        }
+        tx.execute(
+            "UPDATE vault SET salt = ?1, iterations = ?2, updated_at = ?3 WHERE id = 1",
+            params![salt.to_vec(), iterations, now_millis()],
+        )?;
+        tx.commit()?;
+        Ok(())
+    })
+}
+
+/// Genera una clave de datos nueva, recifra todo con ella y descarta las
+/// anteriores. Si se interrumpe, las claves viejas siguen disponibles y la
+/// próxima rotación termina el trabajo.
+pub(crate) fn rotate(store: &OfflineStore, files_root: &Path, secret: &str) -> Result<VaultReport> {
+    let params = params(store)?
+        .ok_or_else(|| Error::NotFound("el almacenamiento aún no está cifrado".into()))?;
+    let kek = derive_kek(secret, &params.salt, params.iterations)?;
+    let (mut keyring, _) = open_keyring(store, secret, params.iterations)?;
+    let (id, key) = add_data_key(store, &kek)?;
+    keyring.keys.insert(id, key);
+    keyring.current = id;
+    store.vault().set_keyring(keyring);
+
+    let report = reseal_all(store, files_root, true)?;
+
+    store.with_conn(|conn| {
+        conn.execute("DELETE FROM vault_keys WHERE id <> ?1", params![id])?;
+        Ok(())
+    })?;
+    if let Ok(mut keyring) = store.vault().keyring.write() {
+        if let Some(keyring) = keyring.as_mut() {
+            keyring.keys.retain(|key_id, _| *key_id == id);
+        }
+    }
+    Ok(report)
+}
+
+/// Ejecuta `f` en un hilo de bloqueo: la derivación del secreto (PBKDF2) y el
+/// recifrado de todo lo guardado tardan segundos y no deben frenar el runtime
+async fn blocking<R: Runtime, T: Send + 'static>(
+    app_handle: AppHandle<R>,
+    f: impl FnOnce(&OfflineStore) -> Result<T> + Send + 'static,
+) -> Result<T> {
+    tauri::async_runtime::spawn_blocking(move || f(&app_handle.state::<OfflineStore>())).await?
+}
+
+/// Borra los archivos descifrados para abrirlos con otra aplicación. Se
+/// llama al bloquear, al arrancar (por si la app se cerró sin bloquear) y al
+/// cambiar de perfil.
+pub(crate) fn clear_opened<R: Runtime>(app_handle: &AppHandle<R>) {
+    if let Ok(cache_dir) = app_handle.path().app_cache_dir() {
+        let _ = fs::remove_dir_all(cache_dir.join(OPENED_DIR));
+    }
+}
+
+// ============================================
+// COMMANDS
+// ============================================
+
+/// Estado del almacenamiento cifrado
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VaultStatus {
+    /// Usuario del perfil abierto
+    pub profile: Option<String>,
+    /// Ya existe una clave de datos
+    pub initialized: bool,
+    pub unlocked: bool,
+}
+
+/// Desbloquea el perfil abierto con el secreto de inicio de sesión.
+/// La primera vez crea la clave y cifra los datos existentes.
+#[tauri::command]
+pub async fn unlock_storage<R: Runtime>(
+    app_handle: AppHandle<R>,
+    secret: String,
+) -> Result<VaultReport> {
+    blocking(app_handle, move |store| {
+        unlock(store, &require_active(store)?.dir, &secret, KDF_ITERATIONS)
+    })
+    .await
+}
+
+/// Bloquea el almacenamiento y cierra el perfil (al cerrar sesión), y borra
+/// las copias descifradas
+#[tauri::command]
+pub async fn lock_storage<R: Runtime>(
+    app_handle: AppHandle<R>,
+    store: State<'_, OfflineStore>,
+) -> Result<()> {
+    store.vault().lock();
+    clear_opened(&app_handle);
+    close(&store)
+}
+
+/// Indica si el almacenamiento está cifrado y desbloqueado
+#[tauri::command]
+pub async fn get_storage_status(store: State<'_, OfflineStore>) -> Result<VaultStatus> {
+    Ok(VaultStatus {
+        profile: store.active_profile().map(|profile| profile.user_id),
+        initialized: params(&store)?.is_some(),
+        unlocked: store.vault().is_unlocked(),
+    })
+}
+
+/// Cambia el secreto que protege las claves (p. ej. tras cambiar la contraseña)
+#[tauri::command]
+pub async fn change_storage_secret<R: Runtime>(
+    app_handle: AppHandle<R>,
+    current_secret: String,
+    new_secret: String,
+) -> Result<()> {
+    blocking(app_handle, move |store| {
+        change_secret(store, &current_secret, &new_secret, KDF_ITERATIONS)
+    })
+    .await
+}
+
+/// Rota la clave de datos y recifra todo lo guardado
+#[tauri::command]
+pub async fn rotate_storage_key<R: Runtime>(
+    app_handle: AppHandle<R>,
+    secret: String,
+) -> Result<VaultReport> {
+    blocking(app_handle, move |store| {
+        rotate(store, &require_active(store)?.dir, &secret)
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Pocas iteraciones para que las pruebas sean rápidas
+    const TEST_ITERATIONS: u32 = 1_000;
+
+    fn raw_value(store: &OfflineStore, key: &str) -> rusqlite::types::Value {
+        store
+            .with_conn(|conn| {
+                Ok(conn.query_row(
+                    "SELECT value FROM offline_entries WHERE key = ?1",
+                    params![key],
+                    |row| row.get(0),
+                )?)
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn sealed_blocks_detect_tampering_and_wrong_context() {
+        let key = aead_key(&[7u8; KEY_LEN]).unwrap();
+        let sealed = seal_with(&key, 3, b"receta", b"k1").unwrap();
+        assert!(is_sealed(&sealed));
+        assert_eq!(sealed_key_id(&sealed).unwrap(), 3);
+        assert_eq!(open_with(&key, &sealed, b"k1").unwrap(), b"receta");
+        assert!(open_with(&key, &sealed, b"k2").is_err());
+
+        let mut tampered = sealed.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(open_with(&key, &tampered, b"k1").is_err());
+    }
+
+    #[test]
+    fn locked_store_refuses_reads_and_writes() {
+        let store = OfflineStore::open_in_memory().unwrap();
+        assert_eq!(store.set("a", "1", None).unwrap_err().code(), "AUTH");
+
+        let root = tempfile::tempdir().unwrap();
+        unlock(&store, root.path(), "secreto", TEST_ITERATIONS).unwrap();
+        store.set("a", "1", None).unwrap();
+        store.vault().lock();
+        assert_eq!(store.get("a").unwrap_err().code(), "AUTH");
+    }
+
+    #[test]
+    fn unlock_migrates_plaintext_entries_and_files() {
+        let root = tempfile::tempdir().unwrap();
+        let legacy = root.path().join("offline_data");
+        fs::create_dir_all(&legacy).unwrap();
+        fs::write(legacy.join("citas.json"), r#"[{"id":1}]"#).unwrap();
+        fs::create_dir_all(root.path().join("recetas")).unwrap();
+        fs::write(root.path().join("recetas/r1.pdf"), b"%PDF-1.4").unwrap();
+
+        let store = OfflineStore::open(&root.path().join("offline.db")).unwrap();
+        store.migrate_json_dir(&legacy).unwrap();
+        assert!(matches!(
+            raw_value(&store, "citas"),
+            rusqlite::types::Value::Text(_)
+        ));
+
+        let report = unlock(&store, root.path(), "secreto", TEST_ITERATIONS).unwrap();
+        assert!(report.created);
+        assert_eq!((report.entries, report.files), (1, 1));
+
+        // En disco ya no queda nada en claro
+        assert!(matches!(
+            raw_value(&store, "citas"),
+            rusqlite::types::Value::Blob(_)
+        ));
+        let on_disk = fs::read(root.path().join("recetas/r1.pdf")).unwrap();
+        assert!(is_sealed_file(&on_disk));
+        // La base de datos no se toma por un archivo del usuario
+        assert!(!is_sealed_file(
+            &fs::read(root.path().join("offline.db")).unwrap()
+        ));
+        assert!(!root.path().join("offline_data.migrated").exists());
+
+        assert_eq!(store.get("citas").unwrap().unwrap(), r#"[{"id":1}]"#);
+        assert_eq!(store.vault().open_file(on_disk).unwrap(), b"%PDF-1.4");
+
+        // Un segundo desbloqueo no vuelve a tocar nada
+        let report = unlock(&store, root.path(), "secreto", TEST_ITERATIONS).unwrap();
+        assert_eq!(
+            (report.created, report.entries, report.files),
+            (false, 0, 0)
+        );
+    }
+
+    #[test]
+    fn files_are_sealed_in_chunks_that_cannot_be_truncated() {
+        let root = tempfile::tempdir().unwrap();
+        let store = OfflineStore::open_in_memory().unwrap();
+        unlock(&store, root.path(), "secreto", TEST_ITERATIONS).unwrap();
+        let vault = store.vault();
+
+        let data: Vec<u8> = (0..FILE_CHUNK * 2 + 100).map(|i| i as u8).collect();
+        let sealed = vault.seal_file(&data).unwrap();
+        assert!(is_sealed_file(&sealed));
+        assert_eq!(vault.open_file(sealed.clone()).unwrap(), data);
+
+        let mut opener = FileOpener::new(vault, sealed.as_slice()).unwrap();
+        assert_eq!(opener.read_up_to(10).unwrap(), data[..10]);
+        assert_eq!(
+            opener.read_up_to(FILE_CHUNK).unwrap(),
+            data[10..FILE_CHUNK + 10]
+        );
+
+        // Sin el último bloque, o cortado justo en un límite, no se abre
+        let tag = AES_256_GCM.tag_len();
+        let two_chunks = FILE_HEADER_LEN + 2 * (FILE_CHUNK + tag);
+        assert!(vault.open_file(sealed[..two_chunks].to_vec()).is_err());
+        assert!(vault
+            .open_file(sealed[..sealed.len() - 1].to_vec())
+            .is_err());
+
+        // Los archivos del formato anterior y los que están en claro se siguen leyendo
+        let legacy = vault.seal(b"antes", FILE_AAD).unwrap();
+        assert_eq!(vault.open_file(legacy).unwrap(), b"antes");
+        assert_eq!(vault.open_file(b"claro".to_vec()).unwrap(), b"claro");
+    }
+
+    #[test]
+    fn wrong_secret_is_rejected() {
+        let root = tempfile::tempdir().unwrap();
+        let store = OfflineStore::open_in_memory().unwrap();
+        unlock(&store, root.path(), "secreto", TEST_ITERATIONS).unwrap();
+        store.vault().lock();
+
+        let err = unlock(&store, root.path(), "otro", TEST_ITERATIONS).unwrap_err();
+        assert_eq!(err.code(), "AUTH");
+        assert!(!store.vault().is_unlocked());
+    }
+
+    #[test]
+    fn changing_the_secret_keeps_the_data() {
+        let root = tempfile::tempdir().unwrap();
+        let store = OfflineStore::open_in_memory().unwrap();
+        unlock(&store, root.path(), "viejo", TEST_ITERATIONS).unwrap();
+        store.set("a", "1", None).unwrap();
+
+        assert_eq!(
+            change_secret(&store, "otro", "nuevo", TEST_ITERATIONS)
+                .unwrap_err()
+                .code(),
+            "AUTH"
+        );
+        change_secret(&store, "viejo", "nuevo", TEST_ITERATIONS).unwrap();
+        store.vault().lock();
+
+        assert!(unlock(&store, root.path(), "viejo", TEST_ITERATIONS).is_err());
+        unlock(&store, root.path(), "nuevo", TEST_ITERATIONS).unwrap();
+        assert_eq!(store.get("a").unwrap().unwrap(), "1");
+    }
+
+    #[test]
+    fn rotation_reseals_everything_with_a_new_key() {
+        let root = tempfile::tempdir().unwrap();
+        let store = OfflineStore::open_in_memory().unwrap();
+        unlock(&store, root.path(), "secreto", TEST_ITERATIONS).unwrap();
+        store.set("a", "1", None).unwrap();
+        fs::create_dir_all(root.path().join("files")).unwrap();
+        let file = root.path().join("files/x.pdf");
+        fs::write(&file, store.vault().seal_file(b"x").unwrap()).unwrap();
+        let old_id = file_key_id(&fs::read(&file).unwrap()).unwrap();
+
+        let report = rotate(&store, root.path(), "secreto").unwrap();
+        assert_eq!((report.entries, report.files), (1, 1));
+        let new_id = file_key_id(&fs::read(&file).unwrap()).unwrap();
+        assert_ne!(old_id, new_id);
+        assert_eq!(wrapped_keys(&store).unwrap().len(), 1);
+
+        store.vault().lock();
+        unlock(&store, root.path(), "secreto", TEST_ITERATIONS).unwrap();
+        assert_eq!(store.get("a").unwrap().unwrap(), "1");
+        assert_eq!(
+            store.vault().open_file(fs::read(&file).unwrap()).unwrap(),
+            b"x"
+        );
+    }
+}
@@ -0,0 +1,30 @@
+use tauri::{AppHandle, Manager, Runtime};
+
+use crate::error::Result;
+
+/// Minimiza la ventana principal
+#[tauri::command]
+pub async fn minimize_window<R: Runtime>(app_handle: AppHandle<R>) -> Result<()> {
+    if let Some(window) = app_handle.get_webview_window("main") {
+        window.minimize()?;
+    }
+    Ok(())
+}
+
+/// Maximiza o restaura la ventana principal
+#[tauri::command]
+pub async fn maximize_window<R: Runtime>(app_handle: AppHandle<R>) -> Result<()> {
+    if let Some(window) = app_handle.get_webview_window("main") {
+        window.maximize()?;
+    }
+    Ok(())
+}
+
+/// Cierra la ventana principal
+#[tauri::command]
+pub async fn close_window<R: Runtime>(app_handle: AppHandle<R>) -> Result<()> {
+    if let Some(window) = app_handle.get_webview_window("main") {
+        window.close()?;
+    }
+    Ok(())
+}
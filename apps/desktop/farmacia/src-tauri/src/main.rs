@@ -24,8 +24,12 @@ fn main() {
         .plugin(tauri_plugin_shell::init())
         .plugin(tauri_plugin_os::init())
         .plugin(tauri_plugin_process::init())
+        .plugin(red_salud_desktop_core::init())
         .invoke_handler(tauri::generate_handler![greet, get_system_info])
         .setup(|app| {
+            // Base local del punto de venta; falla si su esquema no coincide
+            red_salud_desktop_core::pharmacy::setup(app.handle())?;
+
             #[cfg(debug_assertions)]
             {
                 let window = app.get_webview_window("main").unwrap();
@@ -0,0 +1,34 @@
+import { useMutation, useQuery, useQueryClient } from '@tanstack/react-query';
+import { RatesService } from '@/services/rates.service';
+
+export function useExchangeRate() {
+  const queryClient = useQueryClient();
+
+  const {
+    data: rate,
+    isLoading,
+    error,
+  } = useQuery({
+    queryKey: ['exchange-rate', 'USD'],
+    queryFn: () => RatesService.getCurrent('USD'),
+    staleTime: 5 * 60 * 1000,
+    refetchInterval: 15 * 60 * 1000,
+  });
+
+  const refreshMutation = useMutation({
+    mutationFn: () => RatesService.refresh(),
+    onSuccess: () => {
+      queryClient.invalidateQueries({ queryKey: ['exchange-rate'] });
+    },
+  });
+
+  return {
+    rate,
+    /** For display only; amounts are converted in Rust */
+    value: rate ? Number(rate.rate) : null,
+    isLoading,
+    error,
+    refresh: refreshMutation.mutateAsync,
+    isRefreshing: refreshMutation.isPending,
+  };
+}
@@ -8,6 +8,17 @@ export async function getSystemInfo(): Promise<string> {
   return await invoke('get_system_info');
 }
 
+export interface SchemaStatus {
+  version: number;
+  latest: number;
+  migrations: { version: number; name: string; checksum: string; appliedAt: number }[];
+}
+
+// Versión del esquema de la base local (farmacia.db)
+export async function getSchemaVersion(): Promise<SchemaStatus> {
+  return await invoke('plugin:red-salud-desktop-core|get_pharmacy_schema_version');
+}
+
 // Utilidades para verificar si estamos en Tauri
 export const isTauri = () => {
   return '__TAURI__' in window;
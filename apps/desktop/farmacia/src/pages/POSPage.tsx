@@ -12,6 +12,7 @@ import {
 import { useCartStore } from '@/store/cartStore';
 import { useInvoices } from '@/hooks/useInvoices';
 import { useDispensePrescription } from '@/hooks/usePrescriptions';
+import { useExchangeRate } from '@/hooks/useExchangeRate';
 import { deliveriesService } from '@/services/deliveries.service';
 import { ProductSearch } from '@/components/products/ProductSearch';
 import { PaymentModal } from '@/components/pos/PaymentModal';
@@ -20,7 +21,6 @@ import type { Product } from '@/types/product.types';
 import type { PaymentMethod } from '@/types/invoice.types';
 import type { Invoice } from '@/types/invoice.types';
 
-const EXCHANGE_RATE = 36; // TODO: Get from API or config
 const DEFAULT_WAREHOUSE_ID = '00000000-0000-0000-0000-000000000000'; // TODO: Get from user config
 
 export default function POSPage() {
@@ -45,6 +45,7 @@ export default function POSPage() {
 
   const { createInvoice, isCreating } = useInvoices();
   const dispenseMutation = useDispensePrescription();
+  const { rate, value: exchangeRate } = useExchangeRate();
 
   const subtotal = getSubtotal();
   const iva = getIVA();
@@ -64,12 +65,16 @@ export default function POSPage() {
 
   const handleProcessSale = async (paymentMethod: PaymentMethod, paymentDetails?: any) => {
     try {
+      if (exchangeRate === null) {
+        throw new Error('No hay una tasa de cambio vigente');
+      }
+
       // 1. Create Invoice
       const invoice = await createInvoice({
         warehouse_id: DEFAULT_WAREHOUSE_ID,
         payment_method: paymentMethod,
         payment_details: paymentDetails,
-        exchange_rate: EXCHANGE_RATE,
+        exchange_rate: exchangeRate,
         items: items.map(item => ({
           product_id: item.product.id,
           quantity: item.quantity,
@@ -138,7 +143,15 @@ export default function POSPage() {
           <div className="flex items-center gap-2">
             <div className="text-right">
               <p className="text-xs text-muted-foreground">Tasa de Cambio</p>
-              <p className="text-sm font-semibold">${EXCHANGE_RATE.toFixed(2)} USD/Bs.</p>
+              <p className="text-sm font-semibold">
+                {exchangeRate === null ? '—' : `${exchangeRate.toFixed(2)} USD/Bs.`}
+              </p>
+              {rate && (
+                <p className="text-xs text-muted-foreground">
+                  {rate.source === 'bcv' ? 'BCV' : 'Manual'} · {rate.effectiveDate}
+                  {rate.stale ? ' · desactualizada' : ''}
+                </p>
+              )}
             </div>
           </div>
         </div>
@@ -329,7 +342,7 @@ export default function POSPage() {
         onClose={() => setShowPaymentModal(false)}
         onConfirm={handleProcessSale}
         total={total}
-        exchangeRate={EXCHANGE_RATE}
+        exchangeRate={exchangeRate ?? 0}
       />
 
       {/* Invoice Preview */}
@@ -1,5 +1,5 @@
 import { ProductsService } from './products.service';
-import { BatchesService } from './batches.service';
+import { InventoryService } from './inventory.service';
 
 export type AlertType = 'low_stock' | 'expiring_soon' | 'expired' | 'out_of_stock';
 export type AlertPriority = 'low' | 'medium' | 'high' | 'critical';
@@ -59,52 +59,47 @@ export class AlertsService {
       });
     }
 
-    // 2. Expiring soon alerts
-    const expiringBatches = await BatchesService.getExpiringSoon(30);
+    // 2. Expiring and expired batches, from the local inventory
+    const expiringBatches = await InventoryService.getExpiring(30);
     for (const batch of expiringBatches) {
-      const daysUntilExpiry = Math.ceil(
-        (new Date(batch.expiry_date).getTime() - new Date().getTime()) / (1000 * 60 * 60 * 24)
-      );
+      if (batch.expired) {
+        alerts.push({
+          id: `expired-${batch.batchId}`,
+          type: 'expired',
+          priority: 'critical',
+          title: 'Producto vencido',
+          message: `Lote ${batch.lotNumber} de ${batch.productName} está vencido`,
+          product_id: batch.productId,
+          batch_id: batch.batchId,
+          product_name: batch.productName,
+          sku: batch.sku,
+          quantity: batch.quantity,
+          expiry_date: batch.expiryDate,
+          created_at: new Date().toISOString(),
+          resolved: false,
+        });
+        continue;
+      }
 
       let priority: AlertPriority = 'low';
-      if (daysUntilExpiry <= 7) {
+      if (batch.daysLeft <= 7) {
         priority = 'high';
-      } else if (daysUntilExpiry <= 15) {
+      } else if (batch.daysLeft <= 15) {
         priority = 'medium';
       }
 
       alerts.push({
-        id: `expiring-${batch.id}`,
+        id: `expiring-${batch.batchId}`,
         type: 'expiring_soon',
         priority,
         title: 'Producto próximo a vencer',
-        message: `Lote ${batch.lot_number} de ${(batch as any).product?.name || 'producto'} vence en ${daysUntilExpiry} días`,
-        product_id: batch.product_id,
-        batch_id: batch.id,
-        product_name: (batch as any).product?.name,
-        sku: (batch as any).product?.sku,
-        quantity: batch.quantity,
-        expiry_date: batch.expiry_date,
-        created_at: new Date().toISOString(),
-        resolved: false,
-      });
-    }
-
-    // 3. Expired alerts
-    const expiredBatches = await BatchesService.getExpired();
-    for (const batch of expiredBatches) {
-      alerts.push({
-        id: `expired-${batch.id}`,
-        type: 'expired',
-        priority: 'critical',
-        title: 'Producto vencido',
-        message: `Lote ${batch.lot_number} de ${(batch as any).product?.name || 'producto'} está vencido`,
-        product_id: batch.product_id,
-        batch_id: batch.id,
-        product_name: (batch as any).product?.name,
-        sku: (batch as any).product?.sku,
+        message: `Lote ${batch.lotNumber} de ${batch.productName} vence en ${batch.daysLeft} días`,
+        product_id: batch.productId,
+        batch_id: batch.batchId,
+        product_name: batch.productName,
+        sku: batch.sku,
         quantity: batch.quantity,
-        expiry_date: batch.expiry_date,
+        expiry_date: batch.expiryDate,
         created_at: new Date().toISOString(),
         resolved: false,
       });
@@ -0,0 +1,139 @@
+import { tauriInvoke } from '@/lib/tauri';
+
+const plugin = (command: string) => `plugin:red-salud-desktop-core|${command}`;
+
+/** Emitted by the desktop core when the printer status changes */
+export const FISCAL_PRINTER_STATUS_EVENT = 'fiscal-printer-status';
+
+export type PrinterConnection =
+  | { backend: 'serial'; port: string; baudRate?: number }
+  | { backend: 'tcp'; host: string; port: number }
+  | { backend: 'simulated' };
+
+/** In test (training) mode documents have no fiscal validity */
+export type PrinterMode = 'test' | 'fiscal';
+
+export type FiscalMemory = 'available' | 'almost_full' | 'full';
+
+/** The printer does not tell invoices from credit notes */
+export type PrinterState = 'idle' | 'fiscal_document' | 'non_fiscal_document';
+
+export type PrinterError =
+  | 'none'
+  | 'paper_out'
+  | 'mechanical'
+  | 'paper_out_and_mechanical'
+  | 'invalid_value'
+  | 'invalid_rate'
+  | 'no_directives'
+  | 'invalid_command'
+  | 'fiscal_error'
+  | 'fiscal_memory_error'
+  | 'fiscal_memory_full'
+  | 'buffer_full';
+
+export interface PrinterCounters {
+  lastInvoice: number;
+  invoicesToday: number;
+  lastCreditNote: number;
+  creditNotesToday: number;
+  zNumber: number;
+  serial: string;
+}
+
+export interface PrinterStatus {
+  connected: boolean;
+  mode: PrinterMode | null;
+  memory: FiscalMemory | null;
+  state: PrinterState | null;
+  error: PrinterError | null;
+  /** Human-readable problem, if any */
+  message: string | null;
+  counters: PrinterCounters | null;
+  checkedAt: number;
+}
+
+export interface FiscalCustomer {
+  rif: string;
+  name: string;
+}
+
+export interface FiscalDocument {
+  id: number;
+  documentType: 'invoice' | 'credit_note' | 'x_report' | 'z_report';
+  invoiceId: string | null;
+  /** Number assigned by the printer */
+  number: string | null;
+  printerSerial: string;
+  zNumber: number;
+  printedAt: number;
+}
+
+/** Day totals in VES cents, as reported by the printer */
+export interface DailyTotals {
+  exempt: number;
+  /** General, reduced and additional rates */
+  bases: [number, number, number];
+  taxes: [number, number, number];
+  igtf: number;
+  creditNotes: number;
+}
+
+export interface FiscalReport {
+  document: FiscalDocument;
+  totals: DailyTotals;
+}
+
+export class FiscalService {
+  static async listSerialPorts() {
+    return tauriInvoke<string[]>(plugin('list_serial_ports'));
+  }
+
+  static async getPrinter() {
+    return tauriInvoke<PrinterConnection | null>(plugin('get_fiscal_printer'));
+  }
+
+  /**
+   * Saves the printer connection; `null` removes it
+   */
+  static async setPrinter(connection: PrinterConnection | null) {
+    return tauriInvoke<PrinterConnection | null>(plugin('set_fiscal_printer'), { connection });
+  }
+
+  /**
+   * Last polled status; `refresh` asks the printer now
+   */
+  static async getStatus(refresh = false) {
+    return tauriInvoke<PrinterStatus>(plugin('get_fiscal_printer_status'), { refresh });
+  }
+
+  /**
+   * Prints the fiscal invoice of a finalized sale. Without a customer the
+   * printer issues it to "consumidor final".
+   */
+  static async printInvoice(saleId: string, customer?: FiscalCustomer) {
+    return tauriInvoke<FiscalDocument>(plugin('print_fiscal_invoice'), { saleId, customer });
+  }
+
+  /**
+   * Full credit note for a printed invoice; the invoice becomes refunded
+   */
+  static async printCreditNote(invoiceId: string, reason: string, customer?: FiscalCustomer) {
+    return tauriInvoke<FiscalDocument>(plugin('print_fiscal_credit_note'), {
+      invoiceId,
+      reason,
+      customer,
+    });
+  }
+
+  static async printXReport() {
+    return tauriInvoke<FiscalReport>(plugin('print_x_report'));
+  }
+
+  /**
+   * Closes the fiscal day; the printer refuses to invoice after 24h without one
+   */
+  static async printZReport() {
+    return tauriInvoke<FiscalReport>(plugin('print_z_report'));
+  }
+}
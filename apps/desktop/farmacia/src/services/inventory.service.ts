@@ -0,0 +1,54 @@
+import { tauriInvoke } from '@/lib/tauri';
+
+const plugin = (command: string) => `plugin:red-salud-desktop-core|${command}`;
+
+export interface ExpiringBatch {
+  batchId: string;
+  productId: string;
+  productName: string;
+  sku: string;
+  lotNumber: string;
+  expiryDate: string;
+  quantity: number;
+  /** Negative once expired */
+  daysLeft: number;
+  expired: boolean;
+}
+
+export interface Consumption {
+  pharmacyId: string;
+  /** Only this warehouse's batches are used */
+  warehouseId: string;
+  productId: string;
+  /** Without it, batches are taken first-expired-first-out */
+  batchId?: string;
+  quantity: number;
+  unitPriceUsd?: number;
+  documentType?: 'factura' | 'nota_credito' | 'orden_compra' | 'ajuste_manual';
+  documentId?: string;
+  notes?: string;
+  userId?: string;
+}
+
+export interface Allocation {
+  batchId: string;
+  lotNumber: string;
+  expiryDate: string;
+  quantity: number;
+}
+
+export class InventoryService {
+  /**
+   * Batches with stock expiring within `days` (already expired included)
+   */
+  static async getExpiring(days: number = 30) {
+    return tauriInvoke<ExpiringBatch[]>(plugin('get_expiring_batches'), { days });
+  }
+
+  /**
+   * Takes stock outside the POS (deliveries, write-offs) using FEFO
+   */
+  static async consume(consumption: Consumption) {
+    return tauriInvoke<Allocation[]>(plugin('consume_stock'), { consumption });
+  }
+}
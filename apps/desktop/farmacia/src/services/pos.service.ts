@@ -0,0 +1,139 @@
+import { tauriInvoke } from '@/lib/tauri';
+
+const plugin = (command: string) => `plugin:red-salud-desktop-core|${command}`;
+
+// Todos los montos son enteros en céntimos; la tasa viaja como decimal en texto
+
+export type PosPaymentMethod = 'cash' | 'card' | 'pago_movil' | 'zelle' | 'biopago' | 'crypto' | 'transfer';
+export type PosCurrency = 'USD' | 'VES';
+
+export interface NewSale {
+  pharmacyId: string;
+  warehouseId: string;
+  userId: string;
+  patientId?: string;
+  /** Bolívares por dólar, p. ej. "36.52"; sin ella se usa la tasa vigente */
+  exchangeRate?: string;
+}
+
+export interface SaleLine {
+  id: number;
+  productId: string;
+  batchId: string | null;
+  productName: string;
+  genericName: string | null;
+  unitType: string;
+  quantity: number;
+  unitPriceUsd: number;
+  unitPriceVes: number;
+  ivaRate: number;
+  grossUsd: number;
+  discountUsd: number;
+  totalUsd: number;
+  totalVes: number;
+  ivaUsd: number;
+  ivaVes: number;
+  comboId: string | null;
+}
+
+export interface SalePayment {
+  id: number;
+  method: PosPaymentMethod;
+  currency: PosCurrency;
+  amount: number;
+  amountVes: number;
+  reference: string | null;
+}
+
+export interface Sale {
+  id: string;
+  status: 'open' | 'finalized' | 'voided';
+  pharmacyId: string;
+  warehouseId: string;
+  userId: string;
+  patientId: string | null;
+  exchangeRate: string;
+  exchangeRateId: number | null;
+  exchangeRateDate: string | null;
+  invoiceId: string | null;
+  invoiceNumber: string | null;
+  lines: SaleLine[];
+  combos: { id: string; name: string; sets: number; amountUsd: number }[];
+  discounts: { id: string; name: string; amountUsd: number }[];
+  payments: SalePayment[];
+  subtotalUsd: number;
+  subtotalVes: number;
+  discountUsd: number;
+  ivaUsd: number;
+  ivaVes: number;
+  igtfUsd: number;
+  igtfVes: number;
+  totalUsd: number;
+  totalVes: number;
+  paidVes: number;
+  balanceVes: number;
+  changeVes: number;
+  createdAt: number;
+}
+
+export class PosService {
+  static async open(sale: NewSale) {
+    return tauriInvoke<Sale>(plugin('open_sale'), { sale });
+  }
+
+  static async get(id: string) {
+    return tauriInvoke<Sale>(plugin('get_sale'), { id });
+  }
+
+  /**
+   * Open sales left over from a previous session
+   */
+  static async getOpen() {
+    return tauriInvoke<Sale[]>(plugin('get_open_sales'));
+  }
+
+  static async addItem(saleId: string, productId: string, quantity: number, batchId?: string) {
+    return tauriInvoke<Sale>(plugin('add_sale_item'), { saleId, productId, batchId, quantity });
+  }
+
+  /**
+   * Quantity 0 removes the line
+   */
+  static async updateItem(saleId: string, lineId: number, quantity: number) {
+    return tauriInvoke<Sale>(plugin('update_sale_item'), { saleId, lineId, quantity });
+  }
+
+  static async removeItem(saleId: string, lineId: number) {
+    return tauriInvoke<Sale>(plugin('remove_sale_item'), { saleId, lineId });
+  }
+
+  static async applyDiscount(saleId: string, discountId: string) {
+    return tauriInvoke<Sale>(plugin('apply_sale_discount'), { saleId, discountId });
+  }
+
+  static async removeDiscount(saleId: string, discountId: string) {
+    return tauriInvoke<Sale>(plugin('remove_sale_discount'), { saleId, discountId });
+  }
+
+  static async addPayment(
+    saleId: string,
+    payment: { method: PosPaymentMethod; currency: PosCurrency; amount: number; reference?: string }
+  ) {
+    return tauriInvoke<Sale>(plugin('add_sale_payment'), { saleId, payment });
+  }
+
+  static async removePayment(saleId: string, paymentId: number) {
+    return tauriInvoke<Sale>(plugin('remove_sale_payment'), { saleId, paymentId });
+  }
+
+  /**
+   * Writes the invoice and stock movements in one local transaction
+   */
+  static async finalize(saleId: string) {
+    return tauriInvoke<Sale>(plugin('finalize_sale'), { saleId });
+  }
+
+  static async void(saleId: string) {
+    return tauriInvoke<Sale>(plugin('void_sale'), { saleId });
+  }
+}
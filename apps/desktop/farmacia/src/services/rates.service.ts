@@ -0,0 +1,60 @@
+import { tauriInvoke } from '@/lib/tauri';
+
+const plugin = (command: string) => `plugin:red-salud-desktop-core|${command}`;
+
+export type RateCurrency = 'USD' | 'EUR' | 'VES';
+
+export interface ExchangeRate {
+  id: number;
+  currency: Exclude<RateCurrency, 'VES'>;
+  /** Bolívares per unit as a decimal string, e.g. "36.520000" */
+  rate: string;
+  effectiveDate: string;
+  source: 'bcv' | 'manual';
+  fetchedAt: number;
+  checkedAt: number;
+  /** Not confirmed by the source in the last day */
+  stale: boolean;
+}
+
+export interface Conversion {
+  /** Cents of `to` */
+  amount: number;
+  from: RateCurrency;
+  to: RateCurrency;
+  rates: ExchangeRate[];
+}
+
+export class RatesService {
+  /**
+   * Rate in effect today, from the local history
+   */
+  static async getCurrent(currency: Exclude<RateCurrency, 'VES'> = 'USD') {
+    return tauriInvoke<ExchangeRate>(plugin('get_exchange_rate'), { currency });
+  }
+
+  static async getHistory(currency: Exclude<RateCurrency, 'VES'> = 'USD', from?: string, to?: string) {
+    return tauriInvoke<ExchangeRate[]>(plugin('get_exchange_rate_history'), { currency, from, to });
+  }
+
+  /**
+   * Asks the bcv-rate service now instead of waiting for the background refresh
+   */
+  static async refresh() {
+    return tauriInvoke<ExchangeRate[]>(plugin('refresh_exchange_rates'));
+  }
+
+  /**
+   * Manual rate, e.g. when the service is down
+   */
+  static async set(currency: Exclude<RateCurrency, 'VES'>, rate: string, effectiveDate?: string) {
+    return tauriInvoke<ExchangeRate>(plugin('set_exchange_rate'), { currency, rate, effectiveDate });
+  }
+
+  /**
+   * Converts cents between currencies with today's rates, rounded in Rust
+   */
+  static async convert(amount: number, from: RateCurrency, to: RateCurrency) {
+    return tauriInvoke<Conversion>(plugin('convert_amount'), { amount, from, to });
+  }
+}
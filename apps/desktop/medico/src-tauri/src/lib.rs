@@ -1,4 +0,0 @@
-// Librería principal de Red Salud Desktop
-// Este archivo solo es necesario para móvil, no para desktop
-
-pub mod commands;
@@ -0,0 +1,434 @@
+// Doble de Supabase para pruebas
+// Levanta en un hilo propio un servidor HTTP en 127.0.0.1 que imita a
+// PostgREST (`/rest/v1`) y GoTrue (`/auth/v1`) con respuestas programadas,
+// latencia y fallos de conexión, para probar el proxy de desktop-core sin red.
+// Fuera de las rutas de Supabase no exige `apikey`, así que también sirve de
+// doble de otros servicios como `services/bcv-rate`. `MockRealtime` hace lo
+// mismo con el WebSocket de Realtime.
+
+use bytes::Bytes;
+use http_body_util::{BodyExt, Full};
+use hyper::body::Incoming;
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use std::collections::{HashMap, VecDeque};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+use tokio::sync::oneshot;
+
+mod realtime;
+
+pub use realtime::MockRealtime;
+
+/// Clave anónima que el doble exige en la cabecera `apikey`
+pub const ANON_KEY: &str = "mock-anon-key";
+/// Rutas de la API de Supabase, las únicas que piden `apikey`
+const SUPABASE_PREFIXES: [&str; 5] = ["/rest/", "/auth/", "/storage/", "/functions/", "/realtime/"];
+
+/// Respuesta programada
+#[derive(Debug, Clone)]
+pub struct MockResponse {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: String,
+    delay: Duration,
+    close_connection: bool,
+}
+
+impl MockResponse {
+    pub fn new(status: u16) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            body: String::new(),
+            delay: Duration::ZERO,
+            close_connection: false,
+        }
+    }
+
+    /// Respuesta con cuerpo JSON
+    pub fn json(status: u16, body: impl Into<String>) -> Self {
+        Self {
+            body: body.into(),
+            ..Self::new(status)
+        }
+        .header("content-type", "application/json")
+    }
+
+    /// Error con el formato de PostgREST (`{ code, message }`)
+    pub fn postgrest_error(status: u16, code: &str, message: &str) -> Self {
+        Self::json(
+            status,
+            serde_json::json!({ "code": code, "message": message }).to_string(),
+        )
+    }
+
+    /// Respuesta de `/auth/v1/token` de GoTrue
+    pub fn token(access_token: &str, refresh_token: &str, expires_in: i64, user_id: &str) -> Self {
+        Self::json(
+            200,
+            serde_json::json!({
+                "access_token": access_token,
+                "refresh_token": refresh_token,
+                "token_type": "bearer",
+                "expires_in": expires_in,
+                "user": { "id": user_id },
+            })
+            .to_string(),
+        )
+    }
+
+    /// Cuerpo en texto, sin tipo de contenido
+    pub fn body(mut self, body: impl Into<String>) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers
+            .push((name.to_ascii_lowercase(), value.to_string()));
+        self
+    }
+
+    /// ETag de la respuesta. Si la petición trae el mismo `If-None-Match`,
+    /// el doble responde 304 sin cuerpo, como PostgREST.
+    pub fn etag(self, etag: &str) -> Self {
+        self.header("etag", etag)
+    }
+
+    /// Espera antes de responder
+    pub fn delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    /// Cierra la conexión sin responder (tras la espera, si la hay)
+    pub fn close_connection(mut self) -> Self {
+        self.close_connection = true;
+        self
+    }
+
+    fn etag_value(&self) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(name, _)| name == "etag")
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Petición recibida por el doble
+#[derive(Debug, Clone)]
+pub struct RecordedRequest {
+    pub method: String,
+    pub path: String,
+    pub query: Option<String>,
+    /// Nombres en minúsculas
+    pub headers: HashMap<String, String>,
+    pub body: String,
+}
+
+impl RecordedRequest {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .get(&name.to_ascii_lowercase())
+            .map(String::as_str)
+    }
+}
+
+#[derive(Default)]
+struct Route {
+    /// Respuestas de un solo uso, en orden
+    queue: VecDeque<MockResponse>,
+    /// Respuesta cuando la cola está vacía
+    fallback: Option<MockResponse>,
+}
+
+#[derive(Default)]
+struct State {
+    routes: HashMap<(String, String), Route>,
+    requests: Vec<RecordedRequest>,
+}
+
+impl State {
+    /// Busca primero la ruta con query exacta y luego la del path
+    fn next_response(&mut self, method: &str, path: &str, query: Option<&str>) -> MockResponse {
+        let with_query = query.map(|query| format!("{}?{}", path, query));
+        let keys = with_query
+            .into_iter()
+            .chain(std::iter::once(path.to_string()))
+            .map(|path| (method.to_string(), path));
+        for key in keys {
+            if let Some(route) = self.routes.get_mut(&key) {
+                if let Some(response) = route.queue.pop_front().or_else(|| route.fallback.clone()) {
+                    return response;
+                }
+            }
+        }
+        MockResponse::postgrest_error(
+            404,
+            "MOCK404",
+            &format!("sin respuesta para {} {}", method, path),
+        )
+    }
+}
+
+/// Servidor que imita a Supabase mientras viva
+pub struct MockSupabase {
+    addr: SocketAddr,
+    state: Arc<Mutex<State>>,
+    shutdown: Option<oneshot::Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+/// Programa las respuestas de un método y una ruta
+pub struct RouteBuilder<'a> {
+    state: &'a Mutex<State>,
+    key: (String, String),
+}
+
+impl RouteBuilder<'_> {
+    /// Responde una vez con `response`; varias llamadas se encolan en orden
+    pub fn once(self, response: MockResponse) -> Self {
+        self.route(|route| route.queue.push_back(response));
+        self
+    }
+
+    /// Responde siempre con `response` cuando no quedan respuestas de un uso
+    pub fn always(self, response: MockResponse) -> Self {
+        self.route(|route| route.fallback = Some(response));
+        self
+    }
+
+    fn route(&self, f: impl FnOnce(&mut Route)) {
+        let mut state = self.state.lock().unwrap();
+        f(state.routes.entry(self.key.clone()).or_default());
+    }
+}
+
+impl MockSupabase {
+    /// Arranca el doble en un puerto libre
+    pub fn start() -> Self {
+        let listener =
+            std::net::TcpListener::bind("127.0.0.1:0").expect("no se pudo abrir un puerto");
+        listener.set_nonblocking(true).unwrap();
+        let addr = listener.local_addr().unwrap();
+        let state = Arc::new(Mutex::new(State::default()));
+        let (shutdown, mut stopped) = oneshot::channel();
+
+        let server_state = state.clone();
+        let thread = std::thread::spawn(move || {
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap();
+            // Al salir se descarta el runtime y con él todas las conexiones abiertas
+            runtime.block_on(async move {
+                let listener = tokio::net::TcpListener::from_std(listener).unwrap();
+                loop {
+                    tokio::select! {
+                        _ = &mut stopped => break,
+                        accepted = listener.accept() => {
+                            let Ok((stream, _)) = accepted else { continue };
+                            let state = server_state.clone();
+                            tokio::spawn(async move {
+                                let service = service_fn(move |request| {
+                                    handle(state.clone(), request)
+                                });
+                                let _ = http1::Builder::new()
+                                    .serve_connection(TokioIo::new(stream), service)
+                                    .await;
+                            });
+                        }
+                    }
+                }
+            });
+        });
+
+        Self {
+            addr,
+            state,
+            shutdown: Some(shutdown),
+            thread: Some(thread),
+        }
+    }
+
+    /// URL base del proyecto simulado (sin barra final)
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// Programa las respuestas de `method` en `path`. Si `path` lleva query,
+    /// solo coincide con esa query exacta; si no, con cualquiera.
+    pub fn on(&self, method: &str, path: &str) -> RouteBuilder<'_> {
+        RouteBuilder {
+            state: &self.state,
+            key: (method.to_ascii_uppercase(), path.to_string()),
+        }
+    }
+
+    /// Todas las peticiones recibidas, en orden
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.state.lock().unwrap().requests.clone()
+    }
+
+    /// Peticiones recibidas en `method` y `path` (sin query)
+    pub fn requests_to(&self, method: &str, path: &str) -> Vec<RecordedRequest> {
+        self.requests()
+            .into_iter()
+            .filter(|request| request.method.eq_ignore_ascii_case(method) && request.path == path)
+            .collect()
+    }
+
+    /// Olvida las rutas programadas y las peticiones recibidas
+    pub fn reset(&self) {
+        *self.state.lock().unwrap() = State::default();
+    }
+
+    /// Detiene el servidor: las conexiones nuevas se rechazan, como sin red
+    pub fn stop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for MockSupabase {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Error con el que hyper cierra la conexión sin responder
+#[derive(Debug)]
+struct ConnectionClosed;
+
+impl std::fmt::Display for ConnectionClosed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("conexión cerrada por el doble")
+    }
+}
+
+impl std::error::Error for ConnectionClosed {}
+
+async fn handle(
+    state: Arc<Mutex<State>>,
+    request: Request<Incoming>,
+) -> Result<Response<Full<Bytes>>, ConnectionClosed> {
+    let (parts, body) = request.into_parts();
+    let body = body
+        .collect()
+        .await
+        .map(|collected| String::from_utf8_lossy(&collected.to_bytes()).to_string())
+        .unwrap_or_default();
+    let recorded = RecordedRequest {
+        method: parts.method.to_string(),
+        path: parts.uri.path().to_string(),
+        query: parts.uri.query().map(str::to_string),
+        headers: parts
+            .headers
+            .iter()
+            .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+            .collect(),
+        body,
+    };
+
+    let response = {
+        let mut state = state.lock().unwrap();
+        state.requests.push(recorded.clone());
+        let supabase = SUPABASE_PREFIXES
+            .iter()
+            .any(|prefix| recorded.path.starts_with(prefix));
+        if supabase && recorded.header("apikey") != Some(ANON_KEY) {
+            MockResponse::json(401, r#"{"message":"Invalid API key"}"#)
+        } else {
+            state.next_response(&recorded.method, &recorded.path, recorded.query.as_deref())
+        }
+    };
+
+    if !response.delay.is_zero() {
+        tokio::time::sleep(response.delay).await;
+    }
+    if response.close_connection {
+        return Err(ConnectionClosed);
+    }
+
+    let not_modified = response
+        .etag_value()
+        .is_some_and(|etag| recorded.header("if-none-match") == Some(etag));
+    let (status, body) = if not_modified {
+        (StatusCode::NOT_MODIFIED, String::new())
+    } else {
+        (
+            StatusCode::from_u16(response.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            response.body.clone(),
+        )
+    };
+
+    let mut builder = Response::builder().status(status);
+    for (name, value) in &response.headers {
+        builder = builder.header(name, value);
+    }
+    Ok(builder
+        .body(Full::new(Bytes::from(body)))
+        .unwrap_or_else(|_| Response::new(Full::new(Bytes::new()))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn get(mock: &MockSupabase, path: &str, etag: Option<&str>) -> reqwest::Response {
+        let mut request = reqwest::Client::new()
+            .get(format!("{}{}", mock.url(), path))
+            .header("apikey", ANON_KEY);
+        if let Some(etag) = etag {
+            request = request.header("if-none-match", etag);
+        }
+        request.send().await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn scripted_responses_run_in_order() {
+        let mock = MockSupabase::start();
+        mock.on("GET", "/rest/v1/citas")
+            .once(MockResponse::new(503))
+            .always(MockResponse::json(200, "[]").etag("\"v1\""));
+
+        assert_eq!(get(&mock, "/rest/v1/citas", None).await.status(), 503);
+        assert_eq!(
+            get(&mock, "/rest/v1/citas?select=*", None).await.status(),
+            200
+        );
+        assert_eq!(
+            get(&mock, "/rest/v1/citas", Some("\"v1\"")).await.status(),
+            304
+        );
+        assert_eq!(get(&mock, "/rest/v1/otra", None).await.status(), 404);
+        assert_eq!(mock.requests_to("GET", "/rest/v1/citas").len(), 3);
+        assert_eq!(mock.requests()[1].query.as_deref(), Some("select=*"));
+    }
+
+    #[tokio::test]
+    async fn rejects_missing_api_key_and_stops() {
+        let mut mock = MockSupabase::start();
+        let response = reqwest::Client::new()
+            .get(format!("{}/rest/v1/citas", mock.url()))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 401);
+
+        let url = mock.url();
+        mock.stop();
+        assert!(reqwest::get(format!("{}/auth/v1/health", url))
+            .await
+            .is_err());
+    }
+}
@@ -0,0 +1,264 @@
+// Doble de Supabase Realtime
+// Servidor WebSocket en 127.0.0.1 que habla el protocolo de canales de
+// Phoenix (`vsn=1.0.0`): acepta `phx_join`, responde los heartbeats, registra
+// lo recibido y deja empujar cambios o cortar las conexiones desde la prueba.
+
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, oneshot};
+use tokio_tungstenite::tungstenite::handshake::server::{Request, Response};
+use tokio_tungstenite::tungstenite::Message;
+
+/// Mensaje para una conexión abierta
+enum Outgoing {
+    Text(String),
+    Close,
+}
+
+#[derive(Default)]
+struct State {
+    /// Query de cada conexión aceptada, en orden
+    connections: Vec<String>,
+    /// Mensajes recibidos de todas las conexiones
+    received: Vec<Value>,
+    open: Vec<mpsc::UnboundedSender<Outgoing>>,
+    /// Topics cuyo `phx_join` se rechaza
+    rejected: Vec<String>,
+}
+
+/// Servidor que imita a Supabase Realtime mientras viva
+pub struct MockRealtime {
+    addr: SocketAddr,
+    state: Arc<Mutex<State>>,
+    shutdown: Option<oneshot::Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl MockRealtime {
+    /// Arranca el doble en un puerto libre
+    pub fn start() -> Self {
+        let listener =
+            std::net::TcpListener::bind("127.0.0.1:0").expect("no se pudo abrir un puerto");
+        listener.set_nonblocking(true).unwrap();
+        let addr = listener.local_addr().unwrap();
+        let state = Arc::new(Mutex::new(State::default()));
+        let (shutdown, mut stopped) = oneshot::channel();
+
+        let server_state = state.clone();
+        let thread = std::thread::spawn(move || {
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap();
+            runtime.block_on(async move {
+                let listener = tokio::net::TcpListener::from_std(listener).unwrap();
+                loop {
+                    tokio::select! {
+                        _ = &mut stopped => break,
+                        accepted = listener.accept() => {
+                            let Ok((stream, _)) = accepted else { continue };
+                            tokio::spawn(serve(server_state.clone(), stream));
+                        }
+                    }
+                }
+            });
+        });
+
+        Self {
+            addr,
+            state,
+            shutdown: Some(shutdown),
+            thread: Some(thread),
+        }
+    }
+
+    /// URL base del proyecto simulado; el socket está en `/realtime/v1/websocket`
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// Query de cada conexión aceptada (lleva `apikey` y `vsn`)
+    pub fn connections(&self) -> Vec<String> {
+        self.state.lock().unwrap().connections.clone()
+    }
+
+    /// Mensajes recibidos con el evento `event`
+    pub fn received(&self, event: &str) -> Vec<Value> {
+        self.state
+            .lock()
+            .unwrap()
+            .received
+            .iter()
+            .filter(|message| message["event"] == event)
+            .cloned()
+            .collect()
+    }
+
+    /// Rechaza los próximos `phx_join` a `topic`
+    pub fn reject_join(&self, topic: &str) {
+        self.state.lock().unwrap().rejected.push(topic.to_string());
+    }
+
+    /// Envía un mensaje a todas las conexiones abiertas
+    pub fn push(&self, topic: &str, event: &str, payload: Value) {
+        let message = json!({ "topic": topic, "event": event, "payload": payload, "ref": null });
+        self.send(Outgoing::Text(message.to_string()));
+    }
+
+    /// Envía un cambio de `postgres_changes` como lo hace Realtime
+    pub fn postgres_change(
+        &self,
+        topic: &str,
+        event_type: &str,
+        table: &str,
+        record: Value,
+        old_record: Value,
+    ) {
+        self.push(
+            topic,
+            "postgres_changes",
+            json!({
+                "ids": [1],
+                "data": {
+                    "schema": "public",
+                    "table": table,
+                    "type": event_type,
+                    "commit_timestamp": "2026-01-01T00:00:00Z",
+                    "record": record,
+                    "old_record": old_record,
+                    "columns": [],
+                    "errors": null,
+                },
+            }),
+        );
+    }
+
+    /// Cierra todas las conexiones abiertas, como un corte de red
+    pub fn drop_connections(&self) {
+        self.send(Outgoing::Close);
+        self.state.lock().unwrap().open.clear();
+    }
+
+    /// Espera hasta que `condition` se cumpla o venza `timeout`
+    pub async fn wait_until(&self, timeout: Duration, condition: impl Fn(&Self) -> bool) -> bool {
+        let deadline = Instant::now() + timeout;
+        while !condition(self) {
+            if Instant::now() >= deadline {
+                return false;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        true
+    }
+
+    /// Detiene el servidor y corta las conexiones
+    pub fn stop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+
+    fn send(&self, outgoing: Outgoing) {
+        let state = self.state.lock().unwrap();
+        for open in &state.open {
+            let _ = open.send(match &outgoing {
+                Outgoing::Text(text) => Outgoing::Text(text.clone()),
+                Outgoing::Close => Outgoing::Close,
+            });
+        }
+    }
+}
+
+impl Drop for MockRealtime {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+async fn serve(state: Arc<Mutex<State>>, stream: tokio::net::TcpStream) {
+    let query_state = state.clone();
+    // La firma la impone tungstenite
+    #[allow(clippy::result_large_err)]
+    let record_query = move |request: &Request, response: Response| {
+        let query = request.uri().query().unwrap_or_default().to_string();
+        query_state.lock().unwrap().connections.push(query);
+        Ok(response)
+    };
+    let Ok(socket) = tokio_tungstenite::accept_hdr_async(stream, record_query).await else {
+        return;
+    };
+    let (mut sink, mut stream) = socket.split();
+    let (sender, mut outgoing) = mpsc::unbounded_channel();
+    state.lock().unwrap().open.push(sender);
+
+    loop {
+        tokio::select! {
+            message = stream.next() => {
+                let text = match message {
+                    Some(Ok(Message::Text(text))) => text,
+                    Some(Ok(Message::Close(_)) | Err(_)) | None => break,
+                    Some(Ok(_)) => continue,
+                };
+                let Ok(message) = serde_json::from_str::<Value>(&text) else { continue };
+                let reply = reply_to(&state, &message);
+                state.lock().unwrap().received.push(message);
+                if let Some(reply) = reply {
+                    if sink.send(Message::Text(reply.to_string())).await.is_err() {
+                        break;
+                    }
+                }
+            }
+            next = outgoing.recv() => match next {
+                Some(Outgoing::Text(text)) => {
+                    if sink.send(Message::Text(text)).await.is_err() {
+                        break;
+                    }
+                }
+                Some(Outgoing::Close) | None => break,
+            },
+        }
+    }
+}
+
+/// Respuesta de Realtime a un mensaje del cliente
+fn reply_to(state: &Mutex<State>, message: &Value) -> Option<Value> {
+    let topic = message["topic"].as_str()?;
+    let response = match message["event"].as_str()? {
+        "heartbeat" | "phx_leave" => json!({ "status": "ok", "response": {} }),
+        "phx_join" => {
+            let mut state = state.lock().unwrap();
+            if let Some(index) = state.rejected.iter().position(|t| t == topic) {
+                state.rejected.remove(index);
+                json!({ "status": "error", "response": { "reason": "Unauthorized" } })
+            } else {
+                let changes = message["payload"]["config"]["postgres_changes"]
+                    .as_array()
+                    .cloned()
+                    .unwrap_or_default()
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, mut change)| {
+                        change["id"] = json!(index + 1);
+                        change
+                    })
+                    .collect::<Vec<_>>();
+                json!({ "status": "ok", "response": { "postgres_changes": changes } })
+            }
+        }
+        _ => return None,
+    };
+    Some(json!({
+        "topic": topic,
+        "event": "phx_reply",
+        "payload": response,
+        "ref": message["ref"],
+        "join_ref": message["join_ref"],
+    }))
+}
@@ -324,15 +324,17 @@ function CitasContent() {
       // 3. Consulta REST a Supabase a través del proxy de Tauri
       const selectQuery = "id,paciente_id,offline_patient_id,fecha_hora,duracion_minutos,motivo,status,tipo_cita,color,notas_internas,location_id,paciente:profiles!appointments_paciente_id_fkey(nombre_completo,telefono,email,avatar_url),offline_patient:offline_patients!appointments_offline_patient_id_fkey(nombre_completo,telefono,email)";
 
-      const queryParams = new URLSearchParams({
-        select: selectQuery,
-        medico_id: `eq.${doctorId}`,
-        fecha_hora: `gte.${startDate.toISOString()}`,
-      });
-      const queryString = `${queryParams.toString()}&fecha_hora=lte.${endDate.toISOString()}&order=fecha_hora.asc`;
-
-      const data = await tauriApiService.supabaseGet<RawAppointment[]>(
-        `/rest/v1/appointments?${queryString}`,
+      const data = await tauriApiService.supabaseQuery<RawAppointment[]>(
+        {
+          table: 'appointments',
+          select: selectQuery,
+          filters: [
+            { column: 'medico_id', op: 'eq', value: doctorId },
+            { column: 'fecha_hora', op: 'gte', value: startDate.toISOString() },
+            { column: 'fecha_hora', op: 'lte', value: endDate.toISOString() },
+          ],
+          order: [{ column: 'fecha_hora' }],
+        },
         token
       );
 
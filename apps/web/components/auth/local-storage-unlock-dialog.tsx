@@ -0,0 +1,94 @@
+"use client";
+
+import { useState } from "react";
+import { Button } from "@red-salud/ui";
+import { Input } from "@red-salud/ui";
+import { Label } from "@red-salud/ui";
+import { Loader2 } from "lucide-react";
+import {
+    Dialog,
+    DialogContent,
+    DialogDescription,
+    DialogFooter,
+    DialogHeader,
+    DialogTitle,
+} from "@red-salud/ui";
+
+interface LocalStorageUnlockDialogProps {
+    open: boolean;
+    /** La contraseña actual no abrió los datos: se pide la anterior */
+    previousPassword: boolean;
+    onUnlock: (password: string) => Promise<void>;
+    onSignOut: () => Promise<void>;
+}
+
+/**
+ * Pide la contraseña para abrir los datos offline cifrados del escritorio
+ * cuando la sesión se restauró sin ella.
+ */
+export function LocalStorageUnlockDialog({
+    open,
+    previousPassword,
+    onUnlock,
+    onSignOut,
+}: LocalStorageUnlockDialogProps) {
+    const [password, setPassword] = useState("");
+    const [error, setError] = useState<string | null>(null);
+    const [loading, setLoading] = useState(false);
+
+    const handleSubmit = async () => {
+        setLoading(true);
+        setError(null);
+        try {
+            await onUnlock(password);
+            setPassword("");
+        } catch {
+            setError("Contraseña incorrecta");
+        } finally {
+            setLoading(false);
+        }
+    };
+
+    return (
+        <Dialog open={open}>
+            <DialogContent className="sm:max-w-md">
+                <DialogHeader>
+                    <DialogTitle>Desbloquear datos sin conexión</DialogTitle>
+                    <DialogDescription>
+                        {previousPassword
+                            ? "Tus datos locales están protegidos con tu contraseña anterior. Ingrésala para volver a protegerlos con la actual."
+                            : "Ingresa tu contraseña para abrir los datos guardados en este equipo."}
+                    </DialogDescription>
+                </DialogHeader>
+                <form
+                    className="space-y-2 py-4"
+                    onSubmit={(e) => {
+                        e.preventDefault();
+                        handleSubmit();
+                    }}
+                >
+                    <Label htmlFor="local-storage-password">
+                        {previousPassword ? "Contraseña anterior" : "Contraseña"}
+                    </Label>
+                    <Input
+                        id="local-storage-password"
+                        type="password"
+                        autoFocus
+                        value={password}
+                        onChange={(e) => setPassword(e.target.value)}
+                    />
+                    {error && <p className="text-xs text-red-500">{error}</p>}
+                </form>
+                <DialogFooter>
+                    <Button variant="outline" onClick={onSignOut}>
+                        Cerrar sesión
+                    </Button>
+                    <Button onClick={handleSubmit} disabled={!password || loading}>
+                        {loading ? <Loader2 className="h-4 w-4 mr-2 animate-spin" /> : null}
+                        Desbloquear
+                    </Button>
+                </DialogFooter>
+            </DialogContent>
+        </Dialog>
+    );
+}
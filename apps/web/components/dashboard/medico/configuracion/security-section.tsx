@@ -21,6 +21,7 @@ import {
 } from "lucide-react";
 import { useEffect } from "react";
 import { supabase } from "@/lib/supabase/client";
+import { tauriSessionService } from "@/lib/services/tauri-session-service";
 import {
   generateTwoFactorSecretAction,
   verifyAndEnableTwoFactorAction,
@@ -172,6 +173,14 @@ export function SecuritySection() {
 
       if (error) throw error;
 
+      // 3. En escritorio, los datos offline pasan a protegerse con la nueva
+      try {
+        await tauriSessionService.changeStorageSecret(currentPassword, newPassword);
+      } catch (storageError) {
+        console.warn("No se pudo actualizar la clave de los datos locales:", storageError);
+        toast.warning("Al volver a entrar se te pedirá la contraseña anterior para abrir tus datos sin conexión");
+      }
+
       toast.success("Contraseña actualizada correctamente");
       setCurrentPassword("");
       setNewPassword("");
@@ -4,11 +4,15 @@
 import { createContext, useContext, useEffect, useState } from "react";
 import { User, Session } from "@supabase/supabase-js";
 import { supabase } from "@/lib/supabase/client";
+import { tauriSessionService } from "@/lib/services/tauri-session-service";
+import { LocalStorageUnlockDialog } from "@/components/auth/local-storage-unlock-dialog";
 
 interface UserContextType {
   user: User | null;
   session: Session | null;
   isLoading: boolean;
+  /** En escritorio, los datos offline esperan la contraseña del usuario */
+  storageLocked: boolean;
   signOut: () => Promise<void>;
 }
 
@@ -26,6 +30,20 @@ export function SupabaseAuthProvider({ children }: { children: React.ReactNode }
   const [user, setUser] = useState<User | null>(null);
   const [session, setSession] = useState<Session | null>(null);
   const [isLoading, setIsLoading] = useState(true);
+  const [storageLocked, setStorageLocked] = useState(false);
+
+  // Abre y desbloquea el perfil local en cada sesión, sea con contraseña,
+  // OAuth o restaurada. No se espera: llama a Supabase y no puede bloquear
+  // el callback de onAuthStateChange.
+  const openLocalStorage = (user: User) => {
+    tauriSessionService
+      .openStorage(user)
+      .then((state) => setStorageLocked(state === "locked"))
+      .catch((error) => {
+        console.warn("No se pudo desbloquear el almacenamiento local:", error);
+        setStorageLocked(true);
+      });
+  };
 
   // Function to sync session with server
   const syncSessionWithServer = async (session: Session | null) => {
@@ -57,6 +75,8 @@ export function SupabaseAuthProvider({ children }: { children: React.ReactNode }
 
         if (session) {
           console.log("✅ [Auth] Sesión inicial detectada");
+          await tauriSessionService.setSession(session);
+          openLocalStorage(session.user);
           // Optional: sync on mount if needed, but often middleware/proxy handles it
         }
       } catch (error) {
@@ -78,15 +98,35 @@ export function SupabaseAuthProvider({ children }: { children: React.ReactNode }
 
       if (event === "SIGNED_IN" || event === "TOKEN_REFRESHED") {
         await syncSessionWithServer(newSession);
+        if (newSession) await tauriSessionService.setSession(newSession);
+        if (event === "SIGNED_IN" && newSession) openLocalStorage(newSession.user);
       } else if (event === "SIGNED_OUT") {
         setUser(null);
         setSession(null);
+        setStorageLocked(false);
+        await tauriSessionService.lockStorage().catch(() => undefined);
+        await tauriSessionService.clearSession();
         // Optionally notify server of signout
       }
     });
 
+    // En desktop el plugin refresca el token: se le pasa a supabase-js
+    const unlistenTauri = tauriSessionService.listen(
+      (rustSession) => {
+        supabase.auth.setSession({
+          access_token: rustSession.accessToken,
+          refresh_token: rustSession.refreshToken,
+        });
+      },
+      (reason) => {
+        console.warn("[Auth] Sesión expirada en desktop:", reason);
+        supabase.auth.signOut();
+      }
+    );
+
     return () => {
       subscription?.unsubscribe();
+      unlistenTauri.then((unlisten) => unlisten());
     };
   }, []);
 
@@ -94,9 +134,20 @@ export function SupabaseAuthProvider({ children }: { children: React.ReactNode }
     await supabase.auth.signOut();
   };
 
+  const unlockStorage = async (password: string) => {
+    await tauriSessionService.unlockWithPassword(password);
+    setStorageLocked(false);
+  };
+
   return (
-    <UserContext.Provider value={{ user, session, isLoading, signOut }}>
+    <UserContext.Provider value={{ user, session, isLoading, storageLocked, signOut }}>
       {children}
+      <LocalStorageUnlockDialog
+        open={storageLocked && !!user}
+        previousPassword={storageLocked && tauriSessionService.hasPendingSecret()}
+        onUnlock={unlockStorage}
+        onSignOut={signOut}
+      />
     </UserContext.Provider>
   );
 }
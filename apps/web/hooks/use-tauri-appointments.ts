@@ -2,7 +2,6 @@
 
 import { useState, useEffect, useCallback } from "react";
 import { tauriApiService } from "@/lib/services/tauri-api-service";
-import { supabase } from "@/lib/supabase/client";
 
 export interface Appointment {
     id: string;
@@ -30,12 +29,12 @@ export function useTauriAppointments(doctorId: string) {
                 setAppointments(cached);
             }
 
-            const { data: { session } } = await supabase.auth.getSession();
-            const token = session?.access_token || '';
-
-            const data = await tauriApiService.supabaseGet<Appointment[]>(
-                `/rest/v1/appointments?doctor_id=eq.${doctorId}&select=*`,
-                token,
+            const data = await tauriApiService.supabaseQuery<Appointment[]>(
+                {
+                    table: 'appointments',
+                    filters: [{ column: 'doctor_id', op: 'eq', value: doctorId }],
+                },
+                undefined,
                 `appointments_${doctorId}`
             );
             setAppointments(data);
@@ -58,14 +57,10 @@ export function useTauriAppointments(doctorId: string) {
 
     const createAppointment = async (appointmentData: Omit<Appointment, 'id'>) => {
         try {
-            const { data: { session } } = await supabase.auth.getSession();
-            const token = session?.access_token || '';
-
             // For Supabase REST API
             const newAppointment = await tauriApiService.supabasePost<Appointment>(
                 "/rest/v1/appointments",
-                appointmentData,
-                token
+                appointmentData
             );
             setAppointments((prev) => [...prev, newAppointment]);
             return newAppointment;
@@ -77,13 +72,14 @@ export function useTauriAppointments(doctorId: string) {
 
     const updateAppointment = async (id: string, updates: Partial<Appointment>) => {
         try {
-            const { data: { session } } = await supabase.auth.getSession();
-            const token = session?.access_token || '';
-
+            // La fila tal como se editó, para detectar si otro la cambió mientras tanto
+            const base = appointments.find((apt) => apt.id === id);
             const updated = await tauriApiService.supabasePatch<Appointment>(
                 `/rest/v1/appointments?id=eq.${id}`,
                 updates,
-                token
+                undefined,
+                base,
+                crypto.randomUUID()
             );
             setAppointments((prev) =>
                 prev.map((apt) => (apt.id === id ? { ...apt, ...updates } : apt))
@@ -97,12 +93,8 @@ export function useTauriAppointments(doctorId: string) {
 
     const deleteAppointment = async (id: string) => {
         try {
-            const { data: { session } } = await supabase.auth.getSession();
-            const token = session?.access_token || '';
-
             await tauriApiService.supabaseDelete(
-                `/rest/v1/appointments?id=eq.${id}`,
-                token
+                `/rest/v1/appointments?id=eq.${id}`
             );
             setAppointments((prev) => prev.filter((apt) => apt.id !== id));
         } catch (err: unknown) {
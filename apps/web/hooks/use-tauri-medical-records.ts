@@ -2,7 +2,6 @@
 
 import { useState, useEffect, useCallback } from "react";
 import { tauriApiService } from "@/lib/services/tauri-api-service";
-import { supabase } from "@/lib/supabase/client";
 
 export interface MedicalRecord {
     id: string;
@@ -26,12 +25,13 @@ export function useTauriMedicalRecords(patientId: string) {
             const cached = await tauriApiService.getOfflineData<MedicalRecord[]>(`records_${patientId}`);
             if (cached) setRecords(cached);
 
-            const { data: { session } } = await supabase.auth.getSession();
-            const token = session?.access_token || '';
-
-            const data = await tauriApiService.supabaseGet<MedicalRecord[]>(
-                `/rest/v1/medical_records?patient_id=eq.${patientId}&select=*&order=created_at.desc`,
-                token,
+            const data = await tauriApiService.supabaseQuery<MedicalRecord[]>(
+                {
+                    table: 'medical_records',
+                    filters: [{ column: 'patient_id', op: 'eq', value: patientId }],
+                    order: [{ column: 'created_at', ascending: false }],
+                },
+                undefined,
                 `records_${patientId}`
             );
             setRecords(data);
@@ -51,13 +51,9 @@ export function useTauriMedicalRecords(patientId: string) {
 
     const createRecord = async (recordData: Omit<MedicalRecord, 'id' | 'created_at'>) => {
         try {
-            const { data: { session } } = await supabase.auth.getSession();
-            const token = session?.access_token || '';
-
             const newRecord = await tauriApiService.supabasePost<MedicalRecord>(
                 "/rest/v1/medical_records",
-                recordData,
-                token
+                recordData
             );
             setRecords((prev) => [newRecord, ...prev]);
             return newRecord;
@@ -69,13 +65,14 @@ export function useTauriMedicalRecords(patientId: string) {
 
     const updateRecord = async (id: string, updates: Partial<MedicalRecord>) => {
         try {
-            const { data: { session } } = await supabase.auth.getSession();
-            const token = session?.access_token || '';
-
+            // La fila tal como se editó, para detectar si otro la cambió mientras tanto
+            const base = records.find((rec) => rec.id === id);
             const updated = await tauriApiService.supabasePatch<MedicalRecord>(
                 `/rest/v1/medical_records?id=eq.${id}`,
                 updates,
-                token
+                undefined,
+                base,
+                crypto.randomUUID()
             );
             setRecords((prev) =>
                 prev.map((rec) => (rec.id === id ? { ...rec, ...updates } : rec))
@@ -2,7 +2,6 @@
 
 import { useState, useEffect, useCallback } from "react";
 import { tauriApiService } from "@/lib/services/tauri-api-service";
-import { supabase } from "@/lib/supabase/client";
 
 export interface Patient {
     id: string;
@@ -24,12 +23,12 @@ export function useTauriPatients(doctorId: string) {
             const cached = await tauriApiService.getOfflineData<Patient[]>(`patients_${doctorId}`);
             if (cached) setPatients(cached);
 
-            const { data: { session } } = await supabase.auth.getSession();
-            const token = session?.access_token || '';
-
-            const data = await tauriApiService.supabaseGet<Patient[]>(
-                `/rest/v1/patients?doctor_id=eq.${doctorId}&select=*`,
-                token,
+            const data = await tauriApiService.supabaseQuery<Patient[]>(
+                {
+                    table: 'patients',
+                    filters: [{ column: 'doctor_id', op: 'eq', value: doctorId }],
+                },
+                undefined,
                 `patients_${doctorId}`
             );
             setPatients(data);
@@ -55,12 +54,12 @@ export function useTauriPatients(doctorId: string) {
                 if (found) return found;
             }
 
-            const { data: { session } } = await supabase.auth.getSession();
-            const token = session?.access_token || '';
-
-            return await tauriApiService.supabaseGet<Patient>(
-                `/rest/v1/patients?id=eq.${patientId}&select=*`,
-                token,
+            return await tauriApiService.supabaseQuery<Patient>(
+                {
+                    table: 'patients',
+                    filters: [{ column: 'id', op: 'eq', value: patientId }],
+                },
+                undefined,
                 `patient_${patientId}`
             );
         } catch (err: unknown) {
@@ -14,6 +14,7 @@
 
 import { invoke } from '@tauri-apps/api/core';
 import type { NetworkService, RequestOptions } from '../types';
+import { parseSupabaseBody, type SupabaseResponse } from '../../services/tauri-api-service';
 
 /**
  * Network error types for better error handling
@@ -26,6 +27,15 @@ export enum NetworkErrorType {
   UNKNOWN = 'UNKNOWN',
 }
 
+/**
+ * Connectivity state reported by the desktop monitor (`connectivity-changed` event)
+ */
+export interface ConnectivityStatus {
+  state: 'online' | 'degraded' | 'offline';
+  latencyMs: number | null;
+  checkedAt: number | null;
+}
+
 export class NetworkError extends Error {
   constructor(
     message: string,
@@ -75,6 +85,34 @@ export class TauriNetworkService implements NetworkService {
    * Parse error from Rust command invocation
    */
   private parseError(error: unknown): NetworkError {
+    if (error instanceof NetworkError) {
+      return error;
+    }
+
+    // Rust commands reject with { code, message, status }
+    if (error && typeof error === 'object' && 'code' in error) {
+      const { code, message, status } = error as {
+        code: string;
+        message: string;
+        status: number | null;
+      };
+      switch (code) {
+        case 'NETWORK':
+          return new NetworkError(message, NetworkErrorType.CONNECTION_FAILED, undefined, error);
+        case 'AUTH':
+          return new NetworkError(message, NetworkErrorType.AUTHENTICATION_ERROR, 401, error);
+        case 'HTTP':
+          return new NetworkError(
+            message,
+            status !== null && status >= 500 ? NetworkErrorType.SERVER_ERROR : NetworkErrorType.UNKNOWN,
+            status ?? undefined,
+            error
+          );
+      }
+      // QUEUED and CONFLICT are final: retrying would enqueue the mutation twice
+      return new NetworkError(message, NetworkErrorType.UNKNOWN, status ?? undefined, error);
+    }
+
     const errorStr = String(error);
 
     // Check for timeout
@@ -202,12 +240,12 @@ export class TauriNetworkService implements NetworkService {
         // Extract access token from headers if present
         const accessToken = options?.headers?.['Authorization']?.replace('Bearer ', '') || '';
 
-        const result = await invoke<string>('supabase_get', {
+        const result = await invoke<SupabaseResponse>('plugin:red-salud-desktop-core|supabase_get', {
           endpoint: url,
           accessToken,
         });
 
-        return JSON.parse(result);
+        return parseSupabaseBody<T>(result);
       } catch (error) {
         throw this.parseError(error);
       }
@@ -222,13 +260,13 @@ export class TauriNetworkService implements NetworkService {
       try {
         const accessToken = options?.headers?.['Authorization']?.replace('Bearer ', '') || '';
 
-        const result = await invoke<string>('supabase_post', {
+        const result = await invoke<SupabaseResponse>('plugin:red-salud-desktop-core|supabase_post', {
           endpoint: url,
           body: JSON.stringify(body),
           accessToken,
         });
 
-        return JSON.parse(result);
+        return parseSupabaseBody<T>(result);
       } catch (error) {
         throw this.parseError(error);
       }
@@ -243,13 +281,13 @@ export class TauriNetworkService implements NetworkService {
       try {
         const accessToken = options?.headers?.['Authorization']?.replace('Bearer ', '') || '';
 
-        const result = await invoke<string>('supabase_patch', {
+        const result = await invoke<SupabaseResponse>('plugin:red-salud-desktop-core|supabase_patch', {
           endpoint: url,
           body: JSON.stringify(body),
           accessToken,
         });
 
-        return JSON.parse(result);
+        return parseSupabaseBody<T>(result);
       } catch (error) {
         throw this.parseError(error);
       }
@@ -264,12 +302,12 @@ export class TauriNetworkService implements NetworkService {
       try {
         const accessToken = options?.headers?.['Authorization']?.replace('Bearer ', '') || '';
 
-        const result = await invoke<string>('supabase_delete', {
+        const result = await invoke<SupabaseResponse>('plugin:red-salud-desktop-core|supabase_delete', {
           endpoint: url,
           accessToken,
         });
 
-        return JSON.parse(result);
+        return parseSupabaseBody<T>(result);
       } catch (error) {
         throw this.parseError(error);
       }
@@ -281,10 +319,19 @@ export class TauriNetworkService implements NetworkService {
    */
   async checkConnectivity(): Promise<boolean> {
     try {
-      return await invoke<boolean>('check_connectivity');
+      return await invoke<boolean>('plugin:red-salud-desktop-core|check_connectivity');
     } catch (error) {
       console.error('Connectivity check failed:', error);
       return false;
     }
   }
+
+  /**
+   * Get the connectivity monitor state and the latency of the last probe
+   */
+  async getConnectivityStatus(refresh = false): Promise<ConnectivityStatus> {
+    return invoke<ConnectivityStatus>('plugin:red-salud-desktop-core|get_connectivity_status', {
+      refresh,
+    });
+  }
 }
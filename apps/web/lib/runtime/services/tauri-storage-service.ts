@@ -16,7 +16,7 @@ export class TauriStorageService implements StorageService {
    */
   async get<T>(key: string): Promise<T | null> {
     try {
-      const result = await invoke<string | null>('get_offline_data', { key });
+      const result = await invoke<string | null>('plugin:red-salud-desktop-core|get_offline_data', { key });
       return result ? JSON.parse(result) : null;
     } catch (error) {
       console.error(`Failed to get data for key ${key}:`, error);
@@ -29,7 +29,7 @@ export class TauriStorageService implements StorageService {
    */
   async save<T>(key: string, data: T): Promise<void> {
     try {
-      await invoke('save_offline_data', {
+      await invoke('plugin:red-salud-desktop-core|save_offline_data', {
         key,
         data: JSON.stringify(data),
       });
@@ -44,7 +44,7 @@ export class TauriStorageService implements StorageService {
    */
   async delete(key: string): Promise<void> {
     try {
-      await invoke('delete_offline_data', { key });
+      await invoke('plugin:red-salud-desktop-core|delete_offline_data', { key });
     } catch (error) {
       console.error(`Failed to delete data for key ${key}:`, error);
       throw error;
@@ -56,7 +56,7 @@ export class TauriStorageService implements StorageService {
    */
   async clear(): Promise<void> {
     try {
-      await invoke('clear_offline_data');
+      await invoke('plugin:red-salud-desktop-core|clear_offline_data');
     } catch (error) {
       console.error('Failed to clear storage:', error);
       throw error;
@@ -68,7 +68,7 @@ export class TauriStorageService implements StorageService {
    */
   async keys(): Promise<string[]> {
     try {
-      return await invoke<string[]>('get_offline_keys');
+      return await invoke<string[]>('plugin:red-salud-desktop-core|get_offline_keys');
     } catch (error) {
       console.error('Failed to get keys:', error);
       return [];
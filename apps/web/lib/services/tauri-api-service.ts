@@ -1,4 +1,113 @@
 import { invoke } from '@tauri-apps/api/core';
+import { supabase } from '@/lib/supabase/client';
+import { tauriSessionService } from './tauri-session-service';
+
+/** De dónde salió cada valor de la configuración efectiva */
+export type ConfigSource = 'default' | 'file' | 'env' | 'cli';
+
+export interface EffectiveConfig {
+  environment: 'dev' | 'staging' | 'prod';
+  configFile: string | null;
+  supabaseUrl: string;
+  /** Siempre oculta; `null` si no hay clave */
+  supabaseAnonKey: string | null;
+  httpConnectTimeoutSecs: number;
+  httpReadTimeoutSecs: number;
+  httpMaxRetries: number;
+  httpProxy: string | null;
+  httpNoProxy: string | null;
+  /** Servicio de tasas de cambio (`services/bcv-rate`) */
+  ratesUrl: string | null;
+  sources: Record<string, ConfigSource>;
+  valid: boolean;
+  issues: string[];
+}
+
+/**
+ * Respuesta de los comandos proxy de Supabase en desktop
+ */
+export interface SupabaseResponse {
+  status: number;
+  contentRange: string | null;
+  etag: string | null;
+  preferenceApplied: string | null;
+  body: string;
+}
+
+export type PostgrestValue = string | number | boolean;
+
+export type PostgrestFilter = { column: string; not?: boolean } & (
+  | { op: 'eq' | 'neq' | 'gt' | 'gte' | 'lt' | 'lte'; value: PostgrestValue }
+  | { op: 'like' | 'ilike'; value: string }
+  | { op: 'in'; value: PostgrestValue[] }
+  | { op: 'is'; value: boolean | null }
+);
+
+/**
+ * Consulta de lectura para `supabase_query`; Rust construye y codifica la URL
+ */
+export interface PostgrestQuery {
+  table: string;
+  /** Columnas y recursos embebidos; por defecto `*` */
+  select?: string;
+  filters?: PostgrestFilter[];
+  order?: { column: string; ascending?: boolean; nullsFirst?: boolean }[];
+  limit?: number;
+  offset?: number;
+  /** Filas `from..to` incluidas, como `range()` de supabase-js */
+  range?: { from: number; to: number };
+  count?: 'exact' | 'planned' | 'estimated';
+}
+
+const RESERVED_LIST_CHARS = /[,.:()"\\ ]/;
+
+/**
+ * Ruta PostgREST equivalente a la que construye el escritorio, para el modo web
+ */
+export function postgrestPath(query: PostgrestQuery): string {
+  // Sin espacios fuera de comillas, como supabase-js
+  const select = (query.select ?? '*').replace(/\s+(?=(?:[^"]*"[^"]*")*[^"]*$)/g, '');
+  const params = new URLSearchParams({ select });
+  for (const filter of query.filters ?? []) {
+    let value: string;
+    if (filter.op === 'in') {
+      const items = filter.value.map((item) => {
+        const text = String(item);
+        return text === '' || RESERVED_LIST_CHARS.test(text)
+          ? `"${text.replace(/\\/g, '\\\\').replace(/"/g, '\\"')}"`
+          : text;
+      });
+      value = `in.(${items.join(',')})`;
+    } else {
+      value = `${filter.op}.${filter.value === null ? 'null' : String(filter.value)}`;
+    }
+    params.append(filter.column, filter.not ? `not.${value}` : value);
+  }
+  if (query.order?.length) {
+    params.append(
+      'order',
+      query.order
+        .map(({ column, ascending = true, nullsFirst }) =>
+          [column, ascending ? 'asc' : 'desc', nullsFirst === undefined ? null : nullsFirst ? 'nullsfirst' : 'nullslast']
+            .filter(Boolean)
+            .join('.')
+        )
+        .join(',')
+    );
+  }
+  const limit = query.range ? query.range.to - query.range.from + 1 : query.limit;
+  const offset = query.range ? query.range.from : query.offset;
+  if (limit !== undefined) params.append('limit', String(limit));
+  if (offset) params.append('offset', String(offset));
+  return `/rest/v1/${query.table}?${params.toString()}`;
+}
+
+/**
+ * Interpreta el cuerpo JSON de una respuesta; las respuestas vacías (204) son null
+ */
+export function parseSupabaseBody<T>(response: SupabaseResponse): T {
+  return (response.body ? JSON.parse(response.body) : null) as T;
+}
 
 /**
  * Servicio unificado de API que funciona tanto en web como en Tauri
@@ -29,18 +138,40 @@ class TauriApiService {
       };
     }
 
-    return invoke<{ url: string; anon_key: string }>('get_supabase_config');
+    return invoke<{ url: string; anon_key: string }>('plugin:red-salud-desktop-core|get_supabase_config');
   }
 
   /**
-   * Hace una petición GET a Supabase
+   * Token para los comandos `supabase_*`: tras `set_session` el plugin usa y
+   * refresca el suyo, así que el explícito solo se envía antes de eso
    */
+  private desktopToken(accessToken?: string): string | undefined {
+    return tauriSessionService.hasSession() ? undefined : accessToken || undefined;
+  }
+
+  /**
+   * Cabecera `Authorization` del modo web: el token recibido o el de supabase-js
+   */
+  private async webAuthorization(accessToken: string | undefined, anonKey: string): Promise<string> {
+    const token = accessToken || (await supabase.auth.getSession()).data.session?.access_token;
+    return `Bearer ${token || anonKey}`;
+  }
+
+  /**
+   * Configuración efectiva del escritorio (entorno, origen de cada opción y
+   * problemas de validación), sin secretos
+   */
+  async getEffectiveConfig(): Promise<EffectiveConfig | null> {
+    if (!this.isTauri) return null;
+    return invoke<EffectiveConfig>('plugin:red-salud-desktop-core|get_effective_config');
+  }
+
   /**
    * Hace una petición GET a Supabase
    */
   async supabaseGet<T = unknown>(
     endpoint: string,
-    accessToken: string,
+    accessToken?: string,
     cacheKey?: string
   ): Promise<T> {
     if (!this.isTauri) {
@@ -49,18 +180,47 @@ class TauriApiService {
       const response = await fetch(`${config.url}${endpoint}`, {
         headers: {
           apikey: config.anon_key,
-          Authorization: `Bearer ${accessToken}`,
+          Authorization: await this.webAuthorization(accessToken, config.anon_key),
         },
       });
       return response.json();
     }
 
-    const result = await invoke<string>('supabase_get', {
+    const result = await invoke<SupabaseResponse>('plugin:red-salud-desktop-core|supabase_get', {
       endpoint,
-      accessToken,
+      accessToken: this.desktopToken(accessToken),
+      cacheKey,
+    });
+    return parseSupabaseBody<T>(result);
+  }
+
+  /**
+   * Consulta una tabla a partir de una descripción estructurada
+   * (filtros, orden, paginación) sin construir la URL a mano
+   */
+  async supabaseQuery<T = unknown>(
+    query: PostgrestQuery,
+    accessToken?: string,
+    cacheKey?: string
+  ): Promise<T> {
+    if (!this.isTauri) {
+      const config = await this.getSupabaseConfig();
+      const response = await fetch(`${config.url}${postgrestPath(query)}`, {
+        headers: {
+          apikey: config.anon_key,
+          Authorization: await this.webAuthorization(accessToken, config.anon_key),
+          ...(query.count ? { Prefer: `count=${query.count}` } : {}),
+        },
+      });
+      return response.json();
+    }
+
+    const result = await invoke<SupabaseResponse>('plugin:red-salud-desktop-core|supabase_query', {
+      query,
+      accessToken: this.desktopToken(accessToken),
       cacheKey,
     });
-    return JSON.parse(result);
+    return parseSupabaseBody<T>(result);
   }
 
   /**
@@ -69,7 +229,7 @@ class TauriApiService {
   async supabasePost<T = unknown>(
     endpoint: string,
     body: unknown,
-    accessToken: string
+    accessToken?: string
   ): Promise<T> {
     if (!this.isTauri) {
       const config = await this.getSupabaseConfig();
@@ -77,7 +237,7 @@ class TauriApiService {
         method: 'POST',
         headers: {
           apikey: config.anon_key,
-          Authorization: `Bearer ${accessToken}`,
+          Authorization: await this.webAuthorization(accessToken, config.anon_key),
           'Content-Type': 'application/json',
         },
         body: JSON.stringify(body),
@@ -85,21 +245,29 @@ class TauriApiService {
       return response.json();
     }
 
-    const result = await invoke<string>('supabase_post', {
+    const result = await invoke<SupabaseResponse>('plugin:red-salud-desktop-core|supabase_post', {
       endpoint,
       body: JSON.stringify(body),
-      accessToken,
+      accessToken: this.desktopToken(accessToken),
     });
-    return JSON.parse(result);
+    return parseSupabaseBody<T>(result);
   }
 
   /**
    * Hace una petición PATCH a Supabase
+   *
+   * `base` es la fila tal como se leyó antes de editarla (al menos su
+   * `updated_at`): en desktop el PATCH solo se aplica si la fila sigue en esa
+   * versión y, si cambió, se resuelve con la política de la tabla o termina
+   * en `CONFLICT`. `idempotencyKey` evita aplicarlo dos veces si se reintenta
+   * desde la cola offline.
    */
   async supabasePatch<T = unknown>(
     endpoint: string,
     body: unknown,
-    accessToken: string
+    accessToken?: string,
+    base?: Record<string, unknown>,
+    idempotencyKey?: string
   ): Promise<T> {
     if (!this.isTauri) {
       const config = await this.getSupabaseConfig();
@@ -107,20 +275,23 @@ class TauriApiService {
         method: 'PATCH',
         headers: {
           apikey: config.anon_key,
-          Authorization: `Bearer ${accessToken}`,
+          Authorization: await this.webAuthorization(accessToken, config.anon_key),
           'Content-Type': 'application/json',
+          ...(idempotencyKey ? { 'Idempotency-Key': idempotencyKey } : {}),
         },
         body: JSON.stringify(body),
       });
       return response.json();
     }
 
-    const result = await invoke<string>('supabase_patch', {
+    const result = await invoke<SupabaseResponse>('plugin:red-salud-desktop-core|supabase_patch', {
       endpoint,
       body: JSON.stringify(body),
-      accessToken,
+      accessToken: this.desktopToken(accessToken),
+      idempotencyKey,
+      base,
     });
-    return JSON.parse(result);
+    return parseSupabaseBody<T>(result);
   }
 
   /**
@@ -128,7 +299,7 @@ class TauriApiService {
    */
   async supabaseDelete<T = unknown>(
     endpoint: string,
-    accessToken: string
+    accessToken?: string
   ): Promise<T> {
     if (!this.isTauri) {
       const config = await this.getSupabaseConfig();
@@ -136,17 +307,17 @@ class TauriApiService {
         method: 'DELETE',
         headers: {
           apikey: config.anon_key,
-          Authorization: `Bearer ${accessToken}`,
+          Authorization: await this.webAuthorization(accessToken, config.anon_key),
         },
       });
       return response.json();
     }
 
-    const result = await invoke<string>('supabase_delete', {
+    const result = await invoke<SupabaseResponse>('plugin:red-salud-desktop-core|supabase_delete', {
       endpoint,
-      accessToken,
+      accessToken: this.desktopToken(accessToken),
     });
-    return JSON.parse(result);
+    return parseSupabaseBody<T>(result);
   }
 
 
@@ -162,7 +333,7 @@ class TauriApiService {
       throw new Error('File save only available in desktop app');
     }
 
-    return invoke<string>('save_file_locally', {
+    return invoke<string>('plugin:red-salud-desktop-core|save_file_locally', {
       filename,
       data: Array.from(data),
       subfolder,
@@ -178,7 +349,7 @@ class TauriApiService {
       return;
     }
 
-    return invoke('open_file', { path });
+    return invoke('plugin:red-salud-desktop-core|open_file', { path });
   }
 
   /**
@@ -192,7 +363,7 @@ class TauriApiService {
       throw new Error('File read only available in desktop app');
     }
 
-    const data = await invoke<number[]>('read_file_locally', {
+    const data = await invoke<number[]>('plugin:red-salud-desktop-core|read_file_locally', {
       filename,
       subfolder,
     });
@@ -207,7 +378,7 @@ class TauriApiService {
       return navigator.onLine;
     }
 
-    return invoke<boolean>('check_connectivity');
+    return invoke<boolean>('plugin:red-salud-desktop-core|check_connectivity');
   }
 
   /**
@@ -219,7 +390,7 @@ class TauriApiService {
       return;
     }
 
-    return invoke('save_offline_data', {
+    return invoke('plugin:red-salud-desktop-core|save_offline_data', {
       key,
       data: JSON.stringify(data),
     });
@@ -234,7 +405,7 @@ class TauriApiService {
       return data ? JSON.parse(data) : null;
     }
 
-    const result = await invoke<string | null>('get_offline_data', { key });
+    const result = await invoke<string | null>('plugin:red-salud-desktop-core|get_offline_data', { key });
     return result ? JSON.parse(result) : null;
   }
 
@@ -247,7 +418,7 @@ class TauriApiService {
       return;
     }
 
-    return invoke('delete_offline_data', { key });
+    return invoke('plugin:red-salud-desktop-core|delete_offline_data', { key });
   }
 }
 
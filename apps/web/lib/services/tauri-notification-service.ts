@@ -1,4 +1,57 @@
-import { isPermissionGranted, requestPermission, sendNotification } from "@tauri-apps/plugin-notification";
+import { invoke } from "@tauri-apps/api/core";
+import { listen, type UnlistenFn } from "@tauri-apps/api/event";
+import { isPermissionGranted, requestPermission } from "@tauri-apps/plugin-notification";
+
+export type NotificationCategory = "prescription" | "stock_expiry" | "appointment" | "sync_error" | "general";
+
+export type NotificationOutcome = "delivered" | "disabled" | "quiet_hours" | "duplicate";
+
+export interface NotificationAction {
+    id: string;
+    title: string;
+}
+
+/**
+ * Aviso que el backend muestra aplicando preferencias, horas de silencio y
+ * deduplicación por `dedupKey`.
+ */
+export interface DesktopNotification {
+    category: NotificationCategory;
+    title: string;
+    body: string;
+    dedupKey?: string;
+    /** Se muestra aunque sean horas de silencio */
+    urgent?: boolean;
+    actions?: NotificationAction[];
+    data?: unknown;
+}
+
+/** Lo que llega en `desktop-notification`, con las acciones ya resueltas */
+export interface DesktopNotificationEvent extends Omit<DesktopNotification, "actions"> {
+    actions: NotificationAction[];
+    outcome: NotificationOutcome;
+}
+
+export interface CategoryPreference {
+    enabled: boolean;
+    sound: boolean;
+    bypassQuietHours: boolean;
+}
+
+export interface NotificationPreferences {
+    categories: Partial<Record<NotificationCategory, CategoryPreference>>;
+    /** Hora local `HH:MM`; puede cruzar la medianoche */
+    quietHours: { start: string; end: string } | null;
+    utcOffsetMinutes: number;
+}
+
+export interface NotificationLogEntry {
+    id: number;
+    category: NotificationCategory;
+    dedupKey: string | null;
+    outcome: NotificationOutcome;
+    createdAt: number;
+}
 
 export const tauriNotificationService = {
     async init() {
@@ -17,30 +70,57 @@ export const tauriNotificationService = {
         return permissionGranted;
     },
 
-    async notify(title: string, body: string) {
+    async send(notification: DesktopNotification): Promise<NotificationOutcome | null> {
         const permitted = await this.init();
-        if (!permitted) return;
+        if (!permitted) return null;
+
+        return invoke<NotificationOutcome>("plugin:red-salud-desktop-core|send_notification", { notification });
+    },
 
-        await sendNotification({
-            title,
-            body,
+    async notify(title: string, body: string) {
+        await this.send({ category: "general", title, body });
+    },
+
+    async getPreferences(): Promise<NotificationPreferences> {
+        return invoke<NotificationPreferences>("plugin:red-salud-desktop-core|get_notification_preferences");
+    },
+
+    /** El desfase horario se toma del sistema si no se indica */
+    async setPreferences(preferences: Omit<NotificationPreferences, "utcOffsetMinutes"> & { utcOffsetMinutes?: number }) {
+        await invoke("plugin:red-salud-desktop-core|set_notification_preferences", {
+            preferences: {
+                ...preferences,
+                utcOffsetMinutes: preferences.utcOffsetMinutes ?? -new Date().getTimezoneOffset(),
+            },
         });
     },
 
+    async getHistory(limit?: number): Promise<NotificationLogEntry[]> {
+        return invoke<NotificationLogEntry[]>("plugin:red-salud-desktop-core|get_notification_history", { limit });
+    },
+
+    onNotification(callback: (notification: DesktopNotificationEvent) => void): Promise<UnlistenFn> {
+        return listen<DesktopNotificationEvent>("desktop-notification", (event) => callback(event.payload));
+    },
+
     async notifyNewAppointment(appointment: Record<string, unknown>) {
         const patient = appointment.paciente as Record<string, unknown> | undefined;
-        await this.notify(
-            "Nueva Cita Agendada",
-            `Cita con ${patient?.nombre || 'Paciente'} a las ${appointment.hora || 'pendiente'}`
-        );
+        await this.send({
+            category: "appointment",
+            title: "Nueva Cita Agendada",
+            body: `Cita con ${patient?.nombre || 'Paciente'} a las ${appointment.hora || 'pendiente'}`,
+            dedupKey: appointment.id ? `cita:${appointment.id}` : undefined,
+        });
     },
 
     async notifySync(status: "success" | "error") {
-        await this.notify(
-            status === "success" ? "Sincronización Completa" : "Error de Sincronización",
-            status === "success"
+        await this.send({
+            category: status === "success" ? "general" : "sync_error",
+            title: status === "success" ? "Sincronización Completa" : "Error de Sincronización",
+            body: status === "success"
                 ? "Todos los datos están actualizados"
-                : "No se pudo sincronizar. Reintentando..."
-        );
+                : "No se pudo sincronizar. Reintentando...",
+            dedupKey: `sync:${status}`,
+        });
     }
 };
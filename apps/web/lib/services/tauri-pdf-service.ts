@@ -26,7 +26,7 @@ export const tauriPdfService = {
 
       const fileName = `receta_${receta.paciente?.id || 'unknown'}_${Date.now()}.html`;
 
-      const filePath = await invoke<string>("save_file_locally", {
+      const filePath = await invoke<string>("plugin:red-salud-desktop-core|save_file_locally", {
         appHandle: undefined, // Tauri handles this automatically
         filename: fileName,
         data: Array.from(data),
@@ -48,7 +48,7 @@ export const tauriPdfService = {
 
       const fileName = `historia_${historia.paciente?.id || 'unknown'}_${Date.now()}.html`;
 
-      const filePath = await invoke<string>("save_file_locally", {
+      const filePath = await invoke<string>("plugin:red-salud-desktop-core|save_file_locally", {
         filename: fileName,
         data: Array.from(data),
         subfolder: "historias"
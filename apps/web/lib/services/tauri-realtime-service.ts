@@ -0,0 +1,73 @@
+import { invoke } from "@tauri-apps/api/core";
+import { listen, type UnlistenFn } from "@tauri-apps/api/event";
+import type { NotificationCategory } from "./tauri-notification-service";
+
+/**
+ * Suscripción a `postgres_changes` que mantiene el backend de Rust. Sigue
+ * activa aunque la ventana esté oculta y, con `cacheKey`, actualiza esa
+ * entrada de la caché offline.
+ */
+export interface RealtimeSubscription {
+    id: string;
+    table: string;
+    schema?: string;
+    event?: "*" | "INSERT" | "UPDATE" | "DELETE";
+    /** Filtro de Realtime, por ejemplo `medico_id=eq.123` */
+    filter?: string;
+    cacheKey?: string;
+    primaryKey?: string;
+    /** Aviso nativo por cada fila nueva; `body` admite `{columna}` */
+    notify?: { category: NotificationCategory; title?: string; body: string };
+}
+
+export interface RealtimeChange<T = Record<string, unknown>> {
+    subscription: string;
+    schema: string;
+    table: string;
+    eventType: "INSERT" | "UPDATE" | "DELETE";
+    record: T;
+    oldRecord: Partial<T>;
+    commitTimestamp: string | null;
+}
+
+export interface RealtimeStatus {
+    state: "idle" | "connecting" | "connected" | "disconnected";
+    joined: string[];
+    attempt: number;
+    lastError: string | null;
+    connectedAt: number | null;
+    subscriptions: RealtimeSubscription[];
+}
+
+export const tauriRealtimeService = {
+    /**
+     * Se suscribe y escucha los cambios de esa suscripción. Devuelve una
+     * función que deja de escuchar y cancela la suscripción.
+     */
+    async subscribe<T = Record<string, unknown>>(
+        subscription: RealtimeSubscription,
+        onChange: (change: RealtimeChange<T>) => void
+    ): Promise<UnlistenFn> {
+        const unlisten = await listen<RealtimeChange<T>>("realtime-change", (event) => {
+            if (event.payload.subscription === subscription.id) onChange(event.payload);
+        });
+        try {
+            await invoke("plugin:red-salud-desktop-core|realtime_subscribe", { subscription });
+        } catch (error) {
+            unlisten();
+            throw error;
+        }
+        return () => {
+            unlisten();
+            invoke("plugin:red-salud-desktop-core|realtime_unsubscribe", { id: subscription.id }).catch(() => {});
+        };
+    },
+
+    async getStatus(): Promise<RealtimeStatus> {
+        return invoke<RealtimeStatus>("plugin:red-salud-desktop-core|get_realtime_status");
+    },
+
+    onStatus(callback: (status: RealtimeStatus) => void): Promise<UnlistenFn> {
+        return listen<RealtimeStatus>("realtime-status", (event) => callback(event.payload));
+    },
+};
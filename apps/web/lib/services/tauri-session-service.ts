@@ -0,0 +1,209 @@
+import { invoke } from "@tauri-apps/api/core";
+import { listen, type UnlistenFn } from "@tauri-apps/api/event";
+import type { Session, User } from "@supabase/supabase-js";
+import { supabase } from "@/lib/supabase/client";
+
+const PLUGIN = "plugin:red-salud-desktop-core";
+
+export interface RustSession {
+    accessToken: string;
+    refreshToken: string;
+    expiresAt: number;
+    userId: string | null;
+}
+
+export interface LocalProfile {
+    userId: string;
+    label: string | null;
+    createdAt: number;
+    lastUsedAt: number;
+    active: boolean;
+    sizeBytes: number;
+}
+
+export interface StorageStatus {
+    profile: string | null;
+    initialized: boolean;
+    unlocked: boolean;
+}
+
+/** Datos locales del usuario abiertos y legibles, o a la espera de su contraseña */
+export type LocalStorageState = "unlocked" | "locked";
+
+const isTauri = () => typeof window !== "undefined" && "__TAURI__" in window;
+
+/** Si el plugin ya tiene la sesión (`set_session`) y no hace falta pasarle el token */
+let rustSessionSet = false;
+/** Contraseña del inicio de sesión en curso, hasta que `openStorage` la use */
+let loginSecret: string | null = null;
+/**
+ * Contraseña actual de la cuenta cuando no abrió los datos locales: siguen
+ * protegidos con la anterior (se cambió en otro equipo o se restableció)
+ */
+let pendingSecret: string | null = null;
+
+const isAuthError = (error: unknown) =>
+    typeof error === "object" && error !== null && (error as { code?: string }).code === "AUTH";
+
+/** Las cuentas sin contraseña (OAuth) usan un secreto por usuario guardado en Supabase */
+const hasPassword = (user: User) =>
+    (user.app_metadata?.providers ?? [user.app_metadata?.provider]).includes("email");
+
+/**
+ * Mantiene la sesión de Supabase sincronizada con el plugin de escritorio,
+ * que refresca el token y lo inyecta en los comandos `supabase_*`.
+ */
+export const tauriSessionService = {
+    async setSession(session: Session) {
+        if (!isTauri()) return;
+        await invoke(`${PLUGIN}|set_session`, {
+            accessToken: session.access_token,
+            refreshToken: session.refresh_token,
+            expiresAt: session.expires_at,
+            expiresIn: session.expires_in,
+            userId: session.user?.id,
+        });
+        rustSessionSet = true;
+    },
+
+    async clearSession() {
+        if (!isTauri()) return;
+        rustSessionSet = false;
+        await invoke(`${PLUGIN}|clear_session`);
+    },
+
+    hasSession() {
+        return rustSessionSet;
+    },
+
+    /**
+     * Abre el perfil local del usuario: cada usuario del equipo tiene su
+     * propia base offline y sus archivos. Queda bloqueado hasta `unlockStorage`.
+     */
+    async switchProfile(userId: string, label?: string) {
+        if (!isTauri()) return;
+        await invoke(`${PLUGIN}|switch_profile`, { userId, label });
+    },
+
+    async listProfiles(): Promise<LocalProfile[]> {
+        if (!isTauri()) return [];
+        return invoke<LocalProfile[]>(`${PLUGIN}|list_profiles`);
+    },
+
+    /**
+     * Borra del equipo todos los datos locales de un usuario. El perfil de
+     * otro usuario requiere `confirm` y no puede ser el abierto.
+     */
+    async wipeProfile(userId: string, confirm = false) {
+        if (!isTauri()) return;
+        await invoke(`${PLUGIN}|wipe_profile`, { userId, confirm });
+    },
+
+    /**
+     * Desbloquea el perfil abierto con la contraseña recién validada.
+     * La primera vez cifra los datos que había en claro.
+     */
+    async unlockStorage(secret: string) {
+        if (!isTauri()) return;
+        await invoke(`${PLUGIN}|unlock_storage`, { secret });
+    },
+
+    /** Bloquea el almacenamiento, cierra el perfil y borra las copias descifradas */
+    async lockStorage() {
+        loginSecret = null;
+        pendingSecret = null;
+        if (!isTauri()) return;
+        await invoke(`${PLUGIN}|lock_storage`);
+    },
+
+    async getStorageStatus(): Promise<StorageStatus | null> {
+        if (!isTauri()) return null;
+        return invoke<StorageStatus>(`${PLUGIN}|get_storage_status`);
+    },
+
+    /** Vuelve a proteger los datos locales con la nueva contraseña */
+    async changeStorageSecret(currentSecret: string, newSecret: string) {
+        if (!isTauri()) return;
+        await invoke(`${PLUGIN}|change_storage_secret`, { currentSecret, newSecret });
+    },
+
+    /**
+     * Guarda la contraseña de un inicio de sesión con email para que la use
+     * el `openStorage` del evento `SIGNED_IN`; `null` la descarta
+     */
+    setLoginSecret(password: string | null) {
+        loginSecret = isTauri() ? password : null;
+    },
+
+    /**
+     * Abre el perfil local del usuario con sesión y lo desbloquea en
+     * cualquier inicio de sesión: con la contraseña recién usada, con el
+     * secreto de Supabase en cuentas OAuth o, en una sesión restaurada de una
+     * cuenta con contraseña, queda `locked` hasta `unlockWithPassword`.
+     */
+    async openStorage(user: User): Promise<LocalStorageState> {
+        if (!isTauri()) return "unlocked";
+        const password = loginSecret;
+        loginSecret = null;
+
+        await this.switchProfile(user.id, user.email);
+        const status = await this.getStorageStatus();
+        if (status?.unlocked) return "unlocked";
+
+        if (!hasPassword(user)) {
+            const { data: secret, error } = await supabase.rpc("get_local_storage_secret");
+            if (error || typeof secret !== "string") {
+                throw error ?? new Error("No se obtuvo el secreto del almacenamiento local");
+            }
+            await this.unlockStorage(secret);
+            return "unlocked";
+        }
+        if (!password) return "locked";
+        try {
+            await this.unlockStorage(password);
+            return "unlocked";
+        } catch (error) {
+            if (!isAuthError(error)) throw error;
+            pendingSecret = password;
+            return "locked";
+        }
+    },
+
+    /**
+     * Desbloquea con la contraseña que pide la interfaz. Si la cuenta ya usa
+     * otra, los datos locales pasan a protegerse con la actual.
+     */
+    async unlockWithPassword(password: string) {
+        await this.unlockStorage(password);
+        if (pendingSecret && pendingSecret !== password) {
+            await this.changeStorageSecret(password, pendingSecret);
+        }
+        pendingSecret = null;
+    },
+
+    /** Si el último `openStorage` encontró los datos con una contraseña anterior */
+    hasPendingSecret() {
+        return pendingSecret !== null;
+    },
+
+    /**
+     * Escucha los refrescos hechos en Rust (para que supabase-js no reutilice
+     * un refresh token ya rotado) y la expiración de la sesión.
+     */
+    async listen(
+        onRefreshed: (session: RustSession) => void,
+        onExpired: (reason: string) => void
+    ): Promise<UnlistenFn> {
+        if (!isTauri()) return () => {};
+        const unlistenRefreshed = await listen<RustSession>("session-refreshed", (event) =>
+            onRefreshed(event.payload)
+        );
+        const unlistenExpired = await listen<string>("session-expired", (event) =>
+            onExpired(event.payload)
+        );
+        return () => {
+            unlistenRefreshed();
+            unlistenExpired();
+        };
+    },
+};
@@ -0,0 +1,105 @@
+import { invoke } from "@tauri-apps/api/core";
+import { listen, type UnlistenFn } from "@tauri-apps/api/event";
+
+/**
+ * Subida o descarga de un objeto de Supabase Storage hecha desde Rust. El
+ * archivo local vive en los archivos del perfil (`subfolder/filename`).
+ */
+export interface StorageTransfer {
+    id: string;
+    direction: "upload" | "download";
+    bucket: string;
+    objectPath: string;
+    subfolder: string | null;
+    filename: string;
+    contentType: string | null;
+    upsert: boolean;
+    totalBytes: number | null;
+    bytesDone: number;
+    sha256: string | null;
+    expectedSha256: string | null;
+    localPath: string | null;
+    status: "running" | "cancelled" | "failed" | "completed";
+    error: string | null;
+    updatedAt: number;
+}
+
+export interface StorageUploadRequest {
+    bucket: string;
+    objectPath: string;
+    filename: string;
+    subfolder?: string;
+    contentType?: string;
+    upsert?: boolean;
+    /** Si el archivo local no coincide, no se sube */
+    expectedSha256?: string;
+}
+
+export interface StorageDownloadRequest {
+    bucket: string;
+    objectPath: string;
+    filename: string;
+    subfolder?: string;
+    expectedSha256?: string;
+}
+
+export const tauriStorageService = {
+    /**
+     * Sube un archivo guardado con `save_file`; los de más de 6 MB se suben por partes
+     */
+    async upload(request: StorageUploadRequest): Promise<StorageTransfer> {
+        return invoke<StorageTransfer>("plugin:red-salud-desktop-core|storage_upload", { request });
+    },
+
+    /**
+     * Descarga un objeto a los archivos del perfil; se lee después con `read_file`
+     */
+    async download(request: StorageDownloadRequest): Promise<StorageTransfer> {
+        return invoke<StorageTransfer>("plugin:red-salud-desktop-core|storage_download", { request });
+    },
+
+    async cancel(id: string): Promise<void> {
+        await invoke("plugin:red-salud-desktop-core|cancel_storage_transfer", { id });
+    },
+
+    async resume(id: string): Promise<StorageTransfer> {
+        return invoke<StorageTransfer>("plugin:red-salud-desktop-core|resume_storage_transfer", { id });
+    },
+
+    async getTransfers(): Promise<StorageTransfer[]> {
+        return invoke<StorageTransfer[]>("plugin:red-salud-desktop-core|get_storage_transfers");
+    },
+
+    async deleteTransfer(id: string): Promise<void> {
+        await invoke("plugin:red-salud-desktop-core|delete_storage_transfer", { id });
+    },
+
+    onProgress(callback: (transfer: StorageTransfer) => void): Promise<UnlistenFn> {
+        return listen<StorageTransfer>("storage-transfer-progress", (event) => callback(event.payload));
+    },
+
+    /**
+     * Espera a que una transferencia termine; rechaza si falla o se cancela
+     */
+    async waitFor(transfer: StorageTransfer): Promise<StorageTransfer> {
+        let unlisten: UnlistenFn | undefined;
+        try {
+            return await new Promise<StorageTransfer>((resolve, reject) => {
+                const settle = (current: StorageTransfer) => {
+                    if (current.status === "completed") resolve(current);
+                    else if (current.status !== "running") reject(new Error(current.error ?? "Transferencia cancelada"));
+                };
+                this.onProgress((current) => {
+                    if (current.id === transfer.id) settle(current);
+                }).then(async (fn) => {
+                    unlisten = fn;
+                    // Puede haber terminado antes de suscribirse
+                    const latest = (await this.getTransfers()).find((t) => t.id === transfer.id);
+                    if (latest) settle(latest);
+                }, reject);
+            });
+        } finally {
+            unlisten?.();
+        }
+    },
+};
@@ -1,5 +1,24 @@
 import { invoke } from "@tauri-apps/api/core";
+import { listen, type UnlistenFn } from "@tauri-apps/api/event";
 import { tauriNotificationService } from "./tauri-notification-service";
+import type { PostgrestQuery } from "./tauri-api-service";
+
+/**
+ * Descarga paginada de una tabla al almacén offline. Las filas de la página
+ * `n` quedan en la clave `${cacheKey}/${n}`.
+ */
+export interface PagedFetch {
+    id: string;
+    cacheKey: string;
+    endpoint: string;
+    pageSize: number;
+    rowsDone: number;
+    pages: number;
+    total: number | null;
+    status: "running" | "cancelled" | "failed" | "completed";
+    error: string | null;
+    updatedAt: number;
+}
 
 export const tauriSyncService = {
     syncInterval: null as ReturnType<typeof setInterval> | null,
@@ -14,13 +33,52 @@ export const tauriSyncService = {
         if (this.syncInterval) clearInterval(this.syncInterval);
 
         this.syncInterval = setInterval(async () => {
-            const isOnline = await invoke("check_connectivity");
+            const isOnline = await invoke("plugin:red-salud-desktop-core|check_connectivity");
             if (isOnline) {
                 await this.syncAll();
             }
         }, intervalMinutes * 60 * 1000);
     },
 
+    /**
+     * Descarga una tabla completa por páginas; el avance llega por `onPagedFetchProgress`
+     */
+    async startPagedFetch(query: PostgrestQuery, cacheKey: string, pageSize?: number): Promise<PagedFetch> {
+        return invoke<PagedFetch>("plugin:red-salud-desktop-core|start_paged_fetch", { query, cacheKey, pageSize });
+    },
+
+    async cancelPagedFetch(id: string): Promise<void> {
+        await invoke("plugin:red-salud-desktop-core|cancel_paged_fetch", { id });
+    },
+
+    async resumePagedFetch(id: string): Promise<PagedFetch> {
+        return invoke<PagedFetch>("plugin:red-salud-desktop-core|resume_paged_fetch", { id });
+    },
+
+    async getPagedFetches(): Promise<PagedFetch[]> {
+        return invoke<PagedFetch[]>("plugin:red-salud-desktop-core|get_paged_fetches");
+    },
+
+    async deletePagedFetch(id: string): Promise<void> {
+        await invoke("plugin:red-salud-desktop-core|delete_paged_fetch", { id });
+    },
+
+    onPagedFetchProgress(callback: (fetch: PagedFetch) => void): Promise<UnlistenFn> {
+        return listen<PagedFetch>("paged-fetch-progress", (event) => callback(event.payload));
+    },
+
+    /**
+     * Lee las filas guardadas por una descarga, página a página
+     */
+    async *readPagedFetch<T>(fetch: PagedFetch): AsyncGenerator<T[]> {
+        for (let page = 0; page < fetch.pages; page++) {
+            const value = await invoke<string | null>("plugin:red-salud-desktop-core|get_offline_data", {
+                key: `${fetch.cacheKey}/${page}`,
+            });
+            if (value) yield JSON.parse(value) as T[];
+        }
+    },
+
     stopAutoSync() {
         if (this.syncInterval) {
             clearInterval(this.syncInterval);
@@ -33,7 +91,7 @@ export const tauriSyncService = {
             console.log("Iniciando sincronización...");
 
             // Check connectivity first
-            const isOnline = await invoke("check_connectivity");
+            const isOnline = await invoke("plugin:red-salud-desktop-core|check_connectivity");
             if (!isOnline) {
                 console.log("Offline: Skipping sync");
                 return;
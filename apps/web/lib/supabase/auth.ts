@@ -1,4 +1,5 @@
 import { supabase } from "./client";
+import { tauriSessionService } from "@/lib/services/tauri-session-service";
 import type { RegisterFormData, LoginFormData } from "@red-salud/core/validations";
 
 export type UserRole =
@@ -33,6 +34,7 @@ export async function signUp(data: SignUpData) {
     });
 
     if (authError) {
+      tauriSessionService.setLoginSecret(null);
       return {
         success: false,
         error: translateAuthError(authError.message),
@@ -80,6 +82,9 @@ export async function signUp(data: SignUpData) {
  */
 export async function signIn(data: LoginFormData) {
   try {
+    // En escritorio, la contraseña desbloquea los datos offline cifrados del
+    // perfil local; el proveedor de sesión la usa al recibir SIGNED_IN
+    tauriSessionService.setLoginSecret(data.password);
     const { data: authData, error: authError } = await supabase.auth.signInWithPassword({
       email: data.email,
       password: data.password,
@@ -105,6 +110,7 @@ export async function signIn(data: LoginFormData) {
       session: authData.session,
     };
   } catch (error) {
+    tauriSessionService.setLoginSecret(null);
     console.error("Error en signIn:", error);
 
     const errorMessage = error instanceof Error ? error.message : String(error);
@@ -180,6 +186,7 @@ export async function signInWithOAuth(
  */
 export async function signOut() {
   try {
+    await tauriSessionService.lockStorage().catch(() => undefined);
     const { error } = await supabase.auth.signOut();
 
     if (error) {
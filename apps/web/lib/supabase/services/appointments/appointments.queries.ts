@@ -353,8 +353,13 @@ export async function getPatientAppointments(patientId: string) {
 export async function getDoctorAppointments(doctorId: string, accessToken?: string) {
   try {
     if (tauriApiService.isDesktop() && accessToken) {
-      const data = await tauriApiService.supabaseGet<DoctorAppointmentRow[]>(
-        `/rest/v1/appointments?select=*,patient:profiles!appointments_paciente_id_fkey(id,nombre_completo,email,avatar_url)&medico_id=eq.${doctorId}&order=fecha_hora.desc`,
+      const data = await tauriApiService.supabaseQuery<DoctorAppointmentRow[]>(
+        {
+          table: 'appointments',
+          select: '*,patient:profiles!appointments_paciente_id_fkey(id,nombre_completo,email,avatar_url)',
+          filters: [{ column: 'medico_id', op: 'eq', value: doctorId }],
+          order: [{ column: 'fecha_hora', ascending: false }],
+        },
         accessToken,
         `appointments_doctor_${doctorId}`
       );
@@ -5,7 +5,7 @@ import type {
   MedicalRecordFilters,
   MedicalHistorySummary,
 } from "../types/medical-records";
-import { tauriApiService } from "@/lib/services/tauri-api-service";
+import { tauriApiService, type PostgrestFilter } from "@/lib/services/tauri-api-service";
 
 // Obtener historial médico de un paciente
 export async function getPatientMedicalRecords(
@@ -52,15 +52,18 @@ export async function getPatientMedicalRecords(
 
 
     if (tauriApiService.isDesktop() && accessToken) {
-      let endpoint = `/rest/v1/medical_records?select=*,medico:profiles!medical_records_medico_id_fkey(id,nombre_completo,avatar_url,especialidad),appointment:appointments!medical_records_appointment_id_fkey(id,fecha_hora,motivo)&paciente_id=eq.${patientId}&order=created_at.desc`;
-
-      // Simple filter support
+      const queryFilters: PostgrestFilter[] = [{ column: 'paciente_id', op: 'eq', value: patientId }];
       if (filters?.medicoId) {
-        endpoint += `&medico_id=eq.${filters.medicoId}`;
+        queryFilters.push({ column: 'medico_id', op: 'eq', value: filters.medicoId });
       }
 
-      const data = await tauriApiService.supabaseGet<MedicalRecord[]>(
-        endpoint,
+      const data = await tauriApiService.supabaseQuery<MedicalRecord[]>(
+        {
+          table: 'medical_records',
+          select: '*,medico:profiles!medical_records_medico_id_fkey(id,nombre_completo,avatar_url,especialidad),appointment:appointments!medical_records_appointment_id_fkey(id,fecha_hora,motivo)',
+          filters: queryFilters,
+          order: [{ column: 'created_at', ascending: false }],
+        },
         accessToken,
         `medical_records_patient_${patientId}`
       );
@@ -0,0 +1,43 @@
+-- Migration: Secret for the desktop encrypted storage of OAuth accounts
+-- Description: Accounts without a password (Google) unlock their local
+-- offline data with a random per-user secret that only their session can read
+
+CREATE EXTENSION IF NOT EXISTS pgcrypto WITH SCHEMA extensions;
+
+CREATE TABLE IF NOT EXISTS local_storage_secrets (
+    user_id UUID PRIMARY KEY REFERENCES auth.users(id) ON DELETE CASCADE,
+    secret TEXT NOT NULL DEFAULT encode(extensions.gen_random_bytes(32), 'hex'),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+-- Sin políticas: solo se lee a través de get_local_storage_secret()
+ALTER TABLE local_storage_secrets ENABLE ROW LEVEL SECURITY;
+
+-- Devuelve el secreto del usuario autenticado, creándolo la primera vez
+CREATE OR REPLACE FUNCTION get_local_storage_secret()
+RETURNS text
+LANGUAGE plpgsql
+SECURITY DEFINER
+SET search_path = public
+AS $$
+DECLARE
+  u_id uuid;
+  result text;
+BEGIN
+  u_id := auth.uid();
+
+  IF u_id IS NULL THEN
+    RAISE EXCEPTION 'No autenticado';
+  END IF;
+
+  INSERT INTO local_storage_secrets (user_id)
+  VALUES (u_id)
+  ON CONFLICT (user_id) DO NOTHING;
+
+  SELECT secret INTO result FROM local_storage_secrets WHERE user_id = u_id;
+  RETURN result;
+END;
+$$;
+
+REVOKE ALL ON FUNCTION get_local_storage_secret() FROM PUBLIC, anon;
+GRANT EXECUTE ON FUNCTION get_local_storage_secret() TO authenticated;